@@ -0,0 +1,51 @@
+//! Draws a buffer holding a single line of 1 MB, like a minified file, at the start, further
+//! along and at the end of the line. Run with `cargo bench --bench long_line`.
+
+use std::time::{Duration, Instant};
+
+use kilo_edit::{
+    input::{CursorEvent, InputEvent},
+    terminal::Terminal,
+    Editor,
+};
+
+const LINE_BYTES: usize = 1 << 20;
+const BUDGET: Duration = Duration::from_millis(50);
+
+fn main() {
+    let line = "var a=[1,2,3],\tb=\"日本\";".repeat(LINE_BYTES / 20);
+    let mut editor = Editor::new(120, 40);
+    editor.set_buffer(line.parse().unwrap());
+
+    let mut output = Vec::new();
+    let mut terminal = Terminal::new(&mut output);
+    let moves = [
+        vec![],
+        // Counts are capped at u16::MAX columns
+        vec![
+            InputEvent::Count(9),
+            InputEvent::Count(9),
+            InputEvent::Count(9),
+            InputEvent::Count(9),
+            InputEvent::Count(9),
+            InputEvent::CursorEvent(CursorEvent::MoveRight),
+        ],
+        vec![InputEvent::CursorEvent(CursorEvent::MoveEnd)],
+    ];
+
+    for events in moves {
+        for event in events {
+            editor.process_event(event).unwrap();
+        }
+
+        let start = Instant::now();
+        editor.refresh(&mut terminal).unwrap();
+        let elapsed = start.elapsed();
+
+        println!("frame of a {} byte line took {elapsed:?}", line.len());
+        assert!(
+            elapsed < BUDGET,
+            "frame took {elapsed:?}, over the budget of {BUDGET:?}"
+        );
+    }
+}
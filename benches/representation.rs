@@ -0,0 +1,133 @@
+//! Compares keeping a file as one `Row` per line, like `Buffer` does, with keeping it as a
+//! single string and the offsets where its lines start. Times opening a file of 100 000
+//! lines, moving the cursor down through all of it and drawing screens of it with both.
+//! Run with `cargo bench --bench representation`.
+
+use std::{env, fs};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use kilo_edit::{
+    buffer::{split_lines, Row},
+    text::{column_window, ConsoleWidthStr},
+};
+
+const LINES: usize = 100_000;
+const COLS: usize = 120;
+const SCREEN_ROWS: usize = 40;
+
+/// The text of a line by its index, however it's kept.
+trait Lines {
+    fn len(&self) -> usize;
+    fn line(&self, row: usize) -> &str;
+}
+
+/// One allocation per line, like `Buffer`.
+struct RowLines(Vec<Row>);
+
+impl RowLines {
+    fn open(text: &str) -> Self {
+        Self(split_lines(text).lines.into_iter().map(Row::new).collect())
+    }
+}
+
+impl Lines for RowLines {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn line(&self, row: usize) -> &str {
+        self.0[row].buffer()
+    }
+}
+
+/// The whole text in one allocation with the offset every line starts at.
+struct FlatLines {
+    text: String,
+    starts: Vec<usize>,
+}
+
+impl FlatLines {
+    fn open(text: String) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .filter(|start| *start < text.len())
+            .collect();
+
+        Self { text, starts }
+    }
+}
+
+impl Lines for FlatLines {
+    fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    fn line(&self, row: usize) -> &str {
+        let end = self
+            .starts
+            .get(row + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        &self.text[self.starts[row]..end]
+    }
+}
+
+/// Move a cursor wanting column 30 down every line, clamping it to the line like the
+/// editor does. Returns the sum of the columns it ended up on.
+fn move_down<L: Lines>(lines: &L) -> usize {
+    (0..lines.len())
+        .map(|row| lines.line(row).column_width().min(30))
+        .sum()
+}
+
+/// Draw every screen of the file, returning how many bytes were drawn.
+fn draw<L: Lines>(lines: &L) -> usize {
+    (0..lines.len())
+        .step_by(SCREEN_ROWS)
+        .flat_map(|top| top..(top + SCREEN_ROWS).min(lines.len()))
+        .map(|row| column_window(lines.line(row), 0, COLS).len())
+        .sum()
+}
+
+fn representation(c: &mut Criterion) {
+    let content = (0..LINES)
+        .map(|i| match i % 10 {
+            0 => format!("line {i} has\ta few words ⛄ in it"),
+            _ => format!("line {i} has\ta few words in it"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let path = env::temp_dir().join(format!("kilo-edit-representation-{}", std::process::id()));
+    fs::write(&path, &content).unwrap();
+
+    let mut open = c.benchmark_group(format!("open {LINES} lines"));
+    open.sample_size(10);
+    open.bench_function("rows", |b| {
+        b.iter(|| RowLines::open(&fs::read_to_string(&path).unwrap()))
+    });
+    open.bench_function("flat", |b| {
+        b.iter(|| FlatLines::open(fs::read_to_string(&path).unwrap()))
+    });
+    open.finish();
+    fs::remove_file(&path).unwrap();
+
+    let rows = RowLines::open(&content);
+    let flat = FlatLines::open(content);
+    assert_eq!(rows.len(), LINES);
+    assert_eq!(flat.len(), LINES);
+    assert_eq!(move_down(&rows), move_down(&flat));
+    assert_eq!(draw(&rows), draw(&flat));
+
+    let mut moves = c.benchmark_group(format!("move down {LINES} lines"));
+    moves.bench_function("rows", |b| b.iter(|| move_down(black_box(&rows))));
+    moves.bench_function("flat", |b| b.iter(|| move_down(black_box(&flat))));
+    moves.finish();
+
+    let mut frames = c.benchmark_group(format!("draw screens of {LINES} lines"));
+    frames.bench_function("rows", |b| b.iter(|| draw(black_box(&rows))));
+    frames.bench_function("flat", |b| b.iter(|| draw(black_box(&flat))));
+    frames.finish();
+}
+
+criterion_group!(benches, representation);
+criterion_main!(benches);
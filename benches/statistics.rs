@@ -0,0 +1,30 @@
+//! Times `Buffer::statistics` on a large buffer. Run with `cargo bench --bench statistics`.
+
+use std::time::{Duration, Instant};
+
+use kilo_edit::buffer::Buffer;
+
+const LINES: usize = 100_000;
+const BUDGET: Duration = Duration::from_millis(10);
+
+fn main() {
+    let content = (0..LINES)
+        .map(|i| match i % 10 {
+            0 => format!("line {i} has\ta few words ⛄ in it"),
+            _ => format!("line {i} has\ta few words in it"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let buffer = content.parse::<Buffer>().unwrap();
+
+    let start = Instant::now();
+    let stats = buffer.statistics();
+    let elapsed = start.elapsed();
+
+    println!("statistics of {} lines took {elapsed:?}", stats.lines);
+    assert_eq!(stats.lines, LINES);
+    assert!(
+        elapsed < BUDGET,
+        "statistics took {elapsed:?}, over the budget of {BUDGET:?}"
+    );
+}
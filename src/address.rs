@@ -0,0 +1,53 @@
+//! Lines given by number or relative to the cursor, as typed in the goto line prompt.
+
+/// The row `address` refers to in a buffer of `len` rows with the cursor on row `current`,
+/// clamped to the buffer. `None` when the address can't be read.
+///
+/// - `N` is line `N`, counted from 1
+/// - `$` is the last line
+/// - `%` is the middle line
+/// - `+N` and `-N` are `N` lines below or above the cursor
+pub fn resolve_line(address: &str, current: usize, len: usize) -> Option<usize> {
+    let last = len.saturating_sub(1);
+    let number = |digits: &str| match digits.starts_with(['+', '-']) {
+        true => None,
+        false => digits.parse::<usize>().ok(),
+    };
+
+    let row = match address.trim() {
+        "$" => last,
+        "%" => last / 2,
+        address => match address.split_at(address.len().min(1)) {
+            ("+", n) => current.saturating_add(number(n)?),
+            ("-", n) => current.saturating_sub(number(n)?),
+            _ => number(address)?.saturating_sub(1),
+        },
+    };
+
+    Some(row.min(last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case("12" => Some(11); "Absolute")]
+    #[test_case("0" => Some(0); "Line zero")]
+    #[test_case("500" => Some(99); "Past the end")]
+    #[test_case("$" => Some(99); "Last line")]
+    #[test_case("%" => Some(49); "Middle")]
+    #[test_case("-5" => Some(15); "Up")]
+    #[test_case("+10" => Some(30); "Down")]
+    #[test_case("+90" => Some(99); "Down past the end")]
+    #[test_case("-50" => Some(0); "Up past the start")]
+    #[test_case(" $ " => Some(99); "Surrounding spaces")]
+    #[test_case("" => None; "Empty")]
+    #[test_case("+" => None; "Sign only")]
+    #[test_case("+-3" => None; "Two signs")]
+    #[test_case("ten" => None; "Not a number")]
+    fn addresses_should_resolve_to_rows(address: &str) -> Option<usize> {
+        resolve_line(address, 20, 100)
+    }
+}
@@ -0,0 +1,58 @@
+use std::{error::Error, fmt, str::FromStr, time::Duration};
+
+/// How long the status bar stays inverted for a visual bell
+pub const FLASH_DURATION: Duration = Duration::from_millis(100);
+
+/// Feedback given when an event couldn't do anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Bell {
+    #[default]
+    Off,
+    /// Ring the terminal bell
+    Audible,
+    /// Briefly invert the status bar
+    Visual,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseBellError(String);
+
+impl Error for ParseBellError {}
+
+impl fmt::Display for ParseBellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown bell '{}', expected off, audible or visual",
+            self.0
+        )
+    }
+}
+
+impl FromStr for Bell {
+    type Err = ParseBellError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "audible" => Ok(Self::Audible),
+            "visual" => Ok(Self::Visual),
+            _ => Err(ParseBellError(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case("off" => Ok(Bell::Off))]
+    #[test_case("audible" => Ok(Bell::Audible))]
+    #[test_case("visual" => Ok(Bell::Visual))]
+    #[test_case("loud" => Err(ParseBellError(String::from("loud"))))]
+    fn bell_should_parse_from_its_setting_name(setting: &str) -> Result<Bell, ParseBellError> {
+        setting.parse()
+    }
+}
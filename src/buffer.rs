@@ -1,28 +1,64 @@
-use std::{borrow::Cow, cell::RefCell, fmt, fs, path::Path, rc::Rc};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    convert::Infallible,
+    fmt, fs, mem,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+    str::FromStr,
+    time::{Duration, Instant, SystemTime},
+};
 
 use error_stack::{IntoReport, Result, ResultExt};
+use unicode_width::UnicodeWidthChar;
 
 use crate::{
-    cursor::{BoundedCursor, Cursor, HorizontalMovement},
-    text::{char_index, ConsoleWidthChar},
+    annotation::{Annotation, Severity},
+    clock::Clock,
+    cursor::{BoundedCursor, Cursor},
+    diff::{diff_summary, DiffSummary},
+    highlight::detect_filetype,
+    state::SessionEntry,
+    text::{align_at, char_index, display_width, tab_width, ConsoleWidthChar, ConsoleWidthStr},
+    undo::{
+        content_hash, extend_content_hash, EditOp, OperationLog, UndoFile, UndoLimits, UndoStack,
+    },
+    Position,
     SPACES,
     TAB_STOP,
 };
 
 pub type RowBufferRef = Rc<RefCell<Vec<Row>>>;
 
-fn expand_tabs(buffer: &str, tab_stop: usize) -> String {
+/// Rows longer than this many bytes are drawn with `Row::render_at_offset` instead of being
+/// rendered whole, since minified files can hold megabytes on a single line.
+pub const MAX_SAFE_LINE: usize = 1 << 20;
+
+/// The widest tab stop that can be set
+pub const MAX_TAB_STOP: usize = 16;
+
+/// How many of the last edits `Buffer::rows_edited_since` can tell about
+const EDITED_ROWS_SIZE: usize = 100;
+
+/// Expand the tabs of `buffer`, which is drawn starting at column `start`.
+fn expand_tabs(buffer: &str, start: usize, tab_stop: usize) -> String {
     let mut buf = String::with_capacity(buffer.len());
+    let mut col = start;
     for ch in buffer.chars() {
         if ch == '\t' {
-            let spaces = if tab_stop > SPACES.len() {
-                Cow::Owned(" ".repeat(tab_stop))
+            let width = tab_width(col, tab_stop);
+            let spaces = if width > SPACES.len() {
+                Cow::Owned(" ".repeat(width))
             } else {
-                Cow::Borrowed(&SPACES[..tab_stop])
+                Cow::Borrowed(&SPACES[..width])
             };
             buf.push_str(&spaces);
+            col += width;
         } else {
             buf.push(ch);
+            col += ch.width().unwrap_or(1);
         }
     }
 
@@ -41,33 +77,127 @@ impl Row {
         }
     }
 
-    pub fn render_buffer(&self) -> Cow<str> {
-        for (i, ch) in self.buffer.char_indices() {
-            if ch == '\t' {
-                let mut buf = String::with_capacity(self.buffer.len());
-                buf.push_str(&self.buffer[..i]);
+    /// The row as drawn, with every tab expanded to the next multiple of `tab_stop`.
+    pub fn render_buffer(&self, tab_stop: usize) -> Cow<'_, str> {
+        let Some(i) = self.buffer.find('\t') else {
+            return Cow::Borrowed(&self.buffer);
+        };
 
-                #[cfg(feature = "extend_one")]
-                buf.extend_one(expand_tabs(&self.buffer[i..], TAB_STOP));
+        let mut buf = String::with_capacity(self.buffer.len());
+        buf.push_str(&self.buffer[..i]);
+        let start = match self.is_pure_ascii() {
+            true => i,
+            false => self.buffer[..i].column_width(),
+        };
 
-                #[cfg(not(feature = "extend_one"))]
-                buf.extend(std::iter::once(expand_tabs(&self.buffer[i..], TAB_STOP)));
+        #[cfg(feature = "extend_one")]
+        buf.extend_one(expand_tabs(&self.buffer[i..], start, tab_stop));
 
-                return Cow::Owned(buf);
-            }
-        }
+        #[cfg(not(feature = "extend_one"))]
+        buf.extend(std::iter::once(expand_tabs(
+            &self.buffer[i..],
+            start,
+            tab_stop,
+        )));
 
-        Cow::Borrowed(&self.buffer)
+        Cow::Owned(buf)
+    }
+
+    /// Whether every byte of the row is ASCII, making every character a single byte and,
+    /// apart from tabs and control characters, a single column.
+    pub fn is_pure_ascii(&self) -> bool {
+        self.buffer.is_ascii()
+    }
+
+    /// Whether every byte is drawn as exactly one column, so columns can be found without
+    /// decoding the row.
+    fn has_byte_columns(&self) -> bool {
+        self.is_pure_ascii() && !self.buffer.contains(['\t', '\0'])
     }
 
     pub fn insert(&mut self, index: usize, ch: char) {
         self.buffer.insert(index, ch);
     }
 
+    /// Insert `s` at the cursor column `col`, or at the end of the row when `col` is past
+    /// it. Returns the drawn column right after the inserted text, with tabs before it
+    /// expanded to multiples of `tab_stop`.
+    pub fn insert_str(&mut self, col: usize, s: &str, tab_stop: usize) -> usize {
+        let index = char_index(col, &self.buffer);
+        self.buffer.insert_str(index, s);
+
+        display_width(&self.buffer[..index + s.len()], tab_stop)
+    }
+
     pub fn buffer(&self) -> &str {
         &self.buffer
     }
 
+    pub fn byte_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn append(&mut self, text: &str) {
+        self.buffer.push_str(text);
+    }
+
+    /// The rendered columns `start..start + width` of the row, without rendering anything
+    /// past them. Like `column_window`, tabs and wide characters cut by either edge are
+    /// replaced by spaces for the visible cells.
+    pub fn render_at_offset(&self, start: usize, width: usize, tab_stop: usize) -> String {
+        let end = start + width;
+        if self.has_byte_columns() {
+            let len = self.buffer.len();
+            return self.buffer[start.min(len)..end.min(len)].to_owned();
+        }
+
+        let mut window = String::with_capacity(width);
+        let mut col = 0;
+
+        for ch in self.buffer.chars() {
+            let ch_width = match ch {
+                '\t' => tab_width(col, tab_stop),
+                ch => ch.width().unwrap_or(1),
+            };
+            if col >= end && ch_width > 0 {
+                break;
+            }
+
+            let next = col + ch_width;
+            if col >= start && next <= end && ch != '\t' {
+                window.push(ch);
+            } else {
+                let visible = next.min(end).saturating_sub(col.max(start));
+                window.push_str(&SPACES[..visible]);
+            }
+
+            col = next;
+        }
+
+        window
+    }
+
+    /// Columns taken by the row once drawn, with every tab expanded to the next multiple
+    /// of `tab_stop`.
+    pub fn display_width(&self, tab_stop: usize) -> usize {
+        if self.has_byte_columns() {
+            return self.buffer.len();
+        }
+
+        display_width(&self.buffer, tab_stop)
+    }
+
+    /// Remove the character at the cursor column `col`, returning it so the deletion can be
+    /// recorded. Returns `None` when `col` is at or past the end of the row.
+    pub fn delete_char_at(&mut self, col: usize) -> Option<char> {
+        let index = char_index(col, &self.buffer);
+        if index < self.buffer.len() {
+            Some(self.buffer.remove(index))
+        } else {
+            None
+        }
+    }
+
     fn inner_mut(&mut self) -> &mut String {
         &mut self.buffer
     }
@@ -83,7 +213,14 @@ impl<T: Into<String>> From<T> for Row {
 pub enum BufferError {
     FailedToOpen(String),
     FailedToSave(String),
+    FailedToSaveUndo,
+    FailedToRead(String),
+    FailedToWrite(String),
     NoFilename,
+    ReadOnly(String),
+    InvalidOperation(usize),
+    Unencodable(Encoding),
+    Undecodable(Encoding),
 }
 
 impl std::error::Error for BufferError {}
@@ -93,7 +230,16 @@ impl fmt::Display for BufferError {
         match self {
             Self::FailedToOpen(path) => write!(f, "Unable to open file {path}"),
             Self::FailedToSave(path) => write!(f, "Unable to save file {path}"),
+            Self::FailedToSaveUndo => f.write_str("Unable to save the undo history"),
+            Self::FailedToRead(path) => write!(f, "Unable to read file {path}"),
+            Self::FailedToWrite(path) => write!(f, "Unable to write file {path}"),
             Self::NoFilename => f.write_str("No filename was given"),
+            Self::ReadOnly(path) => write!(f, "File {path} is read-only"),
+            Self::InvalidOperation(n) => write!(f, "Operation {n} does not apply to the buffer"),
+            Self::Unencodable(encoding) => {
+                write!(f, "The text can't be encoded as {}", encoding.name())
+            }
+            Self::Undecodable(encoding) => write!(f, "The file isn't valid {}", encoding.name()),
         }
     }
 }
@@ -105,60 +251,678 @@ pub enum BufferState {
     Default,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+    /// A lone `\r`, like on classic Mac OS
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+
+    /// The name shown in the status bar
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+            Self::Cr => "CR",
+        }
+    }
+}
+
+/// Text split into lines at `\r\n`, `\n` and lone `\r`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lines<'a> {
+    /// Like with `str::lines` a terminator at the very end doesn't start another line
+    pub lines: Vec<&'a str>,
+    /// The ending most lines had, `Lf` when there were none
+    pub line_ending: LineEnding,
+    /// Whether more than one kind of ending was found
+    pub mixed: bool,
+}
+
+/// The `content_hash` of every line of `text`, enough to tell which lines changed without
+/// keeping a copy of them.
+fn line_hashes(text: &str) -> Vec<u64> {
+    split_lines(text)
+        .lines
+        .into_iter()
+        .map(content_hash)
+        .collect()
+}
+
+/// Whether the file at `path` can't be written, by its permissions or because its
+/// filesystem is mounted read-only.
+fn is_unwritable(path: &Path) -> bool {
+    let read_only = fs::metadata(path).is_ok_and(|meta| meta.permissions().readonly());
+    read_only || fs::OpenOptions::new().write(true).open(path).is_err()
+}
+
+/// Let the owner of the file at `path` write it.
+fn make_writable(path: &Path) -> std::io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+
+    fs::set_permissions(path, permissions)
+}
+
+pub fn split_lines(text: &str) -> Lines<'_> {
+    let mut lines = Vec::new();
+    // Lf, CrLf and Cr in the order of the variants
+    let mut counts = [0usize; 3];
+    let mut start = 0;
+    let bytes = text.as_bytes();
+    while let Some(offset) = bytes[start..]
+        .iter()
+        .position(|&b| b == b'\n' || b == b'\r')
+    {
+        let end = start + offset;
+        lines.push(&text[start..end]);
+        let (ending, len) = match (bytes[end], bytes.get(end + 1)) {
+            (b'\r', Some(b'\n')) => (1, 2),
+            (b'\r', _) => (2, 1),
+            _ => (0, 1),
+        };
+        counts[ending] += 1;
+        start = end + len;
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+
+    let endings = [LineEnding::Lf, LineEnding::CrLf, LineEnding::Cr];
+    // The first of the most common endings, so ties go to Lf
+    let most = (0..3).rev().max_by_key(|&i| counts[i]).unwrap_or(0);
+    Lines {
+        lines,
+        line_ending: endings[most],
+        mixed: counts.iter().filter(|&&count| count > 0).count() > 1,
+    }
+}
+
+/// How the text of a file is stored on disk. The rows are always kept as UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, every byte is the codepoint of the same value
+    Latin1,
+    /// Latin-1 with printable characters instead of most C1 controls in 0x80-0x9F
+    Windows1252,
+}
+
+/// The characters of Windows-1252 bytes 0x80-0x9F. The five bytes it leaves undefined
+/// keep their Latin-1 control characters so every byte still decodes.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+impl Encoding {
+    /// The name shown in the status bar
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Latin1 => "latin-1",
+            Self::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// `None` when `bytes` aren't valid in this encoding
+    pub fn decode(&self, bytes: Vec<u8>) -> Option<String> {
+        match self {
+            Self::Utf8 => String::from_utf8(bytes).ok(),
+            Self::Latin1 => Some(bytes.into_iter().map(char::from).collect()),
+            Self::Windows1252 => Some(
+                bytes
+                    .into_iter()
+                    .map(|byte| match byte {
+                        0x80..=0x9f => WINDOWS_1252_HIGH[byte as usize - 0x80],
+                        _ => char::from(byte),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// `None` when `text` holds a character this encoding has no bytes for
+    pub fn encode(&self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            Self::Utf8 => Some(text.as_bytes().to_vec()),
+            Self::Latin1 => text.chars().map(|ch| u8::try_from(ch).ok()).collect(),
+            Self::Windows1252 => text.chars().map(windows_1252_byte).collect(),
+        }
+    }
+}
+
+fn windows_1252_byte(ch: char) -> Option<u8> {
+    match WINDOWS_1252_HIGH.iter().position(|&high| high == ch) {
+        Some(index) => Some(0x80 + index as u8),
+        None => u8::try_from(ch)
+            .ok()
+            .filter(|byte| !(0x80..=0x9f).contains(byte)),
+    }
+}
+
+/// Transforms applied to the rows when the buffer is written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SaveOptions {
+    pub trim_trailing_whitespace: bool,
+    pub line_ending: LineEnding,
+    pub final_newline: bool,
+    pub encoding: Encoding,
+}
+
+/// What a save wrote, for confirming it to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveReport {
+    /// The size of the file, after the line endings, final newline and encoding were applied
+    pub bytes: usize,
+    pub rows: usize,
+    pub duration: Duration,
+    pub path: String,
+}
+
+/// Shows as `12,403 bytes in 3ms to src/lib.rs`.
+impl fmt::Display for SaveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.bytes.to_string();
+        let mut bytes = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                bytes.push(',');
+            }
+            bytes.push(digit);
+        }
+
+        write!(
+            f,
+            "{bytes} bytes in {}ms to {}",
+            self.duration.as_millis(),
+            self.path
+        )
+    }
+}
+
+/// Counts over the whole buffer as it would be saved with `\n` line endings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BufferStats {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub bytes: usize,
+    pub longest_line: usize,
+}
+
+/// The counts of a single row, which add up to the `BufferStats` of the rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct RowStats {
+    words: usize,
+    chars: usize,
+    width: usize,
+}
+
+impl RowStats {
+    fn of(line: &str) -> Self {
+        let mut stats = Self::default();
+        let mut in_word = false;
+        if line.is_ascii() {
+            // Every byte is a single column wide char so skip the decoding
+            stats.width = line.len();
+            stats.chars = line.len();
+            for byte in line.bytes() {
+                if byte.is_ascii_whitespace() {
+                    in_word = false;
+                } else if !in_word {
+                    in_word = true;
+                    stats.words += 1;
+                }
+            }
+        } else {
+            for ch in line.chars() {
+                stats.chars += 1;
+                stats.width += ch.width().unwrap_or(1);
+                if ch.is_whitespace() {
+                    in_word = false;
+                } else if !in_word {
+                    in_word = true;
+                    stats.words += 1;
+                }
+            }
+        }
+
+        stats
+    }
+}
+
+/// The counts of every row added up. Kept up to date edit by edit from the operations
+/// recorded for them, so a large buffer isn't gone over again after every change.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct StatsTotals {
+    lines: usize,
+    words: usize,
+    chars: usize,
+    bytes: usize,
+    /// How many rows there are of each width, the widest being the longest line
+    widths: BTreeMap<usize, usize>,
+}
+
+impl StatsTotals {
+    fn of(rows: &[Row]) -> Self {
+        let mut totals = Self::default();
+        for row in rows {
+            totals.add(row.buffer());
+        }
+
+        totals
+    }
+
+    fn add(&mut self, line: &str) {
+        let stats = RowStats::of(line);
+        self.lines += 1;
+        self.words += stats.words;
+        self.chars += stats.chars;
+        self.bytes += line.len();
+        *self.widths.entry(stats.width).or_default() += 1;
+    }
+
+    fn remove(&mut self, line: &str) {
+        let stats = RowStats::of(line);
+        self.lines -= 1;
+        self.words -= stats.words;
+        self.chars -= stats.chars;
+        self.bytes -= line.len();
+        if let Some(count) = self.widths.get_mut(&stats.width) {
+            *count -= 1;
+            if *count == 0 {
+                self.widths.remove(&stats.width);
+            }
+        }
+    }
+
+    /// Account for `op`, which has already been made to `rows`. Only the rows it touched
+    /// are counted again, as they were before the edit and as they are now. Empty rows
+    /// pushed right before the edit, like the one typed into past the last row, are added
+    /// first.
+    fn update(&mut self, op: &EditOp, rows: &[Row]) {
+        let line = |row: usize| rows[row].buffer();
+        let lines = match op {
+            EditOp::SplitRow { .. } => self.lines + 1,
+            EditOp::JoinRows { .. } => self.lines.saturating_sub(1),
+            EditOp::ReplaceParagraph { old, new, .. } => self.lines - old.len() + new.len(),
+            _ => self.lines,
+        };
+        for _ in lines..rows.len() {
+            self.add("");
+        }
+
+        match op {
+            EditOp::Insert { row, index, text } => {
+                let new = line(*row);
+                self.remove(&[&new[..*index], &new[index + text.len()..]].concat());
+                self.add(new);
+            }
+            EditOp::Delete { row, index, text } => {
+                let new = line(*row);
+                self.remove(&[&new[..*index], text, &new[*index..]].concat());
+                self.add(new);
+            }
+            EditOp::TransposeChars { row, index } => {
+                let new = line(*row);
+                let pair = new[*index..].chars().take(2).collect::<String>();
+                let swapped = pair.chars().rev().collect::<String>();
+                self.remove(&[&new[..*index], &swapped, &new[index + pair.len()..]].concat());
+                self.add(new);
+            }
+            EditOp::TransposeRows { .. } => {}
+            EditOp::SplitRow { row, .. } => {
+                self.remove(&[line(*row), line(row + 1)].concat());
+                self.add(line(*row));
+                self.add(line(row + 1));
+            }
+            EditOp::JoinRows { row, index } => {
+                let new = line(*row);
+                self.remove(&new[..*index]);
+                self.remove(&new[*index..]);
+                self.add(new);
+            }
+            EditOp::ReplaceParagraph { old, new, .. } => {
+                old.iter().for_each(|line| self.remove(line));
+                new.iter().for_each(|line| self.add(line));
+            }
+        }
+    }
+
+    fn stats(&self) -> BufferStats {
+        // Count the newlines between rows
+        let newlines = self.lines.saturating_sub(1);
+        BufferStats {
+            lines: self.lines,
+            words: self.words,
+            chars: self.chars + newlines,
+            bytes: self.bytes + newlines,
+            longest_line: self.widths.keys().next_back().copied().unwrap_or(0),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Buffer {
     buffer: RowBufferRef,
     filename: Option<String>,
     cursor: BoundedCursor,
+    scroll_offset: (u16, u16),
     state: BufferState,
+    undo: UndoStack,
+    log: Option<OperationLog>,
+    save_options: SaveOptions,
+    modification_count: u64,
+    /// The rows changed by the last edits, by the modification count each edit made
+    edited_rows: VecDeque<(u64, Range<usize>)>,
+    /// The statistics and the modification count they are up to date with
+    stats: RefCell<Option<(u64, StatsTotals)>>,
+    /// Messages shown under their row, in the order they were added
+    annotations: BTreeMap<usize, Vec<Annotation>>,
+    /// Increases whenever annotations are added or cleared
+    annotation_changes: u64,
+    /// Whether the file had more than one kind of line ending when it was opened
+    mixed_line_endings: bool,
+    /// Columns between tab stops, `None` for the default
+    tab_stop: Option<usize>,
+    /// The filetype set by hand, `None` to detect it
+    filetype: Option<String>,
+    /// The `content_hash` of every line of the file as last read or written, `None` when the
+    /// buffer never was
+    disk_lines: Option<Vec<u64>>,
+    /// The `content_hash` and save options of the buffer as last read or written
+    saved_content: Option<(u64, SaveOptions)>,
+    read_only: bool,
+    clock: Clock,
+    /// When the buffer was last saved, `None` when it wasn't yet
+    saved_at: Option<SystemTime>,
 }
 
 impl Buffer {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BufferError> {
-        let content =
-            fs::read_to_string(&path)
-                .report()
-                .change_context(BufferError::FailedToOpen(
-                    path.as_ref().to_string_lossy().to_string(),
-                ))?;
+        Self::open_with_encoding(path, Encoding::Utf8)
+    }
+
+    /// A buffer of exactly `rows`. Unlike parsing text, an empty last row or a carriage
+    /// return in a row is kept as it is.
+    pub fn from_rows<T: Into<String>>(rows: impl IntoIterator<Item = T>) -> Self {
+        let mut me = Self {
+            buffer: Rc::new(RefCell::new(rows.into_iter().map(Row::new).collect())),
+            ..Default::default()
+        };
+
+        me.cursor.set_buffer(Rc::clone(&me.buffer));
+
+        me
+    }
+
+    /// Open a file stored in `encoding`, which is also used when saving it again, as is the
+    /// most common line ending. A file that can't be written is opened read-only.
+    pub fn open_with_encoding<P: AsRef<Path>>(
+        path: P,
+        encoding: Encoding,
+    ) -> Result<Self, BufferError> {
+        let filename = path.as_ref().to_string_lossy().to_string();
+        let bytes = fs::read(&path)
+            .report()
+            .change_context_lazy(|| BufferError::FailedToRead(filename.clone()))
+            .change_context_lazy(|| BufferError::FailedToOpen(filename.clone()))?;
+        let size = bytes.len();
+        let content = encoding
+            .decode(bytes)
+            .ok_or_else(|| error_stack::report!(BufferError::Undecodable(encoding)))
+            .attach_printable_lazy(|| format!("Read {size} bytes"))
+            .change_context_lazy(|| BufferError::FailedToOpen(filename.clone()))?;
 
+        let lines = split_lines(&content);
         let mut me = Self {
-            buffer: Rc::new(RefCell::new(content.lines().map(Row::new).collect())),
-            filename: Some(path.as_ref().to_string_lossy().into()),
+            buffer: Rc::new(RefCell::new(
+                lines.lines.into_iter().map(Row::new).collect(),
+            )),
+            filename: Some(filename),
+            save_options: SaveOptions {
+                line_ending: lines.line_ending,
+                encoding,
+                ..Default::default()
+            },
+            mixed_line_endings: lines.mixed,
+            disk_lines: Some(line_hashes(&content)),
+            read_only: is_unwritable(path.as_ref()),
             ..Default::default()
         };
 
         me.cursor.set_buffer(Rc::clone(&me.buffer));
+        me.saved_content = Some((me.content_hash(), me.save_options));
 
         Ok(me)
     }
 
-    pub fn save(&mut self) -> Result<(), BufferError> {
+    /// Write the rows to the file, refused for a read-only buffer.
+    pub fn save(&mut self) -> Result<SaveReport, BufferError> {
+        if self.read_only {
+            let filename = self.filename.as_deref().unwrap_or(crate::NO_NAME);
+            let report = error_stack::report!(BufferError::ReadOnly(filename.to_string()))
+                .attach_printable("Force the save to write it anyway")
+                .change_context(BufferError::FailedToSave(filename.to_string()));
+            return Err(report);
+        }
+
+        self.write()
+    }
+
+    /// Write the rows to the file even when the buffer is read-only, like `:w!` in vim. A
+    /// file without write permission is given it first. Errors writing it are still
+    /// reported.
+    pub fn save_force(&mut self) -> Result<SaveReport, BufferError> {
         if let Some(filename) = &self.filename {
-            let contents = self
-                .buffer
-                .borrow()
-                .iter()
-                .map(|row| row.buffer())
-                .collect::<Vec<_>>()
-                .join("\n");
-
-            fs::write(filename, &contents)
-                .report()
-                .change_context_lazy(|| BufferError::FailedToSave(filename.clone()))?;
+            let path = Path::new(filename);
+            if fs::metadata(path).is_ok_and(|meta| meta.permissions().readonly()) {
+                // When this fails writing does as well, which reports why
+                let _ = make_writable(path);
+            }
+        }
 
-            self.state = BufferState::Default;
-        } else {
-            error_stack::bail!(BufferError::NoFilename)
+        let report = self.write()?;
+        self.read_only = false;
+        Ok(report)
+    }
+
+    fn write(&mut self) -> Result<SaveReport, BufferError> {
+        let start = Instant::now();
+        let filename = self
+            .filename
+            .as_ref()
+            .ok_or_else(|| error_stack::report!(BufferError::NoFilename))
+            .attach_printable("Save the buffer under a name first")
+            .change_context(BufferError::FailedToSave(crate::NO_NAME.to_string()))?;
+        let encoding = self.save_options.encoding;
+        let preview = self.save_preview();
+        let contents = encoding
+            .encode(&preview)
+            .ok_or_else(|| error_stack::report!(BufferError::Unencodable(encoding)))
+            .attach_printable_lazy(|| {
+                let ch = preview
+                    .chars()
+                    .find(|ch| encoding.encode(&ch.to_string()).is_none());
+                format!("First character that can't be encoded: {ch:?}")
+            })
+            .change_context_lazy(|| BufferError::FailedToSave(filename.clone()))?;
+
+        fs::write(filename, &contents)
+            .report()
+            .change_context_lazy(|| BufferError::FailedToWrite(filename.clone()))
+            .attach_printable_lazy(|| format!("Writing {} bytes", contents.len()))
+            .change_context_lazy(|| BufferError::FailedToSave(filename.clone()))?;
+
+        self.state = BufferState::Default;
+        self.disk_lines = Some(line_hashes(&preview));
+        self.saved_content = Some((self.content_hash(), self.save_options));
+        self.saved_at = Some(self.clock.now());
+        Ok(SaveReport {
+            bytes: contents.len(),
+            rows: self.buffer.borrow().len(),
+            duration: start.elapsed(),
+            path: filename.clone(),
+        })
+    }
+
+    /// How the file on disk differs from what was last read from or written to it. `None`
+    /// when it doesn't, when it can't be read, or when the buffer never was read or written.
+    pub fn disk_changes(&self) -> Option<DiffSummary> {
+        let known = self.disk_lines.as_ref()?;
+        let bytes = fs::read(self.filename.as_ref()?).ok()?;
+        let content = self.save_options.encoding.decode(bytes)?;
+
+        diff_summary(known, &line_hashes(&content))
+    }
+
+    /// When the buffer was last saved, by its clock
+    pub fn saved_at(&self) -> Option<SystemTime> {
+        self.saved_at
+    }
+
+    /// Take the time of saves from `clock`.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    /// Whether edits and saving are refused
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// The exact contents `save` would write, without touching the disk.
+    pub fn save_preview(&self) -> String {
+        let options = self.save_options;
+        let mut contents = self
+            .buffer
+            .borrow()
+            .iter()
+            .map(|row| {
+                if options.trim_trailing_whitespace {
+                    row.buffer().trim_end()
+                } else {
+                    row.buffer()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(options.line_ending.as_str());
+
+        if options.final_newline && !contents.is_empty() {
+            contents.push_str(options.line_ending.as_str());
         }
 
-        Ok(())
+        contents
+    }
+
+    pub fn save_options(&self) -> SaveOptions {
+        self.save_options
+    }
+
+    pub fn set_save_options(&mut self, options: SaveOptions) {
+        self.save_options = options;
+    }
+
+    /// Columns between tab stops
+    pub fn tab_stop(&self) -> usize {
+        self.tab_stop.unwrap_or(TAB_STOP)
+    }
+
+    /// Draw tabs up to the next multiple of `tab_stop`, kept between 1 and `MAX_TAB_STOP`.
+    pub fn set_tab_stop(&mut self, tab_stop: usize) {
+        self.tab_stop = Some(tab_stop.clamp(1, MAX_TAB_STOP));
+    }
+
+    /// Whether the file had more than one kind of line ending when it was opened. They are
+    /// all saved as `save_options().line_ending`.
+    pub fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
+
+    /// Convert every line ending of the file on the next save, marking the buffer modified
+    /// when it changes.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.save_options.line_ending != line_ending {
+            self.save_options.line_ending = line_ending;
+            self.state = BufferState::Modified;
+        }
+    }
+
+    /// Store the file in `encoding` on the next save, marking the buffer modified when it
+    /// changes.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        if self.save_options.encoding != encoding {
+            self.save_options.encoding = encoding;
+            self.state = BufferState::Modified;
+        }
+    }
+
+    /// The smallest indentation of any row as rendered, taken as the width of one indent level.
+    pub fn indent_width(&self) -> usize {
+        self.buffer
+            .borrow()
+            .iter()
+            .map(|row| {
+                let render = row.render_buffer(self.tab_stop());
+                render.len() - render.trim_start_matches(' ').len()
+            })
+            .filter(|&indent| indent > 0)
+            .min()
+            .unwrap_or(self.tab_stop())
     }
 
     pub fn filename_str(&self) -> Option<&str> {
         self.filename.as_deref()
     }
 
+    pub fn set_filename<T: Into<String>>(&mut self, filename: T) {
+        self.filename = Some(filename.into());
+    }
+
+    /// The filetype set by hand, or else the one detected from the filename and first line.
+    /// Filetypes are named by the extension of their files, like `py`.
+    pub fn filetype(&self) -> Option<String> {
+        self.filetype.clone().or_else(|| {
+            let rows = self.buffer.borrow();
+            let first_line = rows.first().map_or("", Row::buffer);
+            detect_filetype(self.filename_str(), first_line)
+        })
+    }
+
+    /// Use `filetype` whatever the file looks like, or go back to detecting it with `None`.
+    pub fn set_filetype(&mut self, filetype: Option<String>) {
+        self.filetype = filetype;
+    }
+
+    /// The rows, shared with whatever draws them. Changing them through this isn't an edit:
+    /// it doesn't count as a modification and leaves `statistics` behind.
     pub fn buffer(&self) -> &RowBufferRef {
         &self.buffer
     }
@@ -167,10 +931,31 @@ impl Buffer {
         self.cursor = cursor;
     }
 
+    /// Put the saved cursor where `entry` left it, clamped to the rows. Returns false when the
+    /// position no longer fits, like after the file was edited elsewhere.
+    pub fn apply_session(&mut self, entry: &SessionEntry) -> bool {
+        self.cursor
+            .jump_to(Position(entry.cursor.0, entry.cursor.1))
+    }
+
+    /// The cursor kept while another buffer is shown
+    pub fn saved_cursor(&self) -> &BoundedCursor {
+        &self.cursor
+    }
+
     pub fn take_cursor(&mut self) -> BoundedCursor {
         std::mem::take(&mut self.cursor)
     }
 
+    /// Remember how far the screen was scrolled, as `(col, row)`, while another buffer is shown.
+    pub fn save_scroll_offset(&mut self, offset: (u16, u16)) {
+        self.scroll_offset = offset;
+    }
+
+    pub fn scroll_offset(&self) -> (u16, u16) {
+        self.scroll_offset
+    }
+
     pub fn insert_char<T: Cursor>(&mut self, ch: char, cursor: &T) {
         let mut buffer = self.buffer.borrow_mut();
         if cursor.y() as usize == buffer.len() {
@@ -179,45 +964,1454 @@ impl Buffer {
 
         let row = &mut buffer[cursor.y() as usize];
         let index = crate::text::char_index(cursor.x() as usize, row.buffer());
-        row.insert(index, ch);
 
-        self.state = BufferState::Modified;
+        // A cursor in virtual space past the end of the line pads the gap with spaces
+        let padding = (cursor.x() as usize).saturating_sub(row.buffer().column_width());
+        let mut text = " ".repeat(padding);
+        text.push(ch);
+        row.inner_mut().insert_str(index, &text);
+
+        drop(buffer);
+        self.record(EditOp::Insert {
+            row: cursor.y() as usize,
+            index,
+            text,
+        });
     }
 
-    pub fn delete_char<T: Cursor + HorizontalMovement>(&mut self, cursor: &mut T) {
-        let move_cursor = {
-            let mut buf = self.buffer.borrow_mut();
-            if cursor.y() == buf.len() as u16 {
-                return;
-            }
+    /// Delete the character before the cursor, moving the cursor onto where it was.
+    pub fn delete_char(&mut self, cursor: &mut BoundedCursor) {
+        if cursor.x() == 0 {
+            return self.join_with_previous(cursor);
+        }
 
-            if cursor.x() > 0 {
-                let line = &mut buf[cursor.y() as usize];
+        let mut buf = self.buffer.borrow_mut();
+        let line = match buf.get_mut(cursor.y() as usize) {
+            Some(line) if cursor.x() > 0 => line,
+            _ => return,
+        };
 
-                let mut index = char_index(cursor.x() as usize, line.buffer());
-                let width_of_prev_char = line.buffer()[..index]
-                    .chars()
-                    .last()
-                    .map(|x| x.render_width())
-                    .unwrap_or(1);
-                index = char_index(cursor.x() as usize - width_of_prev_char, line.buffer());
+        // In virtual space past the end of the line there is nothing to delete, only a column
+        // to step back over
+        if cursor.x() as usize > line.buffer().column_width() {
+            cursor.position_mut().0 -= 1;
+            return;
+        }
 
-                line.inner_mut().remove(index);
+        let index = char_index(cursor.x() as usize, line.buffer());
+        let width_of_prev_char = line.buffer()[..index]
+            .chars()
+            .last()
+            .map(|x| match x.render_width() {
+                0 => 1,
+                w => w,
+            })
+            .unwrap_or(1);
+        let col = cursor.x() as usize - width_of_prev_char;
 
-                self.state = BufferState::Modified;
+        if let Some(ch) = line.delete_char_at(col) {
+            let index = char_index(col, line.buffer());
 
-                width_of_prev_char
-            } else {
-                0
-            }
+            drop(buf);
+            self.record(EditOp::Delete {
+                row: cursor.y() as usize,
+                index,
+                text: ch.to_string(),
+            });
+            cursor.position_mut().0 = col as u16;
+        }
+    }
+
+    /// Delete the line break before the row of the cursor, moving the cursor to where the
+    /// rows were joined.
+    fn join_with_previous(&mut self, cursor: &mut BoundedCursor) {
+        let y = cursor.y() as usize;
+        let buffer = self.buffer.borrow();
+        if y == 0 || y >= buffer.len() {
+            return;
+        }
+        let index = buffer[y - 1].byte_len();
+        drop(buffer);
+
+        let op = EditOp::JoinRows { row: y - 1, index };
+        *cursor.position_mut() = self.apply(&op);
+        self.undo.push(op);
+    }
+
+    /// Delete the character under the cursor, leaving the cursor in place.
+    pub fn delete_char_forward<T: Cursor>(&mut self, cursor: &T) {
+        let mut buf = self.buffer.borrow_mut();
+        let line = match buf.get_mut(cursor.y() as usize) {
+            Some(line) => line,
+            None => return,
         };
 
-        for _ in 0..move_cursor {
-            cursor.left()
+        if let Some(ch) = line.delete_char_at(cursor.x() as usize) {
+            let index = char_index(cursor.x() as usize, line.buffer());
+
+            drop(buf);
+            self.record(EditOp::Delete {
+                row: cursor.y() as usize,
+                index,
+                text: ch.to_string(),
+            });
         }
     }
 
-    pub fn state(&self) -> BufferState {
-        self.state
+    /// Replace the `find_len` bytes starting at the column of `pos` with `replacement`,
+    /// recorded as a delete followed by an insert. Returns false when the range isn't there.
+    pub fn replace_at(&mut self, pos: Position, find_len: usize, replacement: &str) -> bool {
+        let mut buf = self.buffer.borrow_mut();
+        let line = match buf.get_mut(pos.1 as usize) {
+            Some(line) => line,
+            None => return false,
+        };
+
+        let index = char_index(pos.0 as usize, line.buffer());
+        let text = match line.buffer().get(index..index + find_len) {
+            Some(text) => text.to_string(),
+            None => return false,
+        };
+        // Made in the two steps it's recorded as, each op describes the rows right after it
+        line.inner_mut().replace_range(index..index + find_len, "");
+        drop(buf);
+        let row = pos.1 as usize;
+        self.record(EditOp::Delete { row, index, text });
+
+        self.buffer.borrow_mut()[row]
+            .inner_mut()
+            .insert_str(index, replacement);
+        self.record(EditOp::Insert {
+            row,
+            index,
+            text: replacement.to_string(),
+        });
+
+        true
+    }
+
+    /// Delete everything before the cursor on its line and move the cursor to the beginning
+    /// of the line. Returns the deleted text, empty when the cursor was already there.
+    pub fn kill_to_bol(&mut self, cursor: &mut BoundedCursor) -> String {
+        let mut buf = self.buffer.borrow_mut();
+        let line = match buf.get_mut(cursor.y() as usize) {
+            Some(line) => line,
+            None => return String::new(),
+        };
+
+        let index = char_index(cursor.x() as usize, line.buffer());
+        let text = line.inner_mut().drain(..index).collect::<String>();
+        drop(buf);
+
+        cursor.position_mut().0 = 0;
+        if !text.is_empty() {
+            self.record(EditOp::Delete {
+                row: cursor.y() as usize,
+                index: 0,
+                text: text.clone(),
+            });
+        }
+
+        text
+    }
+
+    /// Swap the characters on either side of the cursor and move past both, or the last two
+    /// characters when the cursor is at the end of the line. At the beginning of a line the
+    /// line is swapped with the one above instead. Returns false when there is nothing to swap.
+    pub fn transpose_chars(&mut self, cursor: &mut BoundedCursor) -> bool {
+        let (x, y) = (cursor.x() as usize, cursor.y() as usize);
+        let op = if x == 0 {
+            EditOp::TransposeRows { row: y }
+        } else {
+            let buffer = self.buffer.borrow();
+            let line = match buffer.get(y) {
+                Some(line) => line.buffer(),
+                None => return false,
+            };
+
+            // At the end of the line the pair is the two characters before the cursor
+            let mut index = char_index(x, line);
+            if index == line.len() {
+                index = line[..index].char_indices().last().map_or(0, |(i, _)| i);
+            }
+
+            let start = line[..index].char_indices().last().map_or(0, |(i, _)| i);
+            EditOp::TransposeChars {
+                row: y,
+                index: start,
+            }
+        };
+
+        if !self.is_valid(&op) {
+            return false;
+        }
+
+        let position = self.apply(&op);
+        self.undo.push(op);
+        if x > 0 {
+            *cursor.position_mut() = position;
+        }
+
+        true
+    }
+
+    /// Wrap the paragraph around the cursor, the rows between the blank ones above and below
+    /// it, to lines of at most `width` columns. Words are put on a line as long as they
+    /// fit, every line starting with the indent of the first. Returns the end of the new
+    /// paragraph, `None` when the cursor isn't in a paragraph or it's already wrapped.
+    pub fn fill_paragraph<T: Cursor>(&mut self, cursor: &T, width: usize) -> Option<Position> {
+        let op = {
+            let (start, old) = self.paragraph_at(cursor.y() as usize)?;
+            let first = &old[0];
+            let indent = &first[..first.len() - first.trim_start().len()];
+            let mut new = Vec::new();
+            let mut line = String::new();
+            for word in old.iter().flat_map(|row| row.split_whitespace()) {
+                let needed = display_width(&line, self.tab_stop()) + 1 + word.column_width();
+                if !line.is_empty() && needed > width {
+                    new.push(mem::take(&mut line));
+                }
+                match line.is_empty() {
+                    true => line = format!("{indent}{word}"),
+                    false => line = format!("{line} {word}"),
+                }
+            }
+            new.push(line);
+            if new == old {
+                return None;
+            }
+
+            EditOp::ReplaceParagraph {
+                row: start,
+                old,
+                new,
+            }
+        };
+
+        let position = self.apply(&op);
+        self.undo.push(op);
+        Some(position)
+    }
+
+    /// Pad the lines of the paragraph around the cursor so the first `delimiter` of each is
+    /// drawn at the same column, see `align_at`. The cursor stays on the character it was
+    /// on. Returns `false` when the cursor isn't in a paragraph or it's already aligned.
+    pub fn align_paragraph(&mut self, cursor: &mut BoundedCursor, delimiter: &str) -> bool {
+        let (x, y) = (cursor.x() as usize, cursor.y() as usize);
+        let Some((start, old)) = self.paragraph_at(y) else {
+            return false;
+        };
+        let new = align_at(&old, delimiter, self.tab_stop());
+        if new == old {
+            return false;
+        }
+
+        let line = &old[y - start];
+        let padding = new[y - start].len() - line.len();
+        let moved = line
+            .find(delimiter)
+            .is_some_and(|index| char_index(x, line) >= index);
+        let op = EditOp::ReplaceParagraph {
+            row: start,
+            old,
+            new,
+        };
+        self.apply(&op);
+        self.undo.push(op);
+        if moved {
+            cursor.position_mut().0 = (x + padding) as u16;
+        }
+
+        true
+    }
+
+    /// The first row and the text of the paragraph `y` is in, the rows between the blank
+    /// ones above and below it. `None` on a blank row.
+    fn paragraph_at(&self, y: usize) -> Option<(usize, Vec<String>)> {
+        let rows = self.buffer.borrow();
+        let blank = |row: &Row| row.buffer().trim().is_empty();
+        if blank(rows.get(y)?) {
+            return None;
+        }
+
+        let start = rows[..y].iter().rposition(blank).map_or(0, |row| row + 1);
+        let end = rows[y..].iter().position(blank).map_or(rows.len(), |row| y + row);
+        let lines = rows[start..end]
+            .iter()
+            .map(|row| row.buffer().to_string())
+            .collect();
+
+        Some((start, lines))
+    }
+
+    /// Break the line at the cursor and move the cursor to the start of the new line.
+    pub fn insert_newline(&mut self, cursor: &mut BoundedCursor) {
+        let (x, y) = (cursor.x() as usize, cursor.y() as usize);
+        let len = self.buffer.borrow().len();
+        if len == 0 {
+            self.buffer.borrow_mut().push(Row::new(""));
+            self.record(EditOp::Insert {
+                row: 0,
+                index: 0,
+                text: String::new(),
+            });
+            *cursor.position_mut() = Position(0, 1);
+            return;
+        }
+
+        // Below the last line a new one is added by breaking the last line at its end
+        let (row, index) = match y < len {
+            true => (y, char_index(x, self.buffer.borrow()[y].buffer())),
+            false => (len - 1, self.buffer.borrow()[len - 1].buffer().len()),
+        };
+        let op = EditOp::SplitRow { row, index };
+        let position = self.apply(&op);
+        self.undo.push(op);
+        *cursor.position_mut() = match y < len {
+            true => position,
+            false => Position(0, len as u16 + 1),
+        };
+    }
+
+    pub fn state(&self) -> BufferState {
+        self.state
+    }
+
+    /// Treat the rows as differing from the file, like after restoring unsaved changes.
+    pub fn mark_modified(&mut self) {
+        self.state = BufferState::Modified;
+    }
+
+    /// Increases with every edit, for caching anything derived from the contents.
+    pub fn modification_count(&self) -> u64 {
+        self.modification_count
+    }
+
+    /// The rows changed since the modification count was `count`, for updating a cache
+    /// from then instead of starting over. `None` when it was too many edits ago to tell.
+    pub fn rows_edited_since(&self, count: u64) -> Option<Range<usize>> {
+        let edits = self.modification_count.checked_sub(count)?;
+        if edits as usize > self.edited_rows.len() {
+            return None;
+        }
+
+        let mut edited = self
+            .edited_rows
+            .iter()
+            .filter(|(edit, _)| *edit > count)
+            .map(|(_, rows)| rows.clone());
+        let first = edited.next().unwrap_or_default();
+
+        Some(edited.fold(first, |rows, edited| {
+            rows.start.min(edited.start)..rows.end.max(edited.end)
+        }))
+    }
+
+    fn note_edited_rows(&mut self, op: &EditOp) {
+        if self.edited_rows.len() == EDITED_ROWS_SIZE {
+            self.edited_rows.pop_front();
+        }
+        self.edited_rows
+            .push_back((self.modification_count, op.rows()));
+    }
+
+    /// Show `message` under `row`. Annotations aren't part of the text and stay on their row
+    /// number while the text is edited, until they are cleared.
+    pub fn add_annotation(&mut self, row: usize, message: String, severity: Severity) {
+        self.annotations
+            .entry(row)
+            .or_default()
+            .push(Annotation { message, severity });
+        self.annotation_changes += 1;
+    }
+
+    pub fn clear_annotations(&mut self) {
+        self.annotations.clear();
+        self.annotation_changes += 1;
+    }
+
+    /// The annotations shown under `row`
+    pub fn annotations(&self, row: usize) -> &[Annotation] {
+        self.annotations.get(&row).map_or(&[], Vec::as_slice)
+    }
+
+    /// How many annotations are shown under the rows in `rows`
+    pub fn annotation_rows(&self, rows: Range<usize>) -> usize {
+        self.annotations.range(rows).map(|(_, row)| row.len()).sum()
+    }
+
+    /// Increases whenever the annotations change, like `modification_count` for the text.
+    pub fn annotation_changes(&self) -> u64 {
+        self.annotation_changes
+    }
+
+    /// The rows rendered wider than `fill_column` along with their width, ordered by row.
+    pub fn long_lines(&self, fill_column: usize) -> Vec<(usize, usize)> {
+        self.buffer
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, row)| {
+                let width = row.display_width(self.tab_stop());
+                (width > fill_column).then_some((i, width))
+            })
+            .collect()
+    }
+
+    /// Line, word and character counts. Once computed they are kept up to date by the edits
+    /// the buffer makes, so asking again only counts the whole buffer after edits it lost
+    /// track of. Rows changed directly through `buffer()` aren't noticed at all.
+    pub fn statistics(&self) -> BufferStats {
+        let mut cache = self.stats.borrow_mut();
+        match cache.as_ref() {
+            Some((count, totals)) if *count == self.modification_count => totals.stats(),
+            _ => {
+                let totals = StatsTotals::of(&self.buffer.borrow());
+                let stats = totals.stats();
+                *cache = Some((self.modification_count, totals));
+                stats
+            }
+        }
+    }
+
+    /// Bring the statistics up to date with `op`, made to the rows but not counted as a
+    /// modification yet. Statistics that were out of date already are left to be counted
+    /// again when asked for.
+    fn update_stats(&mut self, op: &EditOp) {
+        let rows = self.buffer.borrow();
+        if let Some((count, totals)) = self.stats.get_mut() {
+            if *count == self.modification_count {
+                totals.update(op, &rows);
+                *count += 1;
+
+                #[cfg(test)]
+                debug_assert_eq!(*totals, StatsTotals::of(&rows), "drifted after {op:?}");
+            }
+        }
+    }
+
+    pub fn undo_stack(&self) -> &UndoStack {
+        &self.undo
+    }
+
+    pub fn undo_limits(&self) -> UndoLimits {
+        self.undo.limits()
+    }
+
+    pub fn set_undo_limits(&mut self, limits: UndoLimits) {
+        self.undo.set_limits(limits);
+    }
+
+    /// Revert the most recent edit and place the cursor where it happened.
+    /// Returns false if there was nothing to undo.
+    pub fn undo(&mut self, cursor: &mut BoundedCursor) -> bool {
+        match self.undo.undo() {
+            Some(op) => {
+                *cursor.position_mut() = self.apply(&op.inverse());
+                self.update_saved_state();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Apply the most recently undone edit again. Returns false if there was nothing to redo.
+    pub fn redo(&mut self, cursor: &mut BoundedCursor) -> bool {
+        match self.undo.redo() {
+            Some(op) => {
+                *cursor.position_mut() = self.apply(&op);
+                self.update_saved_state();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Hash of the rows joined by `\n`, so it doesn't depend on how the file ends its lines.
+    pub fn content_hash(&self) -> u64 {
+        let rows = self.buffer.borrow();
+        let mut rows = rows.iter().map(Row::buffer);
+        let first = content_hash(rows.next().unwrap_or_default());
+
+        rows.fold(first, |hash, row| {
+            extend_content_hash(extend_content_hash(hash, "\n"), row)
+        })
+    }
+
+    /// Whether `other` holds the same text, whatever the files, line endings and encodings of
+    /// either.
+    pub fn is_same_content(&self, other: &Buffer) -> bool {
+        self.buffer.borrow().len() == other.buffer.borrow().len()
+            && self.content_hash() == other.content_hash()
+    }
+
+    /// Stop counting the buffer as modified when undoing or redoing took it back to what was
+    /// last read or written.
+    fn update_saved_state(&mut self) {
+        if self.saved_content == Some((self.content_hash(), self.save_options)) {
+            self.state = BufferState::Default;
+        }
+    }
+
+    /// Write what can be undone to a file in `dir` named after the file's canonical path.
+    pub fn save_undo_file(&self, dir: &Path) -> Result<(), BufferError> {
+        let path = self
+            .undo_file_path(dir)
+            .ok_or_else(|| error_stack::report!(BufferError::NoFilename))
+            .attach_printable("Undo histories are kept for saved files only")
+            .change_context(BufferError::FailedToSaveUndo)?;
+        let file = UndoFile {
+            hash: self.content_hash(),
+            ops: self.undo_history(),
+        };
+
+        fs::create_dir_all(dir)
+            .and_then(|_| fs::write(&path, file.to_string()))
+            .report()
+            .change_context_lazy(|| BufferError::FailedToWrite(path.to_string_lossy().into()))
+            .change_context(BufferError::FailedToSaveUndo)
+    }
+
+    /// Restore the undo history saved in `dir` for this file. Nothing is loaded when there is
+    /// none or when the file was changed since, returns whether it was restored.
+    pub fn load_undo_file(&mut self, dir: &Path) -> bool {
+        let file = self
+            .undo_file_path(dir)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| contents.parse::<UndoFile>().ok());
+
+        match file {
+            Some(file) if file.hash == self.content_hash() => {
+                self.extend_undo_history(&file.ops);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The operations that can be undone, oldest first
+    pub fn undo_history(&self) -> OperationLog {
+        self.undo.history().fold(OperationLog::default(), |mut ops, op| {
+            ops.push(op.clone());
+            ops
+        })
+    }
+
+    /// Add `ops`, which have to lead up to the current rows, to what can be undone.
+    pub fn extend_undo_history(&mut self, ops: &OperationLog) {
+        // Pushing keeps the history within the current limits
+        for op in ops.ops() {
+            self.undo.push(op.clone());
+        }
+    }
+
+    /// Like vim's undodir the canonical path with every `/` replaced by `%`
+    fn undo_file_path(&self, dir: &Path) -> Option<PathBuf> {
+        let path = fs::canonicalize(self.filename.as_ref()?).ok()?;
+        let name = path.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "%");
+        Some(dir.join(name))
+    }
+
+    /// Start recording every operation applied to this buffer from now on.
+    pub fn start_operation_log(&mut self) {
+        self.log.get_or_insert_with(OperationLog::default);
+    }
+
+    pub fn operation_log(&self) -> Option<&OperationLog> {
+        self.log.as_ref()
+    }
+
+    /// Apply a recorded session to this buffer. Every operation is checked against the
+    /// current contents before it is applied, stopping at the first one that doesn't fit.
+    pub fn replay(&mut self, log: &OperationLog) -> Result<(), BufferError> {
+        for (n, op) in log.ops().iter().enumerate() {
+            if !self.is_valid(op) {
+                return Err(error_stack::report!(BufferError::InvalidOperation(n + 1)))
+                    .attach_printable_lazy(|| format!("{op:?} doesn't fit the buffer"));
+            }
+
+            self.apply(op);
+            self.undo.push(op.clone());
+        }
+
+        Ok(())
+    }
+
+    fn is_valid(&self, op: &EditOp) -> bool {
+        let buffer = self.buffer.borrow();
+        match op {
+            EditOp::Insert { row, index, .. } if *row == buffer.len() => *index == 0,
+            EditOp::Insert { row, index, .. } => {
+                matches!(buffer.get(*row), Some(line) if line.buffer().is_char_boundary(*index))
+            }
+            EditOp::Delete { row, index, text } => {
+                buffer
+                    .get(*row)
+                    .and_then(|line| line.buffer().get(*index..index + text.len()))
+                    == Some(text.as_str())
+            }
+            EditOp::TransposeChars { row, index } => buffer
+                .get(*row)
+                .and_then(|line| line.buffer().get(*index..))
+                .map(|rest| rest.chars().nth(1).is_some())
+                .unwrap_or(false),
+            EditOp::TransposeRows { row } => (1..buffer.len()).contains(row),
+            EditOp::SplitRow { row, index } => {
+                matches!(buffer.get(*row), Some(line) if line.buffer().is_char_boundary(*index))
+            }
+            EditOp::JoinRows { row, index } => {
+                row + 1 < buffer.len() && buffer[*row].buffer().len() == *index
+            }
+            EditOp::ReplaceParagraph { row, old, new } => {
+                !new.is_empty()
+                    && buffer
+                        .get(*row..row + old.len())
+                        .is_some_and(|rows| rows.iter().map(Row::buffer).eq(old))
+            }
+        }
+    }
+
+    /// Keep track of an edit that has already been made to the rows.
+    fn record(&mut self, op: EditOp) {
+        if let Some(log) = self.log.as_mut() {
+            log.push(op.clone());
+        }
+
+        self.update_stats(&op);
+        self.state = BufferState::Modified;
+        self.modification_count += 1;
+        self.note_edited_rows(&op);
+        self.undo.push(op);
+    }
+
+    /// Perform the operation without adding it to the undo history and return the
+    /// position right after the edit.
+    fn apply(&mut self, op: &EditOp) -> Position {
+        if let Some(log) = self.log.as_mut() {
+            log.push(op.clone());
+        }
+
+        let mut buffer = self.buffer.borrow_mut();
+        let (row, index) = match op {
+            EditOp::Insert { row, index, text } => {
+                if *row == buffer.len() {
+                    buffer.push(Row::new(""));
+                }
+
+                buffer[*row].inner_mut().insert_str(*index, text);
+                (*row, index + text.len())
+            }
+            EditOp::Delete { row, index, text } => {
+                buffer[*row]
+                    .inner_mut()
+                    .replace_range(*index..index + text.len(), "");
+                (*row, *index)
+            }
+            EditOp::TransposeChars { row, index } => {
+                let line = buffer[*row].inner_mut();
+                let pair = line[*index..].chars().take(2).collect::<String>();
+                let swapped = pair.chars().rev().collect::<String>();
+                line.replace_range(*index..index + pair.len(), &swapped);
+                (*row, index + pair.len())
+            }
+            EditOp::TransposeRows { row } => {
+                buffer.swap(row - 1, *row);
+                (*row, 0)
+            }
+            EditOp::SplitRow { row, index } => {
+                let rest = buffer[*row].inner_mut().split_off(*index);
+                buffer.insert(row + 1, Row::new(rest));
+                (row + 1, 0)
+            }
+            EditOp::JoinRows { row, index } => {
+                let below = buffer.remove(row + 1);
+                buffer[*row].append(below.buffer());
+                (*row, *index)
+            }
+            EditOp::ReplaceParagraph { row, old, new } => {
+                buffer.splice(*row..row + old.len(), new.iter().map(Row::new));
+                let last = row + new.len() - 1;
+                (last, buffer[last].byte_len())
+            }
+        };
+
+        self.state = BufferState::Modified;
+
+        let column = buffer[row].buffer()[..index].column_width();
+        drop(buffer);
+        self.update_stats(op);
+        self.modification_count += 1;
+        self.note_edited_rows(op);
+
+        Position(column as u16, row as u16)
+    }
+}
+
+impl FromStr for Buffer {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self::from_rows(split_lines(s).lines))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::{HorizontalMovement, LineMovement, VerticalMovement};
+    use crate::scratch::ScratchDir;
+    use crate::text::column_window;
+
+    use quickcheck::TestResult;
+    use quickcheck_macros::quickcheck;
+    use test_case::test_case;
+
+    fn contents(buffer: &Buffer) -> Vec<String> {
+        buffer
+            .buffer()
+            .borrow()
+            .iter()
+            .map(|row| row.buffer().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn replaying_an_operation_log_should_reproduce_the_session() {
+        let mut buffer = Buffer::default();
+        buffer.start_operation_log();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+
+        for ch in "héllo".chars() {
+            buffer.insert_char(ch, &cursor);
+            cursor.right();
+        }
+        buffer.delete_char(&mut cursor);
+        buffer.undo(&mut cursor);
+        buffer.delete_char(&mut cursor);
+
+        let log = buffer.operation_log().unwrap().to_string();
+        let mut replayed = Buffer::default();
+        replayed.replay(&log.parse().unwrap()).unwrap();
+
+        assert_eq!(contents(&replayed), contents(&buffer));
+        assert_eq!(contents(&replayed), vec!["héll"]);
+    }
+
+    #[quickcheck]
+    fn render_at_offset_should_match_the_window_of_the_whole_render(
+        line: String,
+        start: u8,
+        width: u8,
+        tab_stop: u8,
+    ) -> bool {
+        let line = format!("\t{line}日本\ttext");
+        let row = Row::new(line);
+        let (start, width) = (start as usize, width as usize);
+        let tab_stop = tab_stop as usize % MAX_TAB_STOP + 1;
+
+        row.render_at_offset(start, width, tab_stop)
+            == column_window(&row.render_buffer(tab_stop), start, width)
+    }
+
+    #[quickcheck]
+    fn display_width_should_match_the_width_of_the_render(line: String) -> bool {
+        let row = Row::new(format!("a\t{line}\t日\t"));
+
+        row.display_width(TAB_STOP) == row.render_buffer(TAB_STOP).column_width()
+    }
+
+    #[quickcheck]
+    fn ascii_rows_should_draw_like_any_other_row(line: String, start: u8, width: u8) -> TestResult {
+        if !line.is_ascii() {
+            return TestResult::discard();
+        }
+
+        // A character past the end keeps the longer row off the ASCII fast paths
+        let (ascii, general) = (Row::new(line.clone()), Row::new(format!("{line}é")));
+        let (render, general_render) = (
+            ascii.render_buffer(TAB_STOP),
+            general.render_buffer(TAB_STOP),
+        );
+        let len = ascii.display_width(TAB_STOP);
+        let width = (width as usize).min(len);
+        let start = (start as usize).min(len - width);
+
+        TestResult::from_bool(
+            len + 1 == general.display_width(TAB_STOP)
+                && render == general_render.trim_end_matches('é')
+                && ascii.render_at_offset(start, width, TAB_STOP)
+                    == general.render_at_offset(start, width, TAB_STOP)
+                && column_window(&render, start, width)
+                    == column_window(&general_render, start, width),
+        )
+    }
+
+    #[test_case("\tx" => "        x"; "Leading tab")]
+    #[test_case("ab\tx" => "ab      x"; "Tab after text")]
+    #[test_case("日本語\tx" => "日本語  x"; "Tab after wide characters")]
+    fn render_should_expand_tabs_to_the_next_stop(line: &str) -> String {
+        Row::new(line).render_buffer(TAB_STOP).into_owned()
+    }
+
+    #[test_case("ad", 1, "bc", 4 => ("abcd".to_string(), 3); "Between characters")]
+    #[test_case("日x", 2, "本", 4 => ("日本x".to_string(), 4); "After a wide character")]
+    #[test_case("\tx", 1, "a\tb", 4 => ("\ta\tbx".to_string(), 9); "Tabs before and in the text")]
+    #[test_case("\tx", 1, "a\tb", 8 => ("\ta\tbx".to_string(), 17); "Wider tab stop")]
+    #[test_case("ab", 9, "c", 4 => ("abc".to_string(), 3); "Past the end")]
+    fn insert_str_should_return_the_column_after_the_text(
+        line: &str,
+        col: usize,
+        text: &str,
+        tab_stop: usize,
+    ) -> (String, usize) {
+        let mut row = Row::new(line);
+        let end = row.insert_str(col, text, tab_stop);
+
+        (row.buffer().to_string(), end)
+    }
+
+    #[test_case("ab", 1 => (vec!["ba".to_string()], 2); "Around the cursor")]
+    #[test_case("abc", 3 => (vec!["acb".to_string()], 3); "At the end of the line")]
+    #[test_case("日本x", 2 => (vec!["本日x".to_string()], 4); "Wide characters")]
+    #[test_case("é⛄", 3 => (vec!["⛄é".to_string()], 3); "Multi byte at the end")]
+    #[test_case("a", 1 => (vec!["a".to_string()], 1); "Single character")]
+    fn transpose_chars_should_swap_and_move_past_both(line: &str, x: u16) -> (Vec<String>, u16) {
+        let mut buffer = line.parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+        cursor.position_mut().0 = x;
+
+        buffer.transpose_chars(&mut cursor);
+
+        (contents(&buffer), cursor.x())
+    }
+
+    #[test_case("ab", 1 => (vec!["a".to_string(), "b".to_string()], (0, 1)); "Inside the line")]
+    #[test_case("日本", 2 => (vec!["日".to_string(), "本".to_string()], (0, 1)); "Wide characters")]
+    #[test_case("ab", 2 => (vec!["ab".to_string(), "".to_string()], (0, 1)); "At the end")]
+    #[test_case("", 0 => (vec!["".to_string()], (0, 1)); "Empty buffer")]
+    fn insert_newline_should_break_the_line_and_undo(
+        line: &str,
+        x: u16,
+    ) -> (Vec<String>, (u16, u16)) {
+        let mut buffer = line.parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+        cursor.position_mut().0 = x;
+
+        buffer.insert_newline(&mut cursor);
+        let (rows, position) = (contents(&buffer), (cursor.x(), cursor.y()));
+
+        buffer.undo(&mut cursor);
+        if !line.is_empty() {
+            assert_eq!(contents(&buffer), vec![line.to_string()]);
+            assert_eq!(cursor.x(), x);
+        }
+
+        (rows, position)
+    }
+
+    #[test]
+    fn fill_paragraph_should_rewrap_only_the_paragraph_and_undo_in_one_step() {
+        let content = "before\n\n  The quick brown fox jumps\n  over the lazy dog and\nkeeps running.\n\nafter";
+        let mut buffer = content.parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+        cursor.position_mut().1 = 3;
+
+        let end = buffer.fill_paragraph(&cursor, 22);
+
+        assert_eq!(
+            contents(&buffer),
+            vec![
+                "before",
+                "",
+                "  The quick brown fox",
+                "  jumps over the lazy",
+                "  dog and keeps",
+                "  running.",
+                "",
+                "after"
+            ]
+        );
+        assert_eq!(end.map(|end| (end.0, end.1)), Some((10, 5)));
+        assert_eq!(buffer.statistics().lines, 8);
+
+        buffer.undo(&mut cursor);
+        assert_eq!(contents(&buffer).join("\n"), content);
+    }
+
+    #[test_case("\nabc", 0; "Blank line")]
+    #[test_case("abc", 1; "Past the end")]
+    #[test_case("one two\nthree", 0; "Already filled")]
+    fn fill_paragraph_should_leave_nothing_to_undo_without_a_change(content: &str, y: u16) {
+        let mut buffer = content.parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+        cursor.position_mut().1 = y;
+
+        assert!(buffer.fill_paragraph(&cursor, 10).is_none());
+        assert_eq!(buffer.undo_stack().undo_count(), 0);
+    }
+
+    #[test_case("ab\ncd", 1 => (vec!["abcd".to_string()], (2, 0)); "Second line")]
+    #[test_case("日本\n語", 1 => (vec!["日本語".to_string()], (4, 0)); "Wide characters")]
+    #[test_case("ab\ncd", 0 => (vec!["ab".to_string(), "cd".to_string()], (0, 0)); "First line")]
+    #[test_case("ab", 1 => (vec!["ab".to_string()], (0, 1)); "Past the last line")]
+    fn backspace_at_the_line_start_should_join_with_the_line_above(
+        content: &str,
+        y: u16,
+    ) -> (Vec<String>, (u16, u16)) {
+        let mut buffer = content.parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+        *cursor.position_mut() = Position(0, y);
+
+        buffer.delete_char(&mut cursor);
+        let result = (contents(&buffer), (cursor.x(), cursor.y()));
+
+        buffer.undo(&mut cursor);
+        assert_eq!(contents(&buffer).join("\n"), content);
+
+        result
+    }
+
+    #[test_case("abc", 0 => ("".to_string(), "abc".to_string()); "At the beginning")]
+    #[test_case("abc", 3 => ("abc".to_string(), "".to_string()); "At the end")]
+    #[test_case("日本語", 4 => ("日本".to_string(), "語".to_string()); "Wide characters")]
+    #[test_case("é x", 2 => ("é ".to_string(), "x".to_string()); "Multi byte characters")]
+    fn kill_to_bol_should_delete_before_the_cursor(line: &str, x: u16) -> (String, String) {
+        let mut buffer = line.parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+        cursor.position_mut().0 = x;
+
+        let killed = buffer.kill_to_bol(&mut cursor);
+
+        assert_eq!(cursor.x(), 0);
+        (killed, contents(&buffer).remove(0))
+    }
+
+    #[test]
+    fn kill_to_bol_should_be_undone_in_one_step() {
+        let mut buffer = "héllo".parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+        cursor.position_mut().0 = 3;
+
+        buffer.kill_to_bol(&mut cursor);
+        assert_eq!(contents(&buffer), vec!["lo"]);
+
+        buffer.undo(&mut cursor);
+        assert_eq!(contents(&buffer), vec!["héllo"]);
+        assert_eq!(buffer.undo_stack().undo_count(), 0);
+    }
+
+    #[test]
+    fn transpose_at_the_beginning_of_a_line_should_swap_it_with_the_one_above() {
+        let mut buffer = "first\nsecond".parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+
+        assert!(!buffer.transpose_chars(&mut cursor));
+        cursor.down();
+        assert!(buffer.transpose_chars(&mut cursor));
+        assert_eq!(contents(&buffer), vec!["second", "first"]);
+
+        buffer.undo(&mut cursor);
+        assert_eq!(contents(&buffer), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn replace_at_should_be_undone_in_two_steps() {
+        let mut buffer = "日本 foo bar".parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+
+        assert!(buffer.replace_at(Position(5, 0), 3, "quux"));
+        assert_eq!(contents(&buffer), vec!["日本 quux bar"]);
+        assert!(!buffer.replace_at(Position(5, 1), 3, "quux"));
+
+        buffer.undo(&mut cursor);
+        buffer.undo(&mut cursor);
+        assert_eq!(contents(&buffer), vec!["日本 foo bar"]);
+    }
+
+    #[test]
+    fn replay_should_reject_operations_that_do_not_fit_the_buffer() {
+        let mut buffer = "abc".parse::<Buffer>().unwrap();
+
+        let log = "delete 0 1 x\n".parse().unwrap();
+        let err = buffer.replay(&log).unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            BufferError::InvalidOperation(1)
+        ));
+
+        let log = "insert 0 2 x\ninsert 0 9 y\n".parse().unwrap();
+        assert!(buffer.replay(&log).is_err());
+        assert_eq!(contents(&buffer), vec!["abxc"]);
+    }
+
+    #[test]
+    fn save_preview_should_trim_trailing_whitespace_when_enabled() {
+        let mut buffer = "a  \nb\t\n c".parse::<Buffer>().unwrap();
+        assert_eq!(buffer.save_preview(), "a  \nb\t\n c");
+
+        buffer.set_save_options(SaveOptions {
+            trim_trailing_whitespace: true,
+            ..Default::default()
+        });
+
+        assert_eq!(buffer.save_preview(), "a\nb\n c");
+    }
+
+    #[test]
+    fn save_preview_should_use_the_configured_line_ending() {
+        let mut buffer = "a\nb".parse::<Buffer>().unwrap();
+
+        buffer.set_save_options(SaveOptions {
+            line_ending: LineEnding::CrLf,
+            final_newline: true,
+            ..Default::default()
+        });
+
+        assert_eq!(buffer.save_preview(), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn latin1_file_should_round_trip_through_utf8_rows() {
+        let dir = ScratchDir::new("latin1");
+        let path = dir.join("latin1.txt");
+        fs::copy("tests/fixtures/latin1.txt", &path).unwrap();
+
+        assert!(Buffer::open(&path).is_err());
+        let mut buffer = Buffer::open_with_encoding(&path, Encoding::Latin1).unwrap();
+        assert_eq!(contents(&buffer), vec!["café", "naïve"]);
+        assert_eq!(buffer.save_options().line_ending, LineEnding::CrLf);
+
+        buffer.set_save_options(SaveOptions {
+            final_newline: true,
+            ..buffer.save_options()
+        });
+        let report = buffer.save().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"caf\xe9\r\nna\xefve\r\n");
+        assert_eq!(report.bytes as u64, fs::metadata(&path).unwrap().len());
+
+        buffer.set_line_ending(LineEnding::Lf);
+        buffer.set_encoding(Encoding::Utf8);
+        assert_eq!(buffer.state(), BufferState::Modified);
+        let report = buffer.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "café\nnaïve\n");
+        assert_eq!(report.bytes as u64, fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn windows_1252_should_round_trip_every_byte() {
+        let bytes = (0..=255).collect::<Vec<u8>>();
+
+        let text = Encoding::Windows1252.decode(bytes.clone()).unwrap();
+        assert!(text.starts_with("\0\u{1}"));
+        assert_eq!(text.chars().nth(0x80), Some('€'));
+        assert_eq!(text.chars().nth(0x9f), Some('Ÿ'));
+        assert_eq!(text.chars().nth(0xe9), Some('é'));
+
+        assert_eq!(Encoding::Windows1252.encode(&text), Some(bytes));
+        assert_eq!(Encoding::Windows1252.encode("\u{80}"), None);
+        assert_eq!(Encoding::Windows1252.encode("⛄"), None);
+    }
+
+    #[test_case("a\nb\n" => (vec!["a", "b"], LineEnding::Lf, false); "LF")]
+    #[test_case("a\r\nb" => (vec!["a", "b"], LineEnding::CrLf, false); "CRLF")]
+    #[test_case("a\rb\r" => (vec!["a", "b"], LineEnding::Cr, false); "CR")]
+    #[test_case("a\r\r\nb" => (vec!["a", "", "b"], LineEnding::CrLf, true); "CR before CRLF")]
+    #[test_case("a\nb\r\n" => (vec!["a", "b"], LineEnding::Lf, true); "Tie")]
+    #[test_case("" => (vec![], LineEnding::Lf, false); "Empty")]
+    fn split_lines_should_end_lines_at_any_terminator(text: &str) -> (Vec<&str>, LineEnding, bool) {
+        let lines = split_lines(text);
+
+        (lines.lines, lines.line_ending, lines.mixed)
+    }
+
+    #[test_case("cr.txt", 3, b"one\rtwo\rthree\r"; "CR")]
+    #[test_case("mixed_endings.txt", 4, b"one\r\ntwo\r\nthree\r\nfour\r\n"; "Mixed")]
+    fn files_should_save_with_their_most_common_line_ending(
+        fixture: &str,
+        rows: usize,
+        saved: &[u8],
+    ) {
+        let dir = ScratchDir::new(&format!("endings-{fixture}"));
+        let path = dir.join(fixture);
+        fs::copy(format!("tests/fixtures/{fixture}"), &path).unwrap();
+
+        let mut buffer = Buffer::open(&path).unwrap();
+        assert_eq!(buffer.buffer().borrow().len(), rows);
+        assert_eq!(
+            buffer.has_mixed_line_endings(),
+            fixture.starts_with("mixed")
+        );
+
+        buffer.set_save_options(SaveOptions {
+            final_newline: true,
+            ..buffer.save_options()
+        });
+        let report = buffer.save().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), saved);
+        assert_eq!((report.bytes, report.rows), (saved.len(), rows));
+    }
+
+    #[test_case(7 => "7 bytes in 3ms to a.txt"; "Small")]
+    #[test_case(12_403 => "12,403 bytes in 3ms to a.txt"; "Thousands")]
+    #[test_case(1_000_000 => "1,000,000 bytes in 3ms to a.txt"; "Millions")]
+    fn save_report_should_group_the_digits_of_the_size(bytes: usize) -> String {
+        let report = SaveReport {
+            bytes,
+            rows: 1,
+            duration: Duration::from_micros(3_400),
+            path: String::from("a.txt"),
+        };
+
+        report.to_string()
+    }
+
+    #[test]
+    fn saving_a_character_latin1_lacks_should_fail() {
+        let dir = ScratchDir::new("unencodable");
+        let mut buffer = "⛄".parse::<Buffer>().unwrap();
+        buffer.set_filename(dir.join("snowman.txt").to_string_lossy());
+        buffer.set_encoding(Encoding::Latin1);
+
+        let err = buffer.save().unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            BufferError::FailedToSave(_)
+        ));
+        assert!(format!("{err:?}").contains("The text can't be encoded as latin-1"));
+    }
+
+    #[test]
+    fn failing_to_save_should_report_the_write_and_its_cause() {
+        let dir = ScratchDir::new("unwritable");
+        let path = dir
+            .join("missing")
+            .join("file.txt")
+            .to_string_lossy()
+            .to_string();
+        let mut buffer = "text".parse::<Buffer>().unwrap();
+        buffer.set_filename(&path);
+
+        let err = buffer.save().unwrap_err();
+        let report = format!("{err:?}");
+
+        assert_eq!(err.to_string(), format!("Unable to save file {path}"));
+        assert!(report.contains(&format!("Unable to write file {path}")));
+        assert!(report.contains("No such file or directory"));
+
+        let err = "text".parse::<Buffer>().unwrap().save().unwrap_err();
+        assert!(format!("{err:?}").contains("No filename was given"));
+    }
+
+    #[test]
+    fn long_lines_should_count_tabs_to_the_next_tab_stop() {
+        // The second row is only 27 bytes but its two tabs render 16 columns wide
+        let buffer = Buffer::open("tests/fixtures/long_lines.rs").unwrap();
+
+        assert_eq!(buffer.long_lines(30), [(1, 41), (3, 32)]);
+        assert_eq!(buffer.long_lines(41), []);
+    }
+
+    #[test]
+    fn statistics_should_count_lines_words_and_chars() {
+        let buffer = "two words\n\n  ⛄ three  more words".parse::<Buffer>().unwrap();
+
+        assert_eq!(
+            buffer.statistics(),
+            BufferStats {
+                lines: 3,
+                words: 6,
+                chars: 32,
+                bytes: 34,
+                longest_line: 22,
+            }
+        );
+    }
+
+    #[quickcheck]
+    fn statistics_kept_up_by_edits_should_match_counting_again(
+        content: String,
+        edits: Vec<(u8, u8, u8, char)>,
+    ) -> bool {
+        let mut buffer = content.parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+        buffer.statistics();
+
+        for (edit, x, y, ch) in edits {
+            // Moving right never leaves the cursor inside a wide character
+            cursor.jump_to(Position(0, y as u16 % 4));
+            for _ in 0..x % 8 {
+                cursor.right();
+            }
+            match edit % 9 {
+                0 | 1 => buffer.insert_char(ch, &cursor),
+                2 => buffer.delete_char(&mut cursor),
+                3 => buffer.delete_char_forward(&cursor),
+                4 => buffer.insert_newline(&mut cursor),
+                5 => {
+                    buffer.transpose_chars(&mut cursor);
+                }
+                6 => {
+                    buffer.kill_to_bol(&mut cursor);
+                }
+                7 => {
+                    buffer.undo(&mut cursor);
+                }
+                _ => {
+                    buffer.redo(&mut cursor);
+                }
+            }
+        }
+
+        let counted = StatsTotals::of(&buffer.buffer().borrow()).stats();
+        buffer.statistics() == counted
+    }
+
+    #[test]
+    fn statistics_should_be_recomputed_after_an_edit() {
+        let mut buffer = "abc".parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        assert_eq!(buffer.statistics().chars, 3);
+
+        cursor.end();
+        buffer.insert_char(' ', &cursor);
+        cursor.right();
+        buffer.insert_char('d', &cursor);
+
+        assert_eq!(buffer.statistics().words, 2);
+        assert_eq!(buffer.statistics().chars, 5);
+    }
+
+    #[test]
+    fn delete_char_at_should_return_the_removed_char() {
+        let mut row = Row::new("a⛄b");
+
+        assert_eq!(row.delete_char_at(1), Some('⛄'));
+        assert_eq!(row.buffer(), "ab");
+        assert_eq!(row.delete_char_at(2), None);
+    }
+
+    #[test]
+    fn deleting_a_wide_char_should_move_the_cursor_by_its_width() {
+        let mut buffer = "a⛄⛄b".parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.position_mut().0 = 5;
+
+        buffer.delete_char(&mut cursor);
+
+        assert_eq!(contents(&buffer), vec!["a⛄b"]);
+        assert_eq!(cursor.x(), 3);
+    }
+
+    #[test]
+    fn deleting_forward_should_keep_the_cursor_in_place() {
+        let mut buffer = "abc".parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.position_mut().0 = 1;
+
+        buffer.delete_char_forward(&cursor);
+        buffer.delete_char_forward(&cursor);
+        buffer.delete_char_forward(&cursor);
+
+        assert_eq!(contents(&buffer), vec!["a"]);
+        assert_eq!(cursor.x(), 1);
+    }
+
+    #[test]
+    fn inserting_past_the_end_of_a_line_should_pad_it_with_spaces() {
+        let mut buffer = "abc".parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_virtual_edit(true);
+        cursor.position_mut().0 = 6;
+
+        buffer.insert_char('x', &cursor);
+        assert_eq!(contents(&buffer), vec!["abc   x"]);
+
+        buffer.undo(&mut cursor);
+        assert_eq!(contents(&buffer), vec!["abc"]);
+    }
+
+    #[test]
+    fn deleting_past_the_end_of_a_line_should_move_the_cursor_back() {
+        let mut buffer = "abc".parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_virtual_edit(true);
+        cursor.position_mut().0 = 5;
+
+        buffer.delete_char(&mut cursor);
+        assert_eq!(contents(&buffer), vec!["abc"]);
+        assert_eq!(cursor.x(), 4);
+
+        cursor.position_mut().0 = 3;
+        buffer.delete_char(&mut cursor);
+        assert_eq!(contents(&buffer), vec!["ab"]);
+    }
+
+    fn edit_and_save(dir: &Path) -> PathBuf {
+        let path = dir.join("file.txt");
+        fs::write(&path, "abc\n").unwrap();
+
+        let mut buffer = Buffer::open(&path).unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.end();
+        buffer.insert_char('d', &cursor);
+        buffer.save().unwrap();
+        buffer.save_undo_file(&dir.join("undo")).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn read_only_buffers_should_only_be_saved_by_force() {
+        let dir = ScratchDir::new("save-force");
+        let path = dir.join("file.txt");
+        let mut buffer = "abc".parse::<Buffer>().unwrap();
+        buffer.set_filename(path.to_string_lossy());
+        buffer.set_read_only(true);
+
+        let err = buffer.save().unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            BufferError::FailedToSave(_)
+        ));
+        assert!(!path.exists());
+
+        buffer.save_force().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abc");
+        assert!(!buffer.is_read_only());
+    }
+
+    #[test]
+    fn forced_saves_should_still_report_io_errors() {
+        let dir = ScratchDir::new("save-force-missing");
+        let mut buffer = "abc".parse::<Buffer>().unwrap();
+        buffer.set_filename(dir.join("missing").join("file.txt").to_string_lossy());
+        buffer.set_read_only(true);
+
+        assert!(buffer.save_force().is_err());
+        assert!(buffer.is_read_only());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn forced_saves_should_make_the_file_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = ScratchDir::new("save-force-locked");
+        let path = dir.join("locked.txt");
+        fs::write(&path, "abc").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o444)).unwrap();
+        let mut buffer = Buffer::open(&path).unwrap();
+        let cursor = buffer.take_cursor();
+        buffer.insert_char('x', &cursor);
+
+        assert!(buffer.save().is_err());
+        buffer.save_force().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "xabc");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o644);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn files_without_write_permission_should_open_read_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = ScratchDir::new("read-only");
+        let path = dir.join("locked.txt");
+        fs::write(&path, "abc").unwrap();
+        assert!(!Buffer::open(&path).unwrap().is_read_only());
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o444)).unwrap();
+        let buffer = Buffer::open(&path).unwrap();
+
+        assert!(buffer.is_read_only());
+        assert_eq!(contents(&buffer), vec!["abc"]);
+    }
+
+    #[test]
+    fn same_content_should_not_depend_on_the_file() {
+        let dir = ScratchDir::new("same-content");
+        let path = dir.join("crlf.txt");
+        fs::write(&path, "one\r\ntwo\r\n").unwrap();
+        let opened = Buffer::open(&path).unwrap();
+
+        let unnamed = "one\ntwo".parse::<Buffer>().unwrap();
+        let different = "one\nTwo".parse::<Buffer>().unwrap();
+
+        assert!(opened.is_same_content(&unnamed));
+        assert!(!opened.is_same_content(&different));
+        assert_eq!(opened.content_hash(), content_hash("one\ntwo"));
+    }
+
+    #[test]
+    fn undoing_back_to_the_saved_text_should_leave_the_buffer_unmodified() {
+        let dir = ScratchDir::new("undo-to-saved");
+        let path = edit_and_save(&dir);
+        let mut buffer = Buffer::open(&path).unwrap();
+        let mut cursor = buffer.take_cursor();
+
+        buffer.insert_char('x', &cursor);
+        assert_eq!(buffer.state(), BufferState::Modified);
+        buffer.undo(&mut cursor);
+        assert_eq!(buffer.state(), BufferState::Default);
+        buffer.redo(&mut cursor);
+        assert_eq!(buffer.state(), BufferState::Modified);
+    }
+
+    #[test]
+    fn undo_history_should_survive_reopening_the_file() {
+        let dir = ScratchDir::new("undo-file");
+        let path = edit_and_save(&dir);
+
+        let mut buffer = Buffer::open(&path).unwrap();
+        let mut cursor = buffer.take_cursor();
+        assert!(buffer.load_undo_file(&dir.join("undo")));
+        buffer.undo(&mut cursor);
+
+        assert_eq!(contents(&buffer), vec!["abc"]);
+    }
+
+    #[test]
+    fn undo_history_should_be_skipped_when_the_file_changed_since() {
+        let dir = ScratchDir::new("undo-file-changed");
+        let path = edit_and_save(&dir);
+        fs::write(&path, "changed elsewhere\n").unwrap();
+
+        let mut buffer = Buffer::open(&path).unwrap();
+
+        assert!(!buffer.load_undo_file(&dir.join("undo")));
+        assert_eq!(buffer.undo_stack().undo_count(), 0);
+    }
+
+    #[test]
+    fn edits_should_tell_which_rows_they_changed() {
+        let mut buffer = "a\nb\nc".parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        let count = buffer.modification_count();
+
+        buffer.insert_char('x', &cursor);
+        assert_eq!(buffer.rows_edited_since(count), Some(0..1));
+
+        cursor.position_mut().1 = 2;
+        buffer.insert_char('y', &cursor);
+        assert_eq!(buffer.rows_edited_since(count), Some(0..3));
+        assert_eq!(buffer.rows_edited_since(count + 1), Some(2..3));
+
+        for _ in 0..EDITED_ROWS_SIZE {
+            buffer.insert_char('z', &cursor);
+        }
+        assert_eq!(buffer.rows_edited_since(count), None);
     }
 }
@@ -0,0 +1,145 @@
+use std::{env, fmt, io};
+
+use crossterm::{
+    style::{Attribute, Color, SetAttribute, SetBackgroundColor},
+    tty::IsTty,
+    Command,
+};
+
+/// What the terminal the editor draws on can do. A dumb terminal or output that isn't a
+/// terminal at all gets plain text, so redirecting the output doesn't fill it with escapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Colors and attributes like reverse video
+    pub styles: bool,
+    /// Switching to the alternate screen and back
+    pub alternate_screen: bool,
+}
+
+impl Capabilities {
+    /// Decide from the environment and whether stdout is a terminal.
+    pub fn probe() -> Self {
+        Self::from_env(
+            env::var("TERM").ok().as_deref(),
+            env::var_os("NO_COLOR").is_some(),
+            io::stdout().is_tty(),
+        )
+    }
+
+    /// `term` is the value of `TERM` and `no_color` whether `NO_COLOR` is set.
+    pub fn from_env(term: Option<&str>, no_color: bool, is_tty: bool) -> Self {
+        let capable = is_tty && !matches!(term, None | Some("") | Some("dumb"));
+
+        Self {
+            styles: capable && !no_color,
+            alternate_screen: capable,
+        }
+    }
+
+    /// Plain text only
+    pub fn none() -> Self {
+        Self {
+            styles: false,
+            alternate_screen: false,
+        }
+    }
+
+    /// Set `attribute`, or do nothing when the terminal can't show it.
+    pub fn style(&self, attribute: Attribute) -> SetStyle {
+        SetStyle {
+            attribute,
+            enabled: self.styles,
+        }
+    }
+
+    /// Set the background `color`, or do nothing when the terminal can't show it.
+    pub fn background(&self, color: Color) -> SetBackground {
+        SetBackground {
+            color,
+            enabled: self.styles,
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            styles: true,
+            alternate_screen: true,
+        }
+    }
+}
+
+/// `SetAttribute` that writes nothing when it isn't `enabled`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetStyle {
+    attribute: Attribute,
+    enabled: bool,
+}
+
+impl Command for SetStyle {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        if self.enabled {
+            SetAttribute(self.attribute).write_ansi(f)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> crossterm::Result<()> {
+        if self.enabled {
+            SetAttribute(self.attribute).execute_winapi()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// `SetBackgroundColor` that writes nothing when it isn't `enabled`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetBackground {
+    color: Color,
+    enabled: bool,
+}
+
+impl Command for SetBackground {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        if self.enabled {
+            SetBackgroundColor(self.color).write_ansi(f)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> crossterm::Result<()> {
+        if self.enabled {
+            SetBackgroundColor(self.color).execute_winapi()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case(Some("xterm-256color"), false, true => (true, true); "Terminal")]
+    #[test_case(Some("xterm-256color"), true, true => (false, true); "No color")]
+    #[test_case(Some("dumb"), false, true => (false, false); "Dumb terminal")]
+    #[test_case(None, false, true => (false, false); "No TERM")]
+    #[test_case(Some("xterm"), false, false => (false, false); "Redirected")]
+    fn capabilities_should_follow_the_environment(
+        term: Option<&str>,
+        no_color: bool,
+        is_tty: bool,
+    ) -> (bool, bool) {
+        let capabilities = Capabilities::from_env(term, no_color, is_tty);
+
+        (capabilities.styles, capabilities.alternate_screen)
+    }
+}
@@ -0,0 +1,209 @@
+use std::io::{self, Write};
+
+use crate::{
+    buffer::Row,
+    component::{draw_lines, Component, EventOutcome, Focus},
+    input::{CursorEvent, InputEvent},
+    text::ConsoleWidthStr,
+    undo::EditOp,
+    Position,
+};
+
+const MAX_TEXT_DISPLAY: usize = 40;
+
+/// Where an edit in the undo history happened and what it did.
+#[derive(Debug, Clone)]
+pub struct Change {
+    position: Position,
+    kind: &'static str,
+    text: String,
+}
+
+impl Change {
+    /// Describe `op` with its column measured against the current `rows`.
+    pub fn new(op: &EditOp, rows: &[Row]) -> Self {
+        let (kind, row, index, text) = match op {
+            EditOp::Insert { row, index, text } => ("insert", *row, *index, text.clone()),
+            EditOp::Delete { row, index, text } => ("delete", *row, *index, text.clone()),
+            EditOp::TransposeChars { row, index } => {
+                let pair = rows
+                    .get(*row)
+                    .and_then(|line| line.buffer().get(*index..))
+                    .map(|rest| rest.chars().take(2).collect())
+                    .unwrap_or_default();
+                ("transpose", *row, *index, pair)
+            }
+            EditOp::TransposeRows { row } => ("transpose", *row, 0, String::new()),
+            EditOp::SplitRow { row, index } => ("newline", *row, *index, String::new()),
+            EditOp::JoinRows { row, index } => ("join", *row, *index, String::new()),
+            EditOp::ReplaceParagraph { row, new, .. } => (
+                "paragraph",
+                *row,
+                0,
+                new.first().cloned().unwrap_or_default(),
+            ),
+        };
+
+        let column = rows
+            .get(row)
+            .and_then(|line| line.buffer().get(..index))
+            .map(|prefix| prefix.column_width())
+            .unwrap_or(0);
+
+        Self {
+            position: Position(column as u16, row as u16),
+            kind,
+            text,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+}
+
+/// A read-only list of the most recent changes, newest first. Selecting an entry jumps to it.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeList {
+    changes: Vec<Change>,
+    selected: usize,
+    chosen: Option<Position>,
+}
+
+impl ChangeList {
+    pub fn new(changes: Vec<Change>) -> Self {
+        Self {
+            changes,
+            ..Default::default()
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// The position of the entry that was picked with Enter, if any
+    pub fn chosen(&self) -> Option<Position> {
+        self.chosen
+    }
+
+    /// One line per change like `> 12:4   insert "foo"`, the selected one is marked with `>`.
+    pub fn lines(&self) -> Vec<String> {
+        if self.changes.is_empty() {
+            return vec![String::from("No changes")];
+        }
+
+        self.changes
+            .iter()
+            .enumerate()
+            .map(|(i, change)| {
+                let marker = if i == self.selected { '>' } else { ' ' };
+                let text = change.text.escape_debug().to_string();
+                let text = if text.chars().count() > MAX_TEXT_DISPLAY {
+                    let truncated = text.chars().take(MAX_TEXT_DISPLAY - 3).collect::<String>();
+                    format!("{truncated}...")
+                } else {
+                    text
+                };
+
+                format!(
+                    "{marker} {:<8} {:<6} \"{text}\"",
+                    change.position.to_string(),
+                    change.kind
+                )
+            })
+            .collect()
+    }
+}
+
+impl Component for ChangeList {
+    fn handle_event(&mut self, event: InputEvent) -> EventOutcome {
+        match event {
+            InputEvent::CursorEvent(CursorEvent::MoveUp) => {
+                self.selected = self.selected.saturating_sub(1);
+                EventOutcome::consumed()
+            }
+            InputEvent::CursorEvent(CursorEvent::MoveDown) => {
+                self.selected = (self.selected + 1).min(self.changes.len().saturating_sub(1));
+                EventOutcome::consumed()
+            }
+            InputEvent::InsertNewline => {
+                self.chosen = self.changes.get(self.selected).map(Change::position);
+                EventOutcome::consumed().with_focus(Focus::Buffer)
+            }
+            InputEvent::Cancel => EventOutcome::consumed().with_focus(Focus::Buffer),
+            _ => EventOutcome::ignored(),
+        }
+    }
+
+    fn draw(&self, writer: &mut dyn Write, cols: u16, rows: u16) -> io::Result<()> {
+        // Keep the selected entry on screen
+        let offset = self
+            .selected
+            .saturating_sub(rows.saturating_sub(1) as usize);
+        draw_lines(writer, &self.lines(), offset, cols, rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(row: usize, index: usize, text: &str) -> EditOp {
+        EditOp::Insert {
+            row,
+            index,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn changes_should_be_listed_with_row_column_and_text() {
+        let rows = vec![Row::new("⛄ab")];
+        let list = ChangeList::new(vec![Change::new(&insert(0, 3, "a"), &rows)]);
+
+        assert_eq!(list.lines(), vec!["> 1:3      insert \"a\""]);
+    }
+
+    #[test]
+    fn long_text_should_be_truncated() {
+        let text = "x".repeat(50);
+        let list = ChangeList::new(vec![Change::new(&insert(0, 0, &text), &[])]);
+
+        assert!(list.lines()[0].ends_with(&format!("\"{}...\"", "x".repeat(37))));
+    }
+
+    #[test]
+    fn enter_should_choose_the_selected_change() {
+        let rows = vec![Row::new("abc"), Row::new("def")];
+        let mut list = ChangeList::new(vec![
+            Change::new(&insert(1, 2, "f"), &rows),
+            Change::new(&insert(0, 1, "b"), &rows),
+        ]);
+
+        list.handle_event(InputEvent::CursorEvent(CursorEvent::MoveDown));
+        let outcome = list.handle_event(InputEvent::InsertNewline);
+
+        assert_eq!(outcome.focus(), Some(Focus::Buffer));
+        assert_eq!(list.chosen().map(|p| (p.0, p.1)), Some((1, 0)));
+    }
+
+    #[test]
+    fn drawing_should_keep_the_selected_change_on_screen() {
+        let rows = vec![Row::new("abc")];
+        let changes = (0..3)
+            .map(|i| Change::new(&insert(0, i, "x"), &rows))
+            .collect();
+        let mut list = ChangeList::new(changes);
+        list.handle_event(InputEvent::CursorEvent(CursorEvent::MoveDown));
+        list.handle_event(InputEvent::CursorEvent(CursorEvent::MoveDown));
+
+        let mut output = Vec::new();
+        list.draw(&mut output, 10, 2).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("  1:2     "));
+        assert!(output.contains("> 1:3     "));
+        assert!(!output.contains("1:1"));
+    }
+}
@@ -0,0 +1,97 @@
+//! The current time, read from the system or set by hand so tests don't have to wait.
+
+use std::{
+    cell::Cell,
+    fmt,
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+
+/// Where the current time comes from. Clones of a fixed clock share its time, advancing one
+/// advances them all.
+#[derive(Clone, Default)]
+pub struct Clock {
+    fixed: Option<Rc<Cell<SystemTime>>>,
+}
+
+impl Clock {
+    /// The time of the system
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// A clock standing still at `time` until it is advanced
+    pub fn fixed(time: SystemTime) -> Self {
+        Self {
+            fixed: Some(Rc::new(Cell::new(time))),
+        }
+    }
+
+    pub fn now(&self) -> SystemTime {
+        match &self.fixed {
+            Some(time) => time.get(),
+            None => SystemTime::now(),
+        }
+    }
+
+    /// Move a fixed clock `by` ahead. The system clock moves on by itself.
+    pub fn advance(&self, by: Duration) {
+        if let Some(time) = &self.fixed {
+            time.set(time.get() + by);
+        }
+    }
+
+    /// How long ago `time` was, nothing when it's in the future.
+    pub fn since(&self, time: SystemTime) -> Duration {
+        self.now().duration_since(time).unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.fixed {
+            Some(time) => write!(f, "Clock::fixed({:?})", time.get()),
+            None => f.write_str("Clock::system()"),
+        }
+    }
+}
+
+/// `age` in its largest whole unit, like `12s`, `5m`, `3h` or `2d`.
+pub fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..=59 => format!("{seconds}s"),
+        60..=3_599 => format!("{}m", seconds / 60),
+        3_600..=86_399 => format!("{}h", seconds / 3_600),
+        _ => format!("{}d", seconds / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case(0 => "0s"; "Just now")]
+    #[test_case(59 => "59s"; "Seconds")]
+    #[test_case(60 => "1m"; "A minute")]
+    #[test_case(7_199 => "1h"; "Rounded down")]
+    #[test_case(200_000 => "2d"; "Days")]
+    fn ages_should_be_given_in_their_largest_unit(seconds: u64) -> String {
+        format_age(Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn clones_of_a_fixed_clock_should_advance_together() {
+        let clock = Clock::fixed(UNIX_EPOCH);
+        let clone = clock.clone();
+
+        clock.advance(Duration::from_secs(3));
+
+        assert_eq!(clone.since(UNIX_EPOCH), Duration::from_secs(3));
+        assert_eq!(clone.since(UNIX_EPOCH + Duration::from_secs(10)), Duration::ZERO);
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    queue,
+    terminal::{Clear, ClearType},
+};
+
+use crate::{input::InputEvent, text::char_index};
+
+/// The part of the editor that currently receives input events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Focus {
+    #[default]
+    Buffer,
+    Prompt,
+    ChangeList,
+    MessageLog,
+    /// Asking whether to replace the current match
+    Replace,
+    /// Asking whether to quit without saving
+    Quit,
+}
+
+/// Where a component is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Area {
+    /// In place of the rows of text
+    Rows,
+    /// On the message bar, below the status bar
+    MessageBar,
+}
+
+/// What happened when a component was handed an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EventOutcome {
+    consumed: bool,
+    rejected: bool,
+    focus: Option<Focus>,
+}
+
+impl EventOutcome {
+    pub fn consumed() -> Self {
+        Self {
+            consumed: true,
+            ..Default::default()
+        }
+    }
+
+    /// The event was meant for this component but couldn't do anything, like moving left
+    /// at the start of the buffer.
+    pub fn rejected() -> Self {
+        Self {
+            consumed: true,
+            rejected: true,
+            focus: None,
+        }
+    }
+
+    pub fn ignored() -> Self {
+        Self::default()
+    }
+
+    /// Request that focus moves to another component after this event.
+    pub fn with_focus(mut self, focus: Focus) -> Self {
+        self.focus = Some(focus);
+        self
+    }
+
+    pub fn is_consumed(&self) -> bool {
+        self.consumed
+    }
+
+    pub fn is_rejected(&self) -> bool {
+        self.rejected
+    }
+
+    pub fn focus(&self) -> Option<Focus> {
+        self.focus
+    }
+}
+
+/// A focusable piece of the editor that handles its own events and knows how
+/// to draw itself.
+pub trait Component {
+    fn handle_event(&mut self, event: InputEvent) -> EventOutcome;
+    /// Draw into an area `cols` wide and `rows` high, starting where the terminal's cursor
+    /// is.
+    fn draw(&self, writer: &mut dyn Write, cols: u16, rows: u16) -> io::Result<()>;
+
+    fn area(&self) -> Area {
+        Area::Rows
+    }
+}
+
+/// Draw `lines` from `offset` on, one to a row and cut to `cols`, blanking the rows left
+/// over.
+pub fn draw_lines(
+    mut writer: &mut dyn Write,
+    lines: &[String],
+    offset: usize,
+    cols: u16,
+    rows: u16,
+) -> io::Result<()> {
+    for i in 0..rows as usize {
+        if let Some(line) = lines.get(offset + i) {
+            let index = char_index(cols as usize, line);
+            write!(writer, "{}", &line[..index])?;
+        }
+
+        queue!(&mut writer, Clear(ClearType::UntilNewLine))?;
+        write!(writer, "\r\n")?;
+    }
+
+    Ok(())
+}
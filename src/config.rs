@@ -0,0 +1,152 @@
+use std::{error::Error, fmt, fs, path::Path, str::FromStr};
+
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{bell::Bell, buffer::MAX_TAB_STOP, quit::QuitConfirm};
+
+/// Editor settings from a file of `name = value` lines. A setting the file leaves out is
+/// left as it is when the config is applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub tab_width: Option<usize>,
+    pub soft_tabs: Option<bool>,
+    pub auto_indent: Option<bool>,
+    pub auto_close_quotes: Option<bool>,
+    pub soft_wrap: Option<bool>,
+    pub line_numbers: Option<bool>,
+    pub bell: Option<Bell>,
+    pub quit_confirm: Option<QuitConfirm>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    FailedToOpen(String),
+    InvalidLine { line: usize },
+    UnknownSetting { line: usize },
+    InvalidValue { line: usize },
+}
+
+impl Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FailedToOpen(path) => write!(f, "Failed to open the config {path}"),
+            Self::InvalidLine { line } => write!(f, "Expected name = value on line {line}"),
+            Self::UnknownSetting { line } => write!(f, "Unknown setting on line {line}"),
+            Self::InvalidValue { line } => write!(f, "Invalid value on line {line}"),
+        }
+    }
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref().to_string_lossy().to_string();
+        let content = fs::read_to_string(&path)
+            .report()
+            .change_context_lazy(|| ConfigError::FailedToOpen(path.clone()))?;
+
+        content.parse().report()
+    }
+}
+
+fn parse_switch(value: &str) -> Option<bool> {
+    match value {
+        "on" | "true" => Some(true),
+        "off" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+impl FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut config = Self::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let line_number = i + 1;
+            let (name, value) = line
+                .split_once('=')
+                .map(|(name, value)| (name.trim(), value.trim()))
+                .ok_or(ConfigError::InvalidLine { line: line_number })?;
+            let invalid = || ConfigError::InvalidValue { line: line_number };
+            match name {
+                "tab-width" => {
+                    let tab_stop = value
+                        .parse()
+                        .ok()
+                        .filter(|tab_stop| (1..=MAX_TAB_STOP).contains(tab_stop));
+                    config.tab_width = Some(tab_stop.ok_or_else(invalid)?);
+                }
+                "soft-tabs" => config.soft_tabs = Some(parse_switch(value).ok_or_else(invalid)?),
+                "auto-indent" => {
+                    config.auto_indent = Some(parse_switch(value).ok_or_else(invalid)?)
+                }
+                "auto-close-quotes" => {
+                    config.auto_close_quotes = Some(parse_switch(value).ok_or_else(invalid)?)
+                }
+                "soft-wrap" => config.soft_wrap = Some(parse_switch(value).ok_or_else(invalid)?),
+                "line-numbers" => {
+                    config.line_numbers = Some(parse_switch(value).ok_or_else(invalid)?)
+                }
+                "bell" => config.bell = Some(value.parse().map_err(|_| invalid())?),
+                "quit-confirm" => config.quit_confirm = Some(value.parse().map_err(|_| invalid())?),
+                _ => return Err(ConfigError::UnknownSetting { line: line_number }),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test]
+    fn config_should_parse_every_setting() {
+        let config = "# Defaults for every file\n\
+                      tab-width = 4\n\
+                      soft-tabs = on\n\
+                      auto-indent = on\n\
+                      auto-close-quotes = off\n\
+                      \n\
+                      soft-wrap=off\n\
+                      line-numbers = true\n\
+                      bell = visual\n\
+                      quit-confirm = prompt\n"
+            .parse::<Config>();
+
+        assert_eq!(
+            config,
+            Ok(Config {
+                tab_width: Some(4),
+                soft_tabs: Some(true),
+                auto_indent: Some(true),
+                auto_close_quotes: Some(false),
+                soft_wrap: Some(false),
+                line_numbers: Some(true),
+                bell: Some(Bell::Visual),
+                quit_confirm: Some(QuitConfirm::Prompt),
+            })
+        );
+    }
+
+    #[test_case("tab-width 4" => ConfigError::InvalidLine { line: 2 }; "Missing equals")]
+    #[test_case("theme = dark" => ConfigError::UnknownSetting { line: 2 }; "Unknown")]
+    #[test_case("tab-width = 0" => ConfigError::InvalidValue { line: 2 }; "Zero tab width")]
+    #[test_case("soft-wrap = maybe" => ConfigError::InvalidValue { line: 2 }; "Not a switch")]
+    #[test_case("bell = loud" => ConfigError::InvalidValue { line: 2 }; "Unknown bell")]
+    fn invalid_lines_should_be_reported_with_their_number(line: &str) -> ConfigError {
+        format!("soft-tabs = on\n{line}")
+            .parse::<Config>()
+            .unwrap_err()
+    }
+}
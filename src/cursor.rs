@@ -1,4 +1,14 @@
-use crate::{text::{ConsoleWidthChar, ConsoleWidthStr, nth_position_width}, Position};
+use std::{collections::HashSet, fmt, ops::Range};
+
+use crate::{
+    buffer::Row,
+    screen::Screen,
+    text::{
+        char_index, column_at_display, next_word_start, nth_position_width, previous_word_start,
+        ConsoleWidthChar, ConsoleWidthStr,
+    },
+    Position,
+};
 
 pub trait Cursor {
     fn x(&self) -> u16;
@@ -6,7 +16,12 @@ pub trait Cursor {
 }
 
 pub trait CursorMovement:
-    VerticalMovement + HorizontalMovement + LineMovement + PageMovement
+    VerticalMovement
+    + HorizontalMovement
+    + LineMovement
+    + PageMovement
+    + ScreenMovement
+    + CountedMovement
 {
 }
 
@@ -30,9 +45,38 @@ pub trait PageMovement {
     fn bottom(&mut self);
 }
 
+/// Jump to a row currently visible on the screen without scrolling it.
+pub trait ScreenMovement {
+    fn screen_top(&mut self);
+    fn screen_middle(&mut self);
+    fn screen_bottom(&mut self);
+}
+
+/// Motions repeated `count` times. The destination is computed directly instead of
+/// stepping once per count so huge counts are as cheap as small ones.
+pub trait CountedMovement {
+    /// Move left within the line, stopping at its beginning.
+    fn left_by(&mut self, count: u16);
+    /// Move right within the line, stopping at its end.
+    fn right_by(&mut self, count: u16);
+    fn up_by(&mut self, count: u16);
+    fn down_by(&mut self, count: u16);
+    fn pages_up(&mut self, count: u16);
+    fn pages_down(&mut self, count: u16);
+    /// Go to the one based `line`, clamped to the last line of the buffer.
+    fn goto_line(&mut self, line: u16);
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct StaticCursor(u16, u16);
 
+/// Shows the cursor as `row:col` counted from 1, the same as `Position`.
+impl fmt::Display for StaticCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Position(self.0, self.1).fmt(f)
+    }
+}
+
 impl Cursor for StaticCursor {
     fn x(&self) -> u16 {
         self.0
@@ -43,11 +87,27 @@ impl Cursor for StaticCursor {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct BoundedCursor {
     position: Position,
     buffer: crate::RowBufferRef,
     screen: crate::ScreenRef,
+    wrap_horizontal: bool,
+    virtual_edit: bool,
+    tab_stop: usize,
+}
+
+impl Default for BoundedCursor {
+    fn default() -> Self {
+        Self {
+            position: Default::default(),
+            buffer: Default::default(),
+            screen: Default::default(),
+            wrap_horizontal: true,
+            virtual_edit: false,
+            tab_stop: crate::TAB_STOP,
+        }
+    }
 }
 
 impl BoundedCursor {
@@ -63,13 +123,180 @@ impl BoundedCursor {
         self.buffer
             .borrow()
             .get(self.position.1 as usize)
-            .map(|row| render_cursor(row.buffer(), self.x() as usize, crate::TAB_STOP))
-            .unwrap_or(0)
+            .map(|row| {
+                // Columns in virtual space past the end of the line are one cell each
+                match (self.x() as usize).checked_sub(row.buffer().column_width()) {
+                    Some(past_end) => row.display_width(self.tab_stop) + past_end,
+                    None => render_cursor(row.buffer(), self.x() as usize, self.tab_stop),
+                }
+            })
+            .unwrap_or(self.x() as usize)
+    }
+
+    /// The position and the column it's drawn in on one line, like
+    /// `Cursor{x=5, y=3, render_x=7}`, for messages of failing tests.
+    pub fn debug_dump(&self) -> String {
+        format!(
+            "Cursor{{x={}, y={}, render_x={}}}",
+            self.x(),
+            self.y(),
+            self.render()
+        )
+    }
+
+    /// Move to the start of the next word on the line, or to the start of the next line
+    /// from the end of one. Words are letters, digits and `word_chars`.
+    pub fn word_right(&mut self, word_chars: &HashSet<char>) {
+        let buffer = self.buffer.borrow();
+        let y = self.position.1 as usize;
+        let line = match buffer.get(y) {
+            Some(row) => row.buffer(),
+            None => return,
+        };
+
+        let index = char_index(self.position.0 as usize, line);
+        if index < line.len() {
+            let next = next_word_start(line, index, word_chars);
+            self.position.0 = line[..next].column_width() as u16;
+        } else if y + 1 < buffer.len() {
+            self.position = Position(0, y as u16 + 1);
+        }
+    }
+
+    /// Move to the start of the word before the cursor on the line, or to the end of the
+    /// previous line from the start of one.
+    pub fn word_left(&mut self, word_chars: &HashSet<char>) {
+        let buffer = self.buffer.borrow();
+        let y = self.position.1 as usize;
+        let line = buffer.get(y).map_or("", |row| row.buffer());
+
+        let index = char_index(self.position.0 as usize, line);
+        if index > 0 {
+            let start = previous_word_start(line, index, word_chars);
+            self.position.0 = line[..start].column_width() as u16;
+        } else if let Some(above) = y.checked_sub(1).and_then(|y| buffer.get(y)) {
+            self.position = Position(above.buffer().column_width() as u16, y as u16 - 1);
+        }
+    }
+
+    /// Move down one screen row through `rows` wrapped at the width of `screen`, which may
+    /// keep the cursor on the same line. Returns false on the last screen row.
+    pub fn visual_down(&mut self, screen: &Screen, rows: &[Row], tab_stop: usize) -> bool {
+        self.visual_move(true, screen, rows, tab_stop, screen.cols() as usize)
+    }
+
+    /// Move up one screen row through `rows` wrapped at the width of `screen`. Returns false
+    /// on the first screen row.
+    pub fn visual_up(&mut self, screen: &Screen, rows: &[Row], tab_stop: usize) -> bool {
+        self.visual_move(false, screen, rows, tab_stop, screen.cols() as usize)
+    }
+
+    /// Move a screen row down or up through `rows` wrapped at `cols` columns, keeping the
+    /// cursor in the same column of the screen where the row is long enough. `cols` is
+    /// narrower than `screen` when a gutter takes part of it.
+    pub fn visual_move(
+        &mut self,
+        down: bool,
+        screen: &Screen,
+        rows: &[Row],
+        tab_stop: usize,
+        cols: usize,
+    ) -> bool {
+        let wrap = |y: usize| match rows.get(y) {
+            Some(line) => screen.wrap_segments(line, cols, tab_stop),
+            None => (vec![Range::default()], 0),
+        };
+
+        let y = self.position.1 as usize;
+        let (segments, indent) = wrap(y);
+        let render = self.render();
+        let row = segments
+            .iter()
+            .rposition(|segment| segment.start <= render)
+            .unwrap_or(0);
+        let col = match row {
+            0 => render,
+            _ => indent + render - segments[row].start,
+        };
+
+        let (y, row) = match down {
+            true if row + 1 < segments.len() => (y, row + 1),
+            true if y < rows.len() => (y + 1, 0),
+            false if row > 0 => (y, row - 1),
+            false if y > 0 => (y - 1, wrap(y - 1).0.len() - 1),
+            _ => return false,
+        };
+
+        let (segments, indent) = wrap(y);
+        let segment = &segments[row];
+        let col = match row {
+            0 => col,
+            _ => col.saturating_sub(indent),
+        };
+        // Stay on this row instead of going past its end onto the next one
+        let last = match row + 1 < segments.len() {
+            true => segment.end - 1,
+            false => segment.end,
+        };
+        let render = (segment.start + col).min(last);
+        let x = rows
+            .get(y)
+            .map_or(0, |line| column_at_display(line.buffer(), render, tab_stop));
+        self.position = Position(x as u16, y as u16);
+
+        true
+    }
+
+    /// Columns between tab stops of the rows, for the column the cursor is drawn in
+    pub(crate) fn set_tab_stop(&mut self, tab_stop: usize) {
+        self.tab_stop = tab_stop;
+    }
+
+    /// Whether moving left or right past the line edges continues on the neighbouring line
+    /// or stops at the edge.
+    pub fn set_wrap_horizontal(&mut self, wrap: bool) {
+        self.wrap_horizontal = wrap;
+    }
+
+    /// Let the cursor move past the end of lines into virtual space instead of stopping or
+    /// wrapping at the edge. Vertical movement then keeps the column as well.
+    pub fn set_virtual_edit(&mut self, enabled: bool) {
+        self.virtual_edit = enabled;
     }
 
     pub fn position_mut(&mut self) -> &mut Position {
         &mut self.position
     }
+
+    /// Move to `position` clamped to the last row and the end of its line. Returns false when
+    /// the position had to be adjusted to fit.
+    pub fn jump_to(&mut self, position: Position) -> bool {
+        let buffer = self.buffer.borrow();
+        let row = position.1.min(buffer.len().saturating_sub(1) as u16);
+        let width = buffer
+            .get(row as usize)
+            .map(|line| line.buffer().column_width())
+            .unwrap_or(0) as u16;
+        self.position = Position(position.0.min(width), row);
+
+        (self.position.0, self.position.1) == (position.0, position.1)
+    }
+
+    /// Move to the row, clamped to the buffer, keeping the column within the new row.
+    fn jump_to_row(&mut self, row: u16) {
+        let buffer = self.buffer.borrow();
+        self.position.1 = row.min(buffer.len().min(u16::MAX as usize) as u16);
+        if self.virtual_edit {
+            return;
+        }
+
+        self.position.0 = self.position.0.min(
+            buffer
+                .get(self.position.1 as usize)
+                .map(|row| row.buffer().column_width())
+                .unwrap_or(0) as u16,
+        );
+    }
 }
 
 impl Cursor for BoundedCursor {
@@ -89,6 +316,12 @@ impl HorizontalMovement for BoundedCursor {
         let buf = self.buffer.borrow();
         let line = buf.get(self.position.1 as usize);
 
+        let column_width = line.map(|row| row.buffer().column_width()).unwrap_or(0) as u16;
+        if self.virtual_edit && self.position.0 > column_width {
+            self.position.0 -= 1;
+            return;
+        }
+
         let prev_width = line
             .map(|row| nth_position_width(row.buffer(), self.position.0.saturating_sub(1) as usize) as u16)
             .unwrap_or(1);
@@ -98,7 +331,7 @@ impl HorizontalMovement for BoundedCursor {
         // Check if we went past the beginning of the line and where we are in the y axis
         // to determine how we wrap around to the previous line
         match (overflowed, self.position.1) {
-            (true, 1..) => {
+            (true, 1..) if self.wrap_horizontal => {
                 self.position.1 -= 1;
 
                 // We can't use the old line variable here as we are switching lines
@@ -110,7 +343,7 @@ impl HorizontalMovement for BoundedCursor {
 
                 self.position.0 = column_width as u16;
             } // We are not at the top, wrap
-            (true, 0) => self.position.0 = 0, // We are at top and wrapped, keep at 0
+            (true, _) => self.position.0 = 0, // We are at top or not wrapping, keep at 0
             (false, _) => self.position.0 = value, // No wrapping needed, set the value
         }
     }
@@ -127,7 +360,10 @@ impl HorizontalMovement for BoundedCursor {
         let column_width = line.map(|row| row.buffer().column_width()).unwrap_or(0) as u16;
 
         match (value > column_width, self.position.1) {
-            (true, y) if y >= buf.len() as u16 => self.position.0 = column_width,
+            (true, _) if self.virtual_edit => self.position.0 = self.position.0.saturating_add(1),
+            (true, y) if y >= buf.len().min(u16::MAX as usize) as u16 || !self.wrap_horizontal => {
+                self.position.0 = column_width
+            }
             (true, y) => {
                 self.position.0 = 0;
                 self.position.1 = y + 1;
@@ -140,6 +376,9 @@ impl HorizontalMovement for BoundedCursor {
 impl VerticalMovement for BoundedCursor {
     fn up(&mut self) {
         self.position.1 = self.position.1.saturating_sub(1);
+        if self.virtual_edit {
+            return;
+        }
 
         let buffer = self.buffer.borrow();
         self.position.0 = self.position.0.min(
@@ -151,8 +390,11 @@ impl VerticalMovement for BoundedCursor {
     }
 
     fn down(&mut self) {
-        let last_line = self.buffer.borrow().len() as u16;
+        let last_line = self.buffer.borrow().len().min(u16::MAX as usize) as u16;
         self.position.1 = self.position.1.saturating_add(1).min(last_line);
+        if self.virtual_edit {
+            return;
+        }
 
         let buffer = self.buffer.borrow();
         self.position.0 = self.position.0.min(
@@ -180,21 +422,118 @@ impl LineMovement for BoundedCursor {
     }
 }
 
+// Screens of no rows and offsets near the ends of `u16` happen on tiny or resized terminals,
+// so the arithmetic here saturates instead of overflowing.
 impl PageMovement for BoundedCursor {
     fn top(&mut self) {
         let screen = self.screen.borrow();
-        self.position.1 = screen.row_offset();
-        self.position.1 = self.position.1.saturating_sub(screen.rows());
+        let lines = self.buffer.borrow().len().min(u16::MAX as usize) as u16;
+
+        self.position.1 = screen.row_offset().saturating_sub(screen.rows()).min(lines);
     }
 
     fn bottom(&mut self) {
         let screen = self.screen.borrow();
-        let lines = self.buffer.borrow().len() as u16;
-        let y = lines.min(screen.row_offset() + screen.rows() - 1);
+        let lines = self.buffer.borrow().len().min(u16::MAX as usize) as u16;
+        let last_shown = screen
+            .row_offset()
+            .saturating_add(screen.rows().saturating_sub(1));
+
+        self.position.1 = last_shown
+            .min(lines)
+            .saturating_add(screen.rows())
+            .min(lines);
+    }
+}
+
+impl ScreenMovement for BoundedCursor {
+    fn screen_top(&mut self) {
+        let row = self.screen.borrow().row_offset();
+        self.jump_to_row(row);
+    }
+
+    fn screen_middle(&mut self) {
+        let row = {
+            let screen = self.screen.borrow();
+            screen.row_offset().saturating_add(screen.rows() / 2)
+        };
+        self.jump_to_row(row);
+    }
+
+    fn screen_bottom(&mut self) {
+        let row = {
+            let screen = self.screen.borrow();
+            screen
+                .row_offset()
+                .saturating_add(screen.rows())
+                .saturating_sub(1)
+        };
+        self.jump_to_row(row);
+    }
+}
+
+impl CountedMovement for BoundedCursor {
+    fn left_by(&mut self, count: u16) {
+        let buffer = self.buffer.borrow();
+        if let Some(row) = buffer.get(self.position.1 as usize) {
+            let line = row.buffer();
+            let prefix = &line[..char_index(self.position.0 as usize, line)];
+            let start = prefix
+                .char_indices()
+                .rev()
+                .nth(count.saturating_sub(1) as usize)
+                .map(|(i, _)| i)
+                .unwrap_or(0);
+            self.position.0 = line[..start].column_width() as u16;
+        }
+    }
+
+    fn right_by(&mut self, count: u16) {
+        let buffer = self.buffer.borrow();
+        if let Some(row) = buffer.get(self.position.1 as usize) {
+            let line = row.buffer();
+            let index = char_index(self.position.0 as usize, line);
+            let end = line[index..]
+                .char_indices()
+                .nth(count as usize)
+                .map(|(i, _)| index + i)
+                .unwrap_or(line.len());
+            self.position.0 = line[..end].column_width() as u16;
+        }
+    }
+
+    fn up_by(&mut self, count: u16) {
+        self.jump_to_row(self.position.1.saturating_sub(count));
+    }
+
+    fn down_by(&mut self, count: u16) {
+        self.jump_to_row(self.position.1.saturating_add(count));
+    }
+
+    fn pages_up(&mut self, count: u16) {
+        let screen = self.screen.borrow();
+        self.position.1 = screen
+            .row_offset()
+            .saturating_sub(screen.rows().saturating_mul(count));
+    }
 
-        self.position.1 = y;
+    fn pages_down(&mut self, count: u16) {
+        let screen = self.screen.borrow();
+        let lines = self.buffer.borrow().len().min(u16::MAX as usize) as u16;
+        let last_shown = screen
+            .row_offset()
+            .saturating_add(screen.rows().saturating_sub(1));
+
+        self.position.1 = last_shown
+            .min(lines)
+            .saturating_add(screen.rows().saturating_mul(count))
+            .min(lines);
+    }
 
-        self.position.1 = self.position.1.saturating_add(screen.rows()).min(lines);
+    fn goto_line(&mut self, line: u16) {
+        let last_line =
+            (self.buffer.borrow().len().min(u16::MAX as usize) as u16).saturating_sub(1);
+        self.jump_to_row(line.saturating_sub(1).min(last_line));
     }
 }
 
@@ -220,3 +559,284 @@ fn render_cursor(buffer: &str, cursor: usize, tabstop: usize) -> usize {
         })
         .sum()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use quickcheck_macros::quickcheck;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        buffer::{Buffer, Row},
+        screen::Screen,
+    };
+
+    fn cursor_with(rows: usize, screen: Screen) -> BoundedCursor {
+        let buffer = (0..rows).map(|i| Row::new(format!("row {i}"))).collect();
+
+        let mut cursor = BoundedCursor::default();
+        cursor.set_buffer(Rc::new(RefCell::new(buffer)));
+        cursor.set_screen(Rc::new(RefCell::new(screen)));
+        cursor
+    }
+
+    fn scrolled_screen(rows: u16, row_offset: u16) -> Screen {
+        let mut screen = Screen::new(80, rows);
+        screen.scroll(0, row_offset + rows - 1, 0);
+        screen.scroll(0, row_offset, 0);
+        screen
+    }
+
+    #[test]
+    fn debug_dump_should_show_where_the_cursor_is_drawn() {
+        let mut cursor = cursor_with(0, Screen::new(80, 10));
+        cursor.set_buffer(Rc::new(RefCell::new(vec![Row::new("a\tbcdef")])));
+
+        cursor.jump_to(Position(3, 0));
+
+        assert_eq!(cursor.debug_dump(), "Cursor{x=3, y=0, render_x=9}");
+    }
+
+    fn visual_cursor(rows: &[Row], position: Position) -> (BoundedCursor, Screen) {
+        let mut cursor = cursor_with(0, Screen::new(10, 5));
+        cursor.set_buffer(Rc::new(RefCell::new(rows.to_vec())));
+        cursor.jump_to(position);
+        (cursor, Screen::new(10, 5))
+    }
+
+    #[test]
+    fn visual_movement_should_go_through_the_wrapped_rows_of_a_line() {
+        let rows = [Row::new("a".repeat(25)), Row::new("ab")];
+        let (mut cursor, screen) = visual_cursor(&rows, Position(3, 0));
+
+        let mut down = Vec::new();
+        while cursor.visual_down(&screen, &rows, 8) {
+            down.push((cursor.x(), cursor.y()));
+        }
+        let mut up = Vec::new();
+        while cursor.visual_up(&screen, &rows, 8) {
+            up.push((cursor.x(), cursor.y()));
+        }
+
+        assert_eq!(down, vec![(13, 0), (23, 0), (2, 1), (0, 2)]);
+        assert_eq!(up, vec![(0, 1), (20, 0), (10, 0), (0, 0)]);
+    }
+
+    #[test_case("\tabcdefghijkl", 0 => (3, 0); "Tab on the first row")]
+    #[test_case("abcdefghijkl", 9 => (12, 0); "Clamped to the end of the line")]
+    #[test_case("short", 2 => (2, 1); "Unwrapped line")]
+    fn visual_down_should_keep_the_screen_column(line: &str, x: u16) -> (u16, u16) {
+        let rows = [Row::new(line), Row::new("next line")];
+        let (mut cursor, screen) = visual_cursor(&rows, Position(x, 0));
+
+        cursor.visual_down(&screen, &rows, 8);
+
+        (cursor.x(), cursor.y())
+    }
+
+    #[test]
+    fn screen_movement_should_jump_to_visible_rows() {
+        let mut cursor = cursor_with(100, scrolled_screen(10, 20));
+
+        cursor.screen_top();
+        assert_eq!(cursor.y(), 20);
+
+        cursor.screen_middle();
+        assert_eq!(cursor.y(), 25);
+
+        cursor.screen_bottom();
+        assert_eq!(cursor.y(), 29);
+    }
+
+    #[test]
+    fn screen_bottom_should_clamp_to_buffer_length() {
+        let mut cursor = cursor_with(3, Screen::new(80, 10));
+
+        cursor.screen_bottom();
+
+        assert_eq!(cursor.y(), 3);
+    }
+
+    #[test_case(0, 0, 5 => (0, 0); "No rows")]
+    #[test_case(0, u16::MAX, 5 => (5, 5); "No rows far below the end")]
+    #[test_case(10, u16::MAX, 100 => (100, 100); "Offset at the end of u16")]
+    #[test_case(u16::MAX, 0, 3 => (0, 3); "Huge screen")]
+    fn page_movement_should_stay_in_the_buffer_at_the_extremes(
+        rows: u16,
+        row_offset: u16,
+        lines: usize,
+    ) -> (u16, u16) {
+        let mut screen = Screen::new(80, rows);
+        screen.set_offset(0, row_offset);
+        let mut cursor = cursor_with(lines, screen);
+
+        cursor.top();
+        let top = cursor.y();
+        cursor.bottom();
+
+        (top, cursor.y())
+    }
+
+    #[quickcheck]
+    fn page_movement_should_never_leave_the_buffer(
+        rows: u8,
+        reserved: u8,
+        row_offset: u16,
+        lines: u8,
+    ) -> bool {
+        let mut screen = Screen::new(80, (rows % 8) as u16);
+        screen.set_reserved_rows((reserved % 4) as u16);
+        screen.set_offset(0, row_offset);
+        let mut cursor = cursor_with(lines as usize, screen);
+
+        cursor.top();
+        let top = cursor.y();
+        cursor.bottom();
+
+        top <= lines as u16 && top <= cursor.y() && cursor.y() <= lines as u16
+    }
+
+    #[test]
+    fn screen_movement_should_keep_column_within_the_new_row() {
+        let mut cursor = cursor_with(3, Screen::new(80, 10));
+        cursor.position_mut().0 = 40;
+
+        cursor.screen_top();
+
+        assert_eq!((cursor.x(), cursor.y()), (5, 0));
+    }
+
+    #[test]
+    fn counted_horizontal_movement_should_stop_at_the_line_edges() {
+        let mut cursor = cursor_with(3, Screen::new(80, 10));
+        cursor.down();
+
+        cursor.right_by(300);
+        assert_eq!((cursor.x(), cursor.y()), (5, 1));
+
+        cursor.left_by(2);
+        assert_eq!(cursor.x(), 3);
+
+        cursor.left_by(300);
+        assert_eq!((cursor.x(), cursor.y()), (0, 1));
+    }
+
+    #[test]
+    fn goto_line_should_clamp_to_the_last_line() {
+        let mut cursor = cursor_with(100, Screen::new(80, 10));
+
+        cursor.goto_line(300);
+        assert_eq!(cursor.y(), 99);
+
+        cursor.goto_line(42);
+        assert_eq!(cursor.y(), 41);
+    }
+
+    #[test]
+    fn goto_line_should_reach_the_last_u16_line_of_huge_buffers() {
+        let mut cursor = cursor_with(70_000, Screen::new(80, 10));
+
+        cursor.goto_line(u16::MAX);
+
+        assert_eq!(cursor.y(), u16::MAX - 1);
+    }
+
+    #[test_case(u16::MAX, 10, 100, 1 => 100; "Huge screen")]
+    #[test_case(10, u16::MAX, 100, 1 => 100; "Offset at the end of u16")]
+    #[test_case(10, 0, 70_000, u16::MAX => u16::MAX; "Huge buffer")]
+    fn pages_down_should_stay_in_the_buffer_at_the_extremes(
+        rows: u16,
+        row_offset: u16,
+        lines: usize,
+        count: u16,
+    ) -> u16 {
+        let mut screen = Screen::new(80, rows);
+        screen.set_offset(0, row_offset);
+        let mut cursor = cursor_with(lines, screen);
+
+        cursor.pages_down(count);
+
+        cursor.y()
+    }
+
+    #[test_case(true => (5, 0); "wrapping")]
+    #[test_case(false => (0, 1); "stopping")]
+    fn left_at_the_start_of_a_line(wrap: bool) -> (u16, u16) {
+        let mut cursor = cursor_with(3, Screen::new(80, 10));
+        cursor.set_wrap_horizontal(wrap);
+        cursor.down();
+
+        cursor.left();
+
+        (cursor.x(), cursor.y())
+    }
+
+    #[test_case(true => (0, 2); "wrapping")]
+    #[test_case(false => (5, 1); "stopping")]
+    fn right_at_the_end_of_a_line(wrap: bool) -> (u16, u16) {
+        let mut cursor = cursor_with(3, Screen::new(80, 10));
+        cursor.set_wrap_horizontal(wrap);
+        cursor.down();
+        cursor.end();
+
+        cursor.right();
+
+        (cursor.x(), cursor.y())
+    }
+
+    #[test_case((0, 0), false => (0, 0); "Left at the start of the buffer")]
+    #[test_case((0, 1), false => (4, 0); "Left onto the end of a wide line")]
+    #[test_case((2, 0), true => (4, 0); "Right over a wide character")]
+    #[test_case((4, 0), true => (0, 1); "Right off the end of a line")]
+    #[test_case((3, 1), true => (0, 2); "Right off the last line onto the one past it")]
+    #[test_case((0, 2), true => (0, 2); "Right past the end of the buffer")]
+    fn horizontal_movement_should_wrap_at_the_line_ends(
+        start: (u16, u16),
+        right: bool,
+    ) -> (u16, u16) {
+        let mut buffer = "日本\nabc".parse::<Buffer>().unwrap();
+        let mut cursor = buffer.take_cursor();
+        cursor.set_buffer(Rc::clone(buffer.buffer()));
+        *cursor.position_mut() = Position(start.0, start.1);
+
+        match right {
+            true => cursor.right(),
+            false => cursor.left(),
+        }
+
+        (cursor.x(), cursor.y())
+    }
+
+    #[test_case(true => 8; "virtual edit")]
+    #[test_case(false => 5; "stopping at the end")]
+    fn right_past_the_end_of_a_line(virtual_edit: bool) -> u16 {
+        let mut cursor = cursor_with(3, Screen::new(80, 10));
+        cursor.set_wrap_horizontal(false);
+        cursor.set_virtual_edit(virtual_edit);
+
+        for _ in 0..8 {
+            cursor.right();
+        }
+
+        cursor.x()
+    }
+
+    #[test]
+    fn virtual_edit_should_keep_the_column_when_moving_vertically() {
+        let mut cursor = cursor_with(3, Screen::new(80, 10));
+        cursor.set_virtual_edit(true);
+        cursor.position_mut().0 = 12;
+
+        cursor.down();
+        cursor.left();
+
+        assert_eq!((cursor.x(), cursor.y(), cursor.render()), (11, 1, 11));
+    }
+
+    #[test]
+    fn static_cursor_should_display_like_a_position() {
+        assert_eq!(StaticCursor(6, 41).to_string(), "42:7");
+    }
+}
@@ -0,0 +1,248 @@
+//! Which lines differ from an earlier version of the text, for the signs in the gutter and
+//! the summary shown before overwriting a file changed on disk.
+
+use std::fmt;
+
+/// Largest number of old times new lines compared line by line, bigger changes are marked
+/// modified as a whole instead.
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+/// How a line differs from the earlier version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sign {
+    Added,
+    Modified,
+    /// Lines were removed right after this one
+    Removed,
+}
+
+impl Sign {
+    /// The character drawn in the gutter
+    pub fn symbol(&self) -> char {
+        match self {
+            Self::Added => '+',
+            Self::Modified => '~',
+            Self::Removed => '_',
+        }
+    }
+}
+
+/// The sign of every line of `new` that differs from `old`, ordered by line. Lines
+/// replacing removed ones are modified, any more than were removed are added.
+pub fn line_signs<T: AsRef<str>, U: AsRef<str>>(old: &[T], new: &[U]) -> Vec<(usize, Sign)> {
+    let same = |a: &T, b: &U| a.as_ref() == b.as_ref();
+    let (prefix, old, new) = trim_common(old, new, same);
+
+    let mut signs = Vec::new();
+    let mut hunk = Hunk::new(prefix);
+    if old.len() * new.len() > MAX_DIFF_CELLS {
+        hunk.removed = old.len();
+        hunk.added = new.len();
+    } else {
+        for step in edit_script(old, new, same) {
+            match step {
+                Step::Keep => {
+                    hunk.finish(&mut signs);
+                    hunk = Hunk::new(hunk.start + hunk.added + 1);
+                }
+                Step::Remove => hunk.removed += 1,
+                Step::Add => hunk.added += 1,
+            }
+        }
+    }
+    hunk.finish(&mut signs);
+
+    signs
+}
+
+/// How a text differs from an earlier version of it, in lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    /// The first line that differs, counted from 0
+    pub first: usize,
+}
+
+impl fmt::Display for DiffSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "+{} −{} lines, first at line {}",
+            self.added,
+            self.removed,
+            self.first + 1
+        )
+    }
+}
+
+/// How `new` differs from `old`, `None` when it doesn't. Lines can be anything that compares
+/// like them, such as a hash of each line. A modified line is one removed and one added.
+pub fn diff_summary<T: PartialEq>(old: &[T], new: &[T]) -> Option<DiffSummary> {
+    let (first, old, new) = trim_common(old, new, T::eq);
+    if old.is_empty() && new.is_empty() {
+        return None;
+    }
+
+    let (removed, added) = match old.len() * new.len() > MAX_DIFF_CELLS {
+        true => (old.len(), new.len()),
+        false => edit_script(old, new, T::eq).into_iter().fold(
+            (0, 0),
+            |(removed, added), step| match step {
+                Step::Keep => (removed, added),
+                Step::Remove => (removed + 1, added),
+                Step::Add => (removed, added + 1),
+            },
+        ),
+    };
+
+    Some(DiffSummary {
+        added,
+        removed,
+        first,
+    })
+}
+
+/// The number of lines `old` and `new` start with in common, and what is left of both
+/// without the lines they start and end with in common.
+fn trim_common<'a, T, U>(
+    old: &'a [T],
+    new: &'a [U],
+    same: impl Fn(&T, &U) -> bool,
+) -> (usize, &'a [T], &'a [U]) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| same(a, b)).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| same(a, b))
+        .count();
+
+    (
+        prefix,
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    )
+}
+
+/// Lines removed from before `start` and added from `start` on, with no kept line between.
+struct Hunk {
+    start: usize,
+    removed: usize,
+    added: usize,
+}
+
+impl Hunk {
+    fn new(start: usize) -> Self {
+        Self {
+            start,
+            removed: 0,
+            added: 0,
+        }
+    }
+
+    fn finish(&self, signs: &mut Vec<(usize, Sign)>) {
+        let modified = self.removed.min(self.added);
+        let lines = self.start..self.start + self.added;
+        signs.extend(lines.enumerate().map(|(i, line)| match i < modified {
+            true => (line, Sign::Modified),
+            false => (line, Sign::Added),
+        }));
+
+        if self.removed > self.added {
+            // Marked on the line before the gap, or the first line when the gap is at the top
+            let line = (self.start + self.added).saturating_sub(1);
+            if signs.last().map(|(last, _)| *last) != Some(line) {
+                signs.push((line, Sign::Removed));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Keep,
+    Remove,
+    Add,
+}
+
+/// The steps turning `old` into `new` keeping their longest common subsequence of lines
+fn edit_script<T, U>(old: &[T], new: &[U], same: impl Fn(&T, &U) -> bool) -> Vec<Step> {
+    // lengths[i][j] is the longest common subsequence of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if same(&old[i], &new[j]) {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut steps = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && same(&old[i], &new[j]) {
+            steps.push(Step::Keep);
+            i += 1;
+            j += 1;
+        } else if j == new.len()
+            || (i < old.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            steps.push(Step::Remove);
+            i += 1;
+        } else {
+            steps.push(Step::Add);
+            j += 1;
+        }
+    }
+
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case("a\nb\nc", "a\nb\nc" => Vec::<(usize, char)>::new(); "Unchanged")]
+    #[test_case("a\nc", "a\nb\nc" => vec![(1, '+')]; "Added")]
+    #[test_case("a\nb\nc", "a\nx\nc" => vec![(1, '~')]; "Modified")]
+    #[test_case("a\nb\nc", "a\nc" => vec![(0, '_')]; "Removed")]
+    #[test_case("a\nb\nc", "b\nc" => vec![(0, '_')]; "Removed at the top")]
+    #[test_case("a\nb", "x\ny\nz\nb" => vec![(0, '~'), (1, '+'), (2, '+')]; "Modified and added")]
+    #[test_case("a\nb\nc\nd", "x\nd\ne" => vec![(0, '~'), (2, '+')]; "Two hunks")]
+    fn signs_should_mark_the_changed_lines(old: &str, new: &str) -> Vec<(usize, char)> {
+        let old = old.lines().collect::<Vec<_>>();
+        let new = new.lines().collect::<Vec<_>>();
+
+        line_signs(&old, &new)
+            .into_iter()
+            .map(|(line, sign)| (line, sign.symbol()))
+            .collect()
+    }
+
+    #[test_case("a\nb\nc", "a\nb\nc" => None; "Unchanged")]
+    #[test_case("a\nb\nc", "a\nx\nc\nd\ne" => Some((3, 1, 1)); "Modified and added")]
+    #[test_case("a\nb\nc\nd", "a\nd" => Some((0, 2, 1)); "Removed")]
+    #[test_case("a", "" => Some((0, 1, 0)); "Emptied")]
+    fn summary_should_count_the_lines_added_and_removed(
+        old: &str,
+        new: &str,
+    ) -> Option<(usize, usize, usize)> {
+        let old = old.lines().collect::<Vec<_>>();
+        let new = new.lines().collect::<Vec<_>>();
+
+        diff_summary(&old, &new).map(|summary| (summary.added, summary.removed, summary.first))
+    }
+
+    #[test]
+    fn summary_should_name_the_first_line_counted_from_one() {
+        let summary = diff_summary(&[1, 2, 3], &[1, 5, 3, 4]).unwrap();
+
+        assert_eq!(summary.to_string(), "+2 −1 lines, first at line 2");
+    }
+}
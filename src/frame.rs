@@ -0,0 +1,104 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::{component::Focus, LineNumberStyle};
+
+/// Measurements of the last frame drawn by `Editor::refresh`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameStats {
+    pub duration: Duration,
+    pub rows_drawn: u16,
+    pub rows_total: u16,
+    pub bytes_written: usize,
+    pub events_processed: u64,
+}
+
+impl FrameStats {
+    /// Draw the counters on a single line of exactly `HUD_WIDTH` columns without allocating.
+    pub fn draw(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write!(
+            writer,
+            " {:>7}us {:>5}/{:<5}rows {:>7}B {:>7}ev ",
+            self.duration.as_micros().min(9_999_999),
+            self.rows_drawn,
+            self.rows_total,
+            self.bytes_written.min(9_999_999),
+            self.events_processed.min(9_999_999)
+        )
+    }
+}
+
+/// Columns taken by `FrameStats::draw`
+pub const HUD_WIDTH: u16 = 46;
+
+/// Everything the text area depends on. When it is unchanged since the last frame the rows
+/// on the terminal are still correct and drawing them can be skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RowsKey {
+    pub modification_count: u64,
+    pub annotation_changes: u64,
+    pub size: (u16, u16),
+    pub offset: (u16, u16),
+    pub gutter_width: u16,
+    pub line_numbers: LineNumberStyle,
+    pub indent_guides: bool,
+    pub focus: Focus,
+    /// Only set when the rows depend on where the cursor is
+    pub cursor: Option<(u16, u16)>,
+    /// The highlighted entry while the change list covers the rows
+    pub selected: Option<usize>,
+    pub hud: bool,
+}
+
+/// Passes everything through to the inner writer while counting the bytes.
+pub(crate) struct CountingWriter<'a, W: Write> {
+    inner: &'a mut W,
+    count: usize,
+}
+
+impl<'a, W: Write> CountingWriter<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hud_should_always_be_the_same_width() {
+        let small = FrameStats::default();
+        let large = FrameStats {
+            duration: Duration::from_secs(3600),
+            rows_drawn: u16::MAX,
+            rows_total: u16::MAX,
+            bytes_written: usize::MAX,
+            events_processed: u64::MAX,
+        };
+
+        for stats in [small, large] {
+            let mut output = Vec::new();
+            stats.draw(&mut output).unwrap();
+            assert_eq!(output.len(), HUD_WIDTH as usize);
+        }
+    }
+}
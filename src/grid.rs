@@ -0,0 +1,261 @@
+//! An off-screen copy of what the editor draws, for hosts that composite the editor into
+//! their own UI instead of giving it a terminal.
+//!
+//! The grid is filled by reading back the escape sequences a frame is drawn with, so it
+//! only understands the few the editor writes: moving the cursor, clearing to the end of
+//! the line, showing and hiding the cursor, the reverse, bold, dim and italic attributes
+//! and background colors.
+
+use unicode_width::UnicodeWidthChar;
+
+/// One column of the grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    /// What is shown in the cell, empty when the cell is covered by a wide character to
+    /// its left
+    pub symbol: String,
+    pub reverse: bool,
+    pub bold: bool,
+    pub dim: bool,
+    /// How strings are highlighted
+    pub italic: bool,
+    /// The index of the background color in the 256 color palette, `None` for the default
+    pub background: Option<u8>,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: String::from(" "),
+            reverse: false,
+            bold: false,
+            dim: false,
+            italic: false,
+            background: None,
+        }
+    }
+}
+
+/// A fixed number of columns and rows of styled cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    cols: u16,
+    rows: u16,
+    cells: Vec<Cell>,
+    cursor: Option<(u16, u16)>,
+}
+
+impl Grid {
+    /// A grid of blank cells
+    pub fn new(cols: u16, rows: u16) -> Self {
+        Self {
+            cols,
+            rows,
+            cells: vec![Cell::default(); cols as usize * rows as usize],
+            cursor: None,
+        }
+    }
+
+    /// Draw `output`, written for a terminal of the same size, on a blank grid.
+    pub fn from_ansi(cols: u16, rows: u16, output: &[u8]) -> Self {
+        let mut grid = Self::new(cols, rows);
+        Painter::new(&mut grid).paint(&String::from_utf8_lossy(output));
+        grid
+    }
+
+    pub fn cols(&self) -> u16 {
+        self.cols
+    }
+
+    pub fn rows(&self) -> u16 {
+        self.rows
+    }
+
+    /// The cell at column `x` of row `y`, `None` outside of the grid
+    pub fn cell(&self, x: u16, y: u16) -> Option<&Cell> {
+        (x < self.cols && y < self.rows).then(|| &self.cells[self.index(x, y)])
+    }
+
+    /// Where the cursor is shown, `None` when it's hidden
+    pub fn cursor(&self) -> Option<(u16, u16)> {
+        self.cursor
+    }
+
+    /// The symbols of row `y` without their styles
+    pub fn row_text(&self, y: u16) -> String {
+        (0..self.cols)
+            .filter_map(|x| self.cell(x, y))
+            .map(|cell| cell.symbol.as_str())
+            .collect()
+    }
+
+    fn index(&self, x: u16, y: u16) -> usize {
+        y as usize * self.cols as usize + x as usize
+    }
+}
+
+/// Keeps the cursor and the current attributes while reading a frame.
+struct Painter<'a> {
+    grid: &'a mut Grid,
+    x: u16,
+    y: u16,
+    style: Cell,
+}
+
+impl<'a> Painter<'a> {
+    fn new(grid: &'a mut Grid) -> Self {
+        Self {
+            grid,
+            x: 0,
+            y: 0,
+            style: Cell::default(),
+        }
+    }
+
+    fn paint(&mut self, output: &str) {
+        let mut chars = output.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\x1b' if chars.next_if_eq(&'[').is_some() => {
+                    let mut params = String::new();
+                    while let Some(param) = chars.next_if(|ch| !ch.is_ascii_alphabetic()) {
+                        params.push(param);
+                    }
+                    if let Some(command) = chars.next() {
+                        self.control(&params, command);
+                    }
+                }
+                '\r' => self.x = 0,
+                '\n' => self.y = (self.y + 1).min(self.grid.rows.saturating_sub(1)),
+                ch if ch.is_control() => {}
+                ch => self.put(ch),
+            }
+        }
+    }
+
+    fn control(&mut self, params: &str, command: char) {
+        let numbers = || params.split(';').map(|n| n.parse::<u16>().unwrap_or(0));
+
+        match (params, command) {
+            (_, 'H') => {
+                let mut numbers = numbers().map(|n| n.saturating_sub(1));
+                self.y = numbers.next().unwrap_or(0);
+                self.x = numbers.next().unwrap_or(0);
+            }
+            ("" | "0", 'K') => {
+                for x in self.x..self.grid.cols {
+                    self.set(x, Cell::default());
+                }
+            }
+            (_, 'm') => {
+                let mut numbers = numbers();
+                while let Some(n) = numbers.next() {
+                    match n {
+                        0 => self.style = Cell::default(),
+                        1 => self.style.bold = true,
+                        2 => self.style.dim = true,
+                        3 => self.style.italic = true,
+                        7 => self.style.reverse = true,
+                        22 => {
+                            self.style.bold = false;
+                            self.style.dim = false;
+                        }
+                        23 => self.style.italic = false,
+                        27 => self.style.reverse = false,
+                        40..=47 => self.style.background = Some(n as u8 - 40),
+                        48 if numbers.next() == Some(5) => {
+                            self.style.background = numbers.next().map(|color| color as u8);
+                        }
+                        49 => self.style.background = None,
+                        _ => {}
+                    }
+                }
+            }
+            ("?25", 'l') => self.grid.cursor = None,
+            ("?25", 'h') => self.grid.cursor = Some((self.x, self.y)),
+            _ => {}
+        }
+    }
+
+    fn put(&mut self, ch: char) {
+        let width = ch.width().unwrap_or(0) as u16;
+        if width == 0 {
+            let (x, y) = (self.x.saturating_sub(1), self.y);
+            if let Some(index) = self.grid.cell(x, y).map(|_| self.grid.index(x, y)) {
+                self.grid.cells[index].symbol.push(ch);
+            }
+            return;
+        }
+
+        // Like the terminal, a wide character that doesn't fit isn't drawn at all
+        if self.x.saturating_add(width) <= self.grid.cols {
+            self.set(
+                self.x,
+                Cell {
+                    symbol: ch.to_string(),
+                    ..self.style.clone()
+                },
+            );
+            for x in self.x + 1..self.x + width {
+                self.set(
+                    x,
+                    Cell {
+                        symbol: String::new(),
+                        ..self.style.clone()
+                    },
+                );
+            }
+        }
+        self.x = self.x.saturating_add(width);
+    }
+
+    fn set(&mut self, x: u16, cell: Cell) {
+        if x < self.grid.cols && self.y < self.grid.rows {
+            let index = self.grid.index(x, self.y);
+            self.grid.cells[index] = cell;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_should_move_clear_and_style_the_cells() {
+        let grid = Grid::from_ansi(4, 2, b"abcd\x1b[1;2H\x1b[K\x1b[7m\xe4\xb8\xad\x1b[0m\r\nx");
+
+        assert_eq!(grid.row_text(0), "a中 ");
+        assert_eq!(grid.row_text(1), "x   ");
+        assert!(grid.cell(1, 0).unwrap().reverse);
+        assert!(!grid.cell(3, 0).unwrap().reverse);
+        assert_eq!(grid.cell(4, 0), None);
+    }
+
+    #[test]
+    fn attributes_should_be_turned_off_one_at_a_time() {
+        let grid = Grid::from_ansi(
+            6,
+            1,
+            b"\x1b[2m\x1b[3ma\x1b[22mb\x1b[23mc\x1b[7;48;5;1md\x1b[27;49me",
+        );
+
+        let styles = (0..5)
+            .map(|x| {
+                let cell = grid.cell(x, 0).unwrap();
+                (cell.dim, cell.italic, cell.reverse, cell.background)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            styles,
+            vec![
+                (true, true, false, None),
+                (false, true, false, None),
+                (false, false, false, None),
+                (false, false, true, Some(1)),
+                (false, false, false, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,682 @@
+//! Marking comments and strings in the rows. Lines are highlighted one at a time, with the
+//! state at the end of a line carried into the next one so a comment or string can span
+//! several lines.
+
+use std::{collections::BTreeMap, fmt, ops::Range, path::Path, rc::Rc};
+
+use crate::buffer::Row;
+
+/// What a line starts inside of, left open by the lines before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineState {
+    #[default]
+    Normal,
+    InComment,
+    InString,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Comment,
+    String,
+}
+
+/// Bytes of a line to be drawn as `kind`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub kind: SpanKind,
+}
+
+pub trait Highlighter: fmt::Debug {
+    /// The spans of `line`, ordered and not overlapping, when it starts in `state`. Along
+    /// with the state the next line starts in.
+    fn highlight_line(&self, line: &str, state: LineState) -> (Vec<Span>, LineState);
+
+    /// Whether typing `quote` at byte `index` of `line`, which starts in `state`, should
+    /// also insert the quote closing it. Never, unless the highlighter knows its quotes.
+    fn closes_quote(&self, _line: &str, _index: usize, _quote: char, _state: LineState) -> bool {
+        false
+    }
+}
+
+/// The delimiters of comments and strings that may span lines. Strings on a single line
+/// aren't told apart from the code around them, so a delimiter inside one still counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Syntax {
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// Both opens and closes a string, like `"""` in Python
+    pub multiline_string: Option<&'static str>,
+    /// Quotes closed as they are typed
+    pub quotes: &'static [char],
+    /// Quotes also used on their own, like an apostrophe or a Rust lifetime. These are only
+    /// closed where they start a word.
+    pub apostrophes: &'static [char],
+}
+
+impl Syntax {
+    /// `//` and `/* */` comments
+    pub const C: Self = Self {
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        multiline_string: None,
+        quotes: &['"', '\''],
+        apostrophes: &[],
+    };
+
+    /// Comments like C, with `'` starting lifetimes as well as characters
+    pub const RUST: Self = Self {
+        apostrophes: &['\''],
+        ..Self::C
+    };
+
+    /// `#` comments and `"""` strings
+    pub const PYTHON: Self = Self {
+        line_comment: Some("#"),
+        block_comment: None,
+        multiline_string: Some("\"\"\""),
+        quotes: &['"', '\''],
+        apostrophes: &[],
+    };
+
+    /// Prose without comments or strings, where `'` is mostly an apostrophe
+    pub const TEXT: Self = Self {
+        line_comment: None,
+        block_comment: None,
+        multiline_string: None,
+        quotes: &['"', '\''],
+        apostrophes: &['\''],
+    };
+
+    /// Whether the quote `text` starts with opens a string. An apostrophe only does as a
+    /// character like `'a'`, or one still being typed, and not as a lifetime like `'a>`.
+    fn opens_string(&self, text: &str) -> bool {
+        let mut chars = text.chars();
+        match chars.next() {
+            Some(quote) if self.apostrophes.contains(&quote) => match chars.next() {
+                Some('\\') | None => true,
+                Some(_) => !matches!(chars.next(), Some(ch) if ch != quote),
+            },
+            _ => true,
+        }
+    }
+
+    /// Where the region `state` is in ends, with the kind of the region
+    fn close(&self, state: LineState) -> Option<(&'static str, SpanKind)> {
+        match state {
+            LineState::Normal => None,
+            LineState::InComment => Some((self.block_comment?.1, SpanKind::Comment)),
+            LineState::InString => Some((self.multiline_string?, SpanKind::String)),
+        }
+    }
+
+    /// The first delimiter in `text` that starts a region, as its offset, length and the
+    /// state it starts
+    fn open(&self, text: &str) -> Option<(usize, usize, LineState)> {
+        let delimiters = [
+            self.line_comment.map(|open| (open, LineState::Normal)),
+            self.block_comment
+                .map(|(open, _)| (open, LineState::InComment)),
+            self.multiline_string
+                .map(|open| (open, LineState::InString)),
+        ];
+
+        delimiters
+            .into_iter()
+            .flatten()
+            .filter_map(|(open, state)| Some((text.find(open)?, open.len(), state)))
+            .min_by_key(|(offset, _, _)| *offset)
+    }
+}
+
+impl Highlighter for Syntax {
+    fn highlight_line(&self, line: &str, mut state: LineState) -> (Vec<Span>, LineState) {
+        let mut spans = Vec::new();
+        // Where the open region started, and where to look for its end from
+        let (mut start, mut pos) = (0, 0);
+
+        loop {
+            let (close, kind) = match self.close(state) {
+                Some(close) => close,
+                None => match self.open(&line[pos..]) {
+                    // A line comment runs to the end of the line and leaves nothing open
+                    Some((offset, _, LineState::Normal)) => {
+                        spans.push(Span {
+                            range: pos + offset..line.len(),
+                            kind: SpanKind::Comment,
+                        });
+                        return (spans, LineState::Normal);
+                    }
+                    Some((offset, len, opened)) => {
+                        start = pos + offset;
+                        pos = start + len;
+                        state = opened;
+                        continue;
+                    }
+                    None => return (spans, LineState::Normal),
+                },
+            };
+
+            match line[pos..].find(close) {
+                Some(offset) => {
+                    pos += offset + close.len();
+                    spans.push(Span {
+                        range: start..pos,
+                        kind,
+                    });
+                    state = LineState::Normal;
+                }
+                None => {
+                    spans.push(Span {
+                        range: start..line.len(),
+                        kind,
+                    });
+                    return (spans, state);
+                }
+            }
+        }
+    }
+
+    /// Not when the quote comes right after a word, like in `don't`, nor when the cursor is
+    /// in a string already, which the quote might be ending.
+    fn closes_quote(&self, line: &str, index: usize, quote: char, state: LineState) -> bool {
+        if !self.quotes.contains(&quote) {
+            return false;
+        }
+
+        let before = &line[..index];
+        let previous = before.chars().next_back();
+        if previous.is_some_and(|ch| ch.is_alphanumeric() || ch == '_') {
+            return false;
+        }
+        if self.apostrophes.contains(&quote)
+            && previous.is_some_and(|ch| !ch.is_whitespace() && !"([{".contains(ch))
+        {
+            return false;
+        }
+
+        let (spans, state) = self.highlight_line(before, state);
+        if state == LineState::InString {
+            return false;
+        }
+
+        // Strings on a single line aren't highlighted, look for one still open after the
+        // last region
+        let rest = &before[spans.last().map_or(0, |span| span.range.end)..];
+        let mut open = None;
+        let mut escaped = false;
+        for (i, ch) in rest.char_indices() {
+            match open {
+                Some(open_quote) if ch == open_quote && !escaped => open = None,
+                None if self.quotes.contains(&ch) && self.opens_string(&rest[i..]) => {
+                    open = Some(ch)
+                }
+                _ => {}
+            }
+            escaped = ch == '\\' && !escaped;
+        }
+
+        open.is_none()
+    }
+}
+
+/// The built in highlighters by the file extensions they are used for.
+pub fn default_highlighters() -> Vec<(&'static str, Rc<dyn Highlighter>)> {
+    let c: Rc<dyn Highlighter> = Rc::new(Syntax::C);
+    let rust: Rc<dyn Highlighter> = Rc::new(Syntax::RUST);
+    let python: Rc<dyn Highlighter> = Rc::new(Syntax::PYTHON);
+    let text: Rc<dyn Highlighter> = Rc::new(Syntax::TEXT);
+
+    ["c", "h", "cpp", "js", "ts", "java", "go"]
+        .into_iter()
+        .map(|extension| (extension, Rc::clone(&c)))
+        .chain([("rs", rust), ("py", python)])
+        .chain(["txt", "md"].map(|extension| (extension, Rc::clone(&text))))
+        .collect()
+}
+
+/// Filetypes are named by the extension of their files, these are the other names they go
+/// by.
+const FILETYPE_NAMES: &[(&str, &str)] = &[
+    ("rust", "rs"),
+    ("c++", "cpp"),
+    ("javascript", "js"),
+    ("typescript", "ts"),
+    ("golang", "go"),
+    ("python", "py"),
+    ("shell", "sh"),
+    ("bash", "sh"),
+    ("toml", "toml"),
+];
+
+/// The filetype of scripts run by the interpreters named in their shebang line.
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("python", "py"),
+    ("node", "js"),
+    ("deno", "ts"),
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("zsh", "sh"),
+];
+
+/// The filetype called `name`, either an extension or one of the longer names like `python`.
+pub fn filetype_by_name(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    FILETYPE_NAMES
+        .iter()
+        .find(|(long, _)| *long == name)
+        .map_or(name, |(_, filetype)| filetype.to_string())
+}
+
+/// The filetype of scripts run by `interpreter`, with any version after its name like in
+/// `python3.11` left out.
+pub fn filetype_by_interpreter(interpreter: &str) -> Option<&'static str> {
+    let name = interpreter.trim_end_matches(|ch: char| ch.is_ascii_digit() || ch == '.');
+    INTERPRETERS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, filetype)| *filetype)
+}
+
+/// The filetype of a file named `filename` starting with `first_line`. That is its extension,
+/// or without one the interpreter of a shebang like `#!/usr/bin/env python3` or a hint like
+/// `kilo: ft=toml` or `vim: set filetype=python:` on the first line.
+pub fn detect_filetype(filename: Option<&str>, first_line: &str) -> Option<String> {
+    if let Some(extension) = filename.and_then(|name| Path::new(name).extension()) {
+        return Some(extension.to_string_lossy().to_lowercase());
+    }
+
+    if let Some(command) = first_line.strip_prefix("#!") {
+        let mut words = command.split_whitespace();
+        let program = words.next()?.rsplit('/').next()?;
+        let interpreter = match program {
+            "env" => words.find(|word| !word.starts_with('-'))?,
+            program => program,
+        };
+        return filetype_by_interpreter(interpreter).map(String::from);
+    }
+
+    let (_, hint) = ["kilo:", "vim:"]
+        .into_iter()
+        .find_map(|marker| first_line.split_once(marker))?;
+    hint.split(|ch: char| ch.is_whitespace() || ch == ':')
+        .find_map(|setting| {
+            setting
+                .strip_prefix("ft=")
+                .or_else(|| setting.strip_prefix("filetype="))
+        })
+        .filter(|name| !name.is_empty())
+        .map(filetype_by_name)
+}
+
+/// How far above a row `StateCache::sync_point` looks before giving up and starting from
+/// the first row. Most rows aren't sync points, so going over them one by one can cost
+/// more than highlighting forward from states already known.
+const SYNC_SCAN_ROWS: usize = 1000;
+
+/// Whether the line after `line` starts outside of any comment or string, whatever state
+/// `line` itself starts in. Highlighting can start over from such a line without knowing
+/// anything about the lines above it.
+fn ends_in_sync(highlighter: &dyn Highlighter, line: &str) -> bool {
+    [LineState::Normal, LineState::InComment, LineState::InString]
+        .into_iter()
+        .all(|state| highlighter.highlight_line(line, state).1 == LineState::Normal)
+}
+
+/// The states of a run of rows, and the rows highlighting can start over from, kept so
+/// scrolling doesn't have to go over the rows above the screen again. Edits only make the
+/// states below the edited rows stale, those are brought up to date when they are asked for
+/// or a few at a time by `advance`. Only valid for the rows it was computed for, see `key`.
+#[derive(Debug, Clone, Default)]
+pub struct StateCache {
+    key: Option<(usize, u64)>,
+    /// The row `states` starts at, always a sync point
+    start: usize,
+    states: Vec<LineState>,
+    /// The first row whose state is stale, from before the last edits. The states from
+    /// here to the end of `states` are kept to tell when highlighting the edit stops
+    /// making a difference.
+    dirty: usize,
+    /// The rows below the edits, where a stale state coming out the same means every
+    /// state after it is up to date again
+    unedited_from: usize,
+    /// The sync point found for each row a scan started from
+    sync_points: BTreeMap<usize, usize>,
+}
+
+impl StateCache {
+    /// The state `rows[row]` starts in. `key` identifies the rows and their contents, any
+    /// other key than the last one forgets everything computed so far unless `catch_up`
+    /// was told about it.
+    pub fn state_at(
+        &mut self,
+        key: (usize, u64),
+        highlighter: &dyn Highlighter,
+        rows: &[Row],
+        row: usize,
+    ) -> LineState {
+        self.check_key(key);
+
+        let row = row.min(rows.len());
+        if row < self.start || row > self.dirty {
+            let sync = self.sync_point(key, highlighter, rows, row);
+            // Below the states known so far those are still as good a start as any
+            if row < self.start || sync > self.dirty {
+                self.start_at(sync);
+            }
+        }
+
+        self.update_until(highlighter, rows, row);
+        self.states[row - self.start]
+    }
+
+    /// Move on to `key` from the last key for the same rows, keeping the states above the
+    /// rows `edited` reports changed since the modification count of the last key.
+    pub fn catch_up(
+        &mut self,
+        key: (usize, u64),
+        edited: impl FnOnce(u64) -> Option<Range<usize>>,
+    ) {
+        let edited = match self.key {
+            Some((rows, count)) if rows == key.0 && count != key.1 => edited(count),
+            _ => return,
+        };
+
+        match edited {
+            // The state of a row only depends on the rows above it
+            Some(edited) if edited.start >= self.start => {
+                let stale = self.dirty < self.start + self.states.len();
+                self.key = Some(key);
+                self.dirty = self.dirty.min(edited.start.saturating_add(1));
+                self.unedited_from = match stale {
+                    true => self.unedited_from.max(edited.end),
+                    false => edited.end,
+                };
+                // A sync point only depends on the rows from it up to the row it was
+                // found for
+                self.sync_points.retain(|&top, _| top <= edited.start);
+            }
+            _ => self.check_key(key),
+        }
+    }
+
+    /// Bring up to `budget` more rows up to date, returning whether there are rows left
+    /// that aren't.
+    pub fn advance(
+        &mut self,
+        key: (usize, u64),
+        highlighter: &dyn Highlighter,
+        rows: &[Row],
+        budget: usize,
+    ) -> bool {
+        if self.key != Some(key) {
+            return false;
+        }
+
+        let last = self.dirty.saturating_add(budget).saturating_sub(1);
+        self.update_until(highlighter, rows, last.min(rows.len()));
+        self.is_pending(key, rows.len())
+    }
+
+    /// Whether some of the `len` rows identified by `key` aren't up to date
+    pub fn is_pending(&self, key: (usize, u64), len: usize) -> bool {
+        self.key == Some(key) && self.dirty <= len
+    }
+
+    /// The closest row at or above `top` known to start outside of any comment or string,
+    /// the first row when there is none within `SYNC_SCAN_ROWS` rows.
+    pub fn sync_point(
+        &mut self,
+        key: (usize, u64),
+        highlighter: &dyn Highlighter,
+        rows: &[Row],
+        top: usize,
+    ) -> usize {
+        self.check_key(key);
+
+        let top = top.min(rows.len());
+        let mut row = top;
+        let sync = loop {
+            if let Some(&sync) = self.sync_points.get(&row) {
+                break sync;
+            }
+            if row == 0 || ends_in_sync(highlighter, rows[row - 1].buffer()) {
+                break row;
+            }
+            if top - row >= SYNC_SCAN_ROWS {
+                break 0;
+            }
+            row -= 1;
+        };
+
+        self.sync_points.insert(top, sync);
+        sync
+    }
+
+    /// Highlight the rows from the first stale state on until the state of `row` is up to
+    /// date.
+    fn update_until(&mut self, highlighter: &dyn Highlighter, rows: &[Row], row: usize) {
+        while self.dirty <= row {
+            let line = self.dirty - 1;
+            let state = self.states[line - self.start];
+            let state = highlighter.highlight_line(rows[line].buffer(), state).1;
+
+            match self.states.get_mut(self.dirty - self.start) {
+                Some(stale) => {
+                    let settled = *stale == state && self.dirty >= self.unedited_from;
+                    *stale = state;
+                    self.dirty = match settled {
+                        true => self.start + self.states.len(),
+                        false => self.dirty + 1,
+                    };
+                }
+                None => {
+                    self.states.push(state);
+                    self.dirty += 1;
+                }
+            }
+        }
+
+        // Rows below the last one the states can be for were removed by the edits
+        self.states
+            .truncate((rows.len() + 1).saturating_sub(self.start));
+        self.dirty = self.dirty.min(self.start + self.states.len());
+    }
+
+    /// Forget the states and start over from the sync point `row`
+    fn start_at(&mut self, row: usize) {
+        self.start = row;
+        self.states = vec![LineState::Normal];
+        self.dirty = row + 1;
+    }
+
+    fn check_key(&mut self, key: (usize, u64)) {
+        if self.key != Some(key) {
+            self.key = Some(key);
+            self.start_at(0);
+            self.sync_points.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    fn highlight_lines(syntax: Syntax, text: &str) -> Vec<Vec<(&str, SpanKind)>> {
+        let mut state = LineState::Normal;
+        text.lines()
+            .map(|line| {
+                let (spans, next) = syntax.highlight_line(line, state);
+                state = next;
+                spans
+                    .into_iter()
+                    .map(|span| (&line[span.range], span.kind))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn block_comment_should_carry_over_three_lines() {
+        use SpanKind::Comment;
+
+        let lines = highlight_lines(Syntax::C, "let a; /* one\ntwo\nthree */ let b;\nlet c;");
+
+        assert_eq!(
+            lines,
+            vec![
+                vec![("/* one", Comment)],
+                vec![("two", Comment)],
+                vec![("three */", Comment)],
+                vec![],
+            ]
+        );
+    }
+
+    #[test_case("a // b /* c" => vec![("// b /* c", SpanKind::Comment)]; "Line comment")]
+    #[test_case("/* a */ b /* c */" => vec![("/* a */", SpanKind::Comment), ("/* c */", SpanKind::Comment)]; "Two block comments")]
+    #[test_case("/* a // */ b" => vec![("/* a // */", SpanKind::Comment)]; "Line comment in a block")]
+    fn single_lines_should_mark_their_comments(line: &str) -> Vec<(&str, SpanKind)> {
+        highlight_lines(Syntax::C, line).remove(0)
+    }
+
+    const COMMENTS: &str = "let a;\n/* start\nstill comment\n/* inner\nend */ let b;\nlet c;\n/* long\nx\ny\nz */\nlet d;";
+
+    fn comment_rows() -> Vec<Row> {
+        COMMENTS.lines().map(Row::new).collect()
+    }
+
+    #[test_case(0 => 0; "First row")]
+    #[test_case(3 => 0; "Inside a comment opened twice")]
+    #[test_case(5 => 5; "Right after a comment")]
+    #[test_case(8 => 5; "Inside a long comment")]
+    #[test_case(10 => 10; "After the long comment")]
+    #[test_case(50 => 10; "Past the end")]
+    fn sync_point_should_be_the_closest_row_outside_of_comments(top: usize) -> usize {
+        StateCache::default().sync_point((0, 0), &Syntax::C, &comment_rows(), top)
+    }
+
+    /// The state each row starts in, highlighting them all from the first row
+    fn states_from_the_start(rows: &[Row]) -> Vec<LineState> {
+        rows.iter()
+            .scan(LineState::Normal, |state, row| {
+                let start = *state;
+                *state = Syntax::C.highlight_line(row.buffer(), start).1;
+                Some(start)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn states_from_sync_points_should_match_highlighting_from_the_start() {
+        let rows = comment_rows();
+        let expected = states_from_the_start(&rows);
+
+        // Going up from the bottom starts over at each sync point instead of the first row
+        let mut cache = StateCache::default();
+        let states: Vec<_> = (0..rows.len())
+            .rev()
+            .map(|row| cache.state_at((0, 0), &Syntax::C, &rows, row))
+            .collect();
+
+        assert_eq!(states.into_iter().rev().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn edits_should_only_make_the_states_below_them_stale() {
+        let mut rows = comment_rows();
+        let mut cache = StateCache::default();
+        cache.state_at((0, 0), &Syntax::C, &rows, 0);
+        assert!(!cache.advance((0, 0), &Syntax::C, &rows, 100));
+
+        rows[0] = Row::new("let a; /*");
+        cache.catch_up((0, 1), |count| (count == 0).then_some(0..1));
+        assert!(cache.is_pending((0, 1), rows.len()));
+        assert!(!cache.advance((0, 1), &Syntax::C, &rows, 100));
+        let states = (0..rows.len())
+            .map(|row| cache.state_at((0, 1), &Syntax::C, &rows, row))
+            .collect::<Vec<_>>();
+        assert_eq!(states, states_from_the_start(&rows));
+
+        // Once past the edit a state coming out as before settles the rest
+        rows[5] = Row::new("let cc;");
+        cache.catch_up((0, 2), |count| (count == 1).then_some(5..6));
+        assert!(!cache.advance((0, 2), &Syntax::C, &rows, 1));
+    }
+
+    #[test]
+    fn edits_should_only_forget_the_sync_points_below_them() {
+        let rows = comment_rows();
+        let mut cache = StateCache::default();
+        assert_eq!(cache.sync_point((0, 0), &Syntax::C, &rows, 8), 5);
+        assert_eq!(cache.sync_point((0, 0), &Syntax::C, &rows, 3), 0);
+
+        cache.catch_up((0, 1), |count| (count == 0).then_some(5..6));
+        assert_eq!(cache.sync_points, BTreeMap::from([(3, 0)]));
+    }
+
+    #[test_case(Some("notes.TXT"), "#!/bin/sh" => Some("txt".to_string()); "Extension first")]
+    #[test_case(Some("build"), "#!/usr/bin/env python3" => Some("py".to_string()); "Env with a version")]
+    #[test_case(None, "#!/usr/bin/env -S node --harmony" => Some("js".to_string()); "Env with flags")]
+    #[test_case(None, "#!/bin/bash -e" => Some("sh".to_string()); "Interpreter path")]
+    #[test_case(None, "#!/usr/bin/perl" => None; "Unknown interpreter")]
+    #[test_case(None, "# kilo: ft=toml" => Some("toml".to_string()); "Hint")]
+    #[test_case(None, "// vim: set filetype=python:" => Some("py".to_string()); "Hint by name")]
+    #[test_case(None, "[package]" => None; "Nothing to go by")]
+    fn filetype_should_be_detected(filename: Option<&str>, first_line: &str) -> Option<String> {
+        detect_filetype(filename, first_line)
+    }
+
+    #[test]
+    fn triple_quoted_string_should_carry_over_lines() {
+        use SpanKind::{Comment, String};
+
+        let lines = highlight_lines(Syntax::PYTHON, "x = \"\"\"a\n# b\n\"\"\" # c");
+
+        assert_eq!(
+            lines,
+            vec![
+                vec![("\"\"\"a", String)],
+                vec![("# b", String)],
+                vec![("\"\"\"", String), ("# c", Comment)],
+            ]
+        );
+    }
+
+    #[test_case(Syntax::RUST, "fn f(x: &", '\'' => false; "Rust reference lifetime")]
+    #[test_case(Syntax::RUST, "struct S<", '\'' => false; "Rust generic lifetime")]
+    #[test_case(Syntax::RUST, "if ch == ", '\'' => true; "Rust character")]
+    #[test_case(Syntax::RUST, "let s = ", '"' => true; "Rust string")]
+    #[test_case(Syntax::RUST, "let s = \"abc", '"' => false; "Ending a string")]
+    #[test_case(Syntax::RUST, "let s = \"a\\\"", '"' => false; "After an escaped quote")]
+    #[test_case(Syntax::RUST, "let s = \"a\"; f(", '"' => true; "After a closed string")]
+    #[test_case(Syntax::RUST, "/* \" */ f(", '"' => true; "Quote in a comment")]
+    #[test_case(Syntax::RUST, "f(\"say (", '\'' => false; "Inside a string of the other quote")]
+    #[test_case(Syntax::RUST, "f(&'a x, '\"', ", '"' => true; "After a lifetime and a character")]
+    #[test_case(Syntax::RUST, "f('(", '"' => false; "Inside a character being typed")]
+    #[test_case(Syntax::TEXT, "it's \"so ", '\'' => false; "Inside a quotation")]
+    #[test_case(Syntax::TEXT, "I don", '\'' => false; "Contraction")]
+    #[test_case(Syntax::TEXT, "the boys", '\'' => false; "Possessive")]
+    #[test_case(Syntax::TEXT, "say ", '\'' => true; "Start of a word")]
+    #[test_case(Syntax::TEXT, "", '\'' => true; "Start of the line")]
+    #[test_case(Syntax::TEXT, "a) ", '`' => false; "Not a quote")]
+    #[test_case(Syntax::C, "x = sizeof", '\'' => false; "After a word")]
+    #[test_case(Syntax::C, "x = (", '\'' => true; "C character")]
+    fn quotes_should_only_be_closed_where_they_open_a_string(
+        syntax: Syntax,
+        line: &str,
+        quote: char,
+    ) -> bool {
+        syntax.closes_quote(line, line.len(), quote, LineState::Normal)
+    }
+
+    #[test]
+    fn quotes_inside_a_multiline_string_should_not_be_closed() {
+        assert!(!Syntax::PYTHON.closes_quote("x ", 2, '"', LineState::InString));
+        assert!(Syntax::PYTHON.closes_quote("\"\"\" ", 4, '"', LineState::InString));
+    }
+}
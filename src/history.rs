@@ -0,0 +1,59 @@
+use std::collections::VecDeque;
+
+/// How many search queries are remembered
+pub const SEARCH_HISTORY_SIZE: usize = 50;
+
+/// The most recent entries of a prompt, oldest first. Once full the oldest entry is dropped
+/// for each new one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    entries: VecDeque<String>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Remember `entry` as the newest one. Empty entries and repeats of the newest entry
+    /// aren't added.
+    pub fn push<T: Into<String>>(&mut self, entry: T) {
+        let entry = entry.into();
+        if entry.is_empty() || self.entries.back() == Some(&entry) || self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case(&["a", "b"] => vec!["a", "b"]; "In order")]
+    #[test_case(&["a", "a", "b", "a"] => vec!["a", "b", "a"]; "Consecutive repeats")]
+    #[test_case(&["a", "", "b"] => vec!["a", "b"]; "Empty entries")]
+    #[test_case(&["a", "b", "c", "d"] => vec!["b", "c", "d"]; "Full")]
+    fn pushing_should_keep_the_recent_distinct_entries(entries: &[&str]) -> Vec<String> {
+        let mut history = History::new(3);
+        for entry in entries {
+            history.push(*entry);
+        }
+
+        history.entries().map(str::to_string).collect()
+    }
+}
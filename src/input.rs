@@ -1,31 +1,156 @@
-use std::{error::Error, fmt};
+use std::{error::Error, fmt, mem, time::Duration};
 
 use async_std::channel::Sender;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+#[cfg(feature = "links")]
+use crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use crossterm::event::{self, Event};
 use error_stack::{IntoReport, Result, ResultExt};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use crate::match_key;
+use crate::{
+    buffer::{Encoding, LineEnding},
+    key::KeyDescription,
+    keymap::{Chord, Keymap},
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CursorEvent {
     MoveUp,
     MoveDown,
+    /// Move to the buffer line above, even when soft wrap moves up by screen rows
+    MoveLineUp,
+    MoveLineDown,
     MoveLeft,
     MoveRight,
     MoveBegin,
     MoveEnd,
+    MoveWordLeft,
+    MoveWordRight,
     MoveTop,
     MoveBottom,
+    MoveToScreenTop,
+    MoveToScreenMiddle,
+    MoveToScreenBottom,
+    MoveFileEnd,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum InputEvent {
     CursorEvent(CursorEvent),
     InsertChar(char),
+    /// Insert a character as-is, even a tab with soft tabs on
+    InsertLiteral(char),
+    /// Insert the character typed by the next key instead of running what it's bound to
+    QuotedInsert,
     Quit,
     SaveBuffer,
+    /// Save even a read-only buffer or over a file changed on disk, like `:w!` in vim
+    ForceSave,
+    /// Save to a file asked for, which the buffer goes on as. Allowed on read-only buffers.
+    SaveAs,
     DeletePreviousChar,
     DeleteNextChar,
+    TransposeChars,
+    KillToLineStart,
+    InsertNewline,
+    Cancel,
+    Undo,
+    Redo,
+    Find,
+    Replace,
+    OpenFile,
+    SearchNext,
+    SearchPrevious,
+    ClearSearch,
+    ToggleMacroRecording,
+    ReplayMacro(u16),
+    /// Keep the last recorded macro under a name asked for in a prompt
+    SaveMacro,
+    /// Replay a saved macro, asking for its name and a count in a prompt
+    RunMacro,
+    /// Ask for a line to move the cursor to, by number or relative to the cursor
+    GotoLine,
+    DescribeChar,
+    ShowChangeList,
+    ToggleLineNumbers,
+    ToggleRelativeNumbers,
+    /// List the status messages shown so far
+    ShowMessageLog,
+    /// Go to the next `LineNumberStyle`
+    CycleLineNumbers,
+    ToggleIndentGuides,
+    /// Insert spaces up to the next tab stop instead of a tab
+    ToggleSoftTabs,
+    /// Start new lines with the indent of the line they were broken from
+    ToggleAutoIndent,
+    /// Insert the closing quote along with a typed quote that opens a string
+    ToggleAutoCloseQuotes,
+    /// Wrap long lines at the edge of the screen instead of scrolling sideways
+    ToggleSoftWrap,
+    FileInfo,
+    Count(u8),
+    ToggleHud,
+    NextBuffer,
+    PreviousBuffer,
+    /// Go to the next line longer than the fill column
+    NextLongLine,
+    /// Wrap the paragraph around the cursor at the fill column
+    FillParagraph,
+    /// Ask for a delimiter and line up its first occurrence on the lines of the paragraph
+    /// around the cursor
+    Align,
+    CloseBuffer,
+    ReopenBuffer,
+    SetLineEnding(LineEnding),
+    /// Ask for the tab width of the buffer in a prompt
+    SetTabStop,
+    /// Widen or narrow the tabs of the buffer by this many columns
+    ChangeTabStop(i8),
+    /// Ask for the filetype of the buffer in a prompt, overriding the one detected
+    SetFiletype,
+    SetEncoding(Encoding),
+    /// Read the file of the buffer again in another encoding than UTF-8
+    ReopenWithEncoding(Encoding),
+    /// Open the file of the buffer as it is on disk in a read-only buffer
+    ShowDiskVersion,
+    /// Read the configuration files again, handled by the main loop since it owns the keymap
+    ReloadConfig,
+    /// The terminal is now this many columns and rows, handled by the main loop since it
+    /// knows the rows taken by the bars
+    Resize(u16, u16),
+    /// A Ctrl+click at this column and row of the terminal, opening the URL or file path
+    /// clicked on
+    #[cfg(feature = "links")]
+    FollowLink(u16, u16),
+    /// A key without any binding
+    Unbound(KeyDescription),
+}
+
+impl InputEvent {
+    /// Whether the event changes the text of the buffer or its file
+    pub fn modifies_buffer(&self) -> bool {
+        matches!(
+            self,
+            Self::InsertChar(_)
+                | Self::InsertLiteral(_)
+                | Self::SaveBuffer
+                | Self::DeletePreviousChar
+                | Self::DeleteNextChar
+                | Self::TransposeChars
+                | Self::KillToLineStart
+                | Self::InsertNewline
+                | Self::Undo
+                | Self::Redo
+                | Self::Replace
+                | Self::FillParagraph
+                | Self::Align
+                | Self::SetLineEnding(_)
+                | Self::SetEncoding(_)
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -47,36 +172,52 @@ impl fmt::Display for InputError {
 
 pub struct InputSystem {
     sender: Sender<InputEvent>,
+    keymap: Keymap,
+    /// Whether the next key is inserted as the character it types
+    quoting: bool,
 }
 
 impl InputSystem {
     pub fn new(sender: Sender<InputEvent>) -> Self {
-        Self { sender }
+        Self::with_keymap(sender, Keymap::default())
+    }
+
+    pub fn with_keymap(sender: Sender<InputEvent>, keymap: Keymap) -> Self {
+        Self {
+            sender,
+            keymap,
+            quoting: false,
+        }
+    }
+
+    /// Translate keys with `keymap` from now on.
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    /// Wait at most `timeout` for input, returning whether a key is ready to be processed.
+    pub fn poll(&self, timeout: Duration) -> Result<bool, InputError> {
+        event::poll(timeout)
+            .report()
+            .change_context(InputError::ReadFailure)
     }
 
-    pub fn process_key(&self) -> Result<(), InputError> {
+    /// Process the keys already typed without waiting for more, as many as the channel
+    /// has room for.
+    pub fn process_pending(&mut self) -> Result<(), InputError> {
+        while !self.sender.is_full() && self.poll(Duration::ZERO)? {
+            self.process_key()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn process_key(&mut self) -> Result<(), InputError> {
         let key = event::read()
             .report()
             .change_context(InputError::ReadFailure)?;
 
-        let event = match key {
-            match_key!(KeyCode::Char('q'), KeyModifiers::CONTROL) => Some(InputEvent::Quit),
-            match_key!(KeyCode::Left) => Some(InputEvent::CursorEvent(CursorEvent::MoveLeft)),
-            match_key!(KeyCode::Right) => Some(InputEvent::CursorEvent(CursorEvent::MoveRight)),
-            match_key!(KeyCode::Up) => Some(InputEvent::CursorEvent(CursorEvent::MoveUp)),
-            match_key!(KeyCode::Down) => Some(InputEvent::CursorEvent(CursorEvent::MoveDown)),
-            match_key!(KeyCode::PageUp) => Some(InputEvent::CursorEvent(CursorEvent::MoveTop)),
-            match_key!(KeyCode::PageDown) => Some(InputEvent::CursorEvent(CursorEvent::MoveBottom)),
-            match_key!(KeyCode::Home) => Some(InputEvent::CursorEvent(CursorEvent::MoveBegin)),
-            match_key!(KeyCode::End) => Some(InputEvent::CursorEvent(CursorEvent::MoveEnd)),
-            match_key!(KeyCode::Char('s'), KeyModifiers::CONTROL) => Some(InputEvent::SaveBuffer),
-            match_key!(KeyCode::Char(ch), KeyModifiers::NONE) => Some(InputEvent::InsertChar(ch)),
-            match_key!(KeyCode::Backspace) => Some(InputEvent::DeletePreviousChar),
-            match_key!(KeyCode::Delete) => Some(InputEvent::DeleteNextChar),
-            _ => None,
-        };
-
-        if let Some(event) = event {
+        if let Some(event) = self.translate(key) {
             self.sender
                 .try_send(event)
                 .report()
@@ -85,4 +226,44 @@ impl InputSystem {
 
         Ok(())
     }
+
+    /// Map a terminal event to an editor event with the keymap. The key after
+    /// `InputEvent::QuotedInsert` becomes the character it types instead, when it types one.
+    pub fn translate(&mut self, key: Event) -> Option<InputEvent> {
+        if let Event::Resize(cols, rows) = key {
+            return Some(InputEvent::Resize(cols, rows));
+        }
+
+        #[cfg(feature = "links")]
+        if let Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers,
+        }) = key
+        {
+            return modifiers
+                .contains(KeyModifiers::CONTROL)
+                .then_some(InputEvent::FollowLink(column, row));
+        }
+        // Moving the mouse while quoting a key isn't the key to quote
+        if let Event::Mouse(_) = key {
+            return None;
+        }
+
+        if let (true, Event::Key(key)) = (mem::take(&mut self.quoting), key) {
+            if let Some(ch) = Chord::from_key(key.code, key.modifiers).literal() {
+                return Some(InputEvent::InsertLiteral(ch));
+            }
+        }
+
+        let event = self.keymap.translate(key);
+        self.quoting = event == Some(InputEvent::QuotedInsert);
+        event
+    }
+}
+
+/// Map a terminal event to the editor event bound to it in the default keymap, if any.
+pub fn translate(key: Event) -> Option<InputEvent> {
+    Keymap::default().translate(key)
 }
@@ -0,0 +1,192 @@
+//! Keys and the modifiers held with them, as written in keymap files, help and messages.
+//! Kept apart from the terminal library so events carrying a key can be saved and compared.
+
+use std::{fmt, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Key {
+    Char(char),
+    F(u8),
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Backspace,
+    Delete,
+    Insert,
+    Enter,
+    Esc,
+    Tab,
+    BackTab,
+    Null,
+}
+
+impl Key {
+    /// Keys by the names they are written with, in the case they are shown in. Keys with
+    /// several names are shown with the first.
+    const NAMES: &'static [(&'static str, Key)] = &[
+        ("Left", Key::Left),
+        ("Right", Key::Right),
+        ("Up", Key::Up),
+        ("Down", Key::Down),
+        ("Home", Key::Home),
+        ("End", Key::End),
+        ("PageUp", Key::PageUp),
+        ("PageDown", Key::PageDown),
+        ("BS", Key::Backspace),
+        ("Del", Key::Delete),
+        ("Insert", Key::Insert),
+        ("CR", Key::Enter),
+        ("Esc", Key::Esc),
+        ("Tab", Key::Tab),
+        ("BackTab", Key::BackTab),
+        ("Null", Key::Null),
+        ("Backspace", Key::Backspace),
+        ("Delete", Key::Delete),
+        ("Enter", Key::Enter),
+        ("Escape", Key::Esc),
+    ];
+}
+
+/// A key together with the modifiers held down, written like `C-s`, `S-F3` or `PageUp`.
+/// Control characters without a name are written as their byte, like `0x7f`.
+///
+/// Besides that notation, keys are read written out like `ctrl+shift+pageup`, with the
+/// modifier and key names in any case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyDescription {
+    pub key: Key,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+impl KeyDescription {
+    /// `key` without any modifiers
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    /// Read the modifier in front of `rest`, returning what follows it
+    fn strip_modifier<'a>(&mut self, rest: &'a str) -> Option<&'a str> {
+        let (modifier, rest) = match rest.split_once('+') {
+            Some((modifier, rest)) if !rest.is_empty() => (modifier, rest),
+            _ => (rest.get(..1)?, rest.get(1..)?.strip_prefix('-')?),
+        };
+
+        match modifier.to_ascii_lowercase().as_str() {
+            "c" | "ctrl" | "control" => self.ctrl = true,
+            "a" | "m" | "alt" | "meta" => self.alt = true,
+            "s" | "shift" => self.shift = true,
+            _ => return None,
+        }
+
+        Some(rest)
+    }
+}
+
+impl fmt::Display for KeyDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, prefix) in [(self.ctrl, "C-"), (self.alt, "A-"), (self.shift, "S-")] {
+            if held {
+                f.write_str(prefix)?;
+            }
+        }
+
+        match self.key {
+            Key::Char(ch) if ch.is_ascii_control() => write!(f, "0x{:02x}", ch as u8),
+            Key::Char(ch) => write!(f, "{ch}"),
+            Key::F(n) => write!(f, "F{n}"),
+            key => {
+                let (name, _) = Key::NAMES.iter().find(|(_, named)| *named == key).unwrap();
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+impl FromStr for KeyDescription {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut description = Self::new(Key::Null);
+        let mut name = s;
+        while name.chars().count() > 1 {
+            match description.strip_modifier(name) {
+                Some(rest) => name = rest,
+                None => break,
+            }
+        }
+
+        let named = Key::NAMES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|(_, key)| *key);
+        description.key = match named {
+            Some(key) => key,
+            None if name.chars().count() == 1 => Key::Char(name.chars().next().unwrap()),
+            None if name.len() > 1 && name.starts_with(['F', 'f']) => {
+                Key::F(name[1..].parse().map_err(|_| ())?)
+            }
+            None if name.starts_with("0x") => match u8::from_str_radix(&name[2..], 16) {
+                Ok(byte) if byte.is_ascii_control() => Key::Char(byte as char),
+                _ => return Err(()),
+            },
+            None => return Err(()),
+        };
+
+        Ok(description)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case("C-s" => "C-s"; "Control")]
+    #[test_case("S-F3" => "S-F3"; "Function key")]
+    #[test_case("ctrl+shift+pageup" => "C-S-PageUp"; "Written out")]
+    #[test_case("Alt+Enter" => "A-CR"; "Written out in any case")]
+    #[test_case("M-q" => "A-q"; "Meta")]
+    #[test_case("C--" => "C--"; "Minus")]
+    #[test_case("+" => "+"; "Plus")]
+    #[test_case("0x7f" => "0x7f"; "Control character")]
+    fn keys_should_be_written_in_the_short_notation(notation: &str) -> String {
+        notation.parse::<KeyDescription>().unwrap().to_string()
+    }
+
+    #[test_case("ctrl+shift+pageup"; "Written out")]
+    #[test_case("C-A-x"; "Two modifiers")]
+    #[test_case("0x08"; "Control character")]
+    #[test_case("Null"; "Null")]
+    fn keys_should_read_back_as_they_are_shown(notation: &str) {
+        let key = notation.parse::<KeyDescription>().unwrap();
+
+        assert_eq!(key.to_string().parse(), Ok(key));
+    }
+
+    #[test_case(""; "Empty")]
+    #[test_case("C-"; "Modifier only")]
+    #[test_case("hyper+x"; "Unknown modifier")]
+    #[test_case("PageSideways"; "Unknown key")]
+    #[test_case("0x41"; "Printable byte")]
+    fn unknown_keys_should_not_parse(notation: &str) {
+        assert!(notation.parse::<KeyDescription>().is_err());
+    }
+}
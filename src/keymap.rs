@@ -0,0 +1,678 @@
+use std::{collections::HashMap, error::Error, fmt, fs, path::Path, str::FromStr};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::{
+    buffer::{Encoding, LineEnding},
+    input::{CursorEvent, InputEvent},
+    key::{Key, KeyDescription},
+};
+
+/// Every action that can be bound to a key, by the name used in keymap files.
+const ACTIONS: &[(&str, InputEvent)] = &[
+    ("quit", InputEvent::Quit),
+    ("save", InputEvent::SaveBuffer),
+    ("force-save", InputEvent::ForceSave),
+    ("save-as", InputEvent::SaveAs),
+    ("move-left", InputEvent::CursorEvent(CursorEvent::MoveLeft)),
+    (
+        "move-right",
+        InputEvent::CursorEvent(CursorEvent::MoveRight),
+    ),
+    ("move-up", InputEvent::CursorEvent(CursorEvent::MoveUp)),
+    ("move-down", InputEvent::CursorEvent(CursorEvent::MoveDown)),
+    ("line-up", InputEvent::CursorEvent(CursorEvent::MoveLineUp)),
+    (
+        "line-down",
+        InputEvent::CursorEvent(CursorEvent::MoveLineDown),
+    ),
+    ("move-top", InputEvent::CursorEvent(CursorEvent::MoveTop)),
+    (
+        "move-bottom",
+        InputEvent::CursorEvent(CursorEvent::MoveBottom),
+    ),
+    (
+        "move-begin",
+        InputEvent::CursorEvent(CursorEvent::MoveBegin),
+    ),
+    ("move-end", InputEvent::CursorEvent(CursorEvent::MoveEnd)),
+    (
+        "word-left",
+        InputEvent::CursorEvent(CursorEvent::MoveWordLeft),
+    ),
+    (
+        "word-right",
+        InputEvent::CursorEvent(CursorEvent::MoveWordRight),
+    ),
+    (
+        "screen-top",
+        InputEvent::CursorEvent(CursorEvent::MoveToScreenTop),
+    ),
+    (
+        "screen-middle",
+        InputEvent::CursorEvent(CursorEvent::MoveToScreenMiddle),
+    ),
+    (
+        "screen-bottom",
+        InputEvent::CursorEvent(CursorEvent::MoveToScreenBottom),
+    ),
+    ("file-end", InputEvent::CursorEvent(CursorEvent::MoveFileEnd)),
+    ("delete-previous", InputEvent::DeletePreviousChar),
+    ("delete-next", InputEvent::DeleteNextChar),
+    ("transpose-chars", InputEvent::TransposeChars),
+    ("kill-to-line-start", InputEvent::KillToLineStart),
+    ("insert-tab", InputEvent::InsertChar('\t')),
+    ("quoted-insert", InputEvent::QuotedInsert),
+    ("newline", InputEvent::InsertNewline),
+    ("cancel", InputEvent::Cancel),
+    ("undo", InputEvent::Undo),
+    ("redo", InputEvent::Redo),
+    ("find", InputEvent::Find),
+    ("replace", InputEvent::Replace),
+    ("open", InputEvent::OpenFile),
+    ("search-next", InputEvent::SearchNext),
+    ("search-previous", InputEvent::SearchPrevious),
+    ("clear-search", InputEvent::ClearSearch),
+    ("record-macro", InputEvent::ToggleMacroRecording),
+    ("replay-macro", InputEvent::ReplayMacro(1)),
+    ("save-macro", InputEvent::SaveMacro),
+    ("run-macro", InputEvent::RunMacro),
+    ("goto-line", InputEvent::GotoLine),
+    ("describe-char", InputEvent::DescribeChar),
+    ("change-list", InputEvent::ShowChangeList),
+    ("message-log", InputEvent::ShowMessageLog),
+    ("line-numbers", InputEvent::ToggleLineNumbers),
+    ("relative-numbers", InputEvent::ToggleRelativeNumbers),
+    ("cycle-line-numbers", InputEvent::CycleLineNumbers),
+    ("indent-guides", InputEvent::ToggleIndentGuides),
+    ("soft-tabs", InputEvent::ToggleSoftTabs),
+    ("auto-indent", InputEvent::ToggleAutoIndent),
+    ("auto-close-quotes", InputEvent::ToggleAutoCloseQuotes),
+    ("soft-wrap", InputEvent::ToggleSoftWrap),
+    ("fill-paragraph", InputEvent::FillParagraph),
+    ("file-info", InputEvent::FileInfo),
+    ("debug-hud", InputEvent::ToggleHud),
+    ("next-buffer", InputEvent::NextBuffer),
+    ("previous-buffer", InputEvent::PreviousBuffer),
+    ("next-long-line", InputEvent::NextLongLine),
+    ("close-buffer", InputEvent::CloseBuffer),
+    ("reopen-buffer", InputEvent::ReopenBuffer),
+    ("line-ending-lf", InputEvent::SetLineEnding(LineEnding::Lf)),
+    ("line-ending-crlf", InputEvent::SetLineEnding(LineEnding::CrLf)),
+    ("line-ending-cr", InputEvent::SetLineEnding(LineEnding::Cr)),
+    ("tab-width", InputEvent::SetTabStop),
+    ("tab-width-wider", InputEvent::ChangeTabStop(1)),
+    ("tab-width-narrower", InputEvent::ChangeTabStop(-1)),
+    ("filetype", InputEvent::SetFiletype),
+    ("align", InputEvent::Align),
+    ("encoding-utf8", InputEvent::SetEncoding(Encoding::Utf8)),
+    ("encoding-latin1", InputEvent::SetEncoding(Encoding::Latin1)),
+    ("encoding-windows-1252", InputEvent::SetEncoding(Encoding::Windows1252)),
+    ("reopen-latin1", InputEvent::ReopenWithEncoding(Encoding::Latin1)),
+    ("reopen-windows-1252", InputEvent::ReopenWithEncoding(Encoding::Windows1252)),
+    ("disk-version", InputEvent::ShowDiskVersion),
+    ("reload-config", InputEvent::ReloadConfig),
+    ("count-0", InputEvent::Count(0)),
+    ("count-1", InputEvent::Count(1)),
+    ("count-2", InputEvent::Count(2)),
+    ("count-3", InputEvent::Count(3)),
+    ("count-4", InputEvent::Count(4)),
+    ("count-5", InputEvent::Count(5)),
+    ("count-6", InputEvent::Count(6)),
+    ("count-7", InputEvent::Count(7)),
+    ("count-8", InputEvent::Count(8)),
+    ("count-9", InputEvent::Count(9)),
+];
+
+const DEFAULT_KEYMAP: &str = "\
+C-q quit
+C-s save
+F2 save
+S-F2 save-as
+A-o force-save
+Left move-left
+Right move-right
+Up move-up
+Down move-down
+S-Up line-up
+S-Down line-down
+PageUp move-top
+PageDown move-bottom
+Home move-begin
+End move-end
+C-Left word-left
+C-Right word-right
+A-h screen-top
+A-m screen-middle
+A-l screen-bottom
+C-End file-end
+A-j goto-line
+BS delete-previous
+Del delete-next
+C-t transpose-chars
+C-u kill-to-line-start
+Tab insert-tab
+C-v quoted-insert
+CR newline
+Esc cancel
+C-z undo
+C-y redo
+C-f find
+A-s replace
+C-o open
+F3 search-next
+S-F3 search-previous
+C-l clear-search
+C-r record-macro
+C-e replay-macro
+A-e run-macro
+A-g describe-char
+A-d disk-version
+A-c change-list
+A-p message-log
+A-n line-numbers
+A-u relative-numbers
+A-t cycle-line-numbers
+A-i indent-guides
+A-z soft-wrap
+A-q fill-paragraph
+A-k align
+A-+ tab-width-wider
+A-- tab-width-narrower
+A-f filetype
+C-g file-info
+F12 debug-hud
+F5 reload-config
+A-b next-buffer
+C-PageDown next-buffer
+C-PageUp previous-buffer
+A-w close-buffer
+A-r reopen-buffer
+A-0 count-0
+A-1 count-1
+A-2 count-2
+A-3 count-3
+A-4 count-4
+A-5 count-5
+A-6 count-6
+A-7 count-7
+A-8 count-8
+A-9 count-9
+alias C-h BS
+alias 0x7f BS
+";
+
+fn action_name(action: InputEvent) -> &'static str {
+    ACTIONS
+        .iter()
+        .find(|(_, event)| *event == action)
+        .map(|(name, _)| *name)
+        .unwrap_or("unknown")
+}
+
+/// A key together with the modifiers held down as the terminal reports them. Read and
+/// written like a `KeyDescription`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Chord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    /// The chord of a key as read from the terminal. Control characters are read as the
+    /// key they are typed with, so a raw `0x08` is the same chord as `C-h`.
+    pub fn from_key(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        match code {
+            KeyCode::Char('\t') => Self::new(KeyCode::Tab, modifiers),
+            KeyCode::Char('\r' | '\n') => Self::new(KeyCode::Enter, modifiers),
+            KeyCode::Char('\x1b') => Self::new(KeyCode::Esc, modifiers),
+            KeyCode::Char(ch @ '\x01'..='\x1a') => Self::new(
+                KeyCode::Char((ch as u8 - 1 + b'a') as char),
+                modifiers | KeyModifiers::CONTROL,
+            ),
+            code => Self::new(code, modifiers),
+        }
+    }
+
+    /// The character the terminal sends for the chord, `None` for keys like the arrows
+    /// that send a sequence or chords with modifiers it can't send.
+    pub fn literal(&self) -> Option<char> {
+        match (self.code, self.modifiers - KeyModifiers::SHIFT) {
+            (KeyCode::Char(ch), KeyModifiers::NONE) => Some(ch),
+            (KeyCode::Char(ch @ 'a'..='z'), KeyModifiers::CONTROL) => {
+                Some((ch as u8 - b'a' + 1) as char)
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) => Some('\t'),
+            (KeyCode::Enter, KeyModifiers::NONE) => Some('\r'),
+            (KeyCode::Esc, KeyModifiers::NONE) => Some('\x1b'),
+            (KeyCode::Backspace, KeyModifiers::NONE) => Some('\x7f'),
+            _ => None,
+        }
+    }
+}
+
+impl From<Chord> for KeyDescription {
+    fn from(chord: Chord) -> Self {
+        let key = match chord.code {
+            KeyCode::Char(ch) => Key::Char(ch),
+            KeyCode::F(n) => Key::F(n),
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            KeyCode::PageUp => Key::PageUp,
+            KeyCode::PageDown => Key::PageDown,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Delete => Key::Delete,
+            KeyCode::Insert => Key::Insert,
+            KeyCode::Enter => Key::Enter,
+            KeyCode::Esc => Key::Esc,
+            KeyCode::Tab => Key::Tab,
+            KeyCode::BackTab => Key::BackTab,
+            KeyCode::Null => Key::Null,
+        };
+
+        Self {
+            key,
+            ctrl: chord.modifiers.contains(KeyModifiers::CONTROL),
+            alt: chord.modifiers.contains(KeyModifiers::ALT),
+            shift: chord.modifiers.contains(KeyModifiers::SHIFT),
+        }
+    }
+}
+
+impl From<KeyDescription> for Chord {
+    fn from(description: KeyDescription) -> Self {
+        let code = match description.key {
+            Key::Char(ch) => KeyCode::Char(ch),
+            Key::F(n) => KeyCode::F(n),
+            Key::Left => KeyCode::Left,
+            Key::Right => KeyCode::Right,
+            Key::Up => KeyCode::Up,
+            Key::Down => KeyCode::Down,
+            Key::Home => KeyCode::Home,
+            Key::End => KeyCode::End,
+            Key::PageUp => KeyCode::PageUp,
+            Key::PageDown => KeyCode::PageDown,
+            Key::Backspace => KeyCode::Backspace,
+            Key::Delete => KeyCode::Delete,
+            Key::Insert => KeyCode::Insert,
+            Key::Enter => KeyCode::Enter,
+            Key::Esc => KeyCode::Esc,
+            Key::Tab => KeyCode::Tab,
+            Key::BackTab => KeyCode::BackTab,
+            Key::Null => KeyCode::Null,
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for (held, modifier) in [
+            (description.ctrl, KeyModifiers::CONTROL),
+            (description.alt, KeyModifiers::ALT),
+            (description.shift, KeyModifiers::SHIFT),
+        ] {
+            modifiers.set(modifier, held);
+        }
+
+        Self::new(code, modifiers)
+    }
+}
+
+/// Written like its `KeyDescription`.
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        KeyDescription::from(*self).fmt(f)
+    }
+}
+
+impl FromStr for Chord {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        s.parse::<KeyDescription>().map(Self::from)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeymapError {
+    FailedToOpen(String),
+    InvalidChord {
+        line: usize,
+    },
+    UnknownAction {
+        line: usize,
+    },
+    Conflict {
+        chord: Chord,
+        first: &'static str,
+        second: &'static str,
+    },
+}
+
+impl Error for KeymapError {}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FailedToOpen(path) => write!(f, "Failed to open the keymap {path}"),
+            Self::InvalidChord { line } => write!(f, "Invalid key on line {line}"),
+            Self::UnknownAction { line } => write!(f, "Unknown action on line {line}"),
+            Self::Conflict {
+                chord,
+                first,
+                second,
+            } => write!(f, "{chord} is bound to both {first} and {second}"),
+        }
+    }
+}
+
+/// A default binding that a user binding replaced with a different action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShadowedBinding {
+    pub chord: Chord,
+    pub default: InputEvent,
+    pub user: InputEvent,
+}
+
+impl fmt::Display for ShadowedBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} now runs {} instead of {}",
+            self.chord,
+            action_name(self.user),
+            action_name(self.default)
+        )
+    }
+}
+
+/// Maps chords to actions. Several chords may share an action but a chord has only one action.
+///
+/// Keymap files have one `<chord> <action>` binding per line, blank lines and lines starting
+/// with `#` are skipped.
+///
+/// Terminals send some keys in more than one way, like Backspace as `C-h` or `0x7f`. A line
+/// `alias <chord> <key>` reads `chord` as `key` whenever `chord` isn't bound itself, so
+/// binding `C-h` to an action takes it back from Backspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: HashMap<Chord, InputEvent>,
+    aliases: HashMap<Chord, Chord>,
+}
+
+impl Keymap {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, KeymapError> {
+        let path = path.as_ref().to_string_lossy().to_string();
+        let content = fs::read_to_string(&path)
+            .report()
+            .change_context_lazy(|| KeymapError::FailedToOpen(path.clone()))?;
+
+        content.parse().report()
+    }
+
+    /// Add the user bindings and aliases on top of these, returning every binding they
+    /// shadowed.
+    pub fn merge(&mut self, user: Keymap) -> Vec<ShadowedBinding> {
+        self.aliases.extend(user.aliases);
+
+        let mut shadowed = user
+            .bindings
+            .into_iter()
+            .filter_map(|(chord, action)| {
+                let default = self.bindings.insert(chord, action)?;
+                (default != action).then_some(ShadowedBinding {
+                    chord,
+                    default,
+                    user: action,
+                })
+            })
+            .collect::<Vec<_>>();
+        shadowed.sort_by_key(|binding| binding.chord.to_string());
+
+        shadowed
+    }
+
+    pub fn action(&self, chord: Chord) -> Option<InputEvent> {
+        self.bindings.get(&chord).copied()
+    }
+
+    /// Every chord bound to `action` in a stable order.
+    pub fn chords(&self, action: InputEvent) -> Vec<Chord> {
+        let mut chords = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(chord, _)| *chord)
+            .collect::<Vec<_>>();
+        chords.sort_by_key(|chord| chord.to_string());
+
+        chords
+    }
+
+    /// The key `chord` is read as, `None` when it's bound itself or has no alias.
+    pub fn alias(&self, chord: Chord) -> Option<Chord> {
+        match self.bindings.contains_key(&chord) {
+            true => None,
+            false => self.aliases.get(&chord).copied(),
+        }
+    }
+
+    /// One line per bound action listing all of its chords, like `save: C-s, F2`. Followed
+    /// by a line for each alias in use, like `C-h: same as BS`.
+    pub fn help(&self) -> Vec<String> {
+        let mut aliases = self
+            .aliases
+            .keys()
+            .filter_map(|chord| Some(format!("{chord}: same as {}", self.alias(*chord)?)))
+            .collect::<Vec<_>>();
+        aliases.sort();
+
+        ACTIONS
+            .iter()
+            .filter_map(|(name, action)| {
+                let chords = self.chords(*action);
+                if chords.is_empty() {
+                    return None;
+                }
+
+                let chords = chords
+                    .iter()
+                    .map(|chord| chord.to_string())
+                    .collect::<Vec<_>>();
+                Some(format!("{name}: {}", chords.join(", ")))
+            })
+            .chain(aliases)
+            .collect()
+    }
+
+    /// Map a terminal event to its action. Unbound keys with an alias are read as the key
+    /// they alias. Keys bound without modifiers also match when modifiers are held and
+    /// unbound characters are typed as-is. Any other unbound key becomes
+    /// `InputEvent::Unbound` so the editor can tell it does nothing.
+    pub fn translate(&self, key: Event) -> Option<InputEvent> {
+        let chord = match key {
+            Event::Key(KeyEvent { code, modifiers }) => Chord::from_key(code, modifiers),
+            _ => return None,
+        };
+        let Chord { code, modifiers } = self.alias(chord).unwrap_or(chord);
+
+        self.action(Chord::new(code, modifiers))
+            .or_else(|| match code {
+                KeyCode::Char(_) => None,
+                _ => self.action(Chord::new(code, KeyModifiers::NONE)),
+            })
+            .or(match (code, modifiers) {
+                (KeyCode::Char(ch), KeyModifiers::NONE) => Some(InputEvent::InsertChar(ch)),
+                _ => Some(InputEvent::Unbound(chord.into())),
+            })
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        DEFAULT_KEYMAP
+            .parse()
+            .expect("default keymap should be valid")
+    }
+}
+
+impl FromStr for Keymap {
+    type Err = KeymapError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut bindings = HashMap::new();
+        let mut aliases = HashMap::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let line_number = i + 1;
+            let (chord, name) = line
+                .split_once(char::is_whitespace)
+                .ok_or(KeymapError::UnknownAction { line: line_number })?;
+            if chord == "alias" {
+                let (chord, key) = name
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .and_then(|(chord, key)| Some((chord.parse().ok()?, key.trim().parse().ok()?)))
+                    .ok_or(KeymapError::InvalidChord { line: line_number })?;
+                aliases.insert(chord, key);
+                continue;
+            }
+            let chord = chord
+                .parse::<Chord>()
+                .map_err(|_| KeymapError::InvalidChord { line: line_number })?;
+            let action = ACTIONS
+                .iter()
+                .find(|(action, _)| *action == name.trim())
+                .map(|(_, event)| *event)
+                .ok_or(KeymapError::UnknownAction { line: line_number })?;
+
+            match bindings.insert(chord, action) {
+                Some(previous) if previous != action => {
+                    return Err(KeymapError::Conflict {
+                        chord,
+                        first: action_name(previous),
+                        second: action_name(action),
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self { bindings, aliases })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    use crate::scratch::ScratchDir;
+
+    fn key(notation: &str) -> Event {
+        let chord = notation.parse::<Chord>().unwrap();
+        Event::Key(KeyEvent::new(chord.code, chord.modifiers))
+    }
+
+    #[test]
+    fn aliases_should_resolve_to_the_same_action() {
+        let keymap = Keymap::default();
+
+        assert_eq!(keymap.translate(key("C-s")), Some(InputEvent::SaveBuffer));
+        assert_eq!(keymap.translate(key("F2")), Some(InputEvent::SaveBuffer));
+    }
+
+    #[test]
+    fn binding_a_chord_to_two_actions_should_name_both_in_the_error() {
+        let err = "C-s save\nC-s quit\n".parse::<Keymap>().unwrap_err();
+
+        assert_eq!(err.to_string(), "C-s is bound to both save and quit");
+    }
+
+    #[test]
+    fn user_bindings_should_win_and_report_shadowed_defaults() {
+        let mut keymap = Keymap::default();
+
+        let shadowed = keymap.merge("C-s quit\nF2 save\nC-k save".parse().unwrap());
+
+        assert_eq!(keymap.translate(key("C-s")), Some(InputEvent::Quit));
+        assert_eq!(keymap.translate(key("C-k")), Some(InputEvent::SaveBuffer));
+        let shadowed = shadowed.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        assert_eq!(shadowed, vec!["C-s now runs quit instead of save"]);
+    }
+
+    #[test]
+    fn unbound_keys_should_still_produce_an_event() {
+        let keymap = Keymap::default();
+
+        assert_eq!(
+            keymap.translate(key("C-k")),
+            Some(InputEvent::Unbound("C-k".parse().unwrap()))
+        );
+        assert_eq!(keymap.translate(key("x")), Some(InputEvent::InsertChar('x')));
+    }
+
+    #[test_case("", "C-h" => InputEvent::DeletePreviousChar; "Ctrl-H")]
+    #[test_case("", "0x08" => InputEvent::DeletePreviousChar; "Raw backspace byte")]
+    #[test_case("", "0x7f" => InputEvent::DeletePreviousChar; "Raw delete byte")]
+    #[test_case("", "BS" => InputEvent::DeletePreviousChar; "Backspace")]
+    #[test_case("C-h find", "C-h" => InputEvent::Find; "Ctrl-H bound")]
+    #[test_case("C-h find", "0x08" => InputEvent::Find; "Raw backspace byte with Ctrl-H bound")]
+    #[test_case("C-h find", "BS" => InputEvent::DeletePreviousChar; "Backspace with Ctrl-H bound")]
+    #[test_case("alias C-h C-h", "C-h" => InputEvent::Unbound("C-h".parse().unwrap()); "Ctrl-H alias removed")]
+    #[test_case("alias 0x7f Del", "0x7f" => InputEvent::DeleteNextChar; "Raw delete byte aliased")]
+    fn ambiguous_keys_should_follow_the_aliases(user: &str, notation: &str) -> InputEvent {
+        let mut keymap = Keymap::default();
+        keymap.merge(user.parse().unwrap());
+
+        keymap.translate(key(notation)).unwrap()
+    }
+
+    #[test]
+    fn help_should_show_aliases_in_use() {
+        let mut keymap = Keymap::default();
+        assert!(keymap.help().contains(&String::from("C-h: same as BS")));
+
+        keymap.merge("C-h find".parse().unwrap());
+        let help = keymap.help();
+
+        assert!(help.contains(&String::from("find: C-f, C-h")));
+        assert!(!help.contains(&String::from("C-h: same as BS")));
+        assert!(help.contains(&String::from("0x7f: same as BS")));
+    }
+
+    #[test]
+    fn help_should_list_every_chord_of_an_action() {
+        let help = Keymap::default().help();
+
+        assert!(help.contains(&String::from("save: C-s, F2")));
+    }
+
+    #[test]
+    fn loading_a_changed_file_again_should_pick_up_the_new_bindings() {
+        let dir = ScratchDir::new("keymap");
+        let path = dir.join("keymap");
+
+        fs::write(&path, "C-k save\n").unwrap();
+        let keymap = Keymap::load(&path).unwrap();
+        assert_eq!(keymap.translate(key("C-k")), Some(InputEvent::SaveBuffer));
+
+        fs::write(&path, "C-k quit\nC-k save\n").unwrap();
+        assert!(Keymap::load(&path).is_err());
+
+        fs::write(&path, "C-k quit\n").unwrap();
+        let keymap = Keymap::load(&path).unwrap();
+        assert_eq!(keymap.translate(key("C-k")), Some(InputEvent::Quit));
+    }
+}
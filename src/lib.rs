@@ -1,49 +1,134 @@
 #![cfg_attr(feature = "extend_one", feature(extend_one))]
 
 use std::{
-    cell::RefCell,
-    fmt,
+    borrow::Cow,
+    cell::{RefCell, RefMut},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt, fs,
     io::{self, Write},
+    mem,
+    ops::Range,
+    path::{Path, PathBuf},
     rc::Rc,
-    time::SystemTime,
+    time::{Instant, SystemTime},
 };
 
 use buffer::BufferState;
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
     queue,
-    style::{Attribute, Print, SetAttribute},
+    style::{Attribute, Color, Print},
     terminal::{Clear, ClearType},
 };
 use error_stack::Result;
-use text::{ConsoleWidthStr, char_index};
+use text::{ConsoleWidthStr, char_index, column_at_display, column_window, describe_grapheme};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
 
 use crate::{
-    buffer::{Buffer, RowBufferRef},
+    address::resolve_line,
+    annotation::{Annotation, Severity},
+    bell::{Bell, FLASH_DURATION},
+    capabilities::Capabilities,
+    config::Config,
+    buffer::{split_lines, Buffer, Encoding, RowBufferRef, MAX_SAFE_LINE, MAX_TAB_STOP},
+    changes::{Change, ChangeList},
+    clock::{format_age, Clock},
+    component::{Area, Component, EventOutcome, Focus},
     cursor::*,
+    diff::{line_signs, Sign},
+    frame::{CountingWriter, FrameStats, RowsKey, HUD_WIDTH},
+    grid::Grid,
+    highlight::{filetype_by_name, Highlighter, LineState, SpanKind, StateCache},
+    history::{History, SEARCH_HISTORY_SIZE},
     input::{CursorEvent, InputEvent},
+    macro_file::{is_valid_name, MacroError},
+    messages::{LoggedMessage, MessageLog},
+    prompt::{Prompt, PromptKind, PromptState},
+    quit::QuitConfirm,
+    replace::{ReplaceAnswer, ReplaceState},
     screen::Screen,
+    search::SearchState,
+    state::{BufferSnapshot, EditorState, SessionEntry},
+    terminal::Terminal,
+    view::{BufferView, QuitQuestion, ReplaceView},
 };
 
+pub mod address;
+pub mod annotation;
+pub mod bell;
+pub mod capabilities;
 pub mod buffer;
+pub mod changes;
+pub mod clock;
+pub mod component;
+pub mod config;
 pub mod cursor;
+pub mod diff;
 pub mod error;
+pub mod frame;
+pub mod grid;
+pub mod highlight;
+pub mod history;
 pub mod input;
+pub mod key;
+pub mod keymap;
+pub mod macro_file;
 pub mod macros;
+pub mod messages;
+pub mod prompt;
+pub mod quit;
+pub mod replace;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+#[cfg(test)]
+mod scratch;
 pub mod screen;
+pub mod search;
+pub mod state;
+pub mod terminal;
 pub mod text;
+pub mod undo;
+mod view;
 
 const TAB_STOP: usize = 8;
 const SPACES: &str = "                                                                                                                                ";
 const NO_NAME: &str = "[No Name]";
+const QUIT_TIMES: u8 = 3;
+const CHANGE_LIST_SIZE: usize = 100;
+const MESSAGE_LOG_SIZE: usize = 100;
+const INDENT_GUIDE: char = '│';
+const CLOSED_BUFFERS: usize = 10;
+const TAB_NAME_WIDTH: usize = 15;
+const FILENAME_WIDTH: usize = 20;
+const LONG_LINE_BACKGROUND: Color = Color::DarkRed;
+const WRAP_INDICATOR: char = '↪';
+/// Below this size only a note asking for a bigger terminal is drawn
+const MIN_COLS: u16 = 10;
+const MIN_ROWS: u16 = 3;
+const TOO_SMALL: &str = "Terminal too small";
+/// Paragraphs are filled to this width in files without a fill column
+const FILL_WIDTH: usize = 70;
+/// Rows highlighted off the screen each time the editor is idle
+const IDLE_HIGHLIGHT_ROWS: usize = 1000;
 
 type ScreenRef = Rc<RefCell<Screen>>;
+/// The file, its modification time, and the revision and state of the buffer the diff
+/// signs were computed for
+type DiffKey = (Option<String>, Option<SystemTime>, u64, BufferState);
 
 /// The position on screen or buffer. The tuple index represents the horizontal value
 /// x or column while the vertical is y or rows for example.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Position(u16, u16);
 
+/// Shows the position as `row:col`, both counted from 1 like the status bar does.
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.1 as u32 + 1, self.0 as u32 + 1)
+    }
+}
+
 #[derive(Debug)]
 pub enum EditorEventError {
     SaveBuffer,
@@ -59,13 +144,168 @@ impl fmt::Display for EditorEventError {
     }
 }
 
+/// Whether the editor may exit after the user asked to quit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuitDecision {
+    Quit,
+    Stay,
+}
+
+/// Where the tab bar is drawn while more than one buffer is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TabBarPosition {
+    #[default]
+    Top,
+    /// Right above the status bar
+    Bottom,
+}
+
+/// How the file of the shown buffer is named in the status bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilenameDisplay {
+    /// The path the file was opened or saved with
+    #[default]
+    AsOpened,
+    Basename,
+    Absolute,
+    /// From the working directory, files outside of it show their absolute path
+    Relative,
+}
+
+/// What the gutter numbers lines with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineNumberStyle {
+    #[default]
+    Off,
+    Absolute,
+    /// The distance to the cursor line
+    Relative,
+    /// The distance to the cursor line, with the cursor line's own number
+    Hybrid,
+}
+
+impl LineNumberStyle {
+    /// The style after this one, back to `Off` after `Hybrid`
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Absolute,
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Hybrid,
+            Self::Hybrid => Self::Off,
+        }
+    }
+}
+
+/// Called on the shown buffer right before its rows are drawn, after the screen was
+/// scrolled to the cursor. It may change the rows, the frame is drawn with what is left.
+#[derive(Clone)]
+pub struct RenderHook(Rc<dyn Fn(&mut Buffer)>);
+
+impl fmt::Debug for RenderHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RenderHook")
+    }
+}
+
+/// The rest of a wrapped line, drawn on the rows under its first one.
+struct Continuation {
+    render: String,
+    spans: Vec<(Range<usize>, SpanKind)>,
+    segments: std::vec::IntoIter<Range<usize>>,
+    /// The column the text of every continuation row starts at
+    indent: usize,
+    fill_column: Option<usize>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Editor {
     screen: ScreenRef,
     buffer: Buffer,
     status_message: String,
     status_time: SystemTime,
+    clock: Clock,
+    /// Whether the status bar tells how long ago the buffer was saved
+    show_save_age: bool,
     cursor: BoundedCursor,
+    /// Open buffers that aren't shown, the next one to switch to first
+    others: Vec<Buffer>,
+    tab_bar: TabBarPosition,
+    filename_display: FilenameDisplay,
+    /// The tab of the current buffer, tabs keep their order while switching
+    active_tab: usize,
+    /// Filename and cursor of recently closed buffers, the most recent last
+    closed: Vec<SessionEntry>,
+    /// Where undo histories are kept between sessions, if they are
+    undo_dir: Option<PathBuf>,
+    focus: Focus,
+    prompt: Option<Prompt>,
+    changes: Option<ChangeList>,
+    /// The last status messages shown, oldest first
+    message_log: VecDeque<LoggedMessage>,
+    messages: Option<MessageLog>,
+    search: Option<SearchState>,
+    search_history: History,
+    replace: Option<ReplaceState>,
+    quit_confirm: QuitConfirm,
+    quit_times: u8,
+    /// Whether it was answered yes
+    quit_confirmed: bool,
+    /// Whether saving again overwrites a file changed on disk since it was read or written
+    overwrite_pending: bool,
+    line_numbers: bool,
+    /// Number lines by their distance to the cursor. Together with `line_numbers` the
+    /// cursor line keeps its own number.
+    relative_numbers: bool,
+    /// How lines differ from the saved file, ordered by line
+    diff_signs: Vec<(usize, Sign)>,
+    /// The filename, modification count and state the signs were computed for
+    diff_key: Option<DiffKey>,
+    /// Columns past which text is highlighted, by filetype
+    fill_columns: HashMap<String, usize>,
+    /// Characters besides letters and digits that make up words, by filetype
+    word_chars: HashMap<String, HashSet<char>>,
+    default_word_chars: HashSet<char>,
+    /// How comments and strings are found, by filetype
+    highlighters: HashMap<String, Rc<dyn Highlighter>>,
+    /// Tab widths buffers get when they are shown, by filetype
+    tab_stops: HashMap<String, usize>,
+    /// The tab width of buffers whose filetype has none set
+    default_tab_stop: Option<usize>,
+    highlight_states: RefCell<StateCache>,
+    indent_guides: bool,
+    /// Whether long lines wrap at the edge of the screen instead of scrolling sideways
+    soft_wrap: bool,
+    /// Whether typing a tab inserts spaces up to the next tab stop
+    soft_tabs: bool,
+    /// Whether a new line starts with the indent of the line it was broken from
+    auto_indent: bool,
+    /// Whether a typed quote opening a string comes with the quote closing it
+    auto_close_quotes: bool,
+    /// Whether the keys being handled come from `paste`, which inserts text exactly as it
+    /// is without auto indent or soft tabs
+    inserting_paste: bool,
+    /// Whether the last key asked for the next one to be inserted as-is
+    quoting: bool,
+    /// Whether pasted text gets its line endings unified and its final newline dropped
+    normalize_paste: bool,
+    count: Option<u32>,
+    frame_stats: FrameStats,
+    drawn_rows: Option<RowsKey>,
+    hud: bool,
+    bell: Bell,
+    capabilities: Capabilities,
+    bell_pending: bool,
+    flash: bool,
+    deadline: Option<Instant>,
+    recording: Option<Vec<InputEvent>>,
+    macro_register: Vec<InputEvent>,
+    named_macros: BTreeMap<String, Vec<InputEvent>>,
+    /// Where named macros are written, if they are
+    #[cfg(feature = "serde")]
+    macro_dir: Option<PathBuf>,
+    /// A named macro and its count picked in a prompt, replayed once the prompt is closed
+    pending_macro: Option<(Vec<InputEvent>, u16)>,
+    render_hook: Option<RenderHook>,
 }
 
 impl Editor {
@@ -75,11 +315,70 @@ impl Editor {
             buffer: Default::default(),
             status_message: String::new(),
             status_time: SystemTime::now(),
+            clock: Clock::system(),
+            show_save_age: false,
             cursor: Default::default(),
+            others: Vec::new(),
+            tab_bar: TabBarPosition::default(),
+            filename_display: FilenameDisplay::default(),
+            active_tab: 0,
+            closed: Vec::new(),
+            undo_dir: None,
+            focus: Focus::default(),
+            prompt: None,
+            changes: None,
+            message_log: VecDeque::new(),
+            messages: None,
+            search: None,
+            search_history: History::new(SEARCH_HISTORY_SIZE),
+            replace: None,
+            quit_confirm: QuitConfirm::Count(QUIT_TIMES + 1),
+            quit_times: QUIT_TIMES,
+            quit_confirmed: false,
+            overwrite_pending: false,
+            line_numbers: false,
+            relative_numbers: false,
+            diff_signs: Vec::new(),
+            diff_key: None,
+            fill_columns: HashMap::new(),
+            word_chars: HashMap::new(),
+            default_word_chars: text::default_word_chars(),
+            highlighters: HashMap::new(),
+            tab_stops: HashMap::new(),
+            default_tab_stop: None,
+            highlight_states: RefCell::default(),
+            indent_guides: false,
+            soft_wrap: false,
+            soft_tabs: false,
+            auto_indent: false,
+            auto_close_quotes: false,
+            inserting_paste: false,
+            quoting: false,
+            normalize_paste: true,
+            count: None,
+            frame_stats: FrameStats::default(),
+            drawn_rows: None,
+            hud: false,
+            bell: Bell::default(),
+            capabilities: Capabilities::default(),
+            bell_pending: false,
+            flash: false,
+            deadline: None,
+            recording: None,
+            macro_register: Vec::new(),
+            named_macros: BTreeMap::new(),
+            #[cfg(feature = "serde")]
+            macro_dir: None,
+            pending_macro: None,
+            render_hook: None,
         };
 
         me.cursor.set_buffer(Rc::clone(me.buffer.buffer()));
         me.cursor.set_screen(Rc::clone(&me.screen));
+        me.screen
+            .borrow_mut()
+            .set_wrap_indicator(Some(WRAP_INDICATOR));
+        me.screen.borrow_mut().resize(cols, rows);
 
         me
     }
@@ -87,31 +386,152 @@ impl Editor {
     pub fn draw_rows<W: Write>(&self, writer: &mut W) -> io::Result<()> {
         let screen = self.screen.borrow();
         let buf = self.buffer.buffer().borrow();
+        let len = buf.len();
+        let tab_stop = self.buffer.tab_stop();
+        let gutter_width = self.gutter_width() as usize;
+        let indent_width = self.indent_guides.then(|| self.buffer.indent_width());
+        let fill_column = self.fill_column();
+        let popup = match (self.focus, &self.prompt) {
+            (Focus::Prompt, Some(prompt)) => prompt.completions(),
+            _ => &[],
+        };
+        let popup_start = screen.rows().saturating_sub(popup.len() as u16);
+        let highlighter = self.highlighter();
+        // Comments and strings left open above the screen carry into its first row
+        let mut state = highlighter.map_or(LineState::Normal, |highlighter| {
+            let top = screen.row_offset() as usize;
+            let key = self.rows_identity();
+            self.highlight_states()
+                .state_at(key, highlighter, &buf, top)
+        });
+        // Annotations of the last line drawn still waiting for a row, with that line's indent
+        let mut annotations = [].iter();
+        let mut annotation_indent = 0;
+        // What's left of the last line drawn when it wraps
+        let mut continuation: Option<Continuation> = None;
+        let mut next_row = screen.row_offset();
         for i in 0..screen.rows() {
-            let file_row = i + screen.row_offset();
-            if file_row >= buf.len() as u16 {
-                if buf.is_empty() && i == (screen.rows() / 3) {
+            if i >= popup_start {
+                self.draw_completion(writer, popup, (i - popup_start) as usize)?;
+                continue;
+            }
+
+            if let Some(rest) = &mut continuation {
+                if let Some(segment) = rest.segments.next() {
+                    self.draw_continuation(writer, rest, segment)?;
+                    continue;
+                }
+            }
+
+            if let Some(annotation) = annotations.next() {
+                self.draw_annotation(writer, annotation, annotation_indent)?;
+                continue;
+            }
+
+            let file_row = next_row;
+            next_row = next_row.saturating_add(1);
+            annotations = self.buffer.annotations(file_row as usize).iter();
+            if annotations.len() > 0 {
+                let render = buf
+                    .get(file_row as usize)
+                    .map(|row| row.render_buffer(tab_stop));
+                annotation_indent = render.map_or(0, |render| {
+                    render.len() - render.trim_start_matches(' ').len()
+                });
+            }
+
+            if gutter_width > 0 {
+                let mut gutter = String::new();
+                if !self.diff_signs.is_empty() {
+                    let sign = self.diff_sign(file_row as usize);
+                    gutter.push(sign.map_or(' ', |sign| sign.symbol()));
+                }
+
+                let numbers_width = self.numbers_width() as usize;
+                match self.line_number(file_row) {
+                    Some(number) if (file_row as usize) < len => {
+                        gutter += &format!("{:>1$} ", number, numbers_width - 1)
+                    }
+                    _ => gutter += &format!("{:1$}", "", numbers_width),
+                }
+
+                if self.relative_numbers && file_row == self.cursor.y() {
+                    queue!(
+                        writer,
+                        self.capabilities.style(Attribute::Bold),
+                        Print(&gutter[..gutter_width]),
+                        self.capabilities.style(Attribute::NormalIntensity)
+                    )?;
+                } else {
+                    write!(writer, "{}", &gutter[..gutter_width])?;
+                }
+            }
+
+            match buf.get(file_row as usize) {
+                None if len == 0 && i == (screen.rows() / 3) => {
                     let message = self.message();
                     let padding = self.padding(message.len() as u16);
 
                     write!(writer, "{}{}", padding, &message)?;
-                } else {
-                    write!(writer, "~")?;
                 }
-            } else {
-                let len = buf[file_row as usize]
-                    .render_buffer()
-                    .len()
-                    .saturating_sub(screen.col_offset() as usize)
-                    .min(screen.cols() as usize);
-
-                if buf[file_row as usize].render_buffer().len() >= screen.col_offset() as usize {
-                    write!(
-                        writer,
-                        "{}",
-                        &buf[file_row as usize].render_buffer()
-                            [(screen.col_offset() as usize)..screen.col_offset() as usize + len]
-                    )?;
+                None => write!(writer, "~")?,
+                Some(row) => {
+                    let start = screen.col_offset() as usize;
+                    let width = screen.content_cols(self.gutter_width()) as usize;
+
+                    // Rendering a huge row whole would allocate all of it for one screen's worth
+                    if row.byte_len() > MAX_SAFE_LINE {
+                        if let Some(highlighter) = highlighter {
+                            state = highlighter.highlight_line(row.buffer(), state).1;
+                        }
+                        write!(writer, "{}", row.render_at_offset(start, width, tab_stop))?;
+                        queue!(writer, Clear(ClearType::UntilNewLine))?;
+                        write!(writer, "\r\n")?;
+                        continue;
+                    }
+
+                    let render = row.render_buffer(tab_stop);
+                    let visible = column_window(&render, start, width);
+                    let spans = match highlighter {
+                        Some(highlighter) => {
+                            let (spans, next) = highlighter.highlight_line(&render, state);
+                            state = next;
+                            spans
+                                .into_iter()
+                                .map(|span| {
+                                    let from = render[..span.range.start].column_width();
+                                    let to = render[..span.range.end].column_width();
+                                    (from..to, span.kind)
+                                })
+                                .collect()
+                        }
+                        None => Vec::new(),
+                    };
+
+                    let fill_column =
+                        fill_column.filter(|fill| row.display_width(tab_stop) > *fill);
+                    let (within, beyond) = split_at_fill(&visible, start, width, fill_column);
+
+                    let split = match indent_width {
+                        Some(width) => self
+                            .draw_indent_guides(writer, &render, start, &within, file_row, width)?,
+                        None => 0,
+                    };
+                    self.draw_highlighted(writer, &within[split..], start + split, &spans)?;
+                    self.draw_beyond_fill(writer, &beyond)?;
+
+                    if self.soft_wrap {
+                        let (segments, indent) = screen.wrap_segments(row, width, tab_stop);
+                        let mut segments = segments.into_iter();
+                        segments.next();
+                        continuation = Some(Continuation {
+                            render: render.into_owned(),
+                            spans,
+                            segments,
+                            indent,
+                            fill_column,
+                        });
+                    }
                 }
             }
 
@@ -122,177 +542,4433 @@ impl Editor {
         Ok(())
     }
 
+    /// Draw the next row of a wrapped line, `segment` being the columns of the line it
+    /// shows, after the gutter and the wrap indicator.
+    fn draw_continuation<W: Write>(
+        &self,
+        writer: &mut W,
+        rest: &Continuation,
+        segment: Range<usize>,
+    ) -> io::Result<()> {
+        write!(writer, "{:1$}", "", self.gutter_width() as usize)?;
+        let indicator = self.screen.borrow().wrap_indicator();
+        let indicator_width = indicator.map_or(0, |ch| ch.width().unwrap_or(1));
+        let padding = match indicator {
+            Some(indicator) if indicator_width <= rest.indent => {
+                queue!(
+                    writer,
+                    self.capabilities.style(Attribute::Dim),
+                    Print(indicator),
+                    self.capabilities.style(Attribute::NormalIntensity)
+                )?;
+                rest.indent - indicator_width
+            }
+            _ => rest.indent,
+        };
+        write!(writer, "{:1$}", "", padding)?;
+
+        let (start, width) = (segment.start, segment.len());
+        let visible = column_window(&rest.render, start, width);
+        let (within, beyond) = split_at_fill(&visible, start, width, rest.fill_column);
+        self.draw_highlighted(writer, &within, start, &rest.spans)?;
+        self.draw_beyond_fill(writer, &beyond)?;
+
+        queue!(writer, Clear(ClearType::UntilNewLine))?;
+        write!(writer, "\r\n")
+    }
+
+    /// Draw the part of a line past the fill column on the long line background.
+    fn draw_beyond_fill<W: Write>(&self, writer: &mut W, beyond: &str) -> io::Result<()> {
+        if beyond.is_empty() {
+            return Ok(());
+        }
+
+        queue!(
+            writer,
+            self.capabilities.background(LONG_LINE_BACKGROUND),
+            Print(beyond),
+            self.capabilities.background(Color::Reset)
+        )
+    }
+
+    /// Draw the leading whitespace of `visible`, the part of the rendered `row` starting at
+    /// column `start`, with a guide at every indent level. The cursor cell is left blank.
+    /// Returns the length of the whitespace drawn, the rest of `visible` is left to draw.
+    fn draw_indent_guides<W: Write>(
+        &self,
+        writer: &mut W,
+        row: &str,
+        start: usize,
+        visible: &str,
+        file_row: u16,
+        width: usize,
+    ) -> io::Result<usize> {
+        let indent = row.len() - row.trim_start_matches(' ').len();
+        let cursor = (file_row == self.cursor.y()).then(|| self.cursor.render());
+        let split = indent.saturating_sub(start).min(visible.len());
+
+        for col in start..start + split {
+            if col % width == 0 && Some(col) != cursor {
+                queue!(
+                    writer,
+                    self.capabilities.style(Attribute::Dim),
+                    Print(INDENT_GUIDE),
+                    self.capabilities.style(Attribute::NormalIntensity)
+                )?;
+            } else {
+                write!(writer, " ")?;
+            }
+        }
+
+        Ok(split)
+    }
+
+    /// Draw `text` starting at column `start` of its row, with the columns in `spans` styled
+    /// by their kind.
+    fn draw_highlighted<W: Write>(
+        &self,
+        writer: &mut W,
+        text: &str,
+        start: usize,
+        spans: &[(Range<usize>, SpanKind)],
+    ) -> io::Result<()> {
+        if spans.is_empty() {
+            return write!(writer, "{}", text);
+        }
+
+        let mut segments = Vec::new();
+        let (mut col, mut from, mut kind) = (start, 0, None);
+        for (i, ch) in text.char_indices() {
+            let next = spans
+                .iter()
+                .find(|(columns, _)| columns.contains(&col))
+                .map(|(_, kind)| *kind);
+            if next != kind {
+                segments.push((&text[from..i], kind));
+                (from, kind) = (i, next);
+            }
+            col += ch.width().unwrap_or(0);
+        }
+        segments.push((&text[from..], kind));
+
+        for (segment, kind) in segments {
+            let (on, off) = match kind {
+                Some(SpanKind::Comment) => (Attribute::Dim, Attribute::NormalIntensity),
+                Some(SpanKind::String) => (Attribute::Italic, Attribute::NoItalic),
+                None => {
+                    write!(writer, "{}", segment)?;
+                    continue;
+                }
+            };
+            queue!(
+                writer,
+                self.capabilities.style(on),
+                Print(segment),
+                self.capabilities.style(off)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Draw `annotation` dimmed on a row of its own, in line with text indented by `indent`.
+    fn draw_annotation<W: Write>(
+        &self,
+        writer: &mut W,
+        annotation: &Annotation,
+        indent: usize,
+    ) -> io::Result<()> {
+        let screen = self.screen.borrow();
+        let start = screen.col_offset() as usize;
+        let width = screen.content_cols(self.gutter_width()) as usize;
+        let text = format!("{:indent$}{annotation}", "");
+
+        queue!(
+            writer,
+            Print(format!("{:1$}", "", self.gutter_width() as usize)),
+            self.capabilities.style(Attribute::Dim),
+            Print(column_window(&text, start, width)),
+            self.capabilities.style(Attribute::NormalIntensity),
+            Clear(ClearType::UntilNewLine)
+        )?;
+        write!(writer, "\r\n")
+    }
+
+    /// Draw entry `index` of the path completions shown above the prompt, the selected one
+    /// reversed.
+    fn draw_completion<W: Write>(
+        &self,
+        writer: &mut W,
+        completions: &[String],
+        index: usize,
+    ) -> io::Result<()> {
+        let selected = self.prompt.as_ref().and_then(Prompt::selected_completion);
+        let entry = &completions[index];
+        let entry = &entry[..char_index(self.screen.borrow().cols() as usize, entry)];
+
+        if selected == Some(index) {
+            queue!(
+                writer,
+                self.capabilities.style(Attribute::Reverse),
+                Print(entry),
+                self.capabilities.style(Attribute::NoReverse)
+            )?;
+        } else {
+            queue!(writer, Print(entry))?;
+        }
+
+        queue!(writer, Clear(ClearType::UntilNewLine))?;
+        write!(writer, "\r\n")
+    }
+
+    /// One tab for every open buffer with its filename and a `*` when it is modified. Tabs
+    /// are left out from the left until the current one, drawn reversed, fits.
+    fn draw_tab_bar<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let labels = self
+            .tabs()
+            .map(|buf| {
+                let name = buf
+                    .filename_str()
+                    .map(|filename| {
+                        Path::new(filename)
+                            .file_name()
+                            .map_or(filename.into(), |name| name.to_string_lossy())
+                    })
+                    .unwrap_or(NO_NAME.into());
+                let name = &name[..char_index(TAB_NAME_WIDTH, &name)];
+                let modified = match buf.state() {
+                    BufferState::Modified => "*",
+                    _ => "",
+                };
+                format!(" {name}{modified} ")
+            })
+            .collect::<Vec<_>>();
+
+        let cols = self.screen.borrow().cols() as usize;
+        let width = |tabs: &[String]| tabs.iter().map(|tab| tab.column_width()).sum::<usize>();
+        let mut first = 0;
+        while first < self.active_tab && width(&labels[first..=self.active_tab]) > cols {
+            first += 1;
+        }
+
+        let mut remaining = cols;
+        for (i, label) in labels.iter().enumerate().skip(first) {
+            let visible = column_window(label, 0, remaining);
+            remaining -= visible.column_width();
+            if i == self.active_tab {
+                queue!(
+                    writer,
+                    self.capabilities.style(Attribute::Reverse),
+                    Print(visible),
+                    self.capabilities.style(Attribute::NoReverse)
+                )?;
+            } else {
+                queue!(writer, Print(visible))?;
+            }
+        }
+
+        queue!(writer, Clear(ClearType::UntilNewLine))?;
+        write!(writer, "\r\n")
+    }
+
     fn draw_status_bar<W: Write>(&self, writer: &mut W) -> io::Result<()> {
-        let buf = self.buffer.buffer().borrow();
-        let filename = self
-            .buffer
-            .filename_str()
-            .map(|x| &x[..x.len().min(20)])
-            .unwrap_or(NO_NAME);
+        let filename = self.status_filename();
+        let filename = shorten_path(&filename, FILENAME_WIDTH);
 
-        let rows = buf.len();
+        let rows = self.buffer.statistics().lines;
         let modified = match self.buffer.state() {
+            _ if self.buffer.is_read_only() => "(read-only)",
             BufferState::Modified => "(modified)",
             _ => "",
         };
         let left = format!("{filename} - {rows} lines {modified}");
-        let right = format!("{}/{}", self.cursor.y() + 1, rows);
+        let undo = self.buffer.undo_stack();
+        let options = self.buffer.save_options();
+        let undo_indicator = (undo.undo_count() + undo.redo_count() > 0)
+            .then(|| format!("[u:{} r:{}]", undo.undo_count(), undo.redo_count()));
+        let file_format = format!(
+            "{} {}",
+            options.encoding.name(),
+            options.line_ending.name()
+        );
+        let tab_stop = (self.buffer.tab_stop() != TAB_STOP)
+            .then(|| format!("tabs:{}", self.buffer.tab_stop()));
+        // The column on the screen no longer tells where in the line the cursor is
+        let column = self
+            .soft_wrap
+            .then(|| format!("col:{}", self.cursor.x() + 1));
+        let save_age = self.show_save_age.then(|| match self.buffer.saved_at() {
+            Some(saved_at) => format!("saved {} ago", format_age(self.clock.since(saved_at))),
+            None => String::from("never saved"),
+        });
+        let right = self
+            .buffer
+            .filetype()
+            .into_iter()
+            .chain(std::iter::once(file_format))
+            .chain(tab_stop)
+            .chain(self.count.map(|count| count.to_string()))
+            .chain(self.search.as_ref().map(|search| {
+                let rows = self.buffer.buffer().borrow();
+                search.indicator(&rows, self.buffer.modification_count())
+            }))
+            .chain(undo_indicator)
+            .chain(save_age)
+            .chain(column)
+            .chain(std::iter::once(format!("{}/{}", self.cursor.y() + 1, rows)))
+            .collect::<Vec<_>>()
+            .join(" ");
 
-        let fill_length =
-            (self.screen.borrow().cols() as usize).saturating_sub(right.len() + left.len());
+        // When everything doesn't fit, shorten the file info first to keep the position visible
+        let cols = self.screen.borrow().cols() as usize;
+        let left = if left.column_width() + right.len() >= cols {
+            let width = cols.saturating_sub(right.len() + 1);
+            format!("{} ", &left[..char_index(width, &left)])
+        } else {
+            left
+        };
+        let fill_length = cols.saturating_sub(right.len() + left.column_width());
         let modeline = if fill_length < SPACES.len() {
             format!("{left:<}{}{right:>}", &SPACES[..fill_length])
         } else {
             format!("{left:<}{}{right:>}", " ".repeat(fill_length))
         };
+        let modeline = &modeline[..char_index(cols, &modeline)];
+        let attribute = if self.flash {
+            Attribute::NoReverse
+        } else {
+            Attribute::Reverse
+        };
 
         queue!(
             writer,
-            SetAttribute(Attribute::Reverse),
+            self.capabilities.style(attribute),
             Print(modeline),
-            SetAttribute(Attribute::Reset),
+            self.capabilities.style(Attribute::Reset),
             Print("\r\n"),
         )?;
 
         Ok(())
     }
 
-    fn draw_message_bar<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+    /// Draw the rows of text, or the component with focus in their place when it covers
+    /// them.
+    fn draw_text_area<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let (cols, rows) = {
+            let screen = self.screen.borrow();
+            (screen.cols(), screen.rows())
+        };
+        let drawn = self.with_focused(|component| match component.area() {
+            Area::Rows => component.draw(writer, cols, rows).map(|()| true),
+            Area::MessageBar => Ok(false),
+        })?;
+
+        match drawn {
+            true => Ok(()),
+            false => self.draw_rows(writer),
+        }
+    }
+
+    fn draw_message_bar<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
         queue!(writer, Clear(ClearType::UntilNewLine))?;
+        let cols = self.screen.borrow().cols();
+        let drawn = self.with_focused(|component| match component.area() {
+            Area::MessageBar => component.draw(writer, cols, 1).map(|()| true),
+            Area::Rows => Ok(false),
+        })?;
+        if drawn {
+            return Ok(());
+        }
+
+        // A hint for pending input covers the status message without replacing it, so the
+        // message comes back with its timeout untouched once the input is complete
+        if let Some(hint) = self.key_hint() {
+            let cols = self.screen.borrow().cols() as usize;
+            queue!(writer, Print(&hint[..char_index(cols, &hint)]))?;
+            return Ok(());
+        }
+
         let message_len = self
             .status_message
             .column_width()
             .min(self.screen.borrow().cols() as usize);
 
         let index = char_index(message_len, &self.status_message);
-        if let Ok(duration) = self.status_time.elapsed() {
-            if duration.as_secs() < 5 {
-                queue!(writer, Print(&self.status_message[..index]))?;
-            } else {
-                queue!(writer, Print(""))?;
-            }
+        if self.clock.since(self.status_time).as_secs() < 5 {
+            queue!(writer, Print(&self.status_message[..index]))?;
+        } else {
+            queue!(writer, Print(""))?;
         }
 
         Ok(())
     }
 
-    pub fn refresh<W: Write>(&mut self, writer: &mut W) -> crossterm::Result<()> {
+    pub fn refresh<W: Write>(&mut self, terminal: &mut Terminal<W>) -> crossterm::Result<()> {
+        let start = Instant::now();
+        let mut writer = CountingWriter::new(terminal);
+        if self.is_too_small() {
+            self.drawn_rows = None;
+            self.draw_too_small(&mut writer)?;
+            return writer.flush();
+        }
+
+        let ring = mem::take(&mut self.bell_pending);
+        self.flash = ring && self.bell == Bell::Visual;
+
         // Update the render cursor to match cursor position
         let render_x = self.cursor.render() as u16;
 
-        self.screen.borrow_mut().scroll(render_x, self.cursor.y());
+        let gutter_width = self.gutter_width();
+        // Wrapped lines are never scrolled sideways
+        let scroll_x = if self.soft_wrap { 0 } else { render_x };
+        self.screen
+            .borrow_mut()
+            .scroll(scroll_x, self.cursor.y(), gutter_width);
+        if let Some(RenderHook(hook)) = self.render_hook.clone() {
+            hook(&mut self.buffer);
+        }
+        // The hook may have removed rows, the frame sticks to the ones there are now
+        let len = self.buffer.buffer().borrow().len() as u16;
+        let cursor_row = self.cursor.y().min(len);
+        // Annotations and wrapped lines above the cursor take extra rows and can push it off
+        // the screen
+        loop {
+            let screen = self.screen.borrow();
+            let (col, row) = (screen.col_offset(), screen.row_offset());
+            if row >= cursor_row || self.cursor_screen_row(&screen, cursor_row) < screen.rows() {
+                break;
+            }
+            drop(screen);
+            self.screen.borrow_mut().set_offset(col, row + 1);
+        }
         queue!(writer, MoveTo(0, 0), Hide)?;
 
-        self.draw_rows(writer)?;
-        self.draw_status_bar(writer)?;
-        self.draw_message_bar(writer)?;
-        queue!(
-            writer,
-            MoveTo(
-                render_x - self.screen.borrow().col_offset(),
-                self.cursor.y() - self.screen.borrow().row_offset()
-            ),
-            Show
-        )?;
+        let text_top = self.text_top();
+        if text_top > 0 {
+            self.draw_tab_bar(&mut writer)?;
+        }
+
+        let rows_key = self.rows_key();
+        let rows_total = self.screen.borrow().rows();
+        let rows_drawn = if self.drawn_rows == Some(rows_key) {
+            queue!(writer, MoveTo(0, text_top + rows_total))?;
+            0
+        } else {
+            self.draw_text_area(&mut writer)?;
+            self.drawn_rows = Some(rows_key);
+            rows_total
+        };
+        if self.tab_bar_visible() && text_top == 0 {
+            self.draw_tab_bar(&mut writer)?;
+        }
+        self.draw_status_bar(&mut writer)?;
+        self.draw_message_bar(&mut writer)?;
+
+        let cols = self.screen.borrow().cols();
+        if self.hud && rows_total > 0 && cols >= HUD_WIDTH {
+            queue!(writer, MoveTo(cols - HUD_WIDTH, text_top), self.capabilities.style(Attribute::Reverse))?;
+            self.frame_stats.draw(&mut writer)?;
+            queue!(writer, self.capabilities.style(Attribute::Reset))?;
+        }
+
+        let (x, y) = self.screen_cursor();
+
+        // The match waiting for an answer sits under the cursor
+        if let (Focus::Replace, Some(replace)) = (self.focus, &self.replace) {
+            let width = cols.saturating_sub(x) as usize;
+            queue!(
+                writer,
+                MoveTo(x, y),
+                self.capabilities.style(Attribute::Reverse),
+                Print(column_window(replace.find(), 0, width)),
+                self.capabilities.style(Attribute::Reset)
+            )?;
+        }
+        queue!(writer, MoveTo(x, y), Show)?;
+
+        if ring && self.bell == Bell::Audible {
+            queue!(writer, Print('\x07'))?;
+        }
 
         writer.flush()?;
 
+        // The flash only lasts this frame, the next one drawn after the deadline restores it
+        self.deadline = self.flash.then(|| Instant::now() + FLASH_DURATION);
+
+        self.frame_stats = FrameStats {
+            duration: start.elapsed(),
+            rows_drawn,
+            rows_total,
+            bytes_written: writer.count(),
+            events_processed: self.frame_stats.events_processed,
+        };
+
         Ok(())
     }
 
-    pub fn set_status_message<T: Into<String>>(&mut self, message: T) {
-        self.status_message = message.into();
-        self.status_time = SystemTime::now();
+    /// Fit the editor to `cols` by `rows` of text, without the status and message bars. A
+    /// size too small to edit in shows a note until it grows again.
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        self.screen.borrow_mut().resize(cols, rows);
+        self.drawn_rows = None;
     }
 
-    pub fn set_buffer(&mut self, buf: Buffer) {
-        self.buffer = buf;
-        self.cursor.set_buffer(Rc::clone(self.buffer.buffer()));
+    fn is_too_small(&self) -> bool {
+        let screen = self.screen.borrow();
+        screen.cols() < MIN_COLS || screen.rows() < MIN_ROWS
     }
 
-    pub fn buffer(&self) -> &Buffer {
-        &self.buffer
+    /// Blank every row of the frame but the first, which tells the terminal is too small.
+    fn draw_too_small<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let cols = self.screen.borrow().cols() as usize;
+        queue!(writer, Hide)?;
+        for y in 0..=self.message_row() {
+            queue!(writer, MoveTo(0, y), Clear(ClearType::UntilNewLine))?;
+        }
+
+        queue!(
+            writer,
+            MoveTo(0, 0),
+            Print(column_window(TOO_SMALL, 0, cols))
+        )
     }
 
-    pub fn process_event(&mut self, event: InputEvent) -> Result<(), EditorEventError> {
-        macro_rules! cursor {
-            ( $ev:tt ) => {
-                InputEvent::CursorEvent(CursorEvent::$ev)
-            };
+    /// Where the cursor is shown on the screen, in the prompt while it has the focus. Right
+    /// after `refresh` this is where it left the terminal's cursor, for hosts drawing the
+    /// frame themselves.
+    pub fn screen_cursor(&self) -> (u16, u16) {
+        if let (Focus::Prompt, Some(prompt)) = (self.focus, &self.prompt) {
+            return (prompt.cursor() as u16, self.message_row());
         }
 
-        match event {
-            cursor!(MoveLeft) => self.cursor.left(),
-            cursor!(MoveRight) => self.cursor.right(),
-            cursor!(MoveUp) => self.cursor.up(),
-            cursor!(MoveDown) => self.cursor.down(),
-            cursor!(MoveTop) => self.cursor.top(),
-            cursor!(MoveBottom) => self.cursor.bottom(),
-            cursor!(MoveBegin) => self.cursor.begin(),
-            cursor!(MoveEnd) => self.cursor.end(),
-            InputEvent::InsertChar(ch) => {
-                self.buffer.insert_char(ch, &self.cursor);
-                self.cursor.right()
-            }
-            InputEvent::SaveBuffer => {
-                if let Err(_err) = self.buffer.save() {
-                    self.set_status_message(format!(
-                        "Can't save file {}",
-                        self.buffer.filename_str().unwrap_or(crate::NO_NAME)
-                    ));
-                }
-                self.set_status_message(format!(
-                    "Saved {}",
-                    self.buffer.filename_str().unwrap_or("??")
-                ))
-            }
-            InputEvent::DeletePreviousChar => {
-                self.buffer.delete_char(&mut self.cursor);
+        let screen = self.screen.borrow();
+        let x = match self.soft_wrap {
+            true => {
+                let last = screen.content_cols(self.gutter_width()).saturating_sub(1);
+                (self.wrapped_cursor(&screen).1 as u16).min(last)
             }
-            InputEvent::DeleteNextChar => {
-                self.cursor.right();
-                self.buffer.delete_char(&mut self.cursor);
+            false => (self.cursor.render() as u16).saturating_sub(screen.col_offset()),
+        };
+        let len = self.buffer.buffer().borrow().len() as u16;
+        let y = self.cursor_screen_row(&screen, self.cursor.y().min(len));
+
+        (x + self.gutter_width(), y + self.text_top())
+    }
+
+    /// Draw a whole frame into a grid of the screen's size plus the status and message bars
+    /// instead of a terminal.
+    pub fn render_grid(&mut self) -> Grid {
+        let (cols, rows) = (self.screen.borrow().cols(), self.message_row() + 1);
+
+        // The grid starts out blank so every row has to be drawn, and so does the next frame
+        // on the terminal
+        let mut output = Vec::new();
+        self.drawn_rows = None;
+        let drawn = self.refresh(&mut Terminal::new(&mut output));
+        self.drawn_rows = None;
+        drawn.expect("writing to memory can't fail");
+
+        Grid::from_ansi(cols, rows, &output)
+    }
+
+    /// Where buffer row `row` is drawn in the text area, below the annotations of the rows
+    /// between it and the top of `screen`.
+    fn screen_row(&self, screen: &Screen, row: u16) -> u16 {
+        let top = screen.row_offset();
+        let annotations = self
+            .buffer
+            .annotation_rows(top as usize..row.max(top) as usize);
+
+        // Every row wrapped into more than one pushes the rows below it down
+        let wrapped = match self.soft_wrap {
+            true => {
+                let rows = self.buffer.buffer().borrow();
+                let cols = screen.content_cols(self.gutter_width()) as usize;
+                let start = |row: u16| {
+                    let (tab_stop, key) = (self.buffer.tab_stop(), self.rows_identity());
+                    screen.visual_row_for_buffer_line(row as usize, &rows, tab_stop, cols, key)
+                };
+                (start(row.max(top)) - start(top)).saturating_sub((row.max(top) - top) as usize)
             }
-            _ => {}
-        }
+            false => 0,
+        };
 
-        Ok(())
+        row.saturating_sub(top)
+            .saturating_add(annotations as u16)
+            .saturating_add(wrapped as u16)
     }
 
-    fn padding(&self, message_len: u16) -> Padding {
-        let pad_size = (self.screen.borrow().cols() - message_len) / 2;
-        Padding::new('~', pad_size as usize)
+    /// Identifies the rows of the current buffer as they are now, for caches of what is
+    /// measured from them.
+    fn rows_identity(&self) -> (usize, u64) {
+        (
+            Rc::as_ptr(self.buffer.buffer()) as usize,
+            self.buffer.modification_count(),
+        )
     }
 
-    fn message(&self) -> &str {
-        let message = concat!("Kilo editor -- version ", version!());
+    /// The highlight states of the current buffer, with the rows edited since they were
+    /// last used marked stale.
+    fn highlight_states(&self) -> RefMut<'_, StateCache> {
+        let mut states = self.highlight_states.borrow_mut();
+        states.catch_up(self.rows_identity(), |count| {
+            self.buffer.rows_edited_since(count)
+        });
+        states
+    }
 
-        if message.len() > self.screen.borrow().cols() as usize {
-            &message[..self.screen.borrow().cols() as usize]
-        } else {
-            message
+    /// Use a moment without input for work that can wait, like highlighting the rows an
+    /// edit changed off the screen a chunk at a time.
+    pub fn idle(&mut self) {
+        if let Some(highlighter) = self.highlighter() {
+            let buf = self.buffer.buffer().borrow();
+            let key = self.rows_identity();
+            self.highlight_states()
+                .advance(key, highlighter, &buf, IDLE_HIGHLIGHT_ROWS);
         }
     }
-}
 
-#[derive(Debug, Clone, Copy)]
-pub struct Padding {
-    leading: char,
-    size: usize,
-}
+    /// The row of the text area the cursor is drawn on, with the cursor on buffer row
+    /// `cursor_row`.
+    fn cursor_screen_row(&self, screen: &Screen, cursor_row: u16) -> u16 {
+        let within = match self.soft_wrap {
+            true => self.wrapped_cursor(screen).0 as u16,
+            false => 0,
+        };
 
-impl Padding {
-    pub fn new(leading: char, size: usize) -> Self {
-        Self { leading, size }
+        self.screen_row(screen, cursor_row).saturating_add(within)
     }
-}
 
-impl fmt::Display for Padding {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.size > 0 {
-            write!(f, "{:<size$}", self.leading, size = self.size)
-        } else {
-            Ok(())
+    /// The rendered columns buffer row `row` shows on each screen row with soft wrap on, and
+    /// the column its continuation rows start at.
+    fn wrap_segments(&self, screen: &Screen, row: usize) -> (Vec<Range<usize>>, usize) {
+        let cols = screen.content_cols(self.gutter_width()) as usize;
+        match self.buffer.buffer().borrow().get(row) {
+            Some(line) => screen.wrap_segments(line, cols, self.buffer.tab_stop()),
+            None => (vec![Range::default()], 0),
+        }
+    }
+
+    /// Where the cursor is drawn within its wrapped line, as the row of the line and the
+    /// column of the text area.
+    fn wrapped_cursor(&self, screen: &Screen) -> (usize, usize) {
+        let (segments, indent) = self.wrap_segments(screen, self.cursor.y() as usize);
+        let render = self.cursor.render();
+        let row = segments
+            .iter()
+            .rposition(|segment| segment.start <= render)
+            .unwrap_or(0);
+        let indent = if row == 0 { 0 } else { indent };
+
+        (row, indent + render - segments[row].start)
+    }
+
+    /// The row of the message bar, the last one drawn
+    fn message_row(&self) -> u16 {
+        self.screen.borrow().rows() + self.tab_bar_visible() as u16 + 1
+    }
+
+    /// Measurements of the last frame that was drawn
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
+
+    pub fn set_hud(&mut self, enabled: bool) {
+        self.hud = enabled;
+    }
+
+    /// Only use what the terminal can show
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+        self.drawn_rows = None;
+    }
+
+    /// Call `hook` on the shown buffer every time right before its rows are drawn.
+    pub fn set_render_hook<F: Fn(&mut Buffer) + 'static>(&mut self, hook: F) {
+        self.render_hook = Some(RenderHook(Rc::new(hook)));
+    }
+
+    pub fn set_bell(&mut self, bell: Bell) {
+        self.bell = bell;
+    }
+
+    pub fn set_quit_confirm(&mut self, confirm: QuitConfirm) {
+        self.quit_confirm = confirm;
+        self.quit_times = confirm.warnings();
+    }
+
+    /// Whether quitting without saving was confirmed by answering the question asked for
+    /// `QuitConfirm::Prompt`
+    pub fn is_quit_confirmed(&self) -> bool {
+        self.quit_confirmed
+    }
+
+    /// When the screen needs to be refreshed again even if no input arrives, right away
+    /// when there is work left for `idle`
+    pub fn deadline(&self) -> Option<Instant> {
+        let idle_work = self.highlighter().is_some() && {
+            let len = self.buffer.buffer().borrow().len();
+            self.highlight_states()
+                .is_pending(self.rows_identity(), len)
+        };
+
+        self.deadline.or_else(|| idle_work.then(Instant::now))
+    }
+
+    fn rows_key(&self) -> RowsKey {
+        let screen = self.screen.borrow();
+        let cursor = (self.indent_guides || self.relative_numbers)
+            .then(|| (self.cursor.x(), self.cursor.y()));
+
+        RowsKey {
+            modification_count: self.buffer.modification_count(),
+            annotation_changes: self.buffer.annotation_changes(),
+            size: (screen.cols(), screen.rows()),
+            offset: (screen.col_offset(), screen.row_offset()),
+            gutter_width: self.gutter_width(),
+            line_numbers: self.line_number_style(),
+            indent_guides: self.indent_guides,
+            focus: self.focus,
+            cursor,
+            selected: self
+                .changes
+                .as_ref()
+                .map(ChangeList::selected)
+                .or_else(|| self.messages.as_ref().map(MessageLog::offset))
+                .or_else(|| self.replace.as_ref().map(ReplaceState::index)),
+            hud: self.hud,
+        }
+    }
+
+    /// What the pending input is waiting for, shown in place of the status message.
+    fn key_hint(&self) -> Option<String> {
+        match self.quoting {
+            true => Some(String::from("insert literally: ")),
+            false => self.count.map(|count| format!("repeat: {count}")),
         }
     }
+
+    pub fn set_status_message<T: Into<String>>(&mut self, message: T) {
+        self.set_status_message_as(Severity::Info, message);
+    }
+
+    /// Show `message` like `set_status_message` and keep it in the message log as `severity`.
+    pub fn set_status_message_as<T: Into<String>>(&mut self, severity: Severity, message: T) {
+        self.status_message = message.into();
+        self.status_time = self.clock.now();
+
+        if !self.status_message.is_empty() {
+            if self.message_log.len() == MESSAGE_LOG_SIZE {
+                self.message_log.pop_front();
+            }
+            self.message_log
+                .push_back((self.status_message.clone(), self.status_time, severity));
+        }
+    }
+
+    /// The last status messages shown, oldest first
+    pub fn message_log(&self) -> impl DoubleEndedIterator<Item = &LoggedMessage> {
+        self.message_log.iter()
+    }
+
+    /// Take the status message down now instead of when it times out.
+    pub fn clear_status_message(&mut self) {
+        self.status_message.clear();
+    }
+
+    pub fn set_buffer(&mut self, mut buf: Buffer) {
+        self.restore_undo(&mut buf);
+        if let Some(tab_stop) = self.filetype_tab_stop(&buf) {
+            buf.set_tab_stop(tab_stop);
+        }
+        self.buffer = buf;
+        self.drawn_rows = None;
+        self.cursor.set_buffer(Rc::clone(self.buffer.buffer()));
+        self.cursor.set_tab_stop(self.buffer.tab_stop());
+        self.warn_mixed_line_endings();
+    }
+
+    /// Mention that saving the shown buffer will unify its line endings, if it will.
+    fn warn_mixed_line_endings(&mut self) {
+        if self.buffer.has_mixed_line_endings() {
+            let line_ending = self.buffer.save_options().line_ending.name();
+            self.set_status_message(format!("Mixed line endings, saving as {line_ending}"));
+        }
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn buffer_mut(&mut self) -> &mut Buffer {
+        self.drawn_rows = None;
+        &mut self.buffer
+    }
+
+    /// Open another buffer behind the current one.
+    pub fn add_buffer(&mut self, mut buf: Buffer) {
+        self.restore_undo(&mut buf);
+        if let Some(tab_stop) = self.filetype_tab_stop(&buf) {
+            buf.set_tab_stop(tab_stop);
+        }
+
+        // New tabs go last, which is right before the first tab in switching order
+        match self.active_tab {
+            0 => self.others.push(buf),
+            active => self.others.insert(self.buffer_count() - active - 1, buf),
+        }
+        self.update_tab_bar();
+    }
+
+    /// Draw the tab bar at `position` from now on.
+    pub fn set_tab_bar_position(&mut self, position: TabBarPosition) {
+        self.tab_bar = position;
+        self.drawn_rows = None;
+    }
+
+    /// Name the file of the shown buffer in the status bar as `display` says.
+    pub fn set_filename_display(&mut self, display: FilenameDisplay) {
+        self.filename_display = display;
+    }
+
+    /// The path of the shown buffer the way `filename_display` asks for, not yet shortened
+    fn status_filename(&self) -> String {
+        let Some(filename) = self.buffer.filename_str() else {
+            return NO_NAME.to_string();
+        };
+        let path = Path::new(filename);
+        let absolute = || std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+        let shown = match self.filename_display {
+            FilenameDisplay::AsOpened => path.to_path_buf(),
+            FilenameDisplay::Basename => path.file_name().map_or(path.into(), PathBuf::from),
+            FilenameDisplay::Absolute => absolute(),
+            FilenameDisplay::Relative => {
+                let absolute = absolute();
+                std::env::current_dir()
+                    .ok()
+                    .and_then(|cwd| absolute.strip_prefix(cwd).ok().map(Path::to_path_buf))
+                    .unwrap_or(absolute)
+            }
+        };
+
+        shown.to_string_lossy().into_owned()
+    }
+
+    fn tab_bar_visible(&self) -> bool {
+        !self.others.is_empty()
+    }
+
+    /// The first screen row of the text, below the tab bar when it is at the top
+    fn text_top(&self) -> u16 {
+        (self.tab_bar_visible() && self.tab_bar == TabBarPosition::Top) as u16
+    }
+
+    /// Take the row of the tab bar from the text when it is shown.
+    fn update_tab_bar(&mut self) {
+        let rows = self.tab_bar_visible() as u16;
+        self.screen.borrow_mut().set_reserved_rows(rows);
+    }
+
+    /// The open buffers in the order of their tabs
+    fn tabs(&self) -> impl Iterator<Item = &Buffer> {
+        let count = self.buffer_count();
+        (0..count).map(move |tab| match (tab + count - self.active_tab) % count {
+            0 => &self.buffer,
+            i => &self.others[i - 1],
+        })
+    }
+
+    /// Keep the undo history of saved files in `dir` so it survives closing them. Histories
+    /// are loaded when a file is opened and written when it is saved or the editor quits.
+    pub fn set_undo_dir(&mut self, dir: Option<PathBuf>) {
+        self.undo_dir = dir;
+    }
+
+    /// Take the time from `clock`, for status messages and the saves of every buffer.
+    pub fn set_clock(&mut self, clock: Clock) {
+        for buf in std::iter::once(&mut self.buffer).chain(&mut self.others) {
+            buf.set_clock(clock.clone());
+        }
+        self.clock = clock;
+    }
+
+    /// Tell how long ago the buffer was saved in the status bar, like `saved 12s ago`.
+    pub fn set_show_save_age(&mut self, enabled: bool) {
+        self.show_save_age = enabled;
+    }
+
+    /// Load the undo history of `buf` and have it take the time from the editor's clock.
+    fn restore_undo(&self, buf: &mut Buffer) {
+        buf.set_clock(self.clock.clone());
+        if let Some(dir) = &self.undo_dir {
+            buf.load_undo_file(dir);
+        }
+    }
+
+    /// Write the undo history of every buffer that matches its file on disk.
+    pub fn save_undo_files(&self) {
+        if let Some(dir) = &self.undo_dir {
+            std::iter::once(&self.buffer)
+                .chain(&self.others)
+                .filter(|buf| buf.state() == BufferState::Default)
+                .for_each(|buf| {
+                    let _ = buf.save_undo_file(dir);
+                });
+        }
+    }
+
+    pub fn buffer_count(&self) -> usize {
+        self.others.len() + 1
+    }
+
+    /// Show the next open buffer where it was left, sending the current one to the back.
+    pub fn next_buffer(&mut self) -> EventOutcome {
+        if self.others.is_empty() {
+            self.set_status_message("No other buffers");
+            return EventOutcome::rejected();
+        }
+
+        let next = self.others.remove(0);
+        let previous = self.switch_buffer(next);
+        self.others.push(previous);
+        self.active_tab = (self.active_tab + 1) % self.buffer_count();
+
+        let message = format!("Switched to {}", self.buffer.filename_str().unwrap_or(NO_NAME));
+        self.set_status_message(message);
+        EventOutcome::consumed()
+    }
+
+    /// Show the buffer that was current before the last switch, the one in the tab before.
+    pub fn previous_buffer(&mut self) -> EventOutcome {
+        let previous = match self.others.pop() {
+            Some(previous) => previous,
+            None => {
+                self.set_status_message("No other buffers");
+                return EventOutcome::rejected();
+            }
+        };
+
+        let current = self.switch_buffer(previous);
+        self.others.insert(0, current);
+        let count = self.buffer_count();
+        self.active_tab = (self.active_tab + count - 1) % count;
+
+        let message = format!("Switched to {}", self.buffer.filename_str().unwrap_or(NO_NAME));
+        self.set_status_message(message);
+        EventOutcome::consumed()
+    }
+
+    /// Close the current buffer and show the next one, or an empty buffer if it was the last.
+    /// Buffers with unsaved changes are kept open.
+    pub fn close_buffer(&mut self) -> EventOutcome {
+        if self.buffer.state() == BufferState::Modified {
+            self.set_status_message("Buffer has unsaved changes, save it before closing");
+            return EventOutcome::rejected();
+        }
+
+        let next = if self.others.is_empty() {
+            Buffer::default()
+        } else {
+            self.others.remove(0)
+        };
+        let mut closed = self.switch_buffer(next);
+        let cursor = closed.take_cursor();
+
+        if let Some(filename) = closed.filename_str() {
+            if self.closed.len() == CLOSED_BUFFERS {
+                self.closed.remove(0);
+            }
+            self.closed.push(SessionEntry {
+                filename: filename.to_string(),
+                cursor: (cursor.x(), cursor.y()),
+            });
+        }
+        self.active_tab %= self.buffer_count();
+        self.update_tab_bar();
+
+        EventOutcome::consumed()
+    }
+
+    /// Open the most recently closed buffer again with the cursor where it was.
+    pub fn reopen_last_closed(&mut self) -> EventOutcome {
+        let filename = match self.closed.last() {
+            Some(closed) => closed.filename.clone(),
+            None => {
+                self.set_status_message("No closed buffers to reopen");
+                return EventOutcome::rejected();
+            }
+        };
+
+        let mut buf = match Buffer::open(&filename) {
+            Ok(buf) => buf,
+            Err(_) => {
+                self.closed.pop();
+                self.set_status_message(format!("Can't reopen {filename}"));
+                return EventOutcome::rejected();
+            }
+        };
+
+        self.restore_session_cursor(&mut buf);
+        self.show_buffer(buf);
+
+        EventOutcome::consumed()
+    }
+
+    /// Put the cursor of `buf` back where it was when its file was last closed, if it was.
+    /// A cursor that no longer fits the file is clamped, which the status message mentions.
+    fn restore_session_cursor(&mut self, buf: &mut Buffer) {
+        let filename = buf.filename_str();
+        let index = match self
+            .closed
+            .iter()
+            .rposition(|entry| Some(entry.filename.as_str()) == filename)
+        {
+            Some(index) => index,
+            None => return,
+        };
+
+        let entry = self.closed.remove(index);
+        if !buf.apply_session(&entry) {
+            let cursor = buf.saved_cursor();
+            let position = Position(cursor.x(), cursor.y());
+            self.set_status_message(format!("Cursor restored to {position} (adjusted)"));
+        }
+    }
+
+    /// Open the URL drawn at `col` and `row` of the terminal in the browser, or the file path
+    /// drawn there in a new buffer.
+    #[cfg(feature = "links")]
+    pub fn follow_link(&mut self, col: u16, row: u16) -> EventOutcome {
+        let link = self.position_at(col, row).and_then(|Position(x, y)| {
+            let rows = self.buffer.buffer().borrow();
+            text::find_url_or_path_at_col(rows.get(y as usize)?.buffer(), x as usize)
+        });
+
+        match link {
+            Some(text::UrlOrPath::Url(url)) => match open::that(&url) {
+                Ok(()) => self.set_status_message(format!("Opened {url}")),
+                Err(_) => {
+                    self.set_status_message_as(Severity::Error, format!("Can't open {url}"));
+                    return EventOutcome::rejected();
+                }
+            },
+            Some(text::UrlOrPath::Path(path)) => match Buffer::open(&path) {
+                Ok(mut buf) => {
+                    self.restore_session_cursor(&mut buf);
+                    self.show_buffer(buf)
+                }
+                Err(_) => {
+                    let message = format!("Can't open file {}", path.display());
+                    self.set_status_message_as(Severity::Error, message);
+                    return EventOutcome::rejected();
+                }
+            },
+            None => return EventOutcome::rejected(),
+        }
+
+        EventOutcome::consumed()
+    }
+
+    /// The position in the buffer of the character drawn at `col` and `row` of the terminal,
+    /// `None` outside of the text.
+    #[cfg(feature = "links")]
+    fn position_at(&self, col: u16, row: u16) -> Option<Position> {
+        let screen = self.screen.borrow();
+        let row = row.checked_sub(self.text_top()).filter(|row| *row < screen.rows())?;
+        let col = col.checked_sub(self.gutter_width())? as usize;
+
+        let (y, render) = match self.soft_wrap {
+            true => {
+                let (mut y, mut row) = (screen.row_offset() as usize, row as usize);
+                loop {
+                    let (segments, indent) = self.wrap_segments(&screen, y);
+                    if let Some(segment) = segments.get(row) {
+                        let col = match row {
+                            0 => col,
+                            _ => col.checked_sub(indent)?,
+                        };
+                        // Past the end of a row that wraps is still on that row
+                        let last = match row + 1 < segments.len() {
+                            true => segment.end - 1,
+                            false => segment.end,
+                        };
+                        break (y, (segment.start + col).min(last));
+                    }
+                    row -= segments.len();
+                    y += 1;
+                }
+            }
+            false => (
+                screen.row_offset() as usize + row as usize,
+                screen.col_offset() as usize + col,
+            ),
+        };
+
+        let rows = self.buffer.buffer().borrow();
+        let x = column_at_display(rows.get(y)?.buffer(), render, self.buffer.tab_stop());
+        Some(Position(x as u16, y as u16))
+    }
+
+    /// Open the file of the current buffer as it is on disk in a read-only buffer, to compare
+    /// with before overwriting it.
+    pub fn show_disk_version(&mut self) -> EventOutcome {
+        let filename = match self.buffer.filename_str() {
+            Some(filename) => filename.to_string(),
+            None => {
+                self.set_status_message("The buffer has no file");
+                return EventOutcome::rejected();
+            }
+        };
+
+        let encoding = self.buffer.save_options().encoding;
+        match Buffer::open_with_encoding(&filename, encoding) {
+            Ok(mut buf) => {
+                buf.set_read_only(true);
+                self.show_buffer(buf);
+                self.set_status_message(format!("{filename} as it is on disk"));
+                EventOutcome::consumed()
+            }
+            Err(_) => {
+                self.set_status_message(format!("Can't open file {filename}"));
+                EventOutcome::rejected()
+            }
+        }
+    }
+
+    /// Read the file of the current buffer again decoded as `encoding`, for legacy files that
+    /// aren't valid UTF-8. Saving writes the same encoding back unless it is changed.
+    pub fn reopen_with_encoding(&mut self, encoding: Encoding) -> EventOutcome {
+        let filename = match self.buffer.filename_str() {
+            Some(filename) if self.buffer.state() == BufferState::Default => filename.to_string(),
+            Some(_) => {
+                self.set_status_message("Save the changes before reopening the file");
+                return EventOutcome::rejected();
+            }
+            None => {
+                self.set_status_message("The buffer has no file to reopen");
+                return EventOutcome::rejected();
+            }
+        };
+
+        let buf = match Buffer::open_with_encoding(&filename, encoding) {
+            Ok(buf) => buf,
+            Err(_) => {
+                self.set_status_message(format!("Can't reopen {filename}"));
+                return EventOutcome::rejected();
+            }
+        };
+
+        let row = self.cursor.y();
+        self.switch_buffer(buf);
+        self.cursor.goto_line(row.saturating_add(1));
+
+        EventOutcome::consumed()
+    }
+
+    /// Switch to a newly opened buffer, the current one becomes the next to switch back to.
+    fn show_buffer(&mut self, mut buf: Buffer) {
+        self.restore_undo(&mut buf);
+        if let Some(tab_stop) = self.filetype_tab_stop(&buf) {
+            buf.set_tab_stop(tab_stop);
+        }
+        let previous = self.switch_buffer(buf);
+        // The empty buffer left behind by closing the last buffer isn't worth keeping
+        let unnamed_and_empty =
+            previous.filename_str().is_none() && previous.buffer().borrow().is_empty();
+        if !unnamed_and_empty {
+            self.others.insert(0, previous);
+        }
+        self.update_tab_bar();
+        self.warn_mixed_line_endings();
+    }
+
+    /// The open buffers with their cursors and scroll offsets, to be given to `restore` later.
+    pub fn snapshot(&self) -> EditorState {
+        let screen = self.screen.borrow();
+        let current = BufferSnapshot::new(
+            &self.buffer,
+            &self.cursor,
+            (screen.col_offset(), screen.row_offset()),
+        );
+        let others = self
+            .others
+            .iter()
+            .map(|buf| BufferSnapshot::new(buf, buf.saved_cursor(), buf.scroll_offset()));
+
+        EditorState {
+            buffers: std::iter::once(current).chain(others).collect(),
+            active_tab: self.active_tab,
+            search: self.search.as_ref().map(|search| search.pattern().to_string()),
+            search_history: self.search_history.entries().map(str::to_string).collect(),
+            closed: self.closed.clone(),
+        }
+    }
+
+    /// Replace every open buffer with the ones in `state`, closing any prompt. A buffer whose
+    /// file no longer exists keeps its rows as an unnamed buffer, with a warning.
+    pub fn restore(&mut self, state: EditorState) {
+        let mut missing = Vec::new();
+        let mut buffers = Vec::new();
+        for mut snapshot in state.buffers {
+            if let Some(filename) = snapshot.filename.clone() {
+                if !Path::new(&filename).exists() {
+                    snapshot.filename = None;
+                    snapshot.modified = true;
+                    missing.push(filename);
+                }
+            }
+            buffers.push(snapshot.into_buffer());
+        }
+
+        let mut buffers = buffers.into_iter();
+        self.switch_buffer(buffers.next().unwrap_or_default());
+        self.others = buffers.collect();
+        self.active_tab = state.active_tab % self.buffer_count();
+        self.update_tab_bar();
+
+        self.prompt = None;
+        self.replace = None;
+        self.changes = None;
+        self.count = None;
+        self.focus = Focus::default();
+        self.search = state.search.map(SearchState::new);
+        self.closed = state.closed;
+        self.search_history = History::new(SEARCH_HISTORY_SIZE);
+        for query in state.search_history {
+            self.search_history.push(query);
+        }
+
+        if !missing.is_empty() {
+            let message = format!("No longer on disk, kept as {NO_NAME}: {}", missing.join(", "));
+            self.set_status_message(message);
+        }
+    }
+
+    /// Make `buf` the current buffer and hand back the previous one with its cursor and scroll
+    /// offset saved, so switching back to it restores both.
+    fn switch_buffer(&mut self, mut buf: Buffer) -> Buffer {
+        let saved = buf.take_cursor();
+        let mut previous = mem::replace(&mut self.buffer, buf);
+
+        let mut screen = self.screen.borrow_mut();
+        previous.save_cursor(self.cursor.clone());
+        previous.save_scroll_offset((screen.col_offset(), screen.row_offset()));
+        let (col, row) = self.buffer.scroll_offset();
+        screen.set_offset(col, row);
+        drop(screen);
+
+        // Only the position comes from the saved cursor, settings like virtual edit stay
+        *self.cursor.position_mut() = Position(saved.x(), saved.y());
+        self.cursor.set_buffer(Rc::clone(self.buffer.buffer()));
+        self.cursor.set_tab_stop(self.buffer.tab_stop());
+        self.search = None;
+        self.drawn_rows = None;
+
+        previous
+    }
+
+    pub fn focus(&self) -> Focus {
+        self.focus
+    }
+
+    pub fn prompt(&self) -> Option<&Prompt> {
+        self.prompt.as_ref()
+    }
+
+    pub fn open_prompt(&mut self, prompt: Prompt) {
+        self.prompt = Some(prompt);
+        self.focus = Focus::Prompt;
+    }
+
+    /// Decide whether quitting now would lose anything. Open prompts are cancelled
+    /// first and unsaved changes are only discarded as `QuitConfirm` says, either after
+    /// repeated requests or after asking. A second request answers the question.
+    pub fn request_quit(&mut self) -> QuitDecision {
+        if self.prompt.take().is_some() {
+            self.focus = Focus::Buffer;
+            self.set_status_message("Prompt cancelled. Press Ctrl-Q again to quit.");
+            return QuitDecision::Stay;
+        }
+
+        if self.recording.take().is_some() {
+            self.set_status_message("Macro recording stopped. Press Ctrl-Q again to quit.");
+            return QuitDecision::Stay;
+        }
+
+        if self.buffer.state() != BufferState::Modified {
+            return QuitDecision::Quit;
+        }
+
+        match self.quit_confirm {
+            QuitConfirm::Count(_) if self.quit_times > 0 => {
+                self.set_status_message_as(
+                    Severity::Warning,
+                    format!(
+                        "⚠Warning!!⚠, File has unsaved changes. Press Ctrl-Q {} more times to quit.",
+                        self.quit_times
+                    ),
+                );
+                self.quit_times -= 1;
+                QuitDecision::Stay
+            }
+            QuitConfirm::Prompt if self.focus != Focus::Quit => {
+                self.focus = Focus::Quit;
+                self.set_status_message_as(Severity::Warning, "Quit without saving? (y/n)");
+                QuitDecision::Stay
+            }
+            _ => QuitDecision::Quit,
+        }
+    }
+
+    /// Decide on a request to quit only once the events `queued` behind it are handed to
+    /// `handle`, so a save typed right after Ctrl-Q still happens and the warning about
+    /// unsaved changes sees the buffer after it. Every quit among them is another request.
+    /// With `QuitConfirm::Prompt` the question is asked first, for them to answer.
+    pub fn quit_after<I, F>(
+        &mut self,
+        queued: I,
+        mut handle: F,
+    ) -> Result<QuitDecision, EditorEventError>
+    where
+        I: IntoIterator<Item = InputEvent>,
+        F: FnMut(&mut Self, InputEvent) -> Result<(), EditorEventError>,
+    {
+        let mut quits = 1;
+        if self.quit_confirm == QuitConfirm::Prompt {
+            quits = 0;
+            if self.request_quit() == QuitDecision::Quit {
+                return Ok(QuitDecision::Quit);
+            }
+        }
+
+        for event in queued {
+            match event {
+                InputEvent::Quit => quits += 1,
+                event => handle(self, event)?,
+            }
+        }
+
+        let quit = self.quit_confirmed;
+        match quit || (0..quits).any(|_| self.request_quit() == QuitDecision::Quit) {
+            true => Ok(QuitDecision::Quit),
+            false => Ok(QuitDecision::Stay),
+        }
+    }
+
+    /// Type `text` as if it came from the keyboard, one line break at a time. With
+    /// `normalize_paste` every `\r\n` and lone `\r` becomes one line break and a final line
+    /// break is dropped, so pasting whole lines doesn't leave an empty one behind.
+    /// Pasted text already has its indent, so auto indent and soft tabs are left out.
+    pub fn paste(&mut self, text: &str) -> Result<(), EditorEventError> {
+        let text = match self.normalize_paste {
+            // Splitting already drops the final line ending
+            true => split_lines(text).lines.join("\n"),
+            false => text.to_string(),
+        };
+
+        self.inserting_paste = true;
+        let result = text.chars().try_for_each(|ch| {
+            self.process_event(match ch {
+                '\n' => InputEvent::InsertNewline,
+                ch => InputEvent::InsertChar(ch),
+            })
+        });
+        self.inserting_paste = false;
+
+        result
+    }
+
+    /// Hand the event to whichever component has focus and move focus if the
+    /// component asks for it.
+    pub fn process_event(&mut self, event: InputEvent) -> Result<(), EditorEventError> {
+        self.quit_times = self.quit_confirm.warnings();
+        if event != InputEvent::SaveBuffer {
+            self.overwrite_pending = false;
+        }
+        self.frame_stats.events_processed += 1;
+
+        match event {
+            InputEvent::ToggleMacroRecording => {
+                self.toggle_macro_recording();
+                return Ok(());
+            }
+            InputEvent::ReplayMacro(times) => return self.replay_macro(times),
+            _ => {}
+        }
+
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(event);
+        }
+        self.quoting = event == InputEvent::QuotedInsert;
+
+        // Completions cover the rows and may change with every key typed in the prompt
+        if let (Focus::Prompt, Some(prompt)) = (self.focus, &self.prompt) {
+            if prompt.completes() {
+                self.drawn_rows = None;
+            }
+        }
+
+        let outcome = self.with_focused(|component| component.handle_event(event));
+
+        let outcome = match outcome.focus() {
+            Some(focus) => self.change_focus(focus),
+            None => outcome,
+        };
+
+        if outcome.is_rejected() && self.bell != Bell::Off {
+            self.bell_pending = true;
+        }
+
+        match self.pending_macro.take() {
+            Some((events, times)) => self.play_macro(&events, times),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_recording_macro(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    fn toggle_macro_recording(&mut self) {
+        match self.recording.take() {
+            Some(events) => {
+                self.set_status_message(format!("Recorded macro of {} events", events.len()));
+                self.macro_register = events;
+            }
+            None => {
+                self.recording = Some(Vec::new());
+                self.set_status_message("Recording macro...");
+            }
+        }
+    }
+
+    fn replay_macro(&mut self, times: u16) -> Result<(), EditorEventError> {
+        // Replaying while recording would make the macro refer to itself
+        if self.is_recording_macro() {
+            self.set_status_message("Can't replay a macro while recording one");
+            return Ok(());
+        }
+
+        let events = self.macro_register.clone();
+        self.play_macro(&events, times)
+    }
+
+    fn play_macro(&mut self, events: &[InputEvent], times: u16) -> Result<(), EditorEventError> {
+        for _ in 0..times {
+            for event in events {
+                self.process_event(*event)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keep the last recorded macro as `name`, replacing a macro of the same name. With a
+    /// macro directory the macro is written there as well.
+    pub fn save_macro(&mut self, name: &str) -> Result<(), MacroError> {
+        if !is_valid_name(name) {
+            error_stack::bail!(MacroError::InvalidName(name.to_string()))
+        }
+        if self.macro_register.is_empty() {
+            error_stack::bail!(MacroError::NothingRecorded)
+        }
+
+        #[cfg(feature = "serde")]
+        if let Some(dir) = &self.macro_dir {
+            macro_file::save(dir, name, &self.macro_register)?;
+        }
+
+        self.named_macros
+            .insert(name.to_string(), self.macro_register.clone());
+        Ok(())
+    }
+
+    /// The names of the saved macros in alphabetical order
+    pub fn macro_names(&self) -> impl Iterator<Item = &str> {
+        self.named_macros.keys().map(String::as_str)
+    }
+
+    /// Replay the macro saved as `name` `times` times.
+    pub fn run_macro(&mut self, name: &str, times: u16) -> Result<(), EditorEventError> {
+        match self.named_macros.get(name) {
+            Some(events) => self.play_macro(&events.clone(), times),
+            None => {
+                self.set_status_message(MacroError::NotFound(name.to_string()).to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// Keep named macros in `dir`, loading the ones already there. Macros that can't be
+    /// loaded are skipped and reported in the status message.
+    #[cfg(feature = "serde")]
+    pub fn set_macro_dir(&mut self, dir: Option<PathBuf>) {
+        if let Some(dir) = &dir {
+            let (macros, errors) = macro_file::load_dir(dir);
+            self.named_macros.extend(macros);
+            if !errors.is_empty() {
+                let errors = errors.iter().map(|err| err.to_string()).collect::<Vec<_>>();
+                self.set_status_message(errors.join("; "));
+            }
+        }
+
+        self.macro_dir = dir;
+    }
+
+    /// Hand the component with focus to `f`. Focus goes back to the buffer when the
+    /// component it is on is gone.
+    fn with_focused<R>(&mut self, f: impl FnOnce(&mut dyn Component) -> R) -> R {
+        match self.focus {
+            Focus::Buffer => {}
+            Focus::Prompt => {
+                if let Some(prompt) = self.prompt.as_mut() {
+                    return f(prompt);
+                }
+            }
+            Focus::ChangeList => {
+                if let Some(changes) = self.changes.as_mut() {
+                    return f(changes);
+                }
+            }
+            Focus::MessageLog => {
+                if let Some(messages) = self.messages.as_mut() {
+                    return f(messages);
+                }
+            }
+            Focus::Replace => {
+                if self.replace.is_some() {
+                    return f(&mut ReplaceView(self));
+                }
+            }
+            Focus::Quit => return f(&mut QuitQuestion(self)),
+        }
+
+        self.focus = Focus::Buffer;
+        f(&mut BufferView(self))
+    }
+
+    fn change_focus(&mut self, focus: Focus) -> EventOutcome {
+        let mut outcome = EventOutcome::consumed();
+        if self.focus == Focus::Prompt {
+            if let Some(prompt) = self.prompt.take() {
+                outcome = self.close_prompt(prompt);
+            }
+        }
+
+        if self.focus == Focus::ChangeList {
+            if let Some(position) = self.changes.take().and_then(|changes| changes.chosen()) {
+                *self.cursor.position_mut() = position;
+            }
+        }
+        if self.focus == Focus::MessageLog {
+            self.messages = None;
+        }
+
+        // Closing a prompt can hand focus on, like a replace prompt going to its matches
+        self.focus = outcome.focus().unwrap_or(focus);
+        outcome
+    }
+
+    fn close_prompt(&mut self, prompt: Prompt) -> EventOutcome {
+        match (prompt.kind(), prompt.state()) {
+            (PromptKind::SaveAs, PromptState::Submitted) => {
+                // A read-only file can still be saved as another one, which is then edited
+                self.buffer.set_filename(prompt.input());
+                self.buffer.set_read_only(false);
+                self.save_buffer();
+            }
+            (PromptKind::Open, PromptState::Submitted) => match Buffer::open(prompt.input()) {
+                Ok(mut buf) => {
+                    self.restore_session_cursor(&mut buf);
+                    self.show_buffer(buf)
+                }
+                Err(_) => {
+                    self.set_status_message(format!("Can't open file {}", prompt.input()));
+                    return EventOutcome::rejected();
+                }
+            },
+            (PromptKind::Search, PromptState::Submitted) => {
+                self.search_history.push(prompt.input());
+                let mut search = SearchState::new(prompt.input());
+                let from = Position(self.cursor.x(), self.cursor.y());
+                let found = search.select_from(&self.buffer.buffer().borrow(), from);
+                self.search = Some(search);
+                return self.jump_to_match(found);
+            }
+            (PromptKind::Replace, PromptState::Submitted) => return self.start_replace(&prompt),
+            (PromptKind::SaveMacro, PromptState::Submitted) => {
+                match self.save_macro(prompt.input()) {
+                    Ok(()) => self.set_status_message(format!("Saved macro {}", prompt.input())),
+                    Err(err) => {
+                        self.set_status_message(err.current_context().to_string());
+                        return EventOutcome::rejected();
+                    }
+                }
+            }
+            (PromptKind::RunMacro, PromptState::Submitted) => {
+                // Like `fix-imports 3`, the count is optional
+                let mut words = prompt.input().split_whitespace();
+                let name = words.next().unwrap_or_default();
+                let times = match words.next().map(str::parse::<u16>) {
+                    None => Some(1),
+                    Some(count) => count.ok(),
+                };
+                match (self.named_macros.get(name), times) {
+                    (Some(events), Some(times)) => {
+                        self.pending_macro = Some((events.clone(), times))
+                    }
+                    (None, _) => {
+                        self.set_status_message(MacroError::NotFound(name.to_string()).to_string());
+                        return EventOutcome::rejected();
+                    }
+                    (_, None) => {
+                        self.set_status_message("Expected a macro name and a count");
+                        return EventOutcome::rejected();
+                    }
+                }
+            }
+            (PromptKind::GotoLine, PromptState::Submitted) => {
+                let len = self.buffer.buffer().borrow().len();
+                match resolve_line(prompt.input(), self.cursor.y() as usize, len) {
+                    Some(row) => self
+                        .cursor
+                        .goto_line(row.saturating_add(1).min(u16::MAX as usize) as u16),
+                    None => {
+                        self.set_status_message(format!("Invalid line {}", prompt.input()));
+                        return EventOutcome::rejected();
+                    }
+                }
+            }
+            (PromptKind::TabStop, PromptState::Submitted) => match prompt.input().trim().parse() {
+                Ok(tab_stop) if (1..=MAX_TAB_STOP).contains(&tab_stop) => {
+                    self.set_tab_stop(tab_stop)
+                }
+                _ => {
+                    self.set_status_message(format!("Tab width is 1 to {MAX_TAB_STOP} columns"));
+                    return EventOutcome::rejected();
+                }
+            },
+            (PromptKind::Filetype, PromptState::Submitted) => {
+                self.set_filetype(Some(filetype_by_name(prompt.input())))
+            }
+            (PromptKind::Align, PromptState::Submitted) => {
+                let delimiter = prompt.input();
+                match self.buffer.align_paragraph(&mut self.cursor, delimiter) {
+                    true => self.set_status_message(format!("Aligned at {delimiter}")),
+                    false => {
+                        self.set_status_message(format!("Nothing to align at {delimiter}"));
+                        return EventOutcome::rejected();
+                    }
+                }
+            }
+            (_, PromptState::Cancelled) => self.set_status_message("Aborted"),
+            _ => {}
+        }
+
+        EventOutcome::consumed()
+    }
+
+    fn handle_buffer_event(&mut self, event: InputEvent) -> EventOutcome {
+        macro_rules! cursor {
+            ( $ev:tt ) => {
+                InputEvent::CursorEvent(CursorEvent::$ev)
+            };
+        }
+
+        if self.buffer.is_read_only() && event.modifies_buffer() {
+            let name = self.buffer.filename_str().unwrap_or(NO_NAME);
+            self.set_status_message(format!("{name} is read-only"));
+            return EventOutcome::rejected();
+        }
+
+        if let InputEvent::Count(digit) = event {
+            let count = self.count.unwrap_or(0);
+            self.count = Some(count.saturating_mul(10).saturating_add(digit as u32));
+            return EventOutcome::consumed();
+        }
+
+        if let Some(count) = self.count.take() {
+            let count = count.min(u16::MAX as u32) as u16;
+            match event {
+                cursor!(MoveLeft) => return self.move_cursor(|cursor| cursor.left_by(count)),
+                cursor!(MoveRight) => return self.move_cursor(|cursor| cursor.right_by(count)),
+                cursor!(MoveUp) if self.soft_wrap => return self.move_screen_rows(false, count),
+                cursor!(MoveDown) if self.soft_wrap => return self.move_screen_rows(true, count),
+                cursor!(MoveUp) | cursor!(MoveLineUp) => {
+                    return self.move_cursor(|cursor| cursor.up_by(count))
+                }
+                cursor!(MoveDown) | cursor!(MoveLineDown) => {
+                    return self.move_cursor(|cursor| cursor.down_by(count))
+                }
+                cursor!(MoveTop) => self.cursor.pages_up(count),
+                cursor!(MoveBottom) => self.cursor.pages_down(count),
+                cursor!(MoveFileEnd) => self.cursor.goto_line(count),
+                // Cancelling only clears the count
+                InputEvent::Cancel => {}
+                _ => return self.handle_buffer_event(event),
+            }
+
+            return EventOutcome::consumed();
+        }
+
+        match event {
+            cursor!(MoveLeft) => return self.move_cursor(BoundedCursor::left),
+            cursor!(MoveRight) => return self.move_cursor(BoundedCursor::right),
+            cursor!(MoveUp) if self.soft_wrap => return self.move_screen_rows(false, 1),
+            cursor!(MoveDown) if self.soft_wrap => return self.move_screen_rows(true, 1),
+            cursor!(MoveUp) | cursor!(MoveLineUp) => return self.move_cursor(BoundedCursor::up),
+            cursor!(MoveDown) | cursor!(MoveLineDown) => {
+                return self.move_cursor(BoundedCursor::down)
+            }
+            cursor!(MoveTop) => self.cursor.top(),
+            cursor!(MoveBottom) => self.cursor.bottom(),
+            cursor!(MoveBegin) if self.soft_wrap => self.screen_row_edge(false),
+            cursor!(MoveEnd) if self.soft_wrap => self.screen_row_edge(true),
+            cursor!(MoveBegin) => self.cursor.begin(),
+            cursor!(MoveEnd) => self.cursor.end(),
+            cursor!(MoveWordLeft) | cursor!(MoveWordRight) => {
+                let word_chars = self.word_chars().clone();
+                return self.move_cursor(|cursor| match event {
+                    cursor!(MoveWordLeft) => cursor.word_left(&word_chars),
+                    _ => cursor.word_right(&word_chars),
+                });
+            }
+            cursor!(MoveToScreenTop) => self.cursor.screen_top(),
+            cursor!(MoveToScreenMiddle) => self.cursor.screen_middle(),
+            cursor!(MoveToScreenBottom) => self.cursor.screen_bottom(),
+            cursor!(MoveFileEnd) => self.cursor.goto_line(u16::MAX),
+            InputEvent::InsertChar('\t') if self.soft_tabs && !self.inserting_paste => {
+                for _ in 0..self.soft_tab_width() {
+                    self.buffer.insert_char(' ', &self.cursor);
+                    self.cursor.right()
+                }
+            }
+            InputEvent::InsertChar(ch)
+                if self.auto_close_quotes && !self.inserting_paste && self.closes_quote(ch) =>
+            {
+                self.buffer.insert_char(ch, &self.cursor);
+                self.cursor.right();
+                self.buffer.insert_char(ch, &self.cursor);
+            }
+            InputEvent::InsertChar(ch) | InputEvent::InsertLiteral(ch) => {
+                self.buffer.insert_char(ch, &self.cursor);
+                self.cursor.right()
+            }
+            InputEvent::QuotedInsert => {}
+            InputEvent::InsertNewline if self.auto_indent && !self.inserting_paste => {
+                let indent = self.indent_before_cursor();
+                self.buffer.insert_newline(&mut self.cursor);
+                for ch in indent.chars() {
+                    self.buffer.insert_char(ch, &self.cursor);
+                    self.cursor.right()
+                }
+            }
+            InputEvent::InsertNewline => self.buffer.insert_newline(&mut self.cursor),
+            InputEvent::SaveBuffer if self.buffer.filename_str().is_none() => {
+                self.open_prompt(Prompt::new(PromptKind::SaveAs, "Save as: "));
+            }
+            InputEvent::SaveAs => self.open_prompt(Prompt::new(PromptKind::SaveAs, "Save as: ")),
+            InputEvent::SaveBuffer => self.save_buffer(),
+            InputEvent::ForceSave => self.write_buffer(true),
+            InputEvent::DeletePreviousChar | InputEvent::DeleteNextChar => {
+                let before = (self.buffer.modification_count(), self.cursor.x());
+                match event {
+                    InputEvent::DeletePreviousChar => self.buffer.delete_char(&mut self.cursor),
+                    _ => self.buffer.delete_char_forward(&self.cursor),
+                }
+
+                if (self.buffer.modification_count(), self.cursor.x()) == before {
+                    return EventOutcome::rejected();
+                }
+            }
+            InputEvent::KillToLineStart => {
+                if self.buffer.kill_to_bol(&mut self.cursor).is_empty() {
+                    return EventOutcome::rejected();
+                }
+            }
+            InputEvent::TransposeChars => {
+                if !self.buffer.transpose_chars(&mut self.cursor) {
+                    return EventOutcome::rejected();
+                }
+            }
+            InputEvent::Undo => {
+                if !self.buffer.undo(&mut self.cursor) {
+                    self.set_status_message("Already at oldest change");
+                    return EventOutcome::rejected();
+                }
+            }
+            InputEvent::Redo => {
+                if !self.buffer.redo(&mut self.cursor) {
+                    self.set_status_message("Already at newest change");
+                    return EventOutcome::rejected();
+                }
+            }
+            InputEvent::Find => self.open_prompt(
+                Prompt::new(PromptKind::Search, "Search: ")
+                    .with_history(self.search_history.entries()),
+            ),
+            InputEvent::SaveMacro => {
+                self.open_prompt(Prompt::new(PromptKind::SaveMacro, "Save macro as: "))
+            }
+            InputEvent::RunMacro if self.named_macros.is_empty() => {
+                self.set_status_message("No saved macros");
+                return EventOutcome::rejected();
+            }
+            InputEvent::RunMacro => {
+                let names = self.macro_names().collect::<Vec<_>>().join(", ");
+                let label = format!("Run macro ({names}): ");
+                self.open_prompt(Prompt::new(PromptKind::RunMacro, label));
+            }
+            InputEvent::Replace => {
+                self.open_prompt(Prompt::new(PromptKind::Replace, "Replace (old/new): "))
+            }
+            InputEvent::OpenFile => self.open_prompt(Prompt::new(PromptKind::Open, "Open: ")),
+            InputEvent::GotoLine => {
+                self.open_prompt(Prompt::new(PromptKind::GotoLine, "Go to line: "))
+            }
+            InputEvent::SearchNext | InputEvent::SearchPrevious => {
+                if let Some(search) = self.search.as_mut() {
+                    let rows = self.buffer.buffer().borrow();
+                    let found = match event {
+                        InputEvent::SearchNext => search.next_match(&rows),
+                        _ => search.previous_match(&rows),
+                    };
+                    drop(rows);
+                    return self.jump_to_match(found);
+                }
+            }
+            InputEvent::ClearSearch => self.search = None,
+            InputEvent::Cancel => self.clear_status_message(),
+            InputEvent::ShowChangeList => {
+                let rows = self.buffer.buffer().borrow();
+                let changes = self
+                    .buffer
+                    .undo_stack()
+                    .history()
+                    .rev()
+                    .take(CHANGE_LIST_SIZE)
+                    .map(|op| Change::new(op, &rows))
+                    .collect();
+                drop(rows);
+
+                self.changes = Some(ChangeList::new(changes));
+                return EventOutcome::consumed().with_focus(Focus::ChangeList);
+            }
+            InputEvent::ShowMessageLog => {
+                self.messages = Some(MessageLog::new(self.message_log.iter()));
+                return EventOutcome::consumed().with_focus(Focus::MessageLog);
+            }
+            InputEvent::ToggleLineNumbers => self.line_numbers = !self.line_numbers,
+            InputEvent::ToggleRelativeNumbers => self.relative_numbers = !self.relative_numbers,
+            InputEvent::CycleLineNumbers => {
+                let style = self.line_number_style().next();
+                self.set_line_number_style(style);
+                self.set_status_message(match style {
+                    LineNumberStyle::Off => "Line numbers off",
+                    LineNumberStyle::Absolute => "Absolute line numbers",
+                    LineNumberStyle::Relative => "Relative line numbers",
+                    LineNumberStyle::Hybrid => "Hybrid line numbers",
+                });
+            }
+            InputEvent::ToggleIndentGuides => self.indent_guides = !self.indent_guides,
+            InputEvent::ToggleSoftTabs => self.set_soft_tabs(!self.soft_tabs),
+            InputEvent::ToggleAutoIndent => self.set_auto_indent(!self.auto_indent),
+            InputEvent::ToggleAutoCloseQuotes => {
+                self.set_auto_close_quotes(!self.auto_close_quotes)
+            }
+            InputEvent::ToggleSoftWrap => self.set_soft_wrap(!self.soft_wrap),
+            InputEvent::ToggleHud => self.hud = !self.hud,
+            InputEvent::FileInfo => {
+                let stats = self.buffer.statistics();
+                let message = format!(
+                    "\"{}\" {} lines, {} words, {} chars, {} bytes",
+                    self.buffer.filename_str().unwrap_or(NO_NAME),
+                    stats.lines,
+                    stats.words,
+                    stats.chars,
+                    stats.bytes
+                );
+                self.set_status_message(message);
+            }
+            InputEvent::DescribeChar => {
+                let message = self.describe_char_at_cursor();
+                self.set_status_message(message);
+            }
+            InputEvent::NextBuffer => return self.next_buffer(),
+            InputEvent::PreviousBuffer => return self.previous_buffer(),
+            InputEvent::NextLongLine => return self.next_long_line(),
+            InputEvent::FillParagraph => {
+                let width = self.fill_column().unwrap_or(FILL_WIDTH);
+                match self.buffer.fill_paragraph(&self.cursor, width) {
+                    Some(position) => *self.cursor.position_mut() = position,
+                    None => return EventOutcome::rejected(),
+                }
+            }
+            InputEvent::Align => self.open_prompt(Prompt::new(PromptKind::Align, "Align at: ")),
+            InputEvent::CloseBuffer => return self.close_buffer(),
+            InputEvent::ReopenBuffer => return self.reopen_last_closed(),
+            InputEvent::SetLineEnding(line_ending) => self.buffer.set_line_ending(line_ending),
+            InputEvent::SetTabStop => {
+                self.open_prompt(Prompt::new(PromptKind::TabStop, "Tab width: "))
+            }
+            InputEvent::ChangeTabStop(delta) => {
+                let tab_stop = self.buffer.tab_stop().saturating_add_signed(delta as isize);
+                self.set_tab_stop(tab_stop)
+            }
+            InputEvent::SetFiletype => {
+                let mut filetypes = self.highlighters.keys().cloned().collect::<Vec<_>>();
+                filetypes.sort();
+                let prompt = Prompt::new(PromptKind::Filetype, "Filetype: ");
+                self.open_prompt(prompt.with_choices(filetypes))
+            }
+            InputEvent::SetEncoding(encoding) => self.buffer.set_encoding(encoding),
+            InputEvent::ReopenWithEncoding(encoding) => {
+                return self.reopen_with_encoding(encoding)
+            }
+            InputEvent::ShowDiskVersion => return self.show_disk_version(),
+            #[cfg(feature = "links")]
+            InputEvent::FollowLink(col, row) => return self.follow_link(col, row),
+            InputEvent::Unbound(key) => {
+                self.set_status_message(format!("{key} is not bound"));
+                return EventOutcome::rejected();
+            }
+            _ => return EventOutcome::ignored(),
+        }
+
+        EventOutcome::consumed()
+    }
+
+    /// Find the matches of the `old` half of a submitted `old/new` replace prompt and ask
+    /// about the first of them.
+    fn start_replace(&mut self, prompt: &Prompt) -> EventOutcome {
+        let (find, replacement) = match prompt.input().split_once('/') {
+            Some((find, replacement)) if !find.is_empty() => (find, replacement),
+            _ => {
+                self.set_status_message("Expected old/new");
+                return EventOutcome::rejected();
+            }
+        };
+
+        let matches = search::find_all(find, &self.buffer.buffer().borrow());
+        if matches.is_empty() {
+            self.set_status_message(format!("Pattern not found: {find}"));
+            return EventOutcome::rejected();
+        }
+
+        self.replace = Some(ReplaceState::new(find, replacement, matches));
+        self.next_replace_match()
+    }
+
+    fn handle_replace_event(&mut self, event: InputEvent) -> EventOutcome {
+        let answer = match event {
+            InputEvent::InsertChar(ch) => ReplaceAnswer::from_char(ch),
+            InputEvent::Cancel => Some(ReplaceAnswer::Quit),
+            _ => None,
+        };
+        let (answer, replace) = match (answer, self.replace.as_mut()) {
+            (Some(answer), Some(replace)) => (answer, replace),
+            (None, Some(_)) => return EventOutcome::rejected(),
+            (_, None) => return EventOutcome::ignored().with_focus(Focus::Buffer),
+        };
+
+        let find_len = replace.find().len();
+        let replacement = replace.replacement().to_string();
+        for position in replace.answer(answer) {
+            self.buffer.replace_at(position, find_len, &replacement);
+        }
+
+        self.next_replace_match()
+    }
+
+    /// Put the cursor on the match waiting for an answer, or report how many were replaced
+    /// once there are none left.
+    fn next_replace_match(&mut self) -> EventOutcome {
+        let replace = match self.replace.as_ref() {
+            Some(replace) => replace,
+            None => return EventOutcome::ignored().with_focus(Focus::Buffer),
+        };
+
+        if let Some(position) = replace.current() {
+            *self.cursor.position_mut() = position;
+            return EventOutcome::consumed().with_focus(Focus::Replace);
+        }
+
+        let message = format!("Replaced {} of {}", replace.replaced(), replace.total());
+        self.replace = None;
+        self.set_status_message(message);
+        EventOutcome::consumed().with_focus(Focus::Buffer)
+    }
+
+    /// Run a movement that is rejected when it leaves the cursor where it was.
+    fn move_cursor<F: FnOnce(&mut BoundedCursor)>(&mut self, movement: F) -> EventOutcome {
+        let before = (self.cursor.x(), self.cursor.y());
+        movement(&mut self.cursor);
+
+        if before == (self.cursor.x(), self.cursor.y()) {
+            EventOutcome::rejected()
+        } else {
+            EventOutcome::consumed()
+        }
+    }
+
+    /// Move `count` screen rows down or up through wrapped lines, keeping the cursor in the
+    /// same column of the screen where the row is long enough.
+    fn move_screen_rows(&mut self, down: bool, count: u16) -> EventOutcome {
+        let screen = self.screen.borrow();
+        let rows = self.buffer.buffer().borrow();
+        let cols = screen.content_cols(self.gutter_width()) as usize;
+        let tab_stop = self.buffer.tab_stop();
+
+        let moved = (0..count)
+            .take_while(|_| {
+                self.cursor
+                    .visual_move(down, &screen, &rows, tab_stop, cols)
+            })
+            .count();
+
+        match moved {
+            0 => EventOutcome::rejected(),
+            _ => EventOutcome::consumed(),
+        }
+    }
+
+    /// Move to the end or the start of the screen row the cursor is on in a wrapped line.
+    /// When it's already there, move on to the end or start of the whole line.
+    fn screen_row_edge(&mut self, end: bool) {
+        let (x, edge) = {
+            let screen = self.screen.borrow();
+            let (row, _) = self.wrapped_cursor(&screen);
+            let segments = self.wrap_segments(&screen, self.cursor.y() as usize).0;
+            let render = match (end, row + 1 < segments.len()) {
+                (false, _) => segments[row].start,
+                (true, true) => segments[row].end - 1,
+                (true, false) => segments[row].end,
+            };
+            let rows = self.buffer.buffer().borrow();
+            let edge = rows.get(self.cursor.y() as usize).map_or(0, |line| {
+                column_at_display(line.buffer(), render, self.buffer.tab_stop())
+            });
+            (self.cursor.x(), edge as u16)
+        };
+
+        match (x == edge, end) {
+            (false, _) => self.cursor.position_mut().0 = edge,
+            (true, false) => self.cursor.begin(),
+            (true, true) => self.cursor.end(),
+        }
+    }
+
+    /// Spaces a soft tab typed at the cursor takes to reach the next tab stop
+    fn soft_tab_width(&self) -> usize {
+        let rows = self.buffer.buffer().borrow();
+        let before = rows.get(self.cursor.y() as usize).map_or("", |row| {
+            &row.buffer()[..char_index(self.cursor.x() as usize, row.buffer())]
+        });
+
+        let tab_stop = self.buffer.tab_stop();
+        text::tab_width(text::display_width(before, tab_stop), tab_stop)
+    }
+
+    /// Whether typing `quote` at the cursor opens a string, going by the state the
+    /// highlighter leaves the cursor row in.
+    fn closes_quote(&self, quote: char) -> bool {
+        let Some(highlighter) = self.highlighter() else {
+            return false;
+        };
+
+        let rows = self.buffer.buffer().borrow();
+        let y = self.cursor.y() as usize;
+        let line = rows.get(y).map_or("", |row| row.buffer());
+        let index = char_index(self.cursor.x() as usize, line);
+        let state = self
+            .highlight_states()
+            .state_at(self.rows_identity(), highlighter, &rows, y);
+
+        highlighter.closes_quote(line, index, quote, state)
+    }
+
+    /// The spaces and tabs the cursor's line starts with, up to the cursor
+    fn indent_before_cursor(&self) -> String {
+        let rows = self.buffer.buffer().borrow();
+        rows.get(self.cursor.y() as usize)
+            .map(|row| &row.buffer()[..char_index(self.cursor.x() as usize, row.buffer())])
+            .unwrap_or_default()
+            .chars()
+            .take_while(|ch| matches!(ch, ' ' | '\t'))
+            .collect()
+    }
+
+    fn describe_char_at_cursor(&self) -> String {
+        let rows = self.buffer.buffer().borrow();
+        let line = match rows.get(self.cursor.y() as usize) {
+            Some(row) => row.buffer(),
+            None => return String::from("EOF"),
+        };
+
+        let index = char_index(self.cursor.x() as usize, line);
+        line[index..]
+            .graphemes(true)
+            .next()
+            .map(describe_grapheme)
+            .unwrap_or_else(|| String::from("EOL"))
+    }
+
+    fn jump_to_match(&mut self, found: Option<Position>) -> EventOutcome {
+        match (found, &self.search) {
+            (Some(position), _) => *self.cursor.position_mut() = position,
+            (None, Some(search)) => {
+                let message = format!("Pattern not found: {}", search.pattern());
+                self.set_status_message(message);
+                return EventOutcome::rejected();
+            }
+            (None, None) => {}
+        }
+
+        EventOutcome::consumed()
+    }
+
+    fn save_buffer(&mut self) {
+        if !mem::take(&mut self.overwrite_pending) {
+            if let Some(changes) = self.buffer.disk_changes() {
+                let name = self.buffer.filename_str().unwrap_or(NO_NAME);
+                let message = format!(
+                    "{name} changed on disk: {changes}. Save again to overwrite, A-d to see it"
+                );
+                self.set_status_message_as(Severity::Warning, message);
+                self.overwrite_pending = true;
+                return;
+            }
+        }
+
+        self.write_buffer(false);
+    }
+
+    /// Write the buffer to its file, `force` writing it even when it's read-only.
+    fn write_buffer(&mut self, force: bool) {
+        let saved = match force {
+            true => self.buffer.save_force(),
+            false => self.buffer.save(),
+        };
+        let (severity, message) = match saved {
+            Ok(report) => {
+                let undo_saved = match &self.undo_dir {
+                    Some(dir) => self.buffer.save_undo_file(dir).is_ok(),
+                    None => true,
+                };
+                if undo_saved {
+                    (Severity::Info, format!("Saved {report}"))
+                } else {
+                    let message = format!("Saved {report}, but not its undo history");
+                    (Severity::Warning, message)
+                }
+            }
+            Err(_err) => (
+                Severity::Error,
+                format!(
+                    "Can't save file {}",
+                    self.buffer.filename_str().unwrap_or(crate::NO_NAME)
+                ),
+            ),
+        };
+
+        self.set_status_message_as(severity, message);
+    }
+
+    /// Columns in front of each row reserved for things like line numbers. Never wider than
+    /// the screen so a narrow terminal leaves no room for content instead of underflowing.
+    fn gutter_width(&self) -> u16 {
+        let signs = !self.diff_signs.is_empty() as u16;
+        (signs + self.numbers_width()).min(self.screen.borrow().cols())
+    }
+
+    /// Columns of the line numbers in the gutter, including the space after them. The last
+    /// line number is never narrower than a distance between lines, so fitting it keeps the
+    /// text in place as the cursor moves with relative numbers.
+    fn numbers_width(&self) -> u16 {
+        if self.line_numbers || self.relative_numbers {
+            let rows = self.buffer.buffer().borrow().len().max(1);
+            rows.to_string().len() as u16 + 1
+        } else {
+            0
+        }
+    }
+
+    /// The number shown in the gutter for `row`, its distance to the cursor with relative
+    /// numbers except on the cursor line in hybrid mode.
+    fn line_number(&self, row: u16) -> Option<u16> {
+        let cursor = self.cursor.y();
+        match (self.line_numbers, self.relative_numbers) {
+            (_, true) if row != cursor => Some(row.abs_diff(cursor)),
+            (false, true) => Some(0),
+            (true, _) => Some(row + 1),
+            (false, false) => None,
+        }
+    }
+
+    /// Show `signs`, ordered by line, in a column of the gutter. The column is left out
+    /// while there are none.
+    pub fn set_diff_signs(&mut self, signs: Vec<(usize, Sign)>) {
+        if signs != self.diff_signs {
+            self.diff_signs = signs;
+            self.drawn_rows = None;
+        }
+    }
+
+    pub fn diff_signs(&self) -> &[(usize, Sign)] {
+        &self.diff_signs
+    }
+
+    fn diff_sign(&self, line: usize) -> Option<Sign> {
+        self.diff_signs
+            .binary_search_by_key(&line, |(line, _)| *line)
+            .ok()
+            .map(|i| self.diff_signs[i].1)
+    }
+
+    /// Highlight the text of files of `filetype`, like files ending in `.filetype`, past
+    /// `column`, or stop highlighting it with `None`.
+    pub fn set_fill_column<T: Into<String>>(&mut self, filetype: T, column: Option<usize>) {
+        match column {
+            Some(column) => self.fill_columns.insert(filetype.into(), column),
+            None => self.fill_columns.remove(&filetype.into()),
+        };
+        self.drawn_rows = None;
+    }
+
+    /// Make words in files of `filetype` out of letters, digits and `word_chars`, or go back
+    /// to the default of also counting `_` with `None`.
+    pub fn set_word_chars<T: Into<String>>(
+        &mut self,
+        filetype: T,
+        word_chars: Option<HashSet<char>>,
+    ) {
+        match word_chars {
+            Some(word_chars) => self.word_chars.insert(filetype.into(), word_chars),
+            None => self.word_chars.remove(&filetype.into()),
+        };
+    }
+
+    /// The characters besides letters and digits in the words of the current buffer
+    fn word_chars(&self) -> &HashSet<char> {
+        self.buffer
+            .filetype()
+            .and_then(|filetype| self.word_chars.get(&filetype))
+            .unwrap_or(&self.default_word_chars)
+    }
+
+    /// Highlight comments and strings in files of `filetype` with `highlighter`.
+    pub fn set_highlighter<T: Into<String>>(
+        &mut self,
+        filetype: T,
+        highlighter: Rc<dyn Highlighter>,
+    ) {
+        self.highlighters.insert(filetype.into(), highlighter);
+        self.drawn_rows = None;
+    }
+
+    fn highlighter(&self) -> Option<&dyn Highlighter> {
+        self.highlighters
+            .get(&self.buffer.filetype()?)
+            .map(Rc::as_ref)
+    }
+
+    /// The fill column for the file of the current buffer
+    fn fill_column(&self) -> Option<usize> {
+        self.fill_columns.get(&self.buffer.filetype()?).copied()
+    }
+
+    /// Give buffers of `filetype` tabs of `tab_stop` columns when they are shown, or leave
+    /// their tab width alone with `None`.
+    pub fn set_filetype_tab_stop<T: Into<String>>(&mut self, filetype: T, tab_stop: Option<usize>) {
+        match tab_stop {
+            Some(tab_stop) => self.tab_stops.insert(filetype.into(), tab_stop),
+            None => self.tab_stops.remove(&filetype.into()),
+        };
+    }
+
+    /// The tab width set for the filetype of `buf`, or for every filetype
+    fn filetype_tab_stop(&self, buf: &Buffer) -> Option<usize> {
+        buf.filetype()
+            .and_then(|filetype| self.tab_stops.get(&filetype).copied())
+            .or(self.default_tab_stop)
+    }
+
+    /// Take on the settings `config` has, redrawing the open buffers with its tab width
+    /// unless their filetype has another.
+    pub fn apply_config(&mut self, config: &Config) {
+        if let Some(tab_stop) = config.tab_width {
+            self.default_tab_stop = Some(tab_stop);
+            for buf in std::iter::once(&mut self.buffer).chain(&mut self.others) {
+                let filetype = buf.filetype();
+                let filetype_tab_stop = filetype.and_then(|filetype| self.tab_stops.get(&filetype));
+                buf.set_tab_stop(filetype_tab_stop.copied().unwrap_or(tab_stop));
+            }
+            self.cursor.set_tab_stop(self.buffer.tab_stop());
+            self.drawn_rows = None;
+        }
+        if let Some(enabled) = config.soft_tabs {
+            self.soft_tabs = enabled;
+        }
+        if let Some(enabled) = config.auto_indent {
+            self.auto_indent = enabled;
+        }
+        if let Some(enabled) = config.auto_close_quotes {
+            self.auto_close_quotes = enabled;
+        }
+        if let Some(enabled) = config.soft_wrap {
+            self.set_soft_wrap(enabled);
+        }
+        if let Some(enabled) = config.line_numbers {
+            self.line_numbers = enabled;
+            self.drawn_rows = None;
+        }
+        if let Some(bell) = config.bell {
+            self.set_bell(bell);
+        }
+        if let Some(confirm) = config.quit_confirm {
+            self.set_quit_confirm(confirm);
+        }
+    }
+
+    /// Treat the shown buffer as `filetype` whatever its name and content, or go back to
+    /// detecting it with `None`. Its highlighting and settings follow the new filetype.
+    pub fn set_filetype(&mut self, filetype: Option<String>) {
+        self.buffer.set_filetype(filetype);
+        if let Some(tab_stop) = self.filetype_tab_stop(&self.buffer) {
+            self.buffer.set_tab_stop(tab_stop);
+        }
+        self.cursor.set_tab_stop(self.buffer.tab_stop());
+        *self.highlight_states.borrow_mut() = StateCache::default();
+        self.drawn_rows = None;
+
+        let message = match self.buffer.filetype() {
+            Some(filetype) if self.highlighters.contains_key(&filetype) => {
+                format!("Filetype {filetype}")
+            }
+            Some(filetype) => format!("Filetype {filetype}, not highlighted"),
+            None => String::from("No filetype"),
+        };
+        self.set_status_message(message);
+    }
+
+    /// Move to the first line after the cursor that is longer than the fill column, starting
+    /// over from the top after the last one.
+    pub fn next_long_line(&mut self) -> EventOutcome {
+        let fill_column = match self.fill_column() {
+            Some(fill_column) => fill_column,
+            None => {
+                self.set_status_message("No fill column is set for this file");
+                return EventOutcome::rejected();
+            }
+        };
+
+        let long_lines = self.buffer.long_lines(fill_column);
+        let row = self.cursor.y() as usize;
+        let next = long_lines
+            .iter()
+            .position(|(line, _)| *line > row)
+            .or_else(|| (!long_lines.is_empty()).then_some(0));
+
+        match next {
+            Some(i) => {
+                let (line, width) = long_lines[i];
+                self.cursor.goto_line((line + 1).min(u16::MAX as usize) as u16);
+                let message = format!(
+                    "Long line {} of {}: {width} columns, {} over",
+                    i + 1,
+                    long_lines.len(),
+                    width - fill_column
+                );
+                self.set_status_message(message);
+                EventOutcome::consumed()
+            }
+            None => {
+                self.set_status_message(format!("No lines are longer than {fill_column} columns"));
+                EventOutcome::rejected()
+            }
+        }
+    }
+
+    /// Compare the rows with the file they were read from and show how they differ as signs
+    /// in the gutter. The file is only read again once the buffer changed or the file was
+    /// modified.
+    pub fn update_diff_signs(&mut self) {
+        let filename = self.buffer.filename_str();
+        let key = (
+            filename.map(str::to_string),
+            filename.and_then(|filename| fs::metadata(filename).ok()?.modified().ok()),
+            self.buffer.modification_count(),
+            self.buffer.state(),
+        );
+        if self.diff_key.as_ref() == Some(&key) {
+            return;
+        }
+
+        let encoding = self.buffer.save_options().encoding;
+        let saved = key
+            .0
+            .as_ref()
+            .and_then(|filename| fs::read(filename).ok())
+            .and_then(|bytes| encoding.decode(bytes));
+        let signs = match saved {
+            Some(saved) => {
+                let saved = saved.lines().collect::<Vec<_>>();
+                let rows = self.buffer.buffer().borrow();
+                let rows = rows.iter().map(|row| row.buffer()).collect::<Vec<_>>();
+                line_signs(&saved, &rows)
+            }
+            None => Vec::new(),
+        };
+
+        self.diff_key = Some(key);
+        self.set_diff_signs(signs);
+    }
+
+    /// The columns and rows left for buffer content once every gutter and bar is drawn.
+    pub fn text_area(&self) -> (u16, u16) {
+        let screen = self.screen.borrow();
+        (screen.content_cols(self.gutter_width()), screen.rows())
+    }
+
+    pub fn set_line_numbers(&mut self, enabled: bool) {
+        self.line_numbers = enabled;
+    }
+
+    pub fn set_relative_numbers(&mut self, enabled: bool) {
+        self.relative_numbers = enabled;
+    }
+
+    pub fn line_number_style(&self) -> LineNumberStyle {
+        match (self.line_numbers, self.relative_numbers) {
+            (false, false) => LineNumberStyle::Off,
+            (true, false) => LineNumberStyle::Absolute,
+            (false, true) => LineNumberStyle::Relative,
+            (true, true) => LineNumberStyle::Hybrid,
+        }
+    }
+
+    /// Number the lines in the gutter with `style`, drawing every row again.
+    pub fn set_line_number_style(&mut self, style: LineNumberStyle) {
+        self.line_numbers = matches!(style, LineNumberStyle::Absolute | LineNumberStyle::Hybrid);
+        self.relative_numbers =
+            matches!(style, LineNumberStyle::Relative | LineNumberStyle::Hybrid);
+        self.drawn_rows = None;
+    }
+
+    pub fn set_wrap_horizontal(&mut self, wrap: bool) {
+        self.cursor.set_wrap_horizontal(wrap);
+    }
+
+    pub fn set_virtual_edit(&mut self, enabled: bool) {
+        self.cursor.set_virtual_edit(enabled);
+    }
+
+    pub fn set_break_indent(&mut self, enabled: bool) {
+        self.screen.borrow_mut().set_break_indent(enabled);
+        self.drawn_rows = None;
+    }
+
+    pub fn set_indent_guides(&mut self, enabled: bool) {
+        self.indent_guides = enabled;
+    }
+
+    /// Draw the tabs of the shown buffer up to the next multiple of `tab_stop`. The cursor
+    /// stays on the same character.
+    pub fn set_tab_stop(&mut self, tab_stop: usize) {
+        self.buffer.set_tab_stop(tab_stop);
+        self.cursor.set_tab_stop(self.buffer.tab_stop());
+        self.drawn_rows = None;
+        self.set_status_message(format!("Tab width {}", self.buffer.tab_stop()));
+    }
+
+    /// Insert spaces up to the next tab stop for a typed tab. A quoted tab is still a tab.
+    pub fn set_soft_tabs(&mut self, enabled: bool) {
+        self.soft_tabs = enabled;
+        self.set_status_message(match enabled {
+            true => "Soft tabs on",
+            false => "Soft tabs off",
+        });
+    }
+
+    /// Start a new line with the indent of the line it was broken from.
+    pub fn set_auto_indent(&mut self, enabled: bool) {
+        self.auto_indent = enabled;
+        self.set_status_message(match enabled {
+            true => "Auto indent on",
+            false => "Auto indent off",
+        });
+    }
+
+    /// Close a typed quote right away where the highlighter of the file takes it to open a
+    /// string, leaving the cursor between the two.
+    pub fn set_auto_close_quotes(&mut self, enabled: bool) {
+        self.auto_close_quotes = enabled;
+        self.set_status_message(match enabled {
+            true => "Auto close quotes on",
+            false => "Auto close quotes off",
+        });
+    }
+
+    /// Wrap lines longer than the screen onto the rows below instead of scrolling sideways.
+    /// Up and down then move by screen row rather than by line.
+    pub fn set_soft_wrap(&mut self, enabled: bool) {
+        self.soft_wrap = enabled;
+        let row = self.screen.borrow().row_offset();
+        self.screen.borrow_mut().set_offset(0, row);
+        self.drawn_rows = None;
+        self.set_status_message(match enabled {
+            true => "Soft wrap on",
+            false => "Soft wrap off",
+        });
+    }
+
+    /// Mark the continuation rows of wrapped lines with `indicator`, `↪` by default
+    pub fn set_wrap_indicator(&mut self, indicator: Option<char>) {
+        self.screen.borrow_mut().set_wrap_indicator(indicator);
+        self.drawn_rows = None;
+    }
+
+    pub fn set_normalize_paste(&mut self, enabled: bool) {
+        self.normalize_paste = enabled;
+    }
+
+    fn padding(&self, message_len: u16) -> Padding {
+        let pad_size = (self.screen.borrow().cols() - message_len) / 2;
+        Padding::new('~', pad_size as usize)
+    }
+
+    fn message(&self) -> &str {
+        let message = concat!("Kilo editor -- version ", version!());
+
+        if message.len() > self.screen.borrow().cols() as usize {
+            &message[..self.screen.borrow().cols() as usize]
+        } else {
+            message
+        }
+    }
+}
+
+/// `size` characters starting with `leading` and filled up with `fill`, spaces unless
+/// given another one.
+#[derive(Debug, Clone, Copy)]
+pub struct Padding {
+    leading: char,
+    fill: char,
+    size: usize,
+}
+
+impl Padding {
+    pub fn new(leading: char, size: usize) -> Self {
+        Self {
+            leading,
+            fill: ' ',
+            size,
+        }
+    }
+
+    /// A horizontal line `width` columns wide
+    pub fn separator(width: usize) -> Self {
+        Self::new('─', width).with_fill('─')
+    }
+
+    pub fn with_fill(mut self, fill: char) -> Self {
+        self.fill = fill;
+        self
+    }
+}
+
+impl fmt::Display for Padding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Counted in characters, a fill like `─` takes more than one byte
+        if self.size > 0 {
+            write!(f, "{}", self.leading)?;
+            for _ in 1..self.size {
+                write!(f, "{}", self.fill)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `visible`, the columns `start..start + width` of a line, split where the line goes past
+/// `fill_column`. Nothing is past it when there is none.
+fn split_at_fill(
+    visible: &str,
+    start: usize,
+    width: usize,
+    fill_column: Option<usize>,
+) -> (Cow<'_, str>, Cow<'_, str>) {
+    match fill_column.map(|fill| fill.saturating_sub(start)) {
+        Some(split) => (
+            column_window(visible, 0, split),
+            column_window(visible, split, width),
+        ),
+        None => (Cow::Borrowed(visible), Cow::Borrowed("")),
+    }
+}
+
+/// `path` cut from the left to fit in `width` columns. The cut is moved to the next
+/// separator so a directory is either shown whole or not at all, like `…/deep/file.rs`.
+fn shorten_path(path: &str, width: usize) -> Cow<'_, str> {
+    let shortened = text::truncate_left(path, width);
+    let tail = match shortened.strip_prefix('…') {
+        Some(tail) => tail,
+        None => return shortened,
+    };
+
+    match tail.find(std::path::MAIN_SEPARATOR) {
+        Some(separator) => Cow::Owned(format!("…{}", &tail[separator..])),
+        None => shortened,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::{annotation::Severity, input::InputSystem, scratch::ScratchDir};
+    use crossterm::{
+        event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind},
+        style::SetAttribute,
+    };
+    use quickcheck_macros::quickcheck;
+    use test_case::test_case;
+
+    fn editor_with(content: &str) -> Editor {
+        let mut editor = Editor::new(80, 22);
+        editor.set_buffer(content.parse().unwrap());
+        editor
+    }
+
+    #[test_case(Padding::new('~', 4) => "~   "; "Filler")]
+    #[test_case(Padding::new('~', 0) => ""; "Empty")]
+    #[test_case(Padding::separator(3) => "───"; "Separator")]
+    #[test_case(Padding::new('│', 3).with_fill('·') => "│··"; "Multi-byte fill")]
+    fn padding_should_be_as_many_characters_as_its_size(padding: Padding) -> String {
+        padding.to_string()
+    }
+
+    #[test]
+    fn keystrokes_during_an_open_prompt_should_never_reach_the_buffer() {
+        let mut editor = editor_with("abc");
+        editor.open_prompt(Prompt::new(PromptKind::SaveAs, "Save as: "));
+
+        let events = [
+            InputEvent::InsertChar('x'),
+            InputEvent::CursorEvent(CursorEvent::MoveRight),
+            InputEvent::CursorEvent(CursorEvent::MoveDown),
+            InputEvent::InsertChar('y'),
+            InputEvent::DeletePreviousChar,
+            InputEvent::DeleteNextChar,
+        ];
+        for event in events {
+            editor.process_event(event).unwrap();
+        }
+
+        assert_eq!(editor.focus(), Focus::Prompt);
+        assert_eq!(editor.prompt().map(Prompt::input), Some("x"));
+        assert_eq!(editor.buffer().buffer().borrow()[0].buffer(), "abc");
+        assert_eq!((editor.cursor.x(), editor.cursor.y()), (0, 0));
+    }
+
+    #[test]
+    fn cancelling_a_prompt_should_return_focus_to_the_buffer() {
+        let mut editor = editor_with("abc");
+        editor.open_prompt(Prompt::new(PromptKind::SaveAs, "Save as: "));
+
+        editor.process_event(InputEvent::Cancel).unwrap();
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+
+        assert_eq!(editor.focus(), Focus::Buffer);
+        assert!(editor.prompt().is_none());
+        assert_eq!(editor.buffer().buffer().borrow()[0].buffer(), "xabc");
+    }
+
+    #[test]
+    fn saving_an_unnamed_buffer_should_open_a_save_as_prompt() {
+        let mut editor = editor_with("abc");
+
+        editor.process_event(InputEvent::SaveBuffer).unwrap();
+
+        assert_eq!(editor.focus(), Focus::Prompt);
+        assert_eq!(editor.prompt().map(Prompt::kind), Some(PromptKind::SaveAs));
+    }
+
+    #[test]
+    fn undo_and_redo_should_revert_and_reapply_edits() {
+        let mut editor = editor_with("abc");
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+        editor.process_event(InputEvent::DeleteNextChar).unwrap();
+
+        editor.process_event(InputEvent::Undo).unwrap();
+        editor.process_event(InputEvent::Undo).unwrap();
+        assert_eq!(editor.buffer().buffer().borrow()[0].buffer(), "abc");
+        assert_eq!(editor.cursor.x(), 0);
+
+        editor.process_event(InputEvent::Redo).unwrap();
+        assert_eq!(editor.buffer().buffer().borrow()[0].buffer(), "xabc");
+        assert_eq!(editor.cursor.x(), 1);
+    }
+
+    #[test]
+    fn confirmed_search_should_show_in_the_status_bar_until_cleared() {
+        let mut editor = editor_with("foo\nbar foo");
+        editor.process_event(InputEvent::Find).unwrap();
+        for ch in "foo".chars() {
+            editor.process_event(InputEvent::InsertChar(ch)).unwrap();
+        }
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+        editor.process_event(InputEvent::SearchNext).unwrap();
+
+        let mut out = Vec::new();
+        editor.draw_status_bar(&mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains("[/foo 2/2]"));
+        assert_eq!((editor.cursor.x(), editor.cursor.y()), (4, 1));
+
+        editor.process_event(InputEvent::ClearSearch).unwrap();
+        let mut out = Vec::new();
+        editor.draw_status_bar(&mut out).unwrap();
+        assert!(!String::from_utf8_lossy(&out).contains("[/foo"));
+    }
+
+    #[test]
+    fn search_count_should_follow_edits() {
+        let mut editor = editor_with("foo\nbar foo");
+        editor.process_event(InputEvent::Find).unwrap();
+        type_text(&mut editor, "foo");
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+        assert!(editor.render_grid().row_text(22).contains("[/foo 1/2]"));
+
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveBottom))
+            .unwrap();
+        type_text(&mut editor, " foo");
+        assert!(editor.render_grid().row_text(22).contains("[/foo 1/3]"));
+
+        editor.process_event(InputEvent::SearchPrevious).unwrap();
+        assert!(editor.render_grid().row_text(22).contains("[/foo 3/3]"));
+    }
+
+    #[test]
+    fn quitting_with_an_open_prompt_should_cancel_the_prompt_first() {
+        let mut editor = editor_with("abc");
+        editor.open_prompt(Prompt::new(PromptKind::Search, "Search: "));
+
+        assert_eq!(editor.request_quit(), QuitDecision::Stay);
+        assert_eq!(editor.focus(), Focus::Buffer);
+        assert!(editor.status_message.contains("Prompt cancelled"));
+
+        assert_eq!(editor.request_quit(), QuitDecision::Quit);
+    }
+
+    #[test]
+    fn quitting_with_unsaved_changes_should_need_repeated_requests() {
+        let mut editor = editor_with("abc");
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+
+        for remaining in (1..=QUIT_TIMES).rev() {
+            assert_eq!(editor.request_quit(), QuitDecision::Stay);
+            assert!(editor
+                .status_message
+                .contains(&format!("Press Ctrl-Q {remaining} more times")));
+        }
+
+        assert_eq!(editor.request_quit(), QuitDecision::Quit);
+    }
+
+    #[test_case(QuitConfirm::None => vec![QuitDecision::Quit]; "None")]
+    #[test_case(QuitConfirm::Count(1) => vec![QuitDecision::Quit]; "Single press")]
+    #[test_case(QuitConfirm::Count(2) => vec![QuitDecision::Stay, QuitDecision::Quit]; "Two presses")]
+    fn quitting_with_unsaved_changes_should_take_the_configured_presses(
+        confirm: QuitConfirm,
+    ) -> Vec<QuitDecision> {
+        let mut editor = editor_with("abc");
+        editor.set_quit_confirm(confirm);
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+
+        let mut decisions = vec![editor.request_quit()];
+        while decisions.last() == Some(&QuitDecision::Stay) {
+            decisions.push(editor.request_quit());
+        }
+
+        decisions
+    }
+
+    #[test]
+    fn quitting_with_a_prompt_should_wait_for_an_answer() {
+        let mut editor = editor_with("abc");
+        editor.set_quit_confirm(QuitConfirm::Prompt);
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+
+        assert_eq!(editor.request_quit(), QuitDecision::Stay);
+        assert_eq!(editor.focus(), Focus::Quit);
+        assert!(editor
+            .render_grid()
+            .row_text(23)
+            .starts_with("Quit without saving? (y/n)"));
+
+        editor.process_event(InputEvent::InsertChar('n')).unwrap();
+        assert!(editor.status_message.contains("Quit cancelled"));
+        assert!(editor.render_grid().row_text(0).starts_with("xabc "));
+        assert!(!editor.is_quit_confirmed());
+
+        assert_eq!(editor.request_quit(), QuitDecision::Stay);
+        editor.process_event(InputEvent::InsertChar('y')).unwrap();
+        assert!(editor.is_quit_confirmed());
+        assert!(editor.render_grid().row_text(0).starts_with("xabc "));
+    }
+
+    #[test]
+    fn other_keys_should_leave_the_quit_prompt_for_the_buffer() {
+        let mut editor = editor_with("abc");
+        editor.set_quit_confirm(QuitConfirm::Prompt);
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+        editor.request_quit();
+
+        editor.process_event(InputEvent::InsertChar('z')).unwrap();
+
+        assert_eq!(editor.focus(), Focus::Buffer);
+        assert!(editor.render_grid().row_text(0).starts_with("xzabc "));
+    }
+
+    #[test]
+    fn quitting_again_should_answer_the_quit_prompt() {
+        let mut editor = editor_with("abc");
+        editor.set_quit_confirm(QuitConfirm::Prompt);
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+
+        assert_eq!(editor.request_quit(), QuitDecision::Stay);
+        assert_eq!(editor.request_quit(), QuitDecision::Quit);
+    }
+
+    #[test]
+    fn any_other_event_should_reset_the_quit_countdown() {
+        let mut editor = editor_with("abc");
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+        editor.request_quit();
+        editor.request_quit();
+
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveLeft))
+            .unwrap();
+        editor.request_quit();
+
+        assert!(editor
+            .status_message
+            .contains(&format!("Press Ctrl-Q {QUIT_TIMES} more times")));
+    }
+
+    #[test_case("src/lib.rs", FilenameDisplay::AsOpened => "src/lib.rs"; "As opened")]
+    #[test_case("{cwd}/src/lib.rs", FilenameDisplay::Basename => "lib.rs"; "Basename")]
+    #[test_case("src/lib.rs", FilenameDisplay::Absolute => "{cwd}/src/lib.rs"; "Absolute")]
+    #[test_case("{cwd}/src/lib.rs", FilenameDisplay::Relative => "src/lib.rs"; "Relative")]
+    #[test_case("/elsewhere/lib.rs", FilenameDisplay::Relative => "/elsewhere/lib.rs"; "Outside")]
+    fn status_filename_should_follow_the_display(
+        filename: &str,
+        display: FilenameDisplay,
+    ) -> String {
+        let cwd = std::env::current_dir().unwrap();
+        let cwd = cwd.to_string_lossy();
+        let mut editor = editor_with("abc");
+        editor.buffer.set_filename(filename.replace("{cwd}", &cwd));
+
+        editor.set_filename_display(display);
+
+        editor.status_filename().replace(&*cwd, "{cwd}")
+    }
+
+    #[test]
+    fn long_paths_should_lose_whole_directories_from_the_left() {
+        let mut editor = editor_with("abc");
+        editor
+            .buffer
+            .set_filename("/home/someone/projects/deep/path/file.rs");
+
+        let grid = editor.render_grid();
+
+        assert!(grid
+            .row_text(22)
+            .starts_with("…/deep/path/file.rs - 1 lines"));
+    }
+
+    #[test]
+    fn status_bar_should_show_undo_and_redo_counts_once_there_is_history() {
+        let mut editor = editor_with("abc");
+        let status_bar = |editor: &Editor| {
+            let mut out = Vec::new();
+            editor.draw_status_bar(&mut out).unwrap();
+            String::from_utf8_lossy(&out).to_string()
+        };
+        assert!(!status_bar(&editor).contains("[u:"));
+
+        for ch in "xyz".chars() {
+            editor.process_event(InputEvent::InsertChar(ch)).unwrap();
+        }
+        assert!(status_bar(&editor).contains("[u:3 r:0]"));
+
+        editor.process_event(InputEvent::Undo).unwrap();
+        editor.process_event(InputEvent::Undo).unwrap();
+        assert!(status_bar(&editor).contains("[u:1 r:2]"));
+    }
+
+    #[test]
+    fn recorded_macro_should_replay_its_events() {
+        let mut editor = editor_with("a\nb\nc");
+        let recorded = [
+            InputEvent::InsertChar('-'),
+            InputEvent::CursorEvent(CursorEvent::MoveDown),
+            InputEvent::CursorEvent(CursorEvent::MoveBegin),
+        ];
+
+        editor
+            .process_event(InputEvent::ToggleMacroRecording)
+            .unwrap();
+        for event in recorded {
+            editor.process_event(event).unwrap();
+        }
+        editor
+            .process_event(InputEvent::ToggleMacroRecording)
+            .unwrap();
+        editor.process_event(InputEvent::ReplayMacro(2)).unwrap();
+
+        let rows = editor.buffer().buffer().borrow();
+        let rows = rows.iter().map(|row| row.buffer()).collect::<Vec<_>>();
+        assert_eq!(rows, vec!["-a", "-b", "-c"]);
+    }
+
+    fn type_text(editor: &mut Editor, text: &str) {
+        for ch in text.chars() {
+            editor.process_event(InputEvent::InsertChar(ch)).unwrap();
+        }
+    }
+
+    const NONE: KeyModifiers = KeyModifiers::NONE;
+    const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+
+    #[test_case(&[(KeyCode::Tab, NONE)] => "        ab"; "Soft tab")]
+    #[test_case(&[(KeyCode::Char('v'), CTRL), (KeyCode::Tab, NONE)] => "\tab"; "Quoted tab")]
+    #[test_case(&[(KeyCode::Char('v'), CTRL), (KeyCode::Char('s'), CTRL)] => "\x13ab"; "Quoted control key")]
+    #[test_case(&[(KeyCode::Char('v'), CTRL), (KeyCode::Right, NONE), (KeyCode::Tab, NONE)] => "a       b"; "Key without a character")]
+    fn quoted_keys_should_be_inserted_as_is(keys: &[(KeyCode, KeyModifiers)]) -> String {
+        let (sender, _receiver) = async_std::channel::unbounded();
+        let mut input = InputSystem::new(sender);
+        let mut editor = editor_with("ab");
+        editor.set_soft_tabs(true);
+
+        for (code, modifiers) in keys {
+            let key = Event::Key(KeyEvent::new(*code, *modifiers));
+            if let Some(event) = input.translate(key) {
+                editor.process_event(event).unwrap();
+            }
+        }
+
+        let line = editor.buffer().buffer().borrow()[0].buffer().to_string();
+        line
+    }
+
+    #[test]
+    fn mouse_events_should_leave_a_quoted_insert_waiting() {
+        let (sender, _receiver) = async_std::channel::unbounded();
+        let mut input = InputSystem::new(sender);
+        let moved = Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Moved,
+            column: 3,
+            row: 1,
+            modifiers: NONE,
+        });
+
+        assert_eq!(
+            input.translate(Event::Key(KeyEvent::new(KeyCode::Char('v'), CTRL))),
+            Some(InputEvent::QuotedInsert)
+        );
+        assert_eq!(input.translate(moved), None);
+        assert_eq!(
+            input.translate(Event::Key(KeyEvent::new(KeyCode::Tab, NONE))),
+            Some(InputEvent::InsertLiteral('\t'))
+        );
+    }
+
+    /// Record a macro that searches for `x` and ends the line it's on with `;`
+    fn record_semicolon_macro(editor: &mut Editor) {
+        editor
+            .process_event(InputEvent::ToggleMacroRecording)
+            .unwrap();
+        editor.process_event(InputEvent::Find).unwrap();
+        type_text(editor, "x");
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveEnd))
+            .unwrap();
+        type_text(editor, ";");
+        editor
+            .process_event(InputEvent::ToggleMacroRecording)
+            .unwrap();
+    }
+
+    #[test]
+    fn named_macro_should_replay_from_the_prompt() {
+        let mut editor = editor_with("x\nx\nx");
+        record_semicolon_macro(&mut editor);
+
+        editor.process_event(InputEvent::SaveMacro).unwrap();
+        type_text(&mut editor, "semicolon");
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+        editor.process_event(InputEvent::RunMacro).unwrap();
+        assert!(render(&mut editor).contains("Run macro (semicolon): "));
+        type_text(&mut editor, "semicolon 2");
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+
+        let rows = editor.buffer().buffer().borrow();
+        let rows = rows.iter().map(|row| row.buffer()).collect::<Vec<_>>();
+        assert_eq!(rows, vec!["x;", "x;", "x;"]);
+        assert_eq!(editor.focus(), Focus::Buffer);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn saved_macro_should_replay_in_a_new_editor() {
+        let dir = ScratchDir::new("named-macro");
+        let fixture = "tests/fixtures/tabs.rs";
+
+        let mut editor = Editor::new(80, 22);
+        editor.set_macro_dir(Some(dir.to_path_buf()));
+        editor.set_buffer(Buffer::open(fixture).unwrap());
+        record_semicolon_macro(&mut editor);
+        editor.save_macro("semicolon").unwrap();
+
+        let mut fresh = Editor::new(80, 22);
+        fresh.set_macro_dir(Some(dir.to_path_buf()));
+        fresh.set_buffer(Buffer::open(fixture).unwrap());
+        fresh.run_macro("semicolon", 1).unwrap();
+
+        assert_eq!(fresh.macro_names().collect::<Vec<_>>(), vec!["semicolon"]);
+        assert_eq!(fresh.snapshot().buffers[0].rows, editor.snapshot().buffers[0].rows);
+        assert_ne!(fresh.buffer().state(), BufferState::Default);
+    }
+
+    #[test]
+    fn replaying_while_recording_should_be_refused() {
+        let mut editor = editor_with("abc");
+        editor
+            .process_event(InputEvent::ToggleMacroRecording)
+            .unwrap();
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+
+        editor.process_event(InputEvent::ReplayMacro(1)).unwrap();
+        editor
+            .process_event(InputEvent::ToggleMacroRecording)
+            .unwrap();
+
+        assert_eq!(editor.macro_register, vec![InputEvent::InsertChar('x')]);
+        assert_eq!(editor.buffer().buffer().borrow()[0].buffer(), "xabc");
+    }
+
+    #[test]
+    fn quitting_while_recording_should_stop_the_recording_first() {
+        let mut editor = editor_with("abc");
+        editor
+            .process_event(InputEvent::ToggleMacroRecording)
+            .unwrap();
+
+        assert_eq!(editor.request_quit(), QuitDecision::Stay);
+        assert!(!editor.is_recording_macro());
+        assert_eq!(editor.request_quit(), QuitDecision::Quit);
+    }
+
+    #[test]
+    fn describing_a_char_should_report_the_grapheme_under_the_cursor() {
+        let mut editor = editor_with("ae\u{301}");
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveRight))
+            .unwrap();
+
+        editor.process_event(InputEvent::DescribeChar).unwrap();
+
+        assert_eq!(
+            editor.status_message,
+            "'e\u{301}' U+0065 U+0301  65 cc 81  width 1"
+        );
+    }
+
+    #[test]
+    fn describing_a_char_at_the_end_of_a_line_should_report_eol() {
+        let mut editor = editor_with("ab");
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveEnd))
+            .unwrap();
+
+        editor.process_event(InputEvent::DescribeChar).unwrap();
+
+        assert_eq!(editor.status_message, "EOL");
+    }
+
+    #[test]
+    fn picking_from_the_change_list_should_jump_to_the_change() {
+        let mut editor = editor_with("abc\ndef");
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveDown))
+            .unwrap();
+        editor.process_event(InputEvent::InsertChar('y')).unwrap();
+
+        editor.process_event(InputEvent::ShowChangeList).unwrap();
+        assert_eq!(editor.focus(), Focus::ChangeList);
+        editor.process_event(InputEvent::InsertChar('z')).unwrap();
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveDown))
+            .unwrap();
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+
+        assert_eq!(editor.focus(), Focus::Buffer);
+        assert_eq!((editor.cursor.x(), editor.cursor.y()), (0, 0));
+        assert_eq!(editor.buffer().buffer().borrow()[1].buffer(), "dyef");
+    }
+
+    #[test]
+    fn line_numbers_should_reduce_the_text_area_by_the_gutter() {
+        let mut editor = editor_with("a\nb\nc");
+        assert_eq!(editor.text_area(), (80, 22));
+
+        editor.set_line_numbers(true);
+
+        assert_eq!(editor.text_area(), (78, 22));
+    }
+
+    #[test_case(CursorEvent::MoveToScreenTop, 0 => vec![" 8 ", " 1 ", " 2 ", " 3 ", " 4 "]; "Cursor at the top")]
+    #[test_case(CursorEvent::MoveToScreenMiddle, 2 => vec![" 2 ", " 1 ", "10 ", " 1 ", " 2 "]; "Cursor in the middle")]
+    #[test_case(CursorEvent::MoveToScreenBottom, 4 => vec![" 4 ", " 3 ", " 2 ", " 1 ", "12 "]; "Cursor at the bottom")]
+    fn hybrid_numbers_should_show_the_cursor_line_and_distances(
+        movement: CursorEvent,
+        row: u16,
+    ) -> Vec<String> {
+        let mut editor = Editor::new(20, 5);
+        editor.set_buffer("line\n".repeat(20).parse().unwrap());
+        editor.set_line_numbers(true);
+        editor.set_relative_numbers(true);
+        for event in [InputEvent::Count(1), InputEvent::Count(2)] {
+            editor.process_event(event).unwrap();
+        }
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveFileEnd))
+            .unwrap();
+        editor.refresh(&mut Terminal::new(Vec::new())).unwrap();
+        editor
+            .process_event(InputEvent::CursorEvent(movement))
+            .unwrap();
+
+        let grid = editor.render_grid();
+        let bold = (0..5).filter(|y| grid.cell(0, *y).unwrap().bold);
+        assert_eq!(bold.collect::<Vec<_>>(), vec![row]);
+        (0..5).map(|y| grid.row_text(y)[..3].to_string()).collect()
+    }
+
+    #[test_case(1, LineNumberStyle::Absolute, ["1 a", "2 b"]; "Absolute")]
+    #[test_case(2, LineNumberStyle::Relative, ["0 a", "1 b"]; "Relative")]
+    #[test_case(3, LineNumberStyle::Hybrid, ["1 a", "1 b"]; "Hybrid")]
+    #[test_case(4, LineNumberStyle::Off, ["a", "b"]; "Back to off")]
+    fn cycling_line_numbers_should_change_the_gutter(
+        times: usize,
+        style: LineNumberStyle,
+        rows: [&str; 2],
+    ) {
+        let mut editor = editor_with("a\nb\nc");
+        editor.render_grid();
+
+        for _ in 0..times {
+            editor.process_event(InputEvent::CycleLineNumbers).unwrap();
+        }
+
+        let grid = editor.render_grid();
+        assert_eq!(editor.line_number_style(), style);
+        for (y, row) in rows.into_iter().enumerate() {
+            assert_eq!(grid.row_text(y as u16).trim_end(), row);
+        }
+    }
+
+    #[quickcheck]
+    fn rendering_on_a_tiny_screen_should_never_panic(
+        size: (u8, u8),
+        resized: (u8, u8),
+        soft_wrap: bool,
+    ) -> bool {
+        // Both sides of the smallest size drawn normally
+        let tiny =
+            |(cols, rows): (u8, u8)| (cols as u16 % (MIN_COLS + 4), rows as u16 % (MIN_ROWS + 3));
+        let (cols, rows) = tiny(size);
+        let mut editor = Editor::new(cols, rows);
+        editor.set_buffer("\tfn main() {\n  ⛄ wide\n\n".repeat(5).parse().unwrap());
+        editor.set_line_numbers(true);
+        editor.set_indent_guides(true);
+        editor.set_soft_wrap(soft_wrap);
+        editor.set_status_message("A message wider than the screen");
+
+        let events = [
+            InputEvent::CursorEvent(CursorEvent::MoveEnd),
+            InputEvent::CursorEvent(CursorEvent::MoveDown),
+            InputEvent::CursorEvent(CursorEvent::MoveEnd),
+            InputEvent::InsertChar('x'),
+        ];
+        for (i, event) in events.into_iter().enumerate() {
+            if i == 2 {
+                let (cols, rows) = tiny(resized);
+                editor.resize(cols, rows);
+            }
+            editor.process_event(event).unwrap();
+            editor.refresh(&mut Terminal::new(Vec::new())).unwrap();
+            editor.draw_status_bar(&mut Vec::new()).unwrap();
+            editor.draw_message_bar(&mut Vec::new()).unwrap();
+        }
+
+        true
+    }
+
+    #[test]
+    fn a_too_small_terminal_should_show_a_note_until_it_grows() {
+        let mut editor = editor_with("abc");
+
+        editor.resize(8, 22);
+        let grid = editor.render_grid();
+        assert_eq!(grid.row_text(0), "Terminal");
+        assert!((1..grid.rows()).all(|y| grid.row_text(y).trim().is_empty()));
+        assert_eq!(grid.cursor(), None);
+
+        editor.resize(80, 2);
+        assert!(editor.render_grid().row_text(0).starts_with(TOO_SMALL));
+
+        editor.resize(80, 22);
+        assert!(editor.render_grid().row_text(0).starts_with("abc"));
+    }
+
+    fn type_count(editor: &mut Editor, count: &str) {
+        for digit in count.chars().filter_map(|ch| ch.to_digit(10)) {
+            editor.process_event(InputEvent::Count(digit as u8)).unwrap();
+        }
+    }
+
+    #[test]
+    fn count_before_file_end_should_go_to_that_line_clamped_to_the_buffer() {
+        let mut editor = editor_with(&"line\n".repeat(100));
+
+        type_count(&mut editor, "300");
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveFileEnd))
+            .unwrap();
+
+        assert_eq!(editor.cursor.y(), 99);
+    }
+
+    #[test]
+    fn count_before_page_down_should_page_that_many_times() {
+        let mut editor = editor_with("a\nb\nc");
+
+        type_count(&mut editor, "5");
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveBottom))
+            .unwrap();
+
+        assert_eq!(editor.cursor.y(), 3);
+    }
+
+    #[test]
+    fn pending_count_should_show_in_the_status_bar_until_cancelled() {
+        let mut editor = editor_with(&"line\n".repeat(100));
+        let status_bar = |editor: &Editor| {
+            let mut output = Vec::new();
+            editor.draw_status_bar(&mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        type_count(&mut editor, "42");
+        assert!(status_bar(&editor).contains("42 1/100"));
+
+        editor.process_event(InputEvent::Cancel).unwrap();
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveDown))
+            .unwrap();
+
+        assert!(!status_bar(&editor).contains("42"));
+        assert_eq!(editor.cursor.y(), 1);
+    }
+
+    #[test]
+    fn frame_stats_should_show_rows_are_skipped_when_only_the_cursor_moved() {
+        let mut editor = editor_with(&"line\n".repeat(100));
+
+        editor.refresh(&mut Terminal::new(Vec::new())).unwrap();
+        let full = editor.frame_stats();
+        assert_eq!((full.rows_drawn, full.rows_total), (22, 22));
+
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveDown))
+            .unwrap();
+        editor.refresh(&mut Terminal::new(Vec::new())).unwrap();
+        let cursor_only = editor.frame_stats();
+        assert_eq!(cursor_only.rows_drawn, 0);
+        assert!(cursor_only.bytes_written < full.bytes_written);
+        assert_eq!(cursor_only.events_processed, 1);
+
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+        editor.refresh(&mut Terminal::new(Vec::new())).unwrap();
+        assert_eq!(editor.frame_stats().rows_drawn, 22);
+    }
+
+    #[test_case(InputEvent::ToggleLineNumbers; "Line numbers")]
+    #[test_case(InputEvent::ToggleRelativeNumbers; "Relative numbers")]
+    #[test_case(InputEvent::CycleLineNumbers; "Cycling line numbers")]
+    #[test_case(InputEvent::ToggleIndentGuides; "Indent guides")]
+    fn frame_stats_should_show_every_row_drawn_after_changing_the_gutter(event: InputEvent) {
+        let mut editor = editor_with(&"    line\n".repeat(100));
+        editor.set_relative_numbers(true);
+
+        editor.refresh(&mut Terminal::new(Vec::new())).unwrap();
+        editor.process_event(event).unwrap();
+        editor.refresh(&mut Terminal::new(Vec::new())).unwrap();
+
+        assert_eq!(editor.frame_stats().rows_drawn, 22);
+    }
+
+    #[test]
+    fn render_grid_should_have_text_filler_and_the_status_bar() {
+        let mut editor = Editor::new(40, 3);
+        editor.set_buffer("abc".parse().unwrap());
+
+        let grid = editor.render_grid();
+
+        assert_eq!((grid.cols(), grid.rows()), (40, 5));
+        let text = grid.cell(1, 0).unwrap();
+        assert_eq!((text.symbol.as_str(), text.reverse), ("b", false));
+        assert_eq!(grid.cell(0, 1).unwrap().symbol, "~");
+        assert!(grid.row_text(3).starts_with(NO_NAME));
+        assert!((0..40).all(|x| grid.cell(x, 3).unwrap().reverse));
+        assert_eq!(grid.cursor(), Some((0, 0)));
+
+        // The terminal after it still gets every row
+        editor.refresh(&mut Terminal::new(Vec::new())).unwrap();
+        assert_eq!(editor.frame_stats().rows_drawn, 3);
+    }
+
+    /// The state as it would be after storing it and reading it back
+    fn stored(state: EditorState) -> EditorState {
+        #[cfg(feature = "serde")]
+        let state = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+
+        state
+    }
+
+    /// Every row but the message bar, which only holds transient messages
+    fn screen_without_messages(editor: &mut Editor) -> Vec<String> {
+        let grid = editor.render_grid();
+        (0..grid.rows() - 1).map(|y| grid.row_text(y)).collect()
+    }
+
+    #[test]
+    fn search_history_should_fill_the_prompt_after_a_restore() {
+        let mut editor = editor_with("alpha\nbeta");
+        for query in ["alpha", "beta", "beta"] {
+            editor.process_event(InputEvent::Find).unwrap();
+            type_text(&mut editor, query);
+            editor.process_event(InputEvent::InsertNewline).unwrap();
+        }
+        assert_eq!(editor.snapshot().search_history, vec!["alpha", "beta"]);
+
+        let mut restored = Editor::new(80, 22);
+        restored.restore(stored(editor.snapshot()));
+        restored.process_event(InputEvent::Find).unwrap();
+        let up = InputEvent::CursorEvent(CursorEvent::MoveUp);
+        restored.process_event(up).unwrap();
+        assert_eq!(restored.prompt.as_ref().map(Prompt::input), Some("beta"));
+        restored.process_event(up).unwrap();
+        assert_eq!(restored.prompt.as_ref().map(Prompt::input), Some("alpha"));
+    }
+
+    #[test]
+    fn restoring_a_snapshot_should_behave_like_the_original_editor() {
+        let dir = ScratchDir::new("state");
+        let path = dir.join("file.txt");
+        fs::write(&path, "first\nsecond\n").unwrap();
+        let mut editor = Editor::new(40, 5);
+        editor.set_buffer(Buffer::open(&path).unwrap());
+        editor.add_buffer("alpha\nbeta\ngamma".parse().unwrap());
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+        editor.next_buffer();
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveDown))
+            .unwrap();
+        editor.search = Some(SearchState::new("a"));
+        editor.open_prompt(Prompt::new(PromptKind::Search, "Search: "));
+
+        let mut restored = Editor::new(40, 5);
+        restored.restore(stored(editor.snapshot()));
+        editor.prompt = None;
+        editor.focus = Focus::Buffer;
+
+        for event in [
+            InputEvent::SearchNext,
+            InputEvent::NextBuffer,
+            InputEvent::InsertChar('y'),
+        ] {
+            assert_eq!(
+                screen_without_messages(&mut restored),
+                screen_without_messages(&mut editor)
+            );
+            assert_eq!(restored.snapshot(), editor.snapshot());
+            editor.process_event(event).unwrap();
+            restored.process_event(event).unwrap();
+        }
+    }
+
+    #[test]
+    fn restoring_a_buffer_whose_file_is_gone_should_keep_it_unnamed() {
+        let dir = ScratchDir::new("state-missing");
+        let missing = dir.join("file.txt");
+        let mut buffer: Buffer = "kept".parse().unwrap();
+        buffer.set_filename(missing.to_str().unwrap());
+        let mut editor = Editor::new(40, 5);
+        editor.set_buffer(buffer);
+
+        editor.restore(editor.snapshot());
+
+        assert_eq!(editor.buffer.filename_str(), None);
+        assert_eq!(editor.buffer.state(), BufferState::Modified);
+        assert_eq!(editor.buffer.buffer().borrow()[0].buffer(), "kept");
+        assert!(editor.status_message.starts_with("No longer on disk"));
+    }
+
+    #[test]
+    fn undoing_after_a_restore_should_fit_an_empty_last_row() {
+        let mut editor = Editor::new(80, 22);
+        editor.set_buffer(Buffer::from_rows(["a\rb"]));
+        editor.cursor.end();
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+
+        let mut restored = Editor::new(80, 22);
+        restored.restore(stored(editor.snapshot()));
+        let rows = |editor: &Editor| editor.snapshot().buffers[0].rows.clone();
+        assert_eq!(rows(&restored), rows(&editor));
+
+        restored.process_event(InputEvent::Undo).unwrap();
+        assert_eq!(rows(&restored), vec!["a\rb"]);
+    }
+
+    #[test_case(|_| {}; "Top of the file")]
+    #[test_case(|editor| {
+        editor.set_line_numbers(true);
+        editor.cursor.jump_to(Position(60, 30));
+    }; "Scrolled both ways past a gutter")]
+    #[test_case(|editor| {
+        editor.set_soft_wrap(true);
+        editor.cursor.jump_to(Position(50, 3));
+    }; "Soft wrap")]
+    #[test_case(|editor| editor.add_buffer("two".parse().unwrap()); "Under the tab bar")]
+    #[test_case(|editor| {
+        editor.process_event(InputEvent::GotoLine).unwrap();
+        editor.process_event(InputEvent::InsertChar('4')).unwrap();
+    }; "In the prompt")]
+    fn screen_cursor_should_be_where_the_frame_leaves_the_cursor(setup: fn(&mut Editor)) {
+        let line = "a\tb 日本語 ".repeat(8);
+        let mut editor = Editor::new(40, 10);
+        editor.set_buffer(vec![line.as_str(); 40].join("\n").parse().unwrap());
+        setup(&mut editor);
+
+        let grid = editor.render_grid();
+
+        assert_eq!(grid.cursor(), Some(editor.screen_cursor()));
+    }
+
+    #[test]
+    fn tab_bar_should_take_a_text_row_while_buffers_are_open() {
+        let mut editor = Editor::new(40, 4);
+        let mut buffer: Buffer = "one".parse().unwrap();
+        buffer.set_filename("src/a_very_long_filename.rs");
+        editor.set_buffer(buffer);
+        editor.add_buffer("two".parse().unwrap());
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+
+        let grid = editor.render_grid();
+
+        assert_eq!(grid.rows(), 6);
+        assert!(grid
+            .row_text(0)
+            .starts_with(" a_very_long_fil*  [No Name] "));
+        assert!((0..18).all(|x| grid.cell(x, 0).unwrap().reverse));
+        assert!(!grid.cell(18, 0).unwrap().reverse);
+        assert!(grid.row_text(1).starts_with("xone"));
+        assert_eq!(grid.cursor(), Some((1, 1)));
+        assert_eq!(editor.text_area(), (40, 3));
+
+        editor.next_buffer();
+        editor.process_event(InputEvent::CloseBuffer).unwrap();
+        assert_eq!(editor.text_area(), (40, 4));
+    }
+
+    #[test]
+    fn tabs_should_keep_their_order_while_switching() {
+        let mut editor = Editor::new(40, 4);
+        editor.set_tab_bar_position(TabBarPosition::Bottom);
+        for name in ["a", "b", "c"] {
+            let mut buffer = Buffer::default();
+            buffer.set_filename(name);
+            editor.add_buffer(buffer);
+        }
+        let tabs = |editor: &mut Editor| editor.render_grid().row_text(3);
+
+        assert!(tabs(&mut editor).starts_with(" [No Name]  a  b  c "));
+        for event in [
+            InputEvent::PreviousBuffer,
+            InputEvent::NextBuffer,
+            InputEvent::PreviousBuffer,
+        ] {
+            editor.process_event(event).unwrap();
+        }
+        editor.add_buffer(Buffer::default());
+
+        let grid = editor.render_grid();
+        assert!(grid
+            .row_text(3)
+            .starts_with(" [No Name]  a  b  c  [No Name] "));
+        assert!(grid.cell(17, 3).unwrap().reverse);
+        assert_eq!(editor.buffer.filename_str(), Some("c"));
+    }
+
+    #[test]
+    fn diff_signs_should_take_a_gutter_column_in_front_of_the_line_numbers() {
+        let mut editor = editor_with("one\ntwo\nthree");
+        editor.set_diff_signs(vec![(0, Sign::Added), (2, Sign::Modified)]);
+
+        let grid = editor.render_grid();
+        assert_eq!(grid.row_text(0).trim_end(), "+one");
+        assert_eq!(grid.row_text(1).trim_end(), " two");
+        assert_eq!(grid.row_text(2).trim_end(), "~three");
+
+        editor.set_line_numbers(true);
+        editor.set_diff_signs(vec![(1, Sign::Removed)]);
+
+        let grid = editor.render_grid();
+        assert_eq!(grid.row_text(0).trim_end(), " 1 one");
+        assert_eq!(grid.row_text(1).trim_end(), "_2 two");
+        assert_eq!(grid.row_text(3).trim_end(), "   ~");
+        assert_eq!(grid.cursor(), Some((3, 0)));
+    }
+
+    #[test]
+    fn diff_signs_should_follow_edits_until_saved() {
+        let dir = ScratchDir::new("diff");
+        let path = dir.join("file.txt");
+        fs::write(&path, "one\ntwo\n").unwrap();
+        let mut editor = Editor::new(80, 22);
+        editor.set_buffer(Buffer::open(&path).unwrap());
+
+        editor.update_diff_signs();
+        assert_eq!(editor.diff_signs(), []);
+
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+        editor.update_diff_signs();
+        assert_eq!(editor.diff_signs(), [(0, Sign::Modified)]);
+
+        editor.process_event(InputEvent::SaveBuffer).unwrap();
+        editor.update_diff_signs();
+        assert_eq!(editor.diff_signs(), []);
+
+        // Changed on disk, with a time the save can't have had
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_len(0).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH).unwrap();
+        editor.update_diff_signs();
+        assert_eq!(editor.diff_signs(), [(0, Sign::Added), (1, Sign::Added)]);
+    }
+
+    #[test]
+    fn status_bar_should_tell_how_long_ago_the_buffer_was_saved() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let dir = ScratchDir::new("save-age");
+        let path = dir.join("file.txt");
+        fs::write(&path, "one").unwrap();
+        let clock = Clock::fixed(UNIX_EPOCH + Duration::from_secs(1_000));
+        let mut editor = Editor::new(80, 22);
+        editor.set_clock(clock.clone());
+        editor.set_show_save_age(true);
+        editor.set_buffer(Buffer::open(&path).unwrap());
+        assert!(editor.render_grid().row_text(22).contains("never saved"));
+
+        editor.process_event(InputEvent::SaveBuffer).unwrap();
+        clock.advance(Duration::from_secs(12));
+        assert!(editor.render_grid().row_text(22).contains("saved 12s ago"));
+
+        clock.advance(Duration::from_secs(120));
+        assert!(editor.render_grid().row_text(22).contains("saved 2m ago"));
+    }
+
+    #[test]
+    fn saving_over_a_file_changed_on_disk_should_ask_first() {
+        let dir = ScratchDir::new("conflict");
+        let path = dir.join("file.txt");
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let mut editor = Editor::new(80, 22);
+        editor.set_buffer(Buffer::open(&path).unwrap());
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+        fs::write(&path, "one\n2\nthree\nfour\nfive\n").unwrap();
+
+        editor.process_event(InputEvent::SaveBuffer).unwrap();
+        assert!(editor.status_message.ends_with(
+            "changed on disk: +3 −1 lines, first at line 2. Save again to overwrite, A-d to see it"
+        ));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "one\n2\nthree\nfour\nfive\n"
+        );
+
+        editor.process_event(InputEvent::ShowDiskVersion).unwrap();
+        assert!(editor.buffer().is_read_only());
+        assert_eq!(editor.buffer().buffer().borrow()[1].buffer(), "2");
+        editor.process_event(InputEvent::InsertChar('y')).unwrap();
+        assert!(editor.status_message.ends_with("is read-only"));
+
+        editor.process_event(InputEvent::NextBuffer).unwrap();
+        editor.process_event(InputEvent::SaveBuffer).unwrap();
+        assert!(editor.status_message.contains("changed on disk"));
+        editor.process_event(InputEvent::SaveBuffer).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "xone\ntwo\nthree");
+        assert_eq!(editor.buffer().disk_changes(), None);
+    }
+
+    #[test]
+    fn forced_saves_should_skip_the_read_only_and_disk_change_checks() {
+        let dir = ScratchDir::new("force");
+        let path = dir.join("file.txt");
+        fs::write(&path, "one").unwrap();
+        let mut editor = Editor::new(80, 22);
+        editor.set_buffer(Buffer::open(&path).unwrap());
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+        fs::write(&path, "two").unwrap();
+
+        editor.process_event(InputEvent::ForceSave).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "xone");
+        assert!(editor.status_message.starts_with("Saved"));
+
+        editor.buffer.set_read_only(true);
+        editor.process_event(InputEvent::SaveBuffer).unwrap();
+        assert!(editor.status_message.ends_with("is read-only"));
+        editor.process_event(InputEvent::ForceSave).unwrap();
+        assert!(!editor.buffer().is_read_only());
+    }
+
+    #[test]
+    fn read_only_buffers_should_be_saved_as_another_file_which_is_then_edited() {
+        let dir = ScratchDir::new("save-as");
+        let path = dir.join("file.txt");
+        let mut editor = editor_with("abc");
+        editor.buffer.set_read_only(true);
+
+        editor.process_event(InputEvent::SaveAs).unwrap();
+        type_text(&mut editor, path.to_str().unwrap());
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abc");
+        assert!(!editor.buffer().is_read_only());
+        editor.process_event(InputEvent::InsertChar('x')).unwrap();
+        assert_eq!(editor.buffer().buffer().borrow()[0].buffer(), "xabc");
+    }
+
+    #[test_case("main.rs", "f(&'a x, \"" => "f(&'a x, \"\""; "Rust string")]
+    #[test_case("main.rs", "fn f<'a" => "fn f<'a"; "Rust lifetime")]
+    #[test_case("notes.txt", "don't" => "don't"; "Contraction")]
+    #[test_case("notes.txt", "say 'hi" => "say 'hi'"; "Quoting in prose")]
+    fn typed_quotes_should_be_closed_where_they_open_a_string(
+        filename: &str,
+        typed: &str,
+    ) -> String {
+        let mut buffer = "".parse::<Buffer>().unwrap();
+        buffer.set_filename(filename);
+        let mut editor = Editor::new(80, 22);
+        for (extension, highlighter) in highlight::default_highlighters() {
+            editor.set_highlighter(extension, highlighter);
+        }
+        editor.set_buffer(buffer);
+        editor
+            .process_event(InputEvent::ToggleAutoCloseQuotes)
+            .unwrap();
+
+        type_text(&mut editor, typed);
+
+        let line = editor.buffer().buffer().borrow()[0].buffer().to_string();
+        line
+    }
+
+    #[test]
+    fn quotes_should_not_be_closed_unless_asked_for() {
+        let mut buffer = "".parse::<Buffer>().unwrap();
+        buffer.set_filename("main.rs");
+        let mut editor = Editor::new(80, 22);
+        editor.set_highlighter("rs", Rc::new(highlight::Syntax::RUST));
+        editor.set_buffer(buffer);
+
+        type_text(&mut editor, "\"");
+
+        assert_eq!(editor.buffer().buffer().borrow()[0].buffer(), "\"");
+    }
+
+    #[test_case(Some(&['-']) => vec![8, 11]; "Dash in words")]
+    #[test_case(None => vec![4, 8, 11]; "Default")]
+    fn word_right_should_follow_the_word_chars_of_the_file_type(
+        word_chars: Option<&[char]>,
+    ) -> Vec<u16> {
+        let mut buffer = "foo-bar baz".parse::<Buffer>().unwrap();
+        buffer.set_filename("style.css");
+        let mut editor = Editor::new(80, 22);
+        editor.set_buffer(buffer);
+        editor.set_word_chars(
+            "css",
+            word_chars.map(|chars| chars.iter().copied().collect()),
+        );
+
+        let mut stops = Vec::new();
+        let word_right = InputEvent::CursorEvent(CursorEvent::MoveWordRight);
+        while editor.handle_buffer_event(word_right) != EventOutcome::rejected() {
+            stops.push(editor.cursor.x());
+        }
+
+        stops
+    }
+
+    /// A line wrapping onto three rows of a 20 column screen, the rows starting at columns
+    /// 0, 20 and 39 after the indicator, and a short line under it
+    fn wrapped_editor() -> Editor {
+        let mut editor = Editor::new(20, 8);
+        editor.set_buffer(
+            "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ\nend"
+                .parse()
+                .unwrap(),
+        );
+        editor.set_soft_wrap(true);
+        editor
+    }
+
+    #[test]
+    fn wrapped_lines_should_continue_under_an_indicator() {
+        let mut editor = wrapped_editor();
+        editor.cursor.jump_to(Position(24, 0));
+
+        let grid = editor.render_grid();
+
+        let rows = (0..4).map(|y| grid.row_text(y)).collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                "abcdefghijklmnopqrst",
+                "↪uvwxyzABCDEFGHIJKLM",
+                "↪NOPQRSTUVWXYZ      ",
+                "end                 ",
+            ]
+        );
+        assert!(grid.cell(0, 1).unwrap().dim);
+        assert_eq!(grid.cursor(), Some((5, 1)));
+        assert!(grid.row_text(8).contains("col:25"));
+    }
+
+    #[test]
+    fn changing_break_indent_should_redraw_the_text() {
+        let mut editor = wrapped_editor();
+        render(&mut editor);
+        assert!(!render(&mut editor).contains("abcdefghijklmnopqrst"));
+
+        editor.set_break_indent(true);
+
+        assert!(render(&mut editor).contains("abcdefghijklmnopqrst"));
+    }
+
+    #[test]
+    fn the_cursor_row_should_follow_lines_wrapping_above_it() {
+        let mut editor = wrapped_editor();
+        editor.cursor.jump_to(Position(0, 1));
+        assert_eq!(editor.screen_cursor(), (0, 3));
+
+        editor.cursor.jump_to(Position(52, 0));
+        type_text(&mut editor, &"x".repeat(10));
+        editor.cursor.jump_to(Position(0, 1));
+
+        assert_eq!(editor.screen_cursor(), (0, 4));
+    }
+
+    #[test]
+    fn up_and_down_should_move_by_screen_row_when_wrapping() {
+        let mut editor = wrapped_editor();
+        editor.cursor.jump_to(Position(5, 0));
+        let mut moves = |event| {
+            editor.handle_buffer_event(InputEvent::CursorEvent(event));
+            (editor.cursor.x(), editor.cursor.y())
+        };
+
+        let down = [(); 3].map(|_| moves(CursorEvent::MoveDown));
+        let up = moves(CursorEvent::MoveUp);
+
+        assert_eq!(down, [(24, 0), (43, 0), (3, 1)]);
+        assert_eq!(up, (41, 0));
+        assert_eq!(moves(CursorEvent::MoveLineDown), (3, 1));
+        assert_eq!(moves(CursorEvent::MoveLineUp), (3, 0));
+    }
+
+    #[test]
+    fn home_and_end_should_stop_at_the_screen_row_first_when_wrapping() {
+        let mut editor = wrapped_editor();
+        let mut moves = |event| {
+            editor.cursor.jump_to(Position(24, 0));
+            [(); 2].map(|_| {
+                editor.handle_buffer_event(InputEvent::CursorEvent(event));
+                editor.cursor.x()
+            })
+        };
+
+        assert_eq!(moves(CursorEvent::MoveBegin), [20, 0]);
+        assert_eq!(moves(CursorEvent::MoveEnd), [38, 52]);
+    }
+
+    #[test]
+    fn text_past_the_fill_column_should_have_a_warning_background() {
+        let mut editor = Editor::new(50, 6);
+        editor.set_buffer(Buffer::open("tests/fixtures/long_lines.rs").unwrap());
+        editor.set_fill_column("rs", Some(30));
+
+        let grid = editor.render_grid();
+        let background = |x, y| grid.cell(x, y).unwrap().background;
+
+        // The tabs of the second row count as 16 columns
+        assert_eq!(
+            (background(29, 1), background(30, 1), background(40, 1)),
+            (None, Some(1), Some(1))
+        );
+        assert_eq!(background(41, 1), None);
+        assert_eq!((background(30, 3), background(31, 3)), (Some(1), Some(1)));
+        assert_eq!(background(15, 2), None);
+
+        editor.set_fill_column("rs", None);
+        assert_eq!(editor.render_grid().cell(30, 1).unwrap().background, None);
+    }
+
+    #[test]
+    fn next_long_line_should_cycle_through_the_long_lines() {
+        let mut editor = Editor::new(50, 6);
+        editor.set_buffer(Buffer::open("tests/fixtures/long_lines.rs").unwrap());
+        assert!(editor.next_long_line().is_rejected());
+        editor.set_fill_column("rs", Some(30));
+
+        let rows = [(); 3].map(|_| {
+            editor.process_event(InputEvent::NextLongLine).unwrap();
+            editor.cursor.y()
+        });
+
+        assert_eq!(rows, [1, 3, 1]);
+        assert_eq!(editor.status_message, "Long line 1 of 2: 41 columns, 11 over");
+    }
+
+    fn render(editor: &mut Editor) -> String {
+        let mut output = Vec::new();
+        editor.refresh(&mut Terminal::new(&mut output)).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn audible_bell_should_ring_only_for_rejected_events() {
+        let mut editor = editor_with("abc");
+        editor.set_bell(Bell::Audible);
+
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveRight))
+            .unwrap();
+        assert!(!render(&mut editor).contains('\x07'));
+
+        editor
+            .process_event(InputEvent::Unbound("C-k".parse().unwrap()))
+            .unwrap();
+        assert!(render(&mut editor).contains('\x07'));
+        assert!(!render(&mut editor).contains('\x07'));
+    }
+
+    #[test]
+    fn unbound_keys_should_be_named_in_the_status_bar() {
+        let mut editor = editor_with("abc");
+
+        editor
+            .process_event(InputEvent::Unbound("ctrl+alt+k".parse().unwrap()))
+            .unwrap();
+
+        assert!(render(&mut editor).contains("C-A-k is not bound"));
+    }
+
+    #[test]
+    fn message_log_should_list_the_last_messages_newest_first() {
+        let mut editor = editor_with("abc");
+        for i in 0..MESSAGE_LOG_SIZE + 5 {
+            editor.set_status_message(format!("message {i}"));
+        }
+        editor.set_status_message_as(Severity::Error, "Can't save file");
+
+        editor.process_event(InputEvent::ShowMessageLog).unwrap();
+        let grid = editor.render_grid();
+
+        assert_eq!(editor.focus(), Focus::MessageLog);
+        assert_eq!(editor.message_log().count(), MESSAGE_LOG_SIZE);
+        assert!(grid.row_text(0).contains(" error   Can't save file"));
+        assert!(grid.row_text(1).contains(" info    message 104"));
+
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveDown))
+            .unwrap();
+        assert!(editor.render_grid().row_text(0).contains("message 104"));
+
+        editor.process_event(InputEvent::Cancel).unwrap();
+        assert_eq!(editor.focus(), Focus::Buffer);
+        assert_eq!(editor.render_grid().row_text(0).trim_end(), "abc");
+    }
+
+    #[test]
+    fn visual_bell_should_invert_the_status_bar_for_one_frame() {
+        let reverse = format!("{}", SetAttribute(Attribute::Reverse));
+        let mut editor = editor_with("abc");
+        editor.set_bell(Bell::Visual);
+
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveLeft))
+            .unwrap();
+        let flash = render(&mut editor);
+        assert!(!flash.contains(&reverse) && !flash.contains('\x07'));
+        assert!(editor.deadline().is_some());
+
+        assert!(render(&mut editor).contains(&reverse));
+        assert_eq!(editor.deadline(), None);
+    }
+
+    #[test]
+    fn plain_terminal_should_get_no_styles_or_alternate_screen() {
+        let capabilities = Capabilities::none();
+        let mut editor = Editor::new(80, 22);
+        editor.set_capabilities(capabilities);
+        editor.set_buffer("    indented\nabc".parse().unwrap());
+        editor.set_indent_guides(true);
+        editor.set_hud(true);
+        editor.set_bell(Bell::Visual);
+        editor
+            .process_event(InputEvent::Unbound("C-k".parse().unwrap()))
+            .unwrap();
+
+        let mut output = Vec::new();
+        let mut terminal = Terminal::take_over(&mut output, capabilities).unwrap();
+        editor.refresh(&mut terminal).unwrap();
+        editor.refresh(&mut terminal).unwrap();
+        terminal.leave().unwrap();
+        drop(terminal);
+        let output = String::from_utf8(output).unwrap();
+
+        // Every SGR sequence ends in `m`, nothing else drawn does
+        let sgr = output
+            .split('\x1b')
+            .skip(1)
+            .filter_map(|sequence| sequence.strip_prefix('['))
+            .filter(|params| {
+                params
+                    .trim_start_matches(|ch: char| ch.is_ascii_digit() || ch == ';')
+                    .starts_with('m')
+            })
+            .count();
+        assert_eq!(sgr, 0, "{output:?}");
+        assert!(!output.contains("\x1b[?1049"), "{output:?}");
+    }
+
+    #[test]
+    fn capable_terminal_should_enter_and_leave_the_alternate_screen() {
+        let capabilities = Capabilities::from_env(Some("xterm"), false, true);
+        let mut output = Vec::new();
+
+        let mut terminal = Terminal::take_over(&mut output, capabilities).unwrap();
+        terminal.leave().unwrap();
+        drop(terminal);
+
+        let output = String::from_utf8(output).unwrap();
+        let enter = output.find("\x1b[?1049h");
+        let leave = output.find("\x1b[?1049l");
+        assert!(enter.is_some() && enter < leave, "{output:?}");
+    }
+
+    #[test_case("a\r\nb\r\n", true => vec!["a", "b"]; "CRLF")]
+    #[test_case("a\rb", true => vec!["a", "b"]; "Lone CR")]
+    #[test_case("a\nb\n", false => vec!["a", "b", ""]; "Not normalized")]
+    fn pasting_should_insert_the_lines(text: &str, normalize: bool) -> Vec<String> {
+        let mut editor = editor_with("");
+        editor.set_normalize_paste(normalize);
+
+        editor.paste(text).unwrap();
+
+        let rows = editor.buffer.buffer().borrow();
+        rows.iter().map(|row| row.buffer().to_string()).collect()
+    }
+
+    #[test_case("    ab", 6 => vec!["    ab", "    "]; "At the end")]
+    #[test_case("\tab", 1 => vec!["\t", "\tab"]; "Tab before the text")]
+    #[test_case("    ab", 2 => vec!["  ", "    ab"]; "Inside the indent")]
+    fn new_lines_should_keep_the_indent_with_auto_indent(line: &str, x: u16) -> Vec<String> {
+        let mut editor = editor_with(line);
+        editor
+            .process_event(InputEvent::ToggleAutoIndent)
+            .unwrap();
+        editor.cursor.jump_to(Position(x, 0));
+
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+
+        let rows = editor.buffer.buffer().borrow();
+        rows.iter().map(|row| row.buffer().to_string()).collect()
+    }
+
+    #[test]
+    fn pasted_code_should_keep_its_own_indent_with_auto_indent() {
+        let code = "fn main() {\n    if ok {\n\tdone();\n    }\n}";
+        let mut editor = editor_with("");
+        editor.set_auto_indent(true);
+        editor.set_soft_tabs(true);
+
+        editor.paste(code).unwrap();
+
+        let rows = editor.buffer.buffer().borrow();
+        let rows: Vec<_> = rows.iter().map(|row| row.buffer().to_string()).collect();
+        assert_eq!(rows.join("\n"), code);
+    }
+
+    #[test]
+    fn search_without_a_match_should_be_rejected() {
+        let mut editor = editor_with("abc");
+        editor.set_bell(Bell::Audible);
+
+        editor.process_event(InputEvent::Find).unwrap();
+        editor.process_event(InputEvent::InsertChar('z')).unwrap();
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+
+        assert!(render(&mut editor).contains('\x07'));
+    }
+
+    #[test]
+    fn replace_should_only_change_the_confirmed_matches() {
+        let mut editor = editor_with("foo foo\nbar foo foo");
+
+        editor.process_event(InputEvent::Replace).unwrap();
+        for ch in "foo/x".chars() {
+            editor.process_event(InputEvent::InsertChar(ch)).unwrap();
+        }
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+        assert_eq!(editor.focus(), Focus::Replace);
+        assert!(render(&mut editor).contains("Replace with \"x\"? (y/n/a/q) [1/4]"));
+
+        for ch in "yny".chars() {
+            editor.process_event(InputEvent::InsertChar(ch)).unwrap();
+        }
+        assert_eq!((editor.cursor.x(), editor.cursor.y()), (6, 1));
+
+        editor.process_event(InputEvent::Cancel).unwrap();
+        let rows = editor.buffer.buffer().borrow();
+        let lines: Vec<_> = rows.iter().map(|row| row.buffer()).collect();
+        assert_eq!(lines, ["x foo", "bar x foo"]);
+        assert_eq!(editor.focus(), Focus::Buffer);
+        assert_eq!(editor.status_message, "Replaced 2 of 4");
+    }
+
+    #[test]
+    fn position_should_display_as_one_based_row_and_column() {
+        assert_eq!(Position(6, 41).to_string(), "42:7");
+        assert_eq!(Position(u16::MAX, 0).to_string(), "1:65536");
+    }
+
+    #[test]
+    fn switching_buffers_should_restore_each_cursor_and_scroll_offset() {
+        let mut editor = editor_with(&"first\n".repeat(100));
+        editor.add_buffer("second\nbuffer".parse().unwrap());
+        editor.cursor.goto_line(80);
+        editor.cursor.end();
+        editor.refresh(&mut Terminal::new(Vec::new())).unwrap();
+        let scrolled = editor.screen.borrow().row_offset();
+
+        editor.process_event(InputEvent::NextBuffer).unwrap();
+        assert_eq!((editor.cursor.x(), editor.cursor.y()), (0, 0));
+        assert_eq!(editor.screen.borrow().row_offset(), 0);
+        editor.cursor.down();
+        editor.cursor.right();
+
+        editor.process_event(InputEvent::NextBuffer).unwrap();
+        assert_eq!((editor.cursor.x(), editor.cursor.y()), (5, 79));
+        assert_eq!(editor.screen.borrow().row_offset(), scrolled);
+
+        editor.process_event(InputEvent::NextBuffer).unwrap();
+        assert_eq!((editor.cursor.x(), editor.cursor.y()), (1, 1));
+    }
+
+    #[test]
+    fn key_hint_should_cover_the_status_message_until_the_input_completes() {
+        let mut editor = editor_with("abc");
+        editor.set_status_message("Saved file");
+        let status_time = editor.status_time;
+
+        type_count(&mut editor, "12");
+        let mut output = Vec::new();
+        editor.draw_message_bar(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("repeat: 12") && !output.contains("Saved file"));
+
+        editor
+            .process_event(InputEvent::CursorEvent(CursorEvent::MoveRight))
+            .unwrap();
+        let mut output = Vec::new();
+        editor.draw_message_bar(&mut output).unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("Saved file"));
+        assert_eq!(editor.status_time, status_time);
+    }
+
+    #[test]
+    fn escape_should_clear_the_status_message_before_it_times_out() {
+        let mut editor = editor_with("abc");
+        editor.set_status_message("Saved file");
+
+        editor.process_event(InputEvent::Cancel).unwrap();
+
+        let mut output = Vec::new();
+        editor.draw_message_bar(&mut output).unwrap();
+        let cleared = String::from_utf8(output).unwrap();
+        let mut output = Vec::new();
+        queue!(output, Clear(ClearType::UntilNewLine)).unwrap();
+        assert_eq!(cleared, String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn reopening_a_closed_buffer_should_restore_its_path_and_cursor() {
+        let mut editor = Editor::new(80, 22);
+        editor.set_buffer(Buffer::open("tests/fixtures/prose.txt").unwrap());
+        editor.cursor.goto_line(3);
+        editor.cursor.right_by(5);
+
+        editor.process_event(InputEvent::CloseBuffer).unwrap();
+        assert_eq!(editor.buffer().filename_str(), None);
+
+        editor.process_event(InputEvent::ReopenBuffer).unwrap();
+        assert_eq!(editor.buffer().filename_str(), Some("tests/fixtures/prose.txt"));
+        assert_eq!((editor.cursor.x(), editor.cursor.y()), (5, 2));
+        assert_eq!(editor.buffer_count(), 1);
+
+        editor.process_event(InputEvent::ReopenBuffer).unwrap();
+        assert_eq!(editor.status_message, "No closed buffers to reopen");
+    }
+
+    #[test]
+    fn rows_removed_right_before_drawing_should_leave_filler() {
+        let mut editor = editor_with(&"line\n".repeat(30));
+        editor.cursor.goto_line(28);
+        editor.set_render_hook(|buf| buf.buffer().borrow_mut().truncate(10));
+
+        let grid = editor.render_grid();
+
+        assert_eq!(grid.row_text(0).trim_end(), "line");
+        assert_eq!(grid.row_text(21).trim_end(), "~");
+    }
+
+    #[test]
+    fn annotations_should_be_drawn_dimmed_under_their_line() {
+        let mut editor = editor_with("fn main() {\n    let x;\n}");
+        editor
+            .buffer_mut()
+            .add_annotation(1, "unused variable".to_string(), Severity::Warning);
+        editor.cursor.goto_line(3);
+
+        let grid = editor.render_grid();
+
+        assert_eq!(grid.row_text(1).trim_end(), "    let x;");
+        assert_eq!(grid.row_text(2).trim_end(), "    warning: unused variable");
+        assert!(grid.cell(4, 2).unwrap().dim);
+        assert_eq!(grid.row_text(3).trim_end(), "}");
+        assert_eq!(grid.cursor(), Some((0, 3)));
+
+        editor.buffer_mut().clear_annotations();
+        assert_eq!(editor.render_grid().row_text(2).trim_end(), "}");
+    }
+
+    #[test]
+    fn annotations_above_the_cursor_should_scroll_it_into_view() {
+        let mut editor = Editor::new(80, 5);
+        editor.set_buffer("a\nb\nc\nd\ne\nf".parse().unwrap());
+        for row in 0..3 {
+            editor
+                .buffer_mut()
+                .add_annotation(row, format!("note {row}"), Severity::Info);
+        }
+        editor.cursor.goto_line(5);
+
+        let grid = editor.render_grid();
+
+        assert_eq!(grid.row_text(0).trim_end(), "c");
+        assert_eq!(grid.row_text(1).trim_end(), "info: note 2");
+        assert_eq!(grid.row_text(3).trim_end(), "e");
+        assert_eq!(grid.cursor(), Some((0, 3)));
+    }
+
+    #[test_case("$" => Some(19); "Last line")]
+    #[test_case("-5" => Some(6); "Relative")]
+    #[test_case("x" => None; "Invalid")]
+    fn goto_line_prompt_should_move_to_the_address(address: &str) -> Option<u16> {
+        let mut editor = editor_with(&"line\n".repeat(20));
+        editor.cursor.goto_line(12);
+        editor.process_event(InputEvent::GotoLine).unwrap();
+        type_text(&mut editor, address);
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+
+        (editor.status_message.is_empty()).then(|| editor.cursor.y())
+    }
+
+    #[test]
+    fn opening_a_file_with_mixed_line_endings_should_warn() {
+        let mut editor = editor_with("scratch");
+        editor.process_event(InputEvent::OpenFile).unwrap();
+        type_text(&mut editor, "tests/fixtures/mixed_endings.txt");
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+
+        assert_eq!(editor.buffer().buffer().borrow().len(), 4);
+        assert_eq!(editor.status_message, "Mixed line endings, saving as CRLF");
+    }
+
+    #[test]
+    fn comment_opened_above_the_screen_should_dim_its_rows() {
+        let mut editor = Editor::new(80, 5);
+        let mut buf = format!("/* start\n{}end */ code\nafter", "inside\n".repeat(10))
+            .parse::<Buffer>()
+            .unwrap();
+        buf.set_filename("comment.rs");
+        editor.set_buffer(buf);
+        editor.set_highlighter("rs", Rc::new(highlight::Syntax::C));
+        editor.cursor.goto_line(13);
+
+        let grid = editor.render_grid();
+
+        assert_eq!(grid.row_text(0).trim_end(), "inside");
+        assert!(grid.cell(0, 0).unwrap().dim);
+        assert_eq!(grid.row_text(3).trim_end(), "end */ code");
+        assert!(grid.cell(5, 3).unwrap().dim);
+        assert!(!grid.cell(7, 3).unwrap().dim);
+        assert!(!grid.cell(0, 4).unwrap().dim);
+    }
+
+    #[test]
+    fn strings_should_be_drawn_in_italics() {
+        let mut editor = Editor::new(80, 5);
+        let mut buf = "x = \"\"\"doc\nstring\"\"\" # note".parse::<Buffer>().unwrap();
+        buf.set_filename("doc.py");
+        editor.set_buffer(buf);
+        editor.set_highlighter("py", Rc::new(highlight::Syntax::PYTHON));
+
+        let grid = editor.render_grid();
+
+        let italic = |x, y| grid.cell(x, y).unwrap().italic;
+        assert!(!italic(0, 0));
+        assert!(italic(4, 0) && italic(0, 1) && italic(8, 1));
+        assert!(!italic(10, 1) && grid.cell(10, 1).unwrap().dim);
+    }
+
+    /// C highlighting that counts the lines it highlights
+    #[derive(Debug, Default)]
+    struct CountingHighlighter(std::cell::Cell<usize>);
+
+    impl Highlighter for CountingHighlighter {
+        fn highlight_line(
+            &self,
+            line: &str,
+            state: LineState,
+        ) -> (Vec<highlight::Span>, LineState) {
+            self.0.set(self.0.get() + 1);
+            highlight::Syntax::C.highlight_line(line, state)
+        }
+    }
+
+    #[test]
+    fn opening_a_comment_should_leave_the_rows_off_the_screen_to_idle_time() {
+        let mut editor = Editor::new(80, 22);
+        let mut buf = "let a = 1;\n".repeat(10_000).parse::<Buffer>().unwrap();
+        buf.set_filename("long.c");
+        editor.set_buffer(buf);
+        let highlighter = Rc::new(CountingHighlighter::default());
+        editor.set_highlighter("c", highlighter.clone());
+        editor.render_grid();
+        while editor.deadline().is_some() {
+            editor.idle();
+        }
+
+        type_text(&mut editor, "/*");
+        highlighter.0.set(0);
+        editor.render_grid();
+        assert!(highlighter.0.get() <= 22, "{}", highlighter.0.get());
+
+        let mut ticks = 0;
+        while editor.deadline().is_some() {
+            highlighter.0.set(0);
+            editor.idle();
+            assert!(highlighter.0.get() <= IDLE_HIGHLIGHT_ROWS);
+            ticks += 1;
+        }
+        assert_eq!(ticks, 10);
+
+        // The last rows are known to be in the comment without going over the file again
+        editor.cursor.goto_line(10_000);
+        highlighter.0.set(0);
+        let grid = editor.render_grid();
+        assert!(highlighter.0.get() <= 22, "{}", highlighter.0.get());
+        assert!(grid.cell(0, 21).unwrap().dim);
+    }
+
+    #[test]
+    fn setting_the_filetype_should_change_the_highlighting_and_tab_width() {
+        let mut editor = Editor::new(80, 22);
+        let mut buf = "[package]\n\tname = 1 # comment".parse::<Buffer>().unwrap();
+        buf.set_filename("Cargo.txt");
+        editor.set_highlighter("py", Rc::new(highlight::Syntax::PYTHON));
+        editor.set_filetype_tab_stop("py", Some(2));
+        editor.set_buffer(buf);
+        let grid = editor.render_grid();
+        assert!(!grid.cell(15, 1).unwrap().dim);
+        assert!(grid.row_text(22).contains("txt utf-8"));
+
+        editor.process_event(InputEvent::SetFiletype).unwrap();
+        type_text(&mut editor, "python");
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+
+        let grid = editor.render_grid();
+        assert_eq!(editor.buffer().filetype().as_deref(), Some("py"));
+        assert_eq!(grid.row_text(1).trim_end(), "  name = 1 # comment");
+        assert!(grid.cell(11, 1).unwrap().dim);
+        assert!(grid.row_text(22).contains("py utf-8 LF tabs:2"));
+        assert_eq!(editor.status_message, "Filetype py");
+    }
+
+    #[test]
+    fn aligning_should_pad_the_paragraph_once_and_keep_the_cursor_on_its_character() {
+        let mut editor = editor_with("x = 1\nlonger = 2\n\ny = 3");
+        editor.cursor.jump_to(Position(4, 0));
+        let lines = |editor: &Editor| -> Vec<String> {
+            let rows = editor.buffer.buffer().borrow();
+            rows.iter().map(|row| row.buffer().to_string()).collect()
+        };
+        let align = |editor: &mut Editor| {
+            editor.process_event(InputEvent::Align).unwrap();
+            type_text(editor, "=");
+            editor.process_event(InputEvent::InsertNewline).unwrap();
+        };
+
+        align(&mut editor);
+        assert_eq!(lines(&editor), vec!["x      = 1", "longer = 2", "", "y = 3"]);
+        assert_eq!((editor.cursor.x(), editor.cursor.y()), (9, 0));
+        assert_eq!(editor.status_message, "Aligned at =");
+
+        align(&mut editor);
+        assert_eq!(lines(&editor), vec!["x      = 1", "longer = 2", "", "y = 3"]);
+        assert_eq!(editor.status_message, "Nothing to align at =");
+
+        editor.process_event(InputEvent::Undo).unwrap();
+        assert_eq!(lines(&editor), vec!["x = 1", "longer = 2", "", "y = 3"]);
+    }
+
+    #[test]
+    fn scripts_without_an_extension_should_get_the_tab_width_of_their_interpreter() {
+        let mut editor = Editor::new(80, 22);
+        let mut buf = "#!/usr/bin/env python3\n\tpass".parse::<Buffer>().unwrap();
+        buf.set_filename("build");
+        editor.set_filetype_tab_stop("py", Some(4));
+
+        editor.set_buffer(buf);
+
+        assert_eq!(editor.buffer().filetype().as_deref(), Some("py"));
+        assert_eq!(editor.buffer().tab_stop(), 4);
+    }
+
+    #[cfg(feature = "links")]
+    #[test_case(false, 8 => Some("./tests/fixtures/indent.py".to_string()); "Path")]
+    #[test_case(true, 8 => Some("./tests/fixtures/indent.py".to_string()); "Soft wrap")]
+    #[test_case(false, 1 => None; "Not a link")]
+    fn ctrl_click_on_a_path_should_open_it(soft_wrap: bool, col: u16) -> Option<String> {
+        let mut editor = editor_with("see ./tests/fixtures/indent.py now");
+        editor.soft_wrap = soft_wrap;
+        editor.render_grid();
+
+        match editor.follow_link(col, 0).is_rejected() {
+            true => None,
+            false => editor.buffer().filename_str().map(String::from),
+        }
+    }
+
+    #[test]
+    fn changing_the_tab_width_should_keep_the_cursor_on_its_character() {
+        let mut editor = editor_with("\tx\n");
+        editor.cursor.right_by(1);
+        let grid = editor.render_grid();
+        assert_eq!(grid.row_text(0).trim_end(), "        x");
+        assert_eq!(grid.cursor(), Some((8, 0)));
+
+        editor.process_event(InputEvent::SetTabStop).unwrap();
+        type_text(&mut editor, "2");
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+
+        let grid = editor.render_grid();
+        assert_eq!(grid.row_text(0).trim_end(), "  x");
+        assert_eq!(grid.cursor(), Some((2, 0)));
+        assert!(grid.row_text(22).contains("tabs:2"));
+
+        editor.process_event(InputEvent::ChangeTabStop(-1)).unwrap();
+        assert_eq!(editor.render_grid().cursor(), Some((1, 0)));
+    }
+
+    #[test]
+    fn applying_a_config_tab_width_should_redraw_every_buffer_without_a_filetype_width() {
+        let mut editor = editor_with("\tx\n");
+        editor.add_buffer("\ty\n".parse().unwrap());
+        editor.cursor.right_by(1);
+        assert_eq!(editor.render_grid().cursor(), Some((8, 1)));
+
+        editor.apply_config(&"tab-width = 4\nline-numbers = on".parse().unwrap());
+
+        let grid = editor.render_grid();
+        assert_eq!(grid.row_text(1).trim_end(), "1     x");
+        assert_eq!(grid.cursor(), Some((6, 1)));
+        editor.process_event(InputEvent::NextBuffer).unwrap();
+        assert_eq!(editor.buffer().tab_stop(), 4);
+    }
+
+    #[test]
+    fn reopening_a_shortened_file_should_clamp_the_cursor() {
+        let dir = ScratchDir::new("session");
+        let path = dir.join("file.txt");
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let filename = path.to_str().unwrap();
+
+        let mut editor = Editor::new(80, 22);
+        editor.set_buffer(Buffer::open(filename).unwrap());
+        editor.cursor.goto_line(3);
+        editor.cursor.right_by(4);
+        editor.process_event(InputEvent::CloseBuffer).unwrap();
+
+        std::fs::write(&path, "one\n").unwrap();
+        editor.process_event(InputEvent::ReopenBuffer).unwrap();
+
+        assert_eq!((editor.cursor.x(), editor.cursor.y()), (3, 0));
+        assert_eq!(editor.status_message, "Cursor restored to 1:4 (adjusted)");
+    }
+
+    #[test]
+    fn open_prompt_should_list_completions_above_the_prompt_and_open_the_file() {
+        let mut editor = editor_with("scratch");
+        editor.process_event(InputEvent::OpenFile).unwrap();
+        for ch in "tests/fixtures/pr\t".chars() {
+            editor.process_event(InputEvent::InsertChar(ch)).unwrap();
+        }
+
+        let mut output = Vec::new();
+        editor.draw_rows(&mut output).unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("prose.txt"));
+
+        editor.process_event(InputEvent::InsertNewline).unwrap();
+        assert_eq!(editor.buffer().filename_str(), Some("tests/fixtures/prose.txt"));
+        assert_eq!(editor.buffer_count(), 2);
+    }
 }
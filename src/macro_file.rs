@@ -0,0 +1,206 @@
+//! Recorded macros saved under a name. With the `serde` feature each one is kept in its own
+//! file, `<name>.json`, holding a version and the events; without it named macros are gone
+//! once the editor quits.
+
+use std::{error::Error, fmt};
+
+#[cfg(feature = "serde")]
+use std::{fs, path::Path};
+
+#[cfg(feature = "serde")]
+use error_stack::{IntoReport, ResultExt};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "serde")]
+use crate::input::InputEvent;
+
+/// Bumped whenever `InputEvent` changes in a way that older files can't be read with.
+pub const MACRO_FILE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacroError {
+    InvalidName(String),
+    NotFound(String),
+    NothingRecorded,
+    UnsupportedVersion {
+        name: String,
+        version: u32,
+    },
+    /// The file isn't a macro or has events this version doesn't know
+    Unreadable {
+        name: String,
+        reason: String,
+    },
+    FailedToSave(String),
+}
+
+impl Error for MacroError {}
+
+impl fmt::Display for MacroError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(
+                f,
+                "Invalid macro name '{name}', use letters, digits, '-' and '_'"
+            ),
+            Self::NotFound(name) => write!(f, "No macro named {name}"),
+            Self::NothingRecorded => f.write_str("No macro recorded yet"),
+            Self::UnsupportedVersion { name, version } => write!(
+                f,
+                "Can't load macro {name}: version {version}, expected {MACRO_FILE_VERSION}"
+            ),
+            Self::Unreadable { name, reason } => write!(f, "Can't load macro {name}: {reason}"),
+            Self::FailedToSave(name) => write!(f, "Failed to save macro {name}"),
+        }
+    }
+}
+
+/// Names double as filenames, so they are kept to letters, digits, `-` and `_`.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '-' || ch == '_')
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct MacroFile {
+    version: u32,
+    events: Vec<InputEvent>,
+}
+
+/// Read before the events so a file of another version isn't reported as broken.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+#[cfg(feature = "serde")]
+pub fn encode(events: &[InputEvent]) -> String {
+    let file = MacroFile {
+        version: MACRO_FILE_VERSION,
+        events: events.to_vec(),
+    };
+
+    serde_json::to_string_pretty(&file).unwrap_or_default()
+}
+
+/// The events of the macro `name` stored as `text`.
+#[cfg(feature = "serde")]
+pub fn decode(name: &str, text: &str) -> Result<Vec<InputEvent>, MacroError> {
+    let unreadable = |err: serde_json::Error| {
+        // The list of every variant serde expected only buries the one it didn't
+        let reason = err.to_string();
+        let reason = reason.split(", expected").next().unwrap_or_default();
+        MacroError::Unreadable {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        }
+    };
+
+    let header = serde_json::from_str::<Header>(text).map_err(unreadable)?;
+    if header.version != MACRO_FILE_VERSION {
+        return Err(MacroError::UnsupportedVersion {
+            name: name.to_string(),
+            version: header.version,
+        });
+    }
+
+    serde_json::from_str::<MacroFile>(text)
+        .map(|file| file.events)
+        .map_err(unreadable)
+}
+
+/// Every macro in `dir` by name, along with the ones that couldn't be loaded. A missing
+/// directory has no macros.
+#[cfg(feature = "serde")]
+pub fn load_dir(dir: &Path) -> (Vec<(String, Vec<InputEvent>)>, Vec<MacroError>) {
+    let (mut macros, mut errors) = (Vec::new(), Vec::new());
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return (macros, errors),
+    };
+
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) if path.extension().is_some_and(|ext| ext == "json") => name,
+            _ => continue,
+        };
+
+        let result = fs::read_to_string(&path)
+            .map_err(|err| MacroError::Unreadable {
+                name: name.to_string(),
+                reason: err.to_string(),
+            })
+            .and_then(|text| decode(name, &text));
+        match result {
+            Ok(events) => macros.push((name.to_string(), events)),
+            Err(err) => errors.push(err),
+        }
+    }
+
+    macros.sort_by(|(a, _), (b, _)| a.cmp(b));
+    (macros, errors)
+}
+
+/// Write the macro `name` to `dir`, creating the directory if needed.
+#[cfg(feature = "serde")]
+pub fn save(dir: &Path, name: &str, events: &[InputEvent]) -> error_stack::Result<(), MacroError> {
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::write(dir.join(format!("{name}.json")), encode(events)))
+        .report()
+        .change_context_lazy(|| MacroError::FailedToSave(name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[cfg(feature = "serde")]
+    use crate::scratch::ScratchDir;
+
+    #[test_case("fix-imports" => true; "Dashes")]
+    #[test_case("wrap_2" => true; "Underscores and digits")]
+    #[test_case("" => false; "Empty")]
+    #[test_case("../up" => false; "Path")]
+    #[test_case("two words" => false; "Space")]
+    fn names_should_be_usable_as_filenames(name: &str) -> bool {
+        is_valid_name(name)
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn unknown_events_should_only_fail_their_macro() {
+        use crate::input::CursorEvent;
+
+        let dir = ScratchDir::new("macros");
+        let events = [
+            InputEvent::CursorEvent(CursorEvent::MoveEnd),
+            InputEvent::InsertChar(';'),
+        ];
+        save(&dir, "semicolon", &events).unwrap();
+        fs::write(
+            dir.join("future.json"),
+            r#"{"version":1,"events":["Teleport"]}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("newer.json"), r#"{"version":2,"events":[]}"#).unwrap();
+
+        let (macros, mut errors) = load_dir(&dir);
+
+        errors.sort_by_key(|err| err.to_string());
+        assert_eq!(macros, vec![("semicolon".to_string(), events.to_vec())]);
+        assert_eq!(
+            errors.iter().map(|err| err.to_string()).collect::<Vec<_>>(),
+            vec![
+                "Can't load macro future: unknown variant `Teleport`",
+                "Can't load macro newer: version 2, expected 1",
+            ]
+        );
+    }
+}
@@ -1,48 +1,165 @@
-use std::{env, io};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use async_std::channel::{self, TryRecvError};
-use crossterm::{
-    execute,
-    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
-};
 use error_stack::{IntoReport, ResultExt};
 
 use kilo_edit::{
-    buffer::{Buffer, BufferState},
+    bell::Bell,
+    capabilities::Capabilities,
+    buffer::Buffer,
+    config::Config,
     error::ApplicationError,
+    highlight::default_highlighters,
     input::{InputError, InputEvent, InputSystem},
-    Editor,
+    keymap::Keymap,
+    quit::QuitConfirm,
+    terminal::Terminal,
+    Editor, EditorEventError, QuitDecision,
 };
 
-const QUIT_TIMES: u8 = 3;
-
 fn main() -> error_stack::Result<(), ApplicationError> {
-    startup()
+    let capabilities = Capabilities::probe();
+    let mut terminal = Terminal::enter(capabilities)
         .report()
         .change_context(ApplicationError)
         .attach_printable("Failed to initialize screen")?;
 
-    let mut editor = setup_editor()
+    let mut editor = setup_editor(&terminal)
         .report()
         .change_context(ApplicationError)
         .attach_printable("Failed to initialize editor")?;
+    editor.set_capabilities(capabilities);
+    for (extension, highlighter) in default_highlighters() {
+        editor.set_highlighter(extension, highlighter);
+    }
 
     let (tx, rx) = channel::bounded(5);
 
-    let args = env::args().collect::<Vec<_>>();
-    if args.len() >= 2 {
-        let buf = Buffer::open(&args[1])
+    // Command line options come after the config and win over it
+    match load_config() {
+        Ok(config) => editor.apply_config(&config),
+        Err(err) => editor.set_status_message(format!("Config not loaded: {err}")),
+    }
+
+    let mut args = env::args().collect::<Vec<_>>();
+    if let Some(index) = args.iter().position(|arg| arg == "--debug-hud") {
+        args.remove(index);
+        editor.set_hud(true);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--save-age") {
+        args.remove(index);
+        editor.set_show_save_age(true);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--persistent-undo") {
+        args.remove(index);
+        editor.set_undo_dir(undo_dir());
+    }
+
+    #[cfg(feature = "serde")]
+    editor.set_macro_dir(state_dir().map(|dir| dir.join("macros")));
+
+    if let Some(index) = args.iter().position(|arg| arg.starts_with("--bell=")) {
+        let setting = args.remove(index);
+        let bell = setting["--bell=".len()..]
+            .parse::<Bell>()
+            .report()
+            .change_context(ApplicationError)?;
+        editor.set_bell(bell);
+    }
+
+    // Like `--quit-confirm=prompt`, `--quit-confirm=none` or `--quit-confirm=2`
+    if let Some(index) = args
+        .iter()
+        .position(|arg| arg.starts_with("--quit-confirm="))
+    {
+        let setting = args.remove(index);
+        let confirm = setting["--quit-confirm=".len()..]
+            .parse::<QuitConfirm>()
+            .report()
+            .change_context(ApplicationError)?;
+        editor.set_quit_confirm(confirm);
+    }
+
+    // Like `--fill-column=rs:100,md:80`
+    if let Some(index) = args
+        .iter()
+        .position(|arg| arg.starts_with("--fill-column="))
+    {
+        let setting = args.remove(index);
+        for entry in setting["--fill-column=".len()..].split(',') {
+            let fill_column = entry
+                .split_once(':')
+                .and_then(|(extension, column)| Some((extension, column.parse().ok()?)));
+            match fill_column {
+                Some((extension, column)) => editor.set_fill_column(extension, Some(column)),
+                None => {
+                    return Err(
+                        error_stack::report!(ApplicationError).attach_printable(format!(
+                            "Invalid fill column '{entry}', expected extension:column"
+                        )),
+                    )
+                }
+            }
+        }
+    }
+
+    // Like `--tab-width=go:8,py:4`
+    if let Some(index) = args.iter().position(|arg| arg.starts_with("--tab-width=")) {
+        let setting = args.remove(index);
+        for entry in setting["--tab-width=".len()..].split(',') {
+            let tab_stop = entry
+                .split_once(':')
+                .and_then(|(filetype, width)| Some((filetype, width.parse().ok()?)));
+            match tab_stop {
+                Some((filetype, width)) => editor.set_filetype_tab_stop(filetype, Some(width)),
+                None => {
+                    return Err(
+                        error_stack::report!(ApplicationError).attach_printable(format!(
+                            "Invalid tab width '{entry}', expected filetype:width"
+                        )),
+                    )
+                }
+            }
+        }
+    }
+
+    // The first file is shown, the rest are opened behind it
+    for (i, filename) in args.iter().enumerate().skip(1) {
+        let buf = Buffer::open(filename)
             .change_context(ApplicationError)
-            .attach_printable_lazy(|| format!("Unable to open the file: {}", args[1]))?;
-        editor.set_buffer(buf);
+            .attach_printable_lazy(|| format!("Unable to open the file: {}", filename))?;
+        if i == 1 {
+            editor.set_buffer(buf);
+        } else {
+            editor.add_buffer(buf);
+        }
     }
 
-    let input = InputSystem::new(tx);
+    let keymap = match load_keymap() {
+        Ok((keymap, shadowed)) => {
+            if !shadowed.is_empty() {
+                editor.set_status_message(format!("Keymap: {}", shadowed.join(", ")));
+            }
+            keymap
+        }
+        Err(err) => {
+            editor.set_status_message(format!("Keymap not loaded: {err}"));
+            Keymap::default()
+        }
+    };
+    let mut input = InputSystem::with_keymap(tx, keymap);
 
-    let mut quit_times = QUIT_TIMES;
     loop {
-        if let Err(e) = editor.refresh(&mut io::stdout()) {
-            cleanup()
+        editor.update_diff_signs();
+        if let Err(e) = editor.refresh(&mut terminal) {
+            terminal
+                .leave()
                 .report()
                 .change_context(ApplicationError)
                 .attach_printable("Failed to do terminal cleanup")?;
@@ -51,7 +168,19 @@ fn main() -> error_stack::Result<(), ApplicationError> {
                 .attach_printable(format!("Unable to refresh screen: {}", e)));
         }
 
-        let res = input.process_key();
+        // Only block on input when the editor has nothing scheduled to redraw
+        let ready = match editor.deadline() {
+            Some(deadline) => input.poll(deadline.saturating_duration_since(Instant::now())),
+            None => Ok(true),
+        };
+        let res = ready.and_then(|ready| {
+            if ready {
+                input.process_key()
+            } else {
+                editor.idle();
+                Ok(())
+            }
+        });
         if let Err(err) = res {
             match *err.current_context() {
                 InputError::ReadFailure => {
@@ -78,33 +207,44 @@ fn main() -> error_stack::Result<(), ApplicationError> {
             }
         }
 
-        match rx.try_recv() {
+        let handled = match rx.try_recv() {
             Ok(InputEvent::Quit) => {
-                if editor.buffer().state() == BufferState::Modified && quit_times > 0 {
-                    editor.set_status_message(format!("⚠Warning!!⚠, File has unsaved changes. Press Ctrl-Q {quit_times} more times to quit."));
-                    quit_times -= 1;
-                    continue;
-                } else {
-                    break;
+                // Keys typed right after the quit, like a last Ctrl-S, are handled first
+                if let Err(err) = input.process_pending() {
+                    let _ = terminal.leave();
+                    return Err(err).change_context(ApplicationError);
                 }
-            }
-            Ok(event) => {
-                if let Err(rep) = editor.process_event(event) {
-                    let _ = cleanup();
-                    eprintln!("An error occurred when processing the event, Quitting");
-                    return Err(rep).change_context(ApplicationError);
+                let queued = std::iter::from_fn(|| rx.try_recv().ok());
+                let handle = |editor: &mut Editor, event| handle_event(editor, &mut input, event);
+                match editor.quit_after(queued, handle) {
+                    Ok(QuitDecision::Quit) => {
+                        editor.save_undo_files();
+                        break;
+                    }
+                    Ok(QuitDecision::Stay) => Ok(()),
+                    Err(rep) => Err(rep),
                 }
             }
+            Ok(event) => handle_event(&mut editor, &mut input, event),
             Err(TryRecvError::Closed) => {
                 eprintln!("InputSystem closed unexpectedly, Quitting");
                 break;
             }
-            _ => {}
+            _ => Ok(()),
+        };
+        if let Err(rep) = handled {
+            let _ = terminal.leave();
+            eprintln!("An error occurred when processing the event, Quitting");
+            return Err(rep).change_context(ApplicationError);
+        }
+        if editor.is_quit_confirmed() {
+            editor.save_undo_files();
+            break;
         }
-        quit_times = QUIT_TIMES;
     }
 
-    cleanup()
+    terminal
+        .leave()
         .report()
         .change_context(ApplicationError)
         .attach_printable("Failed to do terminal cleanup")?;
@@ -112,22 +252,105 @@ fn main() -> error_stack::Result<(), ApplicationError> {
     Ok(())
 }
 
-fn cleanup() -> crossterm::Result<()> {
-    let err1 = execute!(io::stdout(), LeaveAlternateScreen);
-    let err2 = terminal::disable_raw_mode();
+/// Pass `event` on to the editor, taking care of the events about the terminal and the
+/// configuration here.
+fn handle_event(
+    editor: &mut Editor,
+    input: &mut InputSystem,
+    event: InputEvent,
+) -> error_stack::Result<(), EditorEventError> {
+    match event {
+        // The status and message bars take two rows
+        InputEvent::Resize(cols, rows) => editor.resize(cols, rows.saturating_sub(2)),
+        InputEvent::ReloadConfig => {
+            let config = load_config();
+            if let Ok(config) = &config {
+                editor.apply_config(config);
+            }
+
+            let message = match (config, load_keymap()) {
+                (Ok(_), Ok((keymap, shadowed))) => {
+                    input.set_keymap(keymap);
+                    let mut message = String::from(
+                        "Reloaded the config and keymap, command line options only apply at \
+                         startup",
+                    );
+                    if !shadowed.is_empty() {
+                        message = format!("{message}. Keymap: {}", shadowed.join(", "));
+                    }
+                    message
+                }
+                (Err(err), Ok((keymap, _))) => {
+                    input.set_keymap(keymap);
+                    format!("Config not reloaded, keeping the current one: {err}")
+                }
+                (_, Err(err)) => format!("Keymap not reloaded, keeping the current one: {err}"),
+            };
+            editor.set_status_message(message);
+        }
+        event => return editor.process_event(event),
+    }
+
+    Ok(())
+}
+
+/// The default keymap with the user's bindings from `~/.config/kilo-edit/keymap` on top,
+/// along with the default bindings they shadow. Fails with the message to show when the
+/// user's keymap can't be loaded.
+fn load_keymap() -> Result<(Keymap, Vec<String>), String> {
+    let mut keymap = Keymap::default();
+    let path = match env::var_os("HOME") {
+        Some(home) => Path::new(&home).join(".config/kilo-edit/keymap"),
+        None => return Ok((keymap, Vec::new())),
+    };
+
+    if !path.exists() {
+        return Ok((keymap, Vec::new()));
+    }
+
+    let user = Keymap::load(&path).map_err(|err| err.current_context().to_string())?;
+    let shadowed = keymap
+        .merge(user)
+        .iter()
+        .map(|binding| binding.to_string())
+        .collect();
+
+    Ok((keymap, shadowed))
+}
+
+/// The settings in `~/.config/kilo-edit/config`, none when there is no such file. Fails
+/// with the message to show when it can't be loaded.
+fn load_config() -> Result<Config, String> {
+    let path = match env::var_os("HOME") {
+        Some(home) => Path::new(&home).join(".config/kilo-edit/config"),
+        None => return Ok(Config::default()),
+    };
+
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    Config::load(&path).map_err(|err| err.current_context().to_string())
+}
+
+/// `$XDG_STATE_HOME/kilo-edit`, where state defaults to `~/.local/state`
+fn state_dir() -> Option<PathBuf> {
+    let state = match env::var_os("XDG_STATE_HOME") {
+        Some(state) if !state.is_empty() => PathBuf::from(state),
+        _ => Path::new(&env::var_os("HOME")?).join(".local/state"),
+    };
 
-    err1.and(err2)
+    Some(state.join("kilo-edit"))
 }
 
-fn startup() -> crossterm::Result<()> {
-    terminal::enable_raw_mode()?;
-    execute!(io::stdout(), EnterAlternateScreen)
+fn undo_dir() -> Option<PathBuf> {
+    Some(state_dir()?.join("undo"))
 }
 
-fn setup_editor() -> crossterm::Result<Editor> {
-    let (cols, rows) = terminal::size()?;
+fn setup_editor(terminal: &Terminal) -> crossterm::Result<Editor> {
+    let (cols, rows) = terminal.size()?;
 
-    let mut editor = Editor::new(cols, rows - 2);
+    let mut editor = Editor::new(cols, rows.saturating_sub(2));
     editor.set_status_message("HELP: Ctrl-S = Save | Ctrl-Q = quit");
 
     Ok(editor)
@@ -0,0 +1,154 @@
+use std::{
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    annotation::Severity,
+    component::{draw_lines, Component, EventOutcome, Focus},
+    input::{CursorEvent, InputEvent},
+};
+
+/// A status message as it was shown, with when and how serious it was.
+pub type LoggedMessage = (String, SystemTime, Severity);
+
+/// A read-only list of the status messages shown so far, newest first, scrolled a line at
+/// a time.
+#[derive(Debug, Clone, Default)]
+pub struct MessageLog {
+    lines: Vec<String>,
+    offset: usize,
+}
+
+impl MessageLog {
+    /// List `messages`, given oldest first.
+    pub fn new<'a, I>(messages: I) -> Self
+    where
+        I: DoubleEndedIterator<Item = &'a LoggedMessage>,
+    {
+        let lines = messages
+            .rev()
+            .map(|(message, time, severity)| {
+                let severity = severity.to_string();
+                format!("{} {severity:<7} {message}", clock_time(*time))
+            })
+            .collect();
+
+        Self { lines, offset: 0 }
+    }
+
+    /// The first line shown
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// One line per message like `14:03:27 error   Can't save file`.
+    pub fn lines(&self) -> Vec<String> {
+        match self.lines.is_empty() {
+            true => vec![String::from("No messages")],
+            false => self.lines.clone(),
+        }
+    }
+}
+
+/// The time of day of `time` in UTC, like `14:03:27`.
+fn clock_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() % (24 * 60 * 60));
+
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+impl Component for MessageLog {
+    fn handle_event(&mut self, event: InputEvent) -> EventOutcome {
+        let last = self.lines.len().saturating_sub(1);
+        match event {
+            InputEvent::CursorEvent(CursorEvent::MoveUp) => {
+                self.offset = self.offset.saturating_sub(1)
+            }
+            InputEvent::CursorEvent(CursorEvent::MoveDown) => {
+                self.offset = (self.offset + 1).min(last)
+            }
+            InputEvent::CursorEvent(CursorEvent::MoveTop) => self.offset = 0,
+            InputEvent::CursorEvent(CursorEvent::MoveBottom) => self.offset = last,
+            InputEvent::InsertNewline | InputEvent::Cancel => {
+                return EventOutcome::consumed().with_focus(Focus::Buffer)
+            }
+            _ => return EventOutcome::ignored(),
+        }
+
+        EventOutcome::consumed()
+    }
+
+    fn draw(&self, writer: &mut dyn Write, cols: u16, rows: u16) -> io::Result<()> {
+        draw_lines(writer, &self.lines(), self.offset, cols, rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn at(seconds: u64, message: &str, severity: Severity) -> LoggedMessage {
+        let time = UNIX_EPOCH + Duration::from_secs(seconds);
+        (message.to_string(), time, severity)
+    }
+
+    #[test]
+    fn messages_should_be_listed_newest_first_with_the_time() {
+        let messages = [
+            at(3_600, "Saved a.txt", Severity::Info),
+            at(86_400 + 3_723, "Can't save file b.txt", Severity::Error),
+        ];
+
+        let log = MessageLog::new(messages.iter());
+
+        assert_eq!(
+            log.lines(),
+            vec![
+                "01:02:03 error   Can't save file b.txt",
+                "01:00:00 info    Saved a.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn scrolling_should_stay_within_the_messages() {
+        let messages = [at(0, "one", Severity::Info), at(1, "two", Severity::Info)];
+        let mut log = MessageLog::new(messages.iter());
+
+        log.handle_event(InputEvent::CursorEvent(CursorEvent::MoveUp));
+        assert_eq!(log.offset(), 0);
+
+        for _ in 0..3 {
+            log.handle_event(InputEvent::CursorEvent(CursorEvent::MoveDown));
+        }
+        assert_eq!(log.offset(), 1);
+
+        let outcome = log.handle_event(InputEvent::Cancel);
+        assert_eq!(outcome.focus(), Some(Focus::Buffer));
+    }
+
+    #[test]
+    fn drawing_should_start_at_the_scrolled_line() {
+        let messages = [at(0, "one", Severity::Info), at(1, "two", Severity::Info)];
+        let mut log = MessageLog::new(messages.iter());
+        log.handle_event(InputEvent::CursorEvent(CursorEvent::MoveDown));
+
+        let mut output = Vec::new();
+        log.draw(&mut output, 16, 2).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("00:00:00 info   "));
+        assert!(!output.contains("two"));
+        assert_eq!(output.matches("\r\n").count(), 2);
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    component::{Area, Component, EventOutcome, Focus},
+    input::{CursorEvent, InputEvent},
+    text::{char_index, ConsoleWidthStr},
+};
+
+/// Determines what the editor does with the input once the prompt is submitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    SaveAs,
+    Search,
+    Open,
+    /// Input of the form `old/new`
+    Replace,
+    SaveMacro,
+    /// Input of the form `name [count]`
+    RunMacro,
+    /// A line number, `$`, `%`, `+N` or `-N`
+    GotoLine,
+    TabStop,
+    /// The name of a filetype, like `py` or `python`
+    Filetype,
+    /// The delimiter to line up the paragraph at
+    Align,
+}
+
+/// The most entries listed when completing
+const MAX_COMPLETIONS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PromptState {
+    #[default]
+    Editing,
+    Submitted,
+    Cancelled,
+}
+
+/// A single line of input read in the message bar.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    kind: PromptKind,
+    label: String,
+    input: String,
+    state: PromptState,
+    completions: Option<Completions>,
+    /// Everything the input may be completed to when it isn't a path
+    choices: Vec<String>,
+    /// Earlier input that Up and Down go through, oldest first
+    history: Vec<String>,
+    /// The entry of `history` shown, `None` while showing what was typed
+    history_index: Option<usize>,
+    /// What was typed before going through the history
+    draft: String,
+}
+
+/// Entries of the directory typed so far, or choices starting with the input, that Tab
+/// cycles through.
+#[derive(Debug, Clone)]
+struct Completions {
+    /// The input up to and including the last `/`, empty for choices
+    base: String,
+    entries: Vec<String>,
+    selected: Option<usize>,
+}
+
+impl Prompt {
+    pub fn new<T: Into<String>>(kind: PromptKind, label: T) -> Self {
+        Self {
+            kind,
+            label: label.into(),
+            input: String::new(),
+            state: PromptState::default(),
+            completions: None,
+            choices: Vec::new(),
+            history: Vec::new(),
+            history_index: None,
+            draft: String::new(),
+        }
+    }
+
+    /// Let Up and Down fill in the `entries`, given oldest first.
+    pub fn with_history<'a, I: IntoIterator<Item = &'a str>>(mut self, entries: I) -> Self {
+        self.history = entries.into_iter().map(str::to_string).collect();
+        self
+    }
+
+    /// Let Tab complete the input to one of `choices`, which are listed before anything is
+    /// typed.
+    pub fn with_choices<I: IntoIterator<Item = String>>(mut self, choices: I) -> Self {
+        self.choices = choices.into_iter().collect();
+        self.update_completions();
+        self
+    }
+
+    pub fn kind(&self) -> PromptKind {
+        self.kind
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn state(&self) -> PromptState {
+        self.state
+    }
+
+    /// The column of the cursor counted from the start of the message bar
+    pub fn cursor(&self) -> usize {
+        self.label.column_width() + self.input.column_width()
+    }
+
+    /// Whether the input is a path that can be completed from the file system
+    pub fn takes_paths(&self) -> bool {
+        matches!(self.kind, PromptKind::SaveAs | PromptKind::Open)
+    }
+
+    /// Whether Tab completes the input, from the file system or the choices
+    pub fn completes(&self) -> bool {
+        self.takes_paths() || !self.choices.is_empty()
+    }
+
+    /// Entries of the directory in the input matching what follows the last `/`, directories
+    /// first. Empty unless the input names an existing directory. For prompts with choices,
+    /// the ones starting with the input.
+    pub fn completions(&self) -> &[String] {
+        self.completions
+            .as_ref()
+            .map(|completions| completions.entries.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn selected_completion(&self) -> Option<usize> {
+        self.completions.as_ref().and_then(|completions| completions.selected)
+    }
+
+    fn update_completions(&mut self) {
+        self.completions = match self.input.rfind('/') {
+            Some(index) if self.takes_paths() => {
+                let (base, prefix) = self.input.split_at(index + 1);
+                list_completions(Path::new(base), prefix)
+                    .ok()
+                    .map(|entries| Completions {
+                        base: base.to_string(),
+                        entries,
+                        selected: None,
+                    })
+            }
+            _ if !self.choices.is_empty() => Some(Completions {
+                base: String::new(),
+                entries: self
+                    .choices
+                    .iter()
+                    .filter(|choice| choice.starts_with(&self.input))
+                    .take(MAX_COMPLETIONS)
+                    .cloned()
+                    .collect(),
+                selected: None,
+            }),
+            _ => None,
+        };
+    }
+
+    /// Fill in the entry before or after the one shown, going back to what was typed after
+    /// the newest. Returns false when there is no entry in that direction.
+    fn recall(&mut self, older: bool) -> bool {
+        let index = match (self.history_index, older) {
+            (None, true) if !self.history.is_empty() => {
+                self.draft = std::mem::take(&mut self.input);
+                Some(self.history.len() - 1)
+            }
+            (Some(i), true) if i > 0 => Some(i - 1),
+            (Some(i), false) if i + 1 < self.history.len() => Some(i + 1),
+            (Some(_), false) => None,
+            _ => return false,
+        };
+
+        self.history_index = index;
+        self.input = match index {
+            Some(i) => self.history[i].clone(),
+            None => std::mem::take(&mut self.draft),
+        };
+        true
+    }
+
+    /// Fill in the next completion, returns false when there is nothing to complete.
+    fn complete_next(&mut self) -> bool {
+        let completions = match self.completions.as_mut() {
+            Some(completions) if !completions.entries.is_empty() => completions,
+            _ => return false,
+        };
+
+        let next = completions
+            .selected
+            .map(|i| (i + 1) % completions.entries.len())
+            .unwrap_or(0);
+        completions.selected = Some(next);
+        self.input = format!("{}{}", completions.base, completions.entries[next]);
+        true
+    }
+}
+
+/// Names in `dir` starting with `prefix`, directories first and marked with a trailing `/`.
+fn list_completions(dir: &Path, prefix: &str) -> io::Result<Vec<String>> {
+    let mut entries = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let is_dir = entry.file_type().ok()?.is_dir();
+            name.starts_with(prefix).then_some((!is_dir, name))
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    Ok(entries
+        .into_iter()
+        .take(MAX_COMPLETIONS)
+        .map(|(is_file, name)| if is_file { name } else { format!("{name}/") })
+        .collect())
+}
+
+impl Component for Prompt {
+    fn handle_event(&mut self, event: InputEvent) -> EventOutcome {
+        match event {
+            InputEvent::InsertChar('\t') if self.completes() => {
+                if self.complete_next() {
+                    EventOutcome::consumed()
+                } else {
+                    EventOutcome::rejected()
+                }
+            }
+            InputEvent::InsertChar(ch) | InputEvent::InsertLiteral(ch) => {
+                self.input.push(ch);
+                self.update_completions();
+                EventOutcome::consumed()
+            }
+            InputEvent::DeletePreviousChar => {
+                self.input.pop();
+                self.update_completions();
+                EventOutcome::consumed()
+            }
+            InputEvent::CursorEvent(event @ (CursorEvent::MoveUp | CursorEvent::MoveDown))
+                if !self.history.is_empty() =>
+            {
+                if self.recall(event == CursorEvent::MoveUp) {
+                    EventOutcome::consumed()
+                } else {
+                    EventOutcome::rejected()
+                }
+            }
+            InputEvent::InsertNewline if !self.input.is_empty() => {
+                self.state = PromptState::Submitted;
+                EventOutcome::consumed().with_focus(Focus::Buffer)
+            }
+            InputEvent::Cancel => {
+                self.state = PromptState::Cancelled;
+                EventOutcome::consumed().with_focus(Focus::Buffer)
+            }
+            _ => EventOutcome::ignored(),
+        }
+    }
+
+    fn draw(&self, writer: &mut dyn Write, cols: u16, _rows: u16) -> io::Result<()> {
+        let text = format!("{}{}", self.label, self.input);
+        write!(writer, "{}", &text[..char_index(cols as usize, &text)])
+    }
+
+    fn area(&self) -> Area {
+        Area::MessageBar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_input(prompt: &mut Prompt, input: &str) {
+        for ch in input.chars() {
+            prompt.handle_event(InputEvent::InsertChar(ch));
+        }
+    }
+
+    #[test]
+    fn typing_a_directory_should_list_its_entries_directories_first() {
+        let mut prompt = Prompt::new(PromptKind::Open, "Open: ");
+
+        type_input(&mut prompt, "tests/");
+
+        let completions = prompt.completions();
+        let files = completions.iter().position(|name| !name.ends_with('/'));
+        let (dirs, files) = completions.split_at(files.unwrap_or(completions.len()));
+        assert!(!dirs.is_empty() && !files.is_empty());
+        assert!(files.iter().all(|name| !name.ends_with('/')));
+        assert!(dirs.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(files.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let entries = fs::read_dir("tests").unwrap().count();
+        assert_eq!(completions.len(), entries.min(MAX_COMPLETIONS));
+    }
+
+    #[test]
+    fn tab_should_cycle_through_matching_entries() {
+        let mut prompt = Prompt::new(PromptKind::Open, "Open: ");
+        type_input(&mut prompt, "tests/sess");
+
+        prompt.handle_event(InputEvent::InsertChar('\t'));
+        assert_eq!(prompt.input(), "tests/sessions/");
+
+        prompt.handle_event(InputEvent::InsertChar('\t'));
+        prompt.handle_event(InputEvent::InsertChar('\t'));
+        assert_eq!(prompt.input(), "tests/sessions/");
+        assert_eq!(prompt.selected_completion(), Some(0));
+    }
+
+    #[test]
+    fn tab_should_complete_to_the_choices_starting_with_the_input() {
+        let choices = ["c", "cpp", "py"].map(String::from);
+        let mut prompt = Prompt::new(PromptKind::Filetype, "Filetype: ").with_choices(choices);
+        assert_eq!(prompt.completions(), ["c", "cpp", "py"]);
+
+        type_input(&mut prompt, "c");
+        assert_eq!(prompt.completions(), ["c", "cpp"]);
+
+        prompt.handle_event(InputEvent::InsertChar('\t'));
+        prompt.handle_event(InputEvent::InsertChar('\t'));
+        assert_eq!(prompt.input(), "cpp");
+    }
+
+    #[test]
+    fn up_and_down_should_go_through_the_history_and_back_to_the_input() {
+        let mut prompt =
+            Prompt::new(PromptKind::Search, "Search: ").with_history(["first", "second"]);
+        type_input(&mut prompt, "typed");
+        let (up, down) = (
+            InputEvent::CursorEvent(CursorEvent::MoveUp),
+            InputEvent::CursorEvent(CursorEvent::MoveDown),
+        );
+
+        prompt.handle_event(up);
+        assert_eq!(prompt.input(), "second");
+        prompt.handle_event(up);
+        assert_eq!(prompt.input(), "first");
+        assert!(prompt.handle_event(up).is_rejected());
+        assert_eq!(prompt.input(), "first");
+
+        prompt.handle_event(down);
+        assert_eq!(prompt.input(), "second");
+        prompt.handle_event(down);
+        assert_eq!(prompt.input(), "typed");
+        assert!(prompt.handle_event(down).is_rejected());
+    }
+
+    #[test]
+    fn search_prompts_should_not_complete_paths() {
+        let mut prompt = Prompt::new(PromptKind::Search, "Search: ");
+
+        type_input(&mut prompt, "tests/\t");
+
+        assert!(prompt.completions().is_empty());
+        assert_eq!(prompt.input(), "tests/\t");
+    }
+}
@@ -0,0 +1,70 @@
+use std::{error::Error, fmt, str::FromStr};
+
+/// What it takes to quit while the buffer has unsaved changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuitConfirm {
+    /// Quit right away
+    None,
+    /// Press Ctrl-Q this many times in a row, being warned before each but the last
+    Count(u8),
+    /// Answer `y` to a question
+    Prompt,
+}
+
+impl QuitConfirm {
+    /// How many times Ctrl-Q is refused with a warning before it quits
+    pub fn warnings(&self) -> u8 {
+        match self {
+            Self::Count(presses) => presses.saturating_sub(1),
+            Self::None | Self::Prompt => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseQuitConfirmError(String);
+
+impl Error for ParseQuitConfirmError {}
+
+impl fmt::Display for ParseQuitConfirmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown quit confirmation '{}', expected none, prompt or a number of presses",
+            self.0
+        )
+    }
+}
+
+impl FromStr for QuitConfirm {
+    type Err = ParseQuitConfirmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "prompt" => Ok(Self::Prompt),
+            _ => match s.parse() {
+                Ok(presses) if presses > 0 => Ok(Self::Count(presses)),
+                _ => Err(ParseQuitConfirmError(s.to_string())),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case("none" => Ok(QuitConfirm::None))]
+    #[test_case("prompt" => Ok(QuitConfirm::Prompt))]
+    #[test_case("2" => Ok(QuitConfirm::Count(2)))]
+    #[test_case("0" => Err(ParseQuitConfirmError(String::from("0"))))]
+    #[test_case("twice" => Err(ParseQuitConfirmError(String::from("twice"))))]
+    fn quit_confirm_should_parse_from_its_setting(
+        setting: &str,
+    ) -> Result<QuitConfirm, ParseQuitConfirmError> {
+        setting.parse()
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{text::ConsoleWidthStr, Position};
+
+/// What to do with the match the cursor is on while replacing interactively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplaceAnswer {
+    /// Replace this match and go to the next
+    Yes,
+    /// Skip this match
+    No,
+    /// Replace this and every remaining match
+    All,
+    /// Stop without touching the remaining matches
+    Quit,
+}
+
+impl ReplaceAnswer {
+    pub fn from_char(ch: char) -> Option<Self> {
+        match ch {
+            'y' => Some(Self::Yes),
+            'n' => Some(Self::No),
+            'a' => Some(Self::All),
+            'q' => Some(Self::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Steps through the matches of a pattern asking whether to replace each one. Matches later
+/// on a row are moved along as earlier ones are replaced with text of another width.
+#[derive(Debug, Clone)]
+pub struct ReplaceState {
+    find: String,
+    replacement: String,
+    matches: Vec<Position>,
+    next: usize,
+    replaced: usize,
+}
+
+impl ReplaceState {
+    pub fn new<T: Into<String>, U: Into<String>>(
+        find: T,
+        replacement: U,
+        matches: Vec<Position>,
+    ) -> Self {
+        Self {
+            find: find.into(),
+            replacement: replacement.into(),
+            matches,
+            next: 0,
+            replaced: 0,
+        }
+    }
+
+    pub fn find(&self) -> &str {
+        &self.find
+    }
+
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+
+    /// The match waiting for an answer, `None` once every match was answered
+    pub fn current(&self) -> Option<Position> {
+        self.matches.get(self.next).copied()
+    }
+
+    /// The zero based index of the current match
+    pub fn index(&self) -> usize {
+        self.next
+    }
+
+    pub fn total(&self) -> usize {
+        self.matches.len()
+    }
+
+    pub fn replaced(&self) -> usize {
+        self.replaced
+    }
+
+    /// Take the answer for the current match and return the matches to replace now, in the
+    /// order they have to be replaced for the positions to stay valid.
+    pub fn answer(&mut self, answer: ReplaceAnswer) -> Vec<Position> {
+        let end = match answer {
+            ReplaceAnswer::Yes | ReplaceAnswer::No => self.next + 1,
+            ReplaceAnswer::All | ReplaceAnswer::Quit => self.matches.len(),
+        };
+        let end = end.min(self.matches.len());
+
+        let mut to_replace = Vec::new();
+        if matches!(answer, ReplaceAnswer::Yes | ReplaceAnswer::All) {
+            for i in self.next..end {
+                to_replace.push(self.matches[i]);
+                self.shift_after(i);
+            }
+        }
+
+        self.replaced += to_replace.len();
+        self.next = end;
+        to_replace
+    }
+
+    fn shift_after(&mut self, i: usize) {
+        let delta = self.replacement.column_width() as i64 - self.find.column_width() as i64;
+        let row = self.matches[i].1;
+
+        for later in self.matches[i + 1..].iter_mut().filter(|pos| pos.1 == row) {
+            later.0 = (later.0 as i64 + delta) as u16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case("yny" => vec![(0, 0), (4, 1)]; "Yes and no")]
+    #[test_case("na" => vec![(4, 0), (4, 1), (6, 1)]; "All after skipping one")]
+    #[test_case("yq" => vec![(0, 0)]; "Quit early")]
+    fn answers_should_pick_the_matches_to_replace(answers: &str) -> Vec<(u16, u16)> {
+        // Replacing "foo" with "x" in "foo foo\nbar foo foo", later matches on a row move
+        // two columns left for every replacement before them
+        let matches = vec![
+            Position(0, 0),
+            Position(4, 0),
+            Position(4, 1),
+            Position(8, 1),
+        ];
+        let mut state = ReplaceState::new("foo", "x", matches);
+
+        answers
+            .chars()
+            .flat_map(|ch| state.answer(ReplaceAnswer::from_char(ch).unwrap()))
+            .map(|pos| (pos.0, pos.1))
+            .collect()
+    }
+
+    #[test]
+    fn answering_every_match_should_finish() {
+        let mut state = ReplaceState::new("a", "b", vec![Position(0, 0), Position(0, 1)]);
+
+        state.answer(ReplaceAnswer::Yes);
+        assert_eq!(state.current().map(|pos| (pos.0, pos.1)), Some((0, 1)));
+
+        state.answer(ReplaceAnswer::No);
+        assert_eq!((state.current().is_none(), state.replaced()), (true, 1));
+    }
+}
@@ -0,0 +1,255 @@
+//! A line delimited JSON protocol for driving the editor from scripts and tests.
+//!
+//! Every line read is one command object and gets exactly one response line:
+//!
+//! ```text
+//! {"cmd":"insert","text":"x"}  ->  {"ok":true}
+//! {"cmd":"goto","line":5}      ->  {"ok":true}
+//! {"cmd":"save"}               ->  {"ok":true}
+//! {"cmd":"get_content"}        ->  {"content":"x","ok":true}
+//! {"cmd":"jump"}               ->  {"error":"Unknown command 'jump'","ok":false}
+//! ```
+//!
+//! Commands go through `Editor::process_event` like keystrokes do. Members a command doesn't
+//! use are ignored.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{BufRead, Write},
+};
+
+use error_stack::{IntoReport, Result, ResultExt};
+use serde_json::{json, Value};
+
+use crate::{
+    buffer::BufferState,
+    input::{CursorEvent, InputEvent},
+    Editor,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcError {
+    ReadFailure,
+    WriteFailure,
+    EditorFailure,
+}
+
+impl Error for RpcError {}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReadFailure => f.write_str("Failed to read a command"),
+            Self::WriteFailure => f.write_str("Failed to write a response"),
+            Self::EditorFailure => f.write_str("The editor failed to process a command"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Insert(String),
+    Goto(i64),
+    Save,
+    GetContent,
+}
+
+impl Command {
+    fn parse(line: &str) -> std::result::Result<Self, String> {
+        let object = match serde_json::from_str(line) {
+            Ok(Value::Object(object)) => object,
+            _ => return Err(String::from("Expected a JSON object")),
+        };
+        let text = object.get("text").and_then(Value::as_str);
+        let line = object.get("line").and_then(Value::as_i64);
+
+        match object.get("cmd").and_then(Value::as_str) {
+            Some(cmd) => match (cmd, text, line) {
+                ("insert", Some(text), _) => Ok(Self::Insert(text.to_string())),
+                ("goto", _, Some(line)) => Ok(Self::Goto(line)),
+                ("save", ..) => Ok(Self::Save),
+                ("get_content", ..) => Ok(Self::GetContent),
+                ("insert", ..) => Err(String::from("insert needs a string 'text'")),
+                ("goto", ..) => Err(String::from("goto needs a number 'line'")),
+                _ => Err(format!("Unknown command '{cmd}'")),
+            },
+            None => Err(String::from("Missing string 'cmd'")),
+        }
+    }
+}
+
+/// Answer every command read from `reader` until it ends, writing the responses to `writer`.
+pub fn serve<R: BufRead, W: Write>(
+    editor: &mut Editor,
+    reader: R,
+    mut writer: W,
+) -> Result<(), RpcError> {
+    for line in reader.lines() {
+        let line = line.report().change_context(RpcError::ReadFailure)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match Command::parse(&line) {
+            Ok(command) => execute(editor, command)?,
+            Err(message) => error_response(&message),
+        };
+
+        writeln!(writer, "{response}")
+            .and_then(|_| writer.flush())
+            .report()
+            .change_context(RpcError::WriteFailure)?;
+    }
+
+    Ok(())
+}
+
+fn execute(editor: &mut Editor, command: Command) -> Result<Value, RpcError> {
+    let saving = command == Command::Save;
+    let events = match command {
+        Command::Insert(text) => text
+            .chars()
+            .map(|ch| match ch {
+                '\n' => InputEvent::InsertNewline,
+                ch => InputEvent::InsertChar(ch),
+            })
+            .collect(),
+        // The same as typing the line number as a count before going to the end of the file
+        Command::Goto(line) => line
+            .clamp(1, u16::MAX as i64)
+            .to_string()
+            .bytes()
+            .map(|digit| InputEvent::Count(digit - b'0'))
+            .chain(Some(InputEvent::CursorEvent(CursorEvent::MoveFileEnd)))
+            .collect(),
+        Command::Save if editor.buffer().filename_str().is_none() => {
+            return Ok(error_response("No filename was given"));
+        }
+        Command::Save => vec![InputEvent::SaveBuffer],
+        Command::GetContent => {
+            let rows = editor.buffer().buffer().borrow();
+            let content = rows
+                .iter()
+                .map(|row| row.buffer())
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Ok(json!({ "ok": true, "content": content }));
+        }
+    };
+
+    for event in events {
+        editor
+            .process_event(event)
+            .change_context(RpcError::EditorFailure)?;
+    }
+
+    if saving && editor.buffer().state() == BufferState::Modified {
+        return Ok(error_response("Can't save the file"));
+    }
+
+    Ok(json!({ "ok": true }))
+}
+
+fn error_response(message: &str) -> Value {
+    json!({ "ok": false, "error": message })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, io::Cursor};
+
+    use crate::scratch::ScratchDir;
+
+    fn run(editor: &mut Editor, commands: &str) -> Vec<Value> {
+        let mut output = Vec::new();
+        serve(editor, Cursor::new(commands), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn commands_should_edit_the_buffer_and_queries_return_it() {
+        let mut editor = Editor::new(80, 22);
+        editor.set_buffer("one\ntwo\nthree".parse().unwrap());
+
+        let responses = run(
+            &mut editor,
+            r#"{"cmd":"goto","line":2}
+{"cmd": "insert", "text": "\"é\u00e9\""}
+{"cmd":"get_content"}
+"#,
+        );
+
+        assert_eq!(
+            responses,
+            [
+                json!({ "ok": true }),
+                json!({ "ok": true }),
+                json!({ "ok": true, "content": "one\n\"éé\"two\nthree" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn surrogate_pairs_should_insert_a_single_character() {
+        let mut editor = Editor::new(80, 22);
+
+        let responses = run(
+            &mut editor,
+            r#"{"cmd":"insert","text":"\ud83d\ude00","note":"\b\f","line":-1,"force":false,"id":null}
+{"cmd":"get_content"}
+"#,
+        );
+
+        assert_eq!(
+            responses,
+            [
+                json!({ "ok": true }),
+                json!({ "ok": true, "content": "\u{1f600}" })
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_commands_should_get_an_error_response_and_not_stop_serving() {
+        let mut editor = Editor::new(80, 22);
+
+        let responses = run(
+            &mut editor,
+            "{\"cmd\":\"jump\"}\nnot json\n{\"cmd\":\"save\"}\n{\"cmd\":\"get_content\"}\n",
+        );
+
+        assert_eq!(
+            responses,
+            [
+                json!({ "ok": false, "error": "Unknown command 'jump'" }),
+                json!({ "ok": false, "error": "Expected a JSON object" }),
+                json!({ "ok": false, "error": "No filename was given" }),
+                json!({ "ok": true, "content": "" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn save_should_write_the_file() {
+        let dir = ScratchDir::new("rpc");
+        let path = dir.join("file.txt");
+        fs::write(&path, "abc").unwrap();
+        let mut editor = Editor::new(80, 22);
+        editor.set_buffer(crate::buffer::Buffer::open(&path).unwrap());
+
+        let responses = run(
+            &mut editor,
+            "{\"cmd\":\"insert\",\"text\":\"x\"}\n{\"cmd\":\"save\"}\n",
+        );
+
+        assert_eq!(responses, [json!({ "ok": true }), json!({ "ok": true })]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "xabc");
+    }
+}
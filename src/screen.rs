@@ -1,23 +1,64 @@
+use std::{cell::RefCell, ops::Range};
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::{buffer::Row, text::display_width};
+
+/// Columns continuation rows are shifted past the line's own indent with break indent on
+const BREAK_INDENT_SHIFT: usize = 2;
+
 #[derive(Debug, Clone, Copy, Default)]
 struct ScreenSize(u16, u16);
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct Screen {
     size: ScreenSize,
     offset: Offset,
+    visual_rows: RefCell<VisualRows>,
+    break_indent: bool,
+    /// Drawn at the start of the continuation rows of wrapped lines
+    wrap_indicator: Option<char>,
+    /// Rows of the size taken by bars drawn with the text, like the tab bar
+    reserved_rows: u16,
 }
 
 impl Screen {
     pub fn new(cols: u16, rows: u16) -> Self {
-        Self { size: ScreenSize(cols, rows), offset: Offset(0, 0) }
+        Self {
+            size: ScreenSize(cols, rows),
+            offset: Offset(0, 0),
+            visual_rows: RefCell::default(),
+            break_indent: false,
+            wrap_indicator: None,
+            reserved_rows: 0,
+        }
     }
 
     pub fn cols(&self) -> u16 {
         self.size.0
     }
 
+    /// Change the size to `cols` by `rows`, never less than one of each so there's always a
+    /// cell for the cursor.
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        self.size = ScreenSize(cols.max(1), rows.max(1));
+        self.invalidate_visual_rows();
+    }
+
+    /// The rows available for text, without the reserved ones
     pub fn rows(&self) -> u16 {
-        self.size.1
+        self.size.1.saturating_sub(self.reserved_rows)
+    }
+
+    /// Take `rows` of the screen for a bar, leaving fewer rows for text.
+    pub fn set_reserved_rows(&mut self, rows: u16) {
+        self.reserved_rows = rows;
+    }
+
+    /// The number of columns left for the buffer content once a gutter of `gutter_width`
+    /// columns is drawn in front of it.
+    pub fn content_cols(&self, gutter_width: u16) -> u16 {
+        self.cols().saturating_sub(gutter_width)
     }
 
     pub fn col_offset(&self) -> u16 {
@@ -28,24 +69,333 @@ impl Screen {
         self.offset.1
     }
 
-    pub fn scroll(&mut self, x: u16, y: u16) {
+    /// The size and offsets on one line, like `Screen{cols=80, rows=24, row_off=0, col_off=0}`,
+    /// for messages of failing tests.
+    pub fn debug_dump(&self) -> String {
+        format!(
+            "Screen{{cols={}, rows={}, row_off={}, col_off={}}}",
+            self.cols(),
+            self.rows(),
+            self.row_offset(),
+            self.col_offset()
+        )
+    }
+
+    pub fn set_offset(&mut self, col: u16, row: u16) {
+        self.offset = Offset(col, row);
+    }
+
+    /// Whether the cursor is inside the current window, without scrolling to it like `scroll`
+    /// does.
+    pub fn is_cursor_visible(&self, cursor_x: u16, cursor_y: u16) -> bool {
+        let (x, y) = (cursor_x as u32, cursor_y as u32);
+        let (col, row) = (self.col_offset() as u32, self.row_offset() as u32);
+
+        (col..col + self.cols() as u32).contains(&x) && (row..row + self.rows() as u32).contains(&y)
+    }
+
+    pub fn scroll(&mut self, x: u16, y: u16, gutter_width: u16) {
         if y < self.row_offset() {
             self.offset.1 = y;
         }
 
-        if y >= self.row_offset() + self.rows() {
-            self.offset.1 = y - self.rows() + 1;
+        let rows = self.rows().max(1);
+        if y >= self.row_offset() + rows {
+            self.offset.1 = y - rows + 1;
         }
 
         if x < self.col_offset() {
             self.offset.0 = x;
         }
 
-        if x >= self.col_offset() + self.cols() {
-            self.offset.0 = x - self.cols() + 1;
+        let cols = self.content_cols(gutter_width).max(1);
+        if x >= self.col_offset() + cols {
+            self.offset.0 = x - cols + 1;
+        }
+    }
+
+    /// The screen row where buffer line `n` starts when every line before it wraps at
+    /// `cols` columns. Heights are cached for the rows identified by `key`, until they are
+    /// measured for another key or `invalidate_visual_rows` is called.
+    pub fn visual_row_for_buffer_line(
+        &self,
+        n: usize,
+        rows: &[Row],
+        tab_stop: usize,
+        cols: usize,
+        key: (usize, u64),
+    ) -> usize {
+        let cols = cols.max(1);
+        let mut visual_rows = self.visual_rows.borrow_mut();
+        if (visual_rows.key, visual_rows.cols, visual_rows.tab_stop) != (key, cols, tab_stop) {
+            *visual_rows = VisualRows {
+                key,
+                cols,
+                tab_stop,
+                starts: vec![0],
+            };
+        }
+
+        let n = n.min(rows.len());
+        while visual_rows.starts.len() <= n {
+            let starts = &visual_rows.starts;
+            let (line, start) = (&rows[starts.len() - 1], starts[starts.len() - 1]);
+            let width = line.display_width(tab_stop);
+            let height = self.wrap(line, cols, tab_stop).height(width);
+            visual_rows.starts.push(start + height);
+        }
+
+        visual_rows.starts[n]
+    }
+
+    /// Where rendered column `col` of `row` ends up once the row is wrapped at the screen
+    /// width, as the visual row within the line and the column on that row.
+    pub fn visual_position(&self, row: &Row, col: usize, tab_stop: usize) -> (usize, usize) {
+        let cols = self.cols().max(1) as usize;
+        self.wrap(row, cols, tab_stop).position(col)
+    }
+
+    /// The rendered columns of `row` shown on each screen row once it's wrapped at `cols`
+    /// columns, with the column the continuation rows start at. An empty row still takes
+    /// one row.
+    pub fn wrap_segments(
+        &self,
+        row: &Row,
+        cols: usize,
+        tab_stop: usize,
+    ) -> (Vec<Range<usize>>, usize) {
+        let wrap = self.wrap(row, cols.max(1), tab_stop);
+        let width = row.display_width(tab_stop);
+
+        let first = 0..width.min(wrap.cols);
+        let mut start = first.end;
+        let mut segments = vec![first];
+        while start < width {
+            let end = (start + wrap.cols - wrap.indent).min(width);
+            segments.push(start..end);
+            start = end;
         }
+
+        (segments, wrap.indent)
+    }
+
+    /// Indent wrapped continuation rows to line up with the start of the text, like
+    /// `breakindent` in vim.
+    pub fn set_break_indent(&mut self, enabled: bool) {
+        self.break_indent = enabled;
+        self.invalidate_visual_rows();
+    }
+
+    pub fn wrap_indicator(&self) -> Option<char> {
+        self.wrap_indicator
+    }
+
+    /// Mark the continuation rows of wrapped lines with `indicator`, the rows are indented
+    /// by at least its width to make room for it.
+    pub fn set_wrap_indicator(&mut self, indicator: Option<char>) {
+        self.wrap_indicator = indicator;
+        self.invalidate_visual_rows();
     }
+
+    fn wrap(&self, row: &Row, cols: usize, tab_stop: usize) -> Wrap {
+        let indicator = self.wrap_indicator.map_or(0, |ch| ch.width().unwrap_or(1));
+        Wrap::new(row, cols, tab_stop, self.break_indent, indicator)
+    }
+
+    /// Forget the cached line heights, needed whenever the buffer changes.
+    pub fn invalidate_visual_rows(&mut self) {
+        self.visual_rows = RefCell::default();
+    }
+}
+
+/// How a line is split into screen rows of `cols` columns, continuation rows start `indent`
+/// columns in.
+struct Wrap {
+    cols: usize,
+    indent: usize,
+}
+
+impl Wrap {
+    /// Continuation rows are indented by at least `min_indent`, break indent or not.
+    fn new(row: &Row, cols: usize, tab_stop: usize, break_indent: bool, min_indent: usize) -> Self {
+        let indent = if break_indent {
+            let line = row.buffer();
+            let text = line.trim_start_matches(char::is_whitespace);
+            let leading = display_width(&line[..line.len() - text.len()], tab_stop);
+            leading + BREAK_INDENT_SHIFT
+        } else {
+            0
+        };
+        let indent = indent.max(min_indent);
+
+        // An indent leaving no room for text is dropped rather than wrapping forever
+        let indent = if indent < cols { indent } else { 0 };
+        Self { cols, indent }
+    }
+
+    /// Rows taken by a line `width` columns wide. An empty line still takes a row.
+    fn height(&self, width: usize) -> usize {
+        match width.checked_sub(self.cols) {
+            Some(rest) if rest > 0 => 1 + rest.div_ceil(self.cols - self.indent),
+            _ => 1,
+        }
+    }
+
+    fn position(&self, col: usize) -> (usize, usize) {
+        match col.checked_sub(self.cols) {
+            Some(rest) => {
+                let continuation = self.cols - self.indent;
+                (1 + rest / continuation, self.indent + rest % continuation)
+            }
+            None => (0, col),
+        }
+    }
+}
+
+/// Prefix sums of wrapped line heights, `starts[n]` is the screen row of line `n`.
+#[derive(Debug, Clone, Default)]
+struct VisualRows {
+    /// Identifies the rows measured, see `StateCache` for highlighting
+    key: (usize, u64),
+    cols: usize,
+    tab_stop: usize,
+    starts: Vec<usize>,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
 struct Offset(u16, u16);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    fn rows(content: &str) -> Vec<Row> {
+        content.lines().map(Row::new).collect()
+    }
+
+    #[test]
+    fn scrolling_should_keep_the_cursor_clear_of_the_gutter() {
+        let mut screen = Screen::new(10, 5);
+
+        screen.scroll(7, 0, 4);
+
+        assert_eq!(screen.col_offset(), 2);
+    }
+
+    #[test]
+    fn debug_dump_should_show_the_size_and_offsets() {
+        let mut screen = Screen::new(80, 24);
+
+        screen.scroll(90, 30, 0);
+
+        assert_eq!(
+            screen.debug_dump(),
+            "Screen{cols=80, rows=24, row_off=7, col_off=11}"
+        );
+    }
+
+    #[test]
+    fn reserved_rows_should_scroll_sooner() {
+        let mut screen = Screen::new(10, 5);
+        screen.set_reserved_rows(1);
+
+        screen.scroll(0, 4, 0);
+
+        assert_eq!((screen.rows(), screen.row_offset()), (4, 1));
+    }
+
+    #[test_case(10, 5 => true; "Top left corner")]
+    #[test_case(19, 7 => true; "Bottom right corner")]
+    #[test_case(9, 5 => false; "Left of the window")]
+    #[test_case(20, 5 => false; "Right of the window")]
+    #[test_case(10, 4 => false; "Above the window")]
+    #[test_case(10, 8 => false; "Below the window")]
+    fn cursor_visibility_should_cover_exactly_the_window(x: u16, y: u16) -> bool {
+        let mut screen = Screen::new(10, 3);
+        screen.set_offset(10, 5);
+
+        screen.is_cursor_visible(x, y)
+    }
+
+    #[test]
+    fn empty_or_far_scrolled_screens_should_not_overflow() {
+        let mut screen = Screen::new(0, 0);
+        assert!(!screen.is_cursor_visible(0, 0));
+
+        screen = Screen::new(10, 10);
+        screen.set_offset(u16::MAX, u16::MAX);
+        assert!(screen.is_cursor_visible(u16::MAX, u16::MAX));
+        assert!(!screen.is_cursor_visible(0, 0));
+    }
+
+    #[test]
+    fn wrapped_lines_should_push_later_lines_down() {
+        let screen = Screen::new(4, 5);
+        let rows = rows("abcdefghij\n\n\tx\nabcd");
+
+        let starts = (0..=4)
+            .map(|n| screen.visual_row_for_buffer_line(n, &rows, 4, 4, (0, 0)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(starts, vec![0, 3, 4, 6, 7]);
+    }
+
+    #[test]
+    fn invalidating_should_pick_up_changed_lines() {
+        let mut screen = Screen::new(4, 5);
+        let measure = |screen: &Screen, text, key| {
+            screen.visual_row_for_buffer_line(1, &rows(text), 4, 4, key)
+        };
+        assert_eq!(measure(&screen, "ab\nc", (0, 0)), 1);
+
+        // The same key stands for the same rows
+        assert_eq!(measure(&screen, "abcdefgh\nc", (0, 0)), 1);
+        assert_eq!(measure(&screen, "abcdefgh\nc", (0, 1)), 2);
+
+        screen.invalidate_visual_rows();
+        assert_eq!(measure(&screen, "abcdefghijkl\nc", (0, 1)), 3);
+    }
+
+    #[test]
+    fn break_indent_should_line_continuation_rows_up_with_the_text() {
+        let mut screen = Screen::new(10, 5);
+        let row = Row::new("    abcdefghijklmn");
+        assert_eq!(screen.visual_position(&row, 12, 4), (1, 2));
+
+        screen.set_break_indent(true);
+
+        // Continuation rows start at the indent of 4 plus the shift of 2, leaving 4 columns
+        assert_eq!(screen.visual_position(&row, 9, 4), (0, 9));
+        assert_eq!(screen.visual_position(&row, 12, 4), (1, 8));
+        assert_eq!(screen.visual_position(&row, 14, 4), (2, 6));
+        assert_eq!(
+            screen.visual_row_for_buffer_line(1, &[row], 4, 10, (0, 0)),
+            3
+        );
+    }
+
+    #[test]
+    fn wrap_indicator_should_indent_continuation_rows() {
+        let mut screen = Screen::new(10, 5);
+        let row = Row::new("abcdefghijklmnopqrstuvwxyz");
+        screen.set_wrap_indicator(Some('↪'));
+
+        let (segments, indent) = screen.wrap_segments(&row, 10, 4);
+
+        assert_eq!(segments, vec![0..10, 10..19, 19..26]);
+        assert_eq!(indent, 1);
+        assert_eq!(screen.visual_position(&row, 19, 4), (2, 1));
+    }
+
+    #[test]
+    fn break_indent_wider_than_the_screen_should_be_ignored() {
+        let mut screen = Screen::new(4, 5);
+        screen.set_break_indent(true);
+
+        let row = Row::new("\tab");
+
+        assert_eq!(screen.visual_position(&row, 5, 8), (1, 1));
+    }
+}
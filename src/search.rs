@@ -0,0 +1,288 @@
+use std::cell::RefCell;
+
+use crate::{buffer::Row, text::ConsoleWidthStr, Position};
+
+const MAX_PATTERN_DISPLAY: usize = 20;
+/// Matches are counted up to this many, more are shown as `1000+`
+const MAX_COUNTED: usize = 1000;
+
+/// The first matches of a pattern in the buffer after some number of edits
+#[derive(Debug, Clone)]
+struct Counted {
+    modification_count: u64,
+    /// Never more than `MAX_COUNTED`
+    matches: Vec<Position>,
+    /// Whether there were more matches than those
+    capped: bool,
+}
+
+impl Counted {
+    fn of(pattern: &str, rows: &[Row], modification_count: u64) -> Self {
+        let mut matches = matches_from(pattern, rows, 0)
+            .take(MAX_COUNTED + 1)
+            .collect::<Vec<_>>();
+        let capped = matches.len() > MAX_COUNTED;
+        matches.truncate(MAX_COUNTED);
+
+        Self {
+            modification_count,
+            matches,
+            capped,
+        }
+    }
+}
+
+/// A confirmed search pattern and the match the cursor was last moved to. Moving finds the
+/// next match from there, the matches are only all counted for the status bar.
+#[derive(Debug, Clone, Default)]
+pub struct SearchState {
+    pattern: String,
+    current: Option<Position>,
+    /// Counted again once the buffer is edited
+    counted: RefCell<Option<Counted>>,
+}
+
+impl SearchState {
+    pub fn new<T: Into<String>>(pattern: T) -> Self {
+        Self {
+            pattern: pattern.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The match the cursor was last moved to
+    pub fn current(&self) -> Option<Position> {
+        self.current
+    }
+
+    /// Select the first match at or after `from`, wrapping around to the top of the buffer.
+    pub fn select_from(&mut self, rows: &[Row], from: Position) -> Option<Position> {
+        let found = matches_from(&self.pattern, rows, from.1 as usize)
+            .find(|pos| key(pos) >= key(&from))
+            .or_else(|| matches_from(&self.pattern, rows, 0).next());
+        self.current = found;
+
+        found
+    }
+
+    pub fn next_match(&mut self, rows: &[Row]) -> Option<Position> {
+        let found = self
+            .current
+            .and_then(|at| {
+                matches_from(&self.pattern, rows, at.1 as usize).find(|pos| key(pos) > key(&at))
+            })
+            .or_else(|| matches_from(&self.pattern, rows, 0).next());
+        self.current = found;
+
+        found
+    }
+
+    pub fn previous_match(&mut self, rows: &[Row]) -> Option<Position> {
+        let found = self
+            .current
+            .and_then(|at| self.last_match(rows, Some(at)))
+            .or_else(|| self.last_match(rows, None));
+        self.current = found;
+
+        found
+    }
+
+    /// The last match before `before`, or in the whole buffer
+    fn last_match(&self, rows: &[Row], before: Option<Position>) -> Option<Position> {
+        let end = before.map_or(rows.len(), |at| (at.1 as usize + 1).min(rows.len()));
+        rows[..end].iter().enumerate().rev().find_map(|(y, row)| {
+            row_matches(&self.pattern, row, y)
+                .filter(|pos| before.is_none_or(|at| key(pos) < key(&at)))
+                .last()
+        })
+    }
+
+    /// A short summary for the status bar like `[/foo 3/17]`, counting the matches in `rows`
+    /// unless they were counted after the same `modification_count` already. Past
+    /// `MAX_COUNTED` matches the count is shown as `1000+`.
+    pub fn indicator(&self, rows: &[Row], modification_count: u64) -> String {
+        let pattern = if self.pattern.chars().count() > MAX_PATTERN_DISPLAY {
+            let truncated = self
+                .pattern
+                .chars()
+                .take(MAX_PATTERN_DISPLAY - 3)
+                .collect::<String>();
+            format!("{truncated}...")
+        } else {
+            self.pattern.clone()
+        };
+
+        let mut cache = self.counted.borrow_mut();
+        let counted = match cache.take() {
+            Some(counted) if counted.modification_count == modification_count => {
+                cache.insert(counted)
+            }
+            _ => cache.insert(Counted::of(&self.pattern, rows, modification_count)),
+        };
+        let found = self
+            .current
+            .map(|at| counted.matches.binary_search_by_key(&key(&at), key));
+        let current = match found {
+            Some(Ok(index)) => (index + 1).to_string(),
+            Some(Err(index)) if counted.capped && index == MAX_COUNTED => {
+                format!("{MAX_COUNTED}+")
+            }
+            _ => String::from("0"),
+        };
+        let count = match counted.capped {
+            true => format!("{MAX_COUNTED}+"),
+            false => counted.matches.len().to_string(),
+        };
+
+        format!("[/{pattern} {current}/{count}]")
+    }
+}
+
+/// Every match of `pattern` in `rows`, in order
+pub fn find_all(pattern: &str, rows: &[Row]) -> Vec<Position> {
+    matches_from(pattern, rows, 0).collect()
+}
+
+/// The matches of `pattern` on the rows from `from` on
+fn matches_from<'a>(
+    pattern: &'a str,
+    rows: &'a [Row],
+    from: usize,
+) -> impl Iterator<Item = Position> + 'a {
+    rows.iter()
+        .enumerate()
+        .skip(from)
+        .flat_map(move |(y, row)| row_matches(pattern, row, y))
+}
+
+fn row_matches<'a>(
+    pattern: &'a str,
+    row: &'a Row,
+    y: usize,
+) -> impl Iterator<Item = Position> + 'a {
+    let line = row.buffer();
+    line.match_indices(pattern)
+        .map(move |(index, _)| Position(line[..index].column_width() as u16, y as u16))
+}
+
+/// Positions ordered the way they are in the buffer, by row and then column
+fn key(pos: &Position) -> (u16, u16) {
+    (pos.1, pos.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(content: &str) -> Vec<Row> {
+        content.lines().map(Row::new).collect()
+    }
+
+    #[test]
+    fn indicator_should_show_pattern_and_match_index() {
+        let rows = rows("foo bar\nbar foo\nfoo");
+        let mut search = SearchState::new("foo");
+        search.next_match(&rows);
+        search.next_match(&rows);
+
+        assert_eq!(search.indicator(&rows, 0), "[/foo 2/3]");
+    }
+
+    #[test]
+    fn indicator_should_truncate_long_patterns() {
+        let pattern = "a".repeat(25);
+        let search = SearchState::new(pattern.as_str());
+
+        assert_eq!(
+            search.indicator(&rows(&pattern), 0),
+            format!("[/{}... 0/1]", "a".repeat(17))
+        );
+    }
+
+    #[test]
+    fn navigation_should_wrap_around_the_buffer() {
+        let rows = rows("x\n x\n  x");
+        let mut search = SearchState::new("x");
+
+        assert_eq!(
+            search
+                .select_from(&rows, Position(0, 2))
+                .map(|p| (p.0, p.1)),
+            Some((2, 2))
+        );
+        assert_eq!(search.next_match(&rows).map(|p| (p.0, p.1)), Some((0, 0)));
+        assert_eq!(
+            search.previous_match(&rows).map(|p| (p.0, p.1)),
+            Some((2, 2))
+        );
+    }
+
+    #[test]
+    fn match_columns_should_account_for_wide_characters() {
+        let matches = find_all("x", &rows("⛄x"));
+
+        assert_eq!(matches.first().map(|p| p.0), Some(2));
+    }
+
+    #[test]
+    fn indicator_should_follow_navigation_both_ways() {
+        let rows = rows("ab ab\n\nab");
+        let mut search = SearchState::new("ab");
+        let mut indicators = Vec::new();
+
+        search.select_from(&rows, Position(1, 0));
+        indicators.push(search.indicator(&rows, 0));
+        for _ in 0..3 {
+            search.next_match(&rows);
+            indicators.push(search.indicator(&rows, 0));
+        }
+        for _ in 0..2 {
+            search.previous_match(&rows);
+            indicators.push(search.indicator(&rows, 0));
+        }
+
+        assert_eq!(
+            indicators,
+            vec![
+                "[/ab 2/3]",
+                "[/ab 3/3]",
+                "[/ab 1/3]",
+                "[/ab 2/3]",
+                "[/ab 1/3]",
+                "[/ab 3/3]"
+            ]
+        );
+    }
+
+    #[test]
+    fn indicator_should_count_again_only_after_an_edit() {
+        let mut rows = rows("x x");
+        let mut search = SearchState::new("x");
+        search.next_match(&rows);
+        assert_eq!(search.indicator(&rows, 1), "[/x 1/2]");
+
+        rows.push(Row::new("x"));
+        assert_eq!(search.indicator(&rows, 1), "[/x 1/2]");
+        assert_eq!(search.indicator(&rows, 2), "[/x 1/3]");
+    }
+
+    #[test]
+    fn indicator_should_stop_counting_at_the_cap() {
+        let rows = vec![Row::new("x".repeat(MAX_COUNTED + 5))];
+        let mut search = SearchState::new("x");
+
+        search.next_match(&rows);
+        assert_eq!(search.indicator(&rows, 0), "[/x 1/1000+]");
+
+        search.previous_match(&rows);
+        assert_eq!(search.current().map(|p| p.0), Some(MAX_COUNTED as u16 + 4));
+        assert_eq!(search.indicator(&rows, 0), "[/x 1000+/1000+]");
+
+        search.next_match(&rows);
+        assert_eq!(search.indicator(&rows, 0), "[/x 1/1000+]");
+    }
+}
@@ -0,0 +1,101 @@
+//! Everything needed to put the editor back the way it was, for hosts embedding it and for
+//! recovering after a crash. With the `serde` feature the state can be serialized.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buffer::{Buffer, BufferState, SaveOptions},
+    cursor::{BoundedCursor, Cursor},
+    undo::OperationLog,
+    Position,
+};
+
+/// The open buffers and what is shown of them. Prompts aren't kept, one that was open is
+/// gone after restoring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EditorState {
+    /// The shown buffer first, then the others in the order they are switched to
+    pub buffers: Vec<BufferSnapshot>,
+    /// The tab of the shown buffer. `buffers` go from there to the last tab and wrap around.
+    pub active_tab: usize,
+    /// The pattern of the last confirmed search
+    pub search: Option<String>,
+    /// Confirmed searches, oldest first. Missing from states stored before it was kept.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub search_history: Vec<String>,
+    /// Where the cursor was left in recently closed files, the most recent last
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub closed: Vec<SessionEntry>,
+}
+
+/// The cursor of a file that isn't open, put back when the file is opened again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SessionEntry {
+    pub filename: String,
+    /// `(col, row)` of the cursor
+    pub cursor: (u16, u16),
+}
+
+/// The rows of a buffer with where it was left. The rows are kept even when they match
+/// the file so a file that disappeared can still be restored, and kept as they are so the
+/// undo history still fits them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BufferSnapshot {
+    pub filename: Option<String>,
+    pub rows: Vec<String>,
+    pub modified: bool,
+    pub save_options: SaveOptions,
+    /// `(col, row)` of the cursor
+    pub cursor: (u16, u16),
+    /// `(col, row)` the screen was scrolled to
+    pub scroll_offset: (u16, u16),
+    /// What can be undone, as written to undo files. Redoing isn't kept.
+    pub undo: String,
+}
+
+impl BufferSnapshot {
+    pub fn new(buffer: &Buffer, cursor: &BoundedCursor, scroll_offset: (u16, u16)) -> Self {
+        let rows = buffer
+            .buffer()
+            .borrow()
+            .iter()
+            .map(|row| row.buffer().to_string())
+            .collect();
+
+        Self {
+            filename: buffer.filename_str().map(str::to_string),
+            rows,
+            modified: buffer.state() == BufferState::Modified,
+            save_options: buffer.save_options(),
+            cursor: (cursor.x(), cursor.y()),
+            scroll_offset,
+            undo: buffer.undo_history().to_string(),
+        }
+    }
+
+    /// A buffer with the rows, the cursor saved and the scroll offset saved
+    pub fn into_buffer(self) -> Buffer {
+        let mut buffer = Buffer::from_rows(self.rows);
+        if let Some(filename) = self.filename {
+            buffer.set_filename(filename);
+        }
+        buffer.set_save_options(self.save_options);
+        if let Ok(ops) = self.undo.parse::<OperationLog>() {
+            buffer.extend_undo_history(&ops);
+        }
+        if self.modified {
+            buffer.mark_modified();
+        }
+
+        let mut cursor = buffer.take_cursor();
+        *cursor.position_mut() = Position(self.cursor.0, self.cursor.1);
+        buffer.save_cursor(cursor);
+        buffer.save_scroll_offset(self.scroll_offset);
+
+        buffer
+    }
+}
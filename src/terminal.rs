@@ -0,0 +1,125 @@
+use std::io::{self, Stdout, Write};
+
+#[cfg(feature = "links")]
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::{
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::capabilities::Capabilities;
+
+/// Where the editor draws. A terminal that was entered is restored to the primary screen
+/// when it is left or dropped, which includes unwinding from a panic, and to cooked mode if
+/// entering switched to raw mode.
+#[derive(Debug)]
+pub struct Terminal<W: Write = Stdout> {
+    writer: W,
+    entered: bool,
+    /// Set when this terminal switched to raw mode and switches back when left
+    raw_mode: bool,
+    alternate_screen: bool,
+}
+
+impl Terminal {
+    /// Take over stdout, switching to raw mode and the alternate screen if it has one.
+    pub fn enter(capabilities: Capabilities) -> crossterm::Result<Self> {
+        terminal::enable_raw_mode()?;
+
+        let mut me = Self::take_over(io::stdout(), capabilities)?;
+        me.raw_mode = true;
+        Ok(me)
+    }
+
+    /// The columns and rows of the terminal window
+    pub fn size(&self) -> crossterm::Result<(u16, u16)> {
+        terminal::size()
+    }
+}
+
+impl<W: Write> Terminal<W> {
+    /// Draw into `writer` without changing any terminal modes, like when recording frames.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            entered: false,
+            raw_mode: false,
+            alternate_screen: false,
+        }
+    }
+
+    /// Switch `writer` to the alternate screen if the terminal has one, as if it was
+    /// entered. Raw mode is left to the caller, leaving doesn't switch it off.
+    pub fn take_over(writer: W, capabilities: Capabilities) -> crossterm::Result<Self> {
+        let mut me = Self {
+            writer,
+            entered: true,
+            raw_mode: false,
+            alternate_screen: capabilities.alternate_screen,
+        };
+        if me.alternate_screen {
+            execute!(me.writer, EnterAlternateScreen)?;
+        }
+        // Ctrl+click follows links
+        #[cfg(feature = "links")]
+        execute!(me.writer, EnableMouseCapture)?;
+
+        Ok(me)
+    }
+
+    /// Go back to the primary screen, and to cooked mode if entering switched to raw mode.
+    /// Does nothing unless the terminal was entered and hasn't been left yet.
+    pub fn leave(&mut self) -> crossterm::Result<()> {
+        if !self.entered {
+            return Ok(());
+        }
+
+        self.entered = false;
+        #[cfg(feature = "links")]
+        let _ = execute!(self.writer, DisableMouseCapture);
+        let err1 = if self.alternate_screen {
+            execute!(self.writer, LeaveAlternateScreen)
+        } else {
+            Ok(())
+        };
+        let err2 = if self.raw_mode {
+            terminal::disable_raw_mode()
+        } else {
+            Ok(())
+        };
+
+        err1.and(err2)
+    }
+}
+
+impl<W: Write> Write for Terminal<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Drop for Terminal<W> {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taken_over_terminals_should_leave_raw_mode_to_the_caller() {
+        let capabilities = Capabilities::from_env(Some("xterm"), false, true);
+
+        let mut terminal = Terminal::take_over(Vec::new(), capabilities).unwrap();
+        assert!(!terminal.raw_mode);
+
+        terminal.leave().unwrap();
+        assert!(!terminal.entered);
+    }
+}
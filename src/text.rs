@@ -1,3 +1,5 @@
+use std::{borrow::Cow, collections::HashSet, path::PathBuf};
+
 use unicode_width::UnicodeWidthChar;
 
 /// This trait is to determine the width of a character so that we can move the cursor
@@ -61,6 +63,75 @@ pub fn buffer_width(buffer: &str) -> usize {
     })
 }
 
+/// Columns from `col` to the next multiple of `tab_stop`, taken by a tab drawn at `col`.
+pub fn tab_width(col: usize, tab_stop: usize) -> usize {
+    let tab_stop = tab_stop.max(1);
+    tab_stop - col % tab_stop
+}
+
+/// Columns taken by `buffer` once drawn from the start of a line, with every tab expanded
+/// to the next tab stop.
+pub fn display_width(buffer: &str, tab_stop: usize) -> usize {
+    buffer.chars().fold(0, |col, ch| match ch {
+        '\t' => col + tab_width(col, tab_stop),
+        ch => col + ch.width().unwrap_or(1),
+    })
+}
+
+/// The cursor column of the character drawn at display column `col` of `line`, the end
+/// of the line when it's narrower. Undoes the tab expansion of `display_width`.
+pub fn column_at_display(line: &str, col: usize, tab_stop: usize) -> usize {
+    let (mut cursor, mut display) = (0, 0);
+    for ch in line.chars() {
+        let width = match ch {
+            '\t' => tab_width(display, tab_stop),
+            ch => ch.width().unwrap_or(1),
+        };
+        if display + width > col {
+            break;
+        }
+
+        display += width;
+        cursor += ch.width().unwrap_or(1);
+    }
+
+    cursor
+}
+
+/// `lines` with spaces put in front of the first `delimiter` of each, so that all of them
+/// are drawn at the same column with tabs expanded to `tab_stop`. Lines without it are
+/// left alone, as are lines already aligned. Whitespace around `delimiter` is ignored, the
+/// padding would be taken for it otherwise.
+pub fn align_at<S: AsRef<str>>(lines: &[S], delimiter: &str, tab_stop: usize) -> Vec<String> {
+    let delimiter = delimiter.trim();
+    let found = lines
+        .iter()
+        .map(|line| {
+            let line = line.as_ref();
+            let index = line.find(delimiter).filter(|_| !delimiter.is_empty())?;
+            Some((index, display_width(&line[..index], tab_stop)))
+        })
+        .collect::<Vec<_>>();
+    let target = found.iter().flatten().map(|(_, col)| *col).max();
+
+    lines
+        .iter()
+        .zip(found)
+        .map(|(line, found)| {
+            let line = line.as_ref();
+            match (found, target) {
+                (Some((index, col)), Some(target)) => format!(
+                    "{}{}{}",
+                    &line[..index],
+                    " ".repeat(target - col),
+                    &line[index..]
+                ),
+                _ => line.to_string(),
+            }
+        })
+        .collect()
+}
+
 pub fn char_index(cursor: usize, buffer: &str) -> usize {
     buffer
         .chars()
@@ -79,6 +150,172 @@ pub fn char_index(cursor: usize, buffer: &str) -> usize {
         .sum()
 }
 
+/// The part of `line` covering the columns `start..start + width`. A wide character cut by
+/// either edge is replaced by spaces for the cells that are visible, so the result never
+/// holds half a glyph and takes exactly the window's width when the line is long enough.
+pub fn column_window(line: &str, start: usize, width: usize) -> Cow<'_, str> {
+    let end = start + width;
+    // Every ASCII character but NUL takes one column, the window is a slice of the bytes
+    if line.is_ascii() && !line.contains('\0') {
+        let len = line.len();
+        return Cow::Borrowed(&line[start.min(len)..end.min(len)]);
+    }
+
+    let (mut pad_left, mut pad_right) = (0, 0);
+    let (mut first, mut last) = (line.len(), line.len());
+    let mut col = 0;
+
+    for (i, ch) in line.char_indices() {
+        let ch_width = ch.width().unwrap_or(1);
+        let next = col + ch_width;
+        // Zero width characters at the edge still belong to the last visible one
+        if (col >= end && ch_width > 0) || (next > end && col >= start) {
+            pad_right = end.saturating_sub(col.max(start));
+            last = i;
+            break;
+        }
+
+        if col < start && next > start {
+            pad_left = next.min(end) - start;
+        } else if col >= start && first == line.len() {
+            first = i;
+        }
+
+        col = next;
+    }
+
+    let first = first.min(last);
+    if pad_left == 0 && pad_right == 0 {
+        return Cow::Borrowed(&line[first..last]);
+    }
+
+    let mut window = String::with_capacity(last - first + pad_left + pad_right);
+    // Padding is never wider than a single glyph
+    window.push_str(&crate::SPACES[..pad_left]);
+    window.push_str(&line[first..last]);
+    window.push_str(&crate::SPACES[..pad_right]);
+    Cow::Owned(window)
+}
+
+/// The end of `text` that fits in `width` columns, after a `…` when the start was cut.
+pub fn truncate_left(text: &str, width: usize) -> Cow<'_, str> {
+    match width {
+        _ if text.column_width() <= width => return Cow::Borrowed(text),
+        0 => return Cow::Borrowed(""),
+        _ => {}
+    }
+
+    let mut remaining = width - 1;
+    let start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, ch)| match remaining.checked_sub(ch.width().unwrap_or(1)) {
+            Some(left) => {
+                remaining = left;
+                true
+            }
+            None => false,
+        })
+        .last()
+        .map_or(text.len(), |(i, _)| i);
+
+    Cow::Owned(format!("…{}", &text[start..]))
+}
+
+/// The characters words are made of besides letters and digits, unless a file type sets
+/// its own.
+pub fn default_word_chars() -> HashSet<char> {
+    HashSet::from(['_'])
+}
+
+/// Whether `ch` is part of a word, being a letter, a digit or one of `word_chars`.
+pub fn is_word_char(ch: char, word_chars: &HashSet<char>) -> bool {
+    ch.is_alphanumeric() || word_chars.contains(&ch)
+}
+
+/// Byte index of the first word after the one `index` is in, the end of `line` when there
+/// is none.
+pub fn next_word_start(line: &str, index: usize, word_chars: &HashSet<char>) -> usize {
+    line[index..]
+        .char_indices()
+        .skip_while(|(_, ch)| is_word_char(*ch, word_chars))
+        .find(|(_, ch)| is_word_char(*ch, word_chars))
+        .map_or(line.len(), |(i, _)| index + i)
+}
+
+/// Byte index of the start of the word before `index`, the start of `line` when there is
+/// none.
+pub fn previous_word_start(line: &str, index: usize, word_chars: &HashSet<char>) -> usize {
+    line[..index]
+        .char_indices()
+        .rev()
+        .skip_while(|(_, ch)| !is_word_char(*ch, word_chars))
+        .take_while(|(_, ch)| is_word_char(*ch, word_chars))
+        .last()
+        .map_or(0, |(i, _)| i)
+}
+
+/// A link in the text, see `find_url_or_path_at_col`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlOrPath {
+    Url(String),
+    Path(PathBuf),
+}
+
+/// The URL or file path in `row` at cursor column `col`. Links end at whitespace, quotes and
+/// brackets, leaving out punctuation like the full stop of a sentence. URLs start with
+/// `http://` or `https://` and paths with `/`, `./` or `../`.
+pub fn find_url_or_path_at_col(row: &str, col: usize) -> Option<UrlOrPath> {
+    let is_delimiter = |ch: char| ch.is_whitespace() || "\"'`<>()[]{}".contains(ch);
+    let index = char_index(col, row);
+    if row[index..].chars().next().is_none_or(is_delimiter) {
+        return None;
+    }
+
+    let start = row[..index]
+        .char_indices()
+        .rev()
+        .find(|(_, ch)| is_delimiter(*ch))
+        .map_or(0, |(i, ch)| i + ch.len_utf8());
+    let end = row[index..]
+        .find(is_delimiter)
+        .map_or(row.len(), |i| index + i);
+    let link = row[start..end].trim_end_matches(['.', ',', ';', ':', '!', '?']);
+
+    let is_url = ["http://", "https://"]
+        .into_iter()
+        .any(|scheme| link.len() > scheme.len() && link.starts_with(scheme));
+    let is_path = ["/", "./", "../"]
+        .into_iter()
+        .any(|prefix| link.starts_with(prefix));
+    match (is_url, is_path) {
+        (true, _) => Some(UrlOrPath::Url(link.to_string())),
+        (_, true) => Some(UrlOrPath::Path(PathBuf::from(link))),
+        _ => None,
+    }
+}
+
+/// Describe a grapheme the way `ga` does in vim, e.g. `'é' U+00E9  c3 a9  width 1`.
+/// Every codepoint of a cluster is listed and the width is what the editor renders.
+pub fn describe_grapheme(grapheme: &str) -> String {
+    let codepoints = grapheme
+        .chars()
+        .map(|ch| format!("U+{:04X}", ch as u32))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let bytes = grapheme
+        .bytes()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "'{}' {codepoints}  {bytes}  width {}",
+        grapheme.escape_debug(),
+        grapheme.column_width()
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -87,6 +324,31 @@ mod tests {
     use quickcheck_macros::quickcheck;
     use test_case::test_case;
 
+    #[test_case("foo-bar baz", 0, &[] => 4; "Punctuation splits words")]
+    #[test_case("foo-bar baz", 0, &['-'] => 8; "Word character")]
+    #[test_case("foo_bar baz", 0, &['_'] => 8; "Underscore by default")]
+    #[test_case("$HOME $PATH", 1, &['$'] => 6; "From inside a word")]
+    #[test_case("foo  ", 0, &[] => 5; "No word after")]
+    fn next_word_start_should_skip_the_word_and_what_follows(
+        line: &str,
+        index: usize,
+        word_chars: &[char],
+    ) -> usize {
+        next_word_start(line, index, &word_chars.iter().copied().collect())
+    }
+
+    #[test_case("foo-bar baz", 7, &[] => 4; "Punctuation splits words")]
+    #[test_case("foo-bar baz", 7, &['-'] => 0; "Word character")]
+    #[test_case("foo bar", 4, &[] => 0; "At the start of a word")]
+    #[test_case("  foo", 2, &[] => 0; "No word before")]
+    fn previous_word_start_should_find_the_start_of_the_word_before(
+        line: &str,
+        index: usize,
+        word_chars: &[char],
+    ) -> usize {
+        previous_word_start(line, index, &word_chars.iter().copied().collect())
+    }
+
     #[test_case("⛄⛄", 4 => 6; "Two two-width, three byte")]
     #[test_case("⛄", 2 => 3; "Single two-width, three byte")]
     #[test_case("❄❄", 2 => 6; "Two one-width, three byte")]
@@ -97,6 +359,27 @@ mod tests {
         char_index(cursor, input)
     }
 
+    #[test_case("abc", 0 => 0; "Start of row")]
+    #[test_case("abc", 3 => 3; "End of row")]
+    #[test_case("abc", 10 => 3; "Past the end of row")]
+    #[test_case("⛄x", 3 => 4; "End of row after a wide character")]
+    #[test_case("⛄x", 4 => 4; "Past the end of row after a wide character")]
+    #[test_case("", 0 => 0; "Empty row")]
+    #[test_case("", 5 => 0; "Past the end of an empty row")]
+    fn char_index_at_the_row_boundaries_should_stay_within_the_row(
+        input: &str,
+        cursor: usize,
+    ) -> usize {
+        char_index(cursor, input)
+    }
+
+    #[test_case("a" => "'a' U+0061  61  width 1"; "ASCII")]
+    #[test_case("e\u{301}" => "'e\u{301}' U+0065 U+0301  65 cc 81  width 1"; "Combining acute accent")]
+    #[test_case("👩\u{200d}🔬" => "'👩\\u{200d}🔬' U+1F469 U+200D U+1F52C  f0 9f 91 a9 e2 80 8d f0 9f 94 ac  width 4"; "Zero-width joiner emoji")]
+    fn describe_grapheme_should_list_codepoints_bytes_and_width(grapheme: &str) -> String {
+        describe_grapheme(grapheme)
+    }
+
     #[quickcheck]
     fn char_index_at_buffer_width_should_be_equal_to_string_byte_length(input: String) -> bool {
         char_index(buffer_width(&input), &input) == input.len()
@@ -110,4 +393,104 @@ mod tests {
 
         TestResult::from_bool(buffer_width(&input) > 0)
     }
+
+    #[test_case("a\tb", 4 => 5; "Tab to the next stop")]
+    #[test_case("abcd\t", 4 => 8; "Tab on a stop")]
+    #[test_case("\t\t", 8 => 16; "Leading tabs")]
+    #[test_case("日本\tx", 8 => 9; "Wide characters before a tab")]
+    fn display_width_should_expand_tabs_to_the_next_stop(input: &str, tab_stop: usize) -> usize {
+        display_width(input, tab_stop)
+    }
+
+    #[quickcheck]
+    fn display_width_without_tabs_should_be_the_column_width(input: String) -> TestResult {
+        if input.contains('\t') {
+            return TestResult::discard();
+        }
+
+        TestResult::from_bool(display_width(&input, 8) == input.column_width())
+    }
+
+    #[test_case("abc", 2 => 2; "Plain text")]
+    #[test_case("a\tb", 6 => 1; "Inside a tab")]
+    #[test_case("a\tb", 8 => 2; "After a tab")]
+    #[test_case("日本語", 3 => 2; "Second half of a wide glyph")]
+    #[test_case("abc", 10 => 3; "Past the end")]
+    fn column_at_display_should_find_the_character_drawn_there(line: &str, col: usize) -> usize {
+        column_at_display(line, col, 8)
+    }
+
+    #[test_case("日本語", 1, 4 => " 本 "; "Cut on both edges")]
+    #[test_case("日本語", 2, 4 => "本語"; "Aligned")]
+    #[test_case("日本語", 1, 1 => " "; "Inside a single glyph")]
+    #[test_case("abc", 1, 10 => "bc"; "Shorter than the window")]
+    #[test_case("e\u{301}x", 0, 1 => "e\u{301}"; "Combining mark at the edge")]
+    fn column_window_should_never_split_a_glyph(line: &str, start: usize, width: usize) -> String {
+        column_window(line, start, width).into_owned()
+    }
+
+    #[test_case("file.rs", 10 => "file.rs"; "Fits")]
+    #[test_case("/deep/path/file.rs", 10 => "…h/file.rs"; "Cut")]
+    #[test_case("日本語.txt", 7 => "…語.txt"; "Wide characters")]
+    #[test_case("日本語.txt", 6 => "….txt"; "Wide character cut")]
+    #[test_case("abc", 0 => ""; "No room")]
+    fn truncate_left_should_keep_the_end(text: &str, width: usize) -> String {
+        truncate_left(text, width).into_owned()
+    }
+
+    fn url(url: &str) -> Option<UrlOrPath> {
+        Some(UrlOrPath::Url(url.to_string()))
+    }
+
+    fn path(path: &str) -> Option<UrlOrPath> {
+        Some(UrlOrPath::Path(PathBuf::from(path)))
+    }
+
+    #[test_case("see https://example.com/a?b=1.", 10 => url("https://example.com/a?b=1"); "Url in a sentence")]
+    #[test_case("see https://example.com/a?b=1.", 4 => url("https://example.com/a?b=1"); "First character")]
+    #[test_case("(http://x.org)", 1 => url("http://x.org"); "In brackets")]
+    #[test_case("open \"./src/main.rs\" now", 9 => path("./src/main.rs"); "Quoted path")]
+    #[test_case("/etc/hosts", 9 => path("/etc/hosts"); "Absolute path")]
+    #[test_case("日本 ../a.txt", 7 => path("../a.txt"); "After wide characters")]
+    #[test_case("see https://example.com", 3 => None; "Whitespace")]
+    #[test_case("a/b and https:// only", 1 => None; "Relative without a dot")]
+    #[test_case("https:// only", 2 => None; "Scheme only")]
+    #[test_case("short", 10 => None; "Past the end")]
+    fn links_should_be_found_under_the_cursor(row: &str, col: usize) -> Option<UrlOrPath> {
+        find_url_or_path_at_col(row, col)
+    }
+
+    #[test_case(&["a = 1", "long = 2"], "=" => vec!["a    = 1", "long = 2"]; "Assignments")]
+    #[test_case(&["\tx: u8,", "id: u32,"], ":" => vec!["\tx: u8,", "id       : u32,"]; "Tab before the delimiter")]
+    #[test_case(&["a => 1", "bcd => 2"], "=>" => vec!["a   => 1", "bcd => 2"]; "Longer delimiter")]
+    #[test_case(&["é → 1", "αβγ → 2"], "→" => vec!["é   → 1", "αβγ → 2"]; "Multi-byte delimiter")]
+    #[test_case(&["| a | b |", "| ab | c |"], "|" => vec!["| a | b |", "| ab | c |"]; "First occurrence only")]
+    #[test_case(&["a = 1", "", "no delimiter", "bc = 2"], "=" => vec!["a  = 1", "", "no delimiter", "bc = 2"]; "Lines without it")]
+    #[test_case(&["a  = 1", "bc = 2"], "=" => vec!["a  = 1", "bc = 2"]; "Already aligned")]
+    #[test_case(&["a = 1", "bc = 2"], " = " => vec!["a  = 1", "bc = 2"]; "Spaces around the delimiter")]
+    #[test_case(&["a = 1"], " " => vec!["a = 1"]; "Blank delimiter")]
+    fn align_at_should_line_up_the_first_delimiter_of_each_line(
+        lines: &[&str],
+        delimiter: &str,
+    ) -> Vec<String> {
+        align_at(lines, delimiter, 8)
+    }
+
+    #[quickcheck]
+    fn align_at_should_do_nothing_the_second_time(lines: Vec<String>, delimiter: char) {
+        let delimiter = delimiter.to_string();
+        let aligned = align_at(&lines, &delimiter, 4);
+
+        assert_eq!(align_at(&aligned, &delimiter, 4), aligned);
+    }
+
+    #[test]
+    fn column_window_of_cjk_text_should_fill_a_narrow_screen_at_every_offset() {
+        let line = "漢字とかなの混ざったテキストを表示する";
+
+        for start in 0..=line.column_width() - 10 {
+            let window = column_window(line, start, 10);
+            assert_eq!(window.column_width(), 10, "offset {start}: {window:?}");
+        }
+    }
 }
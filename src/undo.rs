@@ -0,0 +1,478 @@
+use std::{collections::VecDeque, error::Error, fmt, mem, ops::Range, str::FromStr};
+
+/// A single reversible change to the buffer. `index` is the byte index into the row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditOp {
+    Insert { row: usize, index: usize, text: String },
+    Delete { row: usize, index: usize, text: String },
+    /// Swap the two characters starting at `index`
+    TransposeChars { row: usize, index: usize },
+    /// Swap `row` with the row above it
+    TransposeRows { row: usize },
+    /// Break `row` at `index`, the rest of it becomes the row below
+    SplitRow { row: usize, index: usize },
+    /// Append the row below to `row`, which is `index` bytes long
+    JoinRows { row: usize, index: usize },
+    /// Replace the rows from `row` on, which are `old`, with `new` as a single step
+    ReplaceParagraph {
+        row: usize,
+        old: Vec<String>,
+        new: Vec<String>,
+    },
+}
+
+impl EditOp {
+    /// The operation that reverts this one.
+    pub fn inverse(&self) -> Self {
+        match self.clone() {
+            Self::Insert { row, index, text } => Self::Delete { row, index, text },
+            Self::Delete { row, index, text } => Self::Insert { row, index, text },
+            // Swapping twice restores the original order
+            op @ (Self::TransposeChars { .. } | Self::TransposeRows { .. }) => op,
+            Self::SplitRow { row, index } => Self::JoinRows { row, index },
+            Self::JoinRows { row, index } => Self::SplitRow { row, index },
+            Self::ReplaceParagraph { row, old, new } => Self::ReplaceParagraph {
+                row,
+                old: new,
+                new: old,
+            },
+        }
+    }
+
+    /// The rows this operation changes, numbered as they are after it. Adding or removing
+    /// rows moves every row below, which all count as changed then.
+    pub fn rows(&self) -> Range<usize> {
+        match self {
+            Self::Insert { row, .. }
+            | Self::Delete { row, .. }
+            | Self::TransposeChars { row, .. } => *row..row + 1,
+            Self::TransposeRows { row } => row - 1..row + 1,
+            Self::SplitRow { row, .. } | Self::JoinRows { row, .. } => *row..usize::MAX,
+            Self::ReplaceParagraph { row, old, new } if old.len() == new.len() => {
+                *row..row + new.len()
+            }
+            Self::ReplaceParagraph { row, .. } => *row..usize::MAX,
+        }
+    }
+
+    /// Approximate number of bytes this operation keeps alive while in the history.
+    pub fn size(&self) -> usize {
+        let heap = match self {
+            Self::Insert { text, .. } | Self::Delete { text, .. } => text.len(),
+            Self::TransposeChars { .. }
+            | Self::TransposeRows { .. }
+            | Self::SplitRow { .. }
+            | Self::JoinRows { .. } => 0,
+            Self::ReplaceParagraph { old, new, .. } => old
+                .iter()
+                .chain(new)
+                .map(|line| mem::size_of::<String>() + line.len())
+                .sum(),
+        };
+
+        mem::size_of::<Self>() + heap
+    }
+}
+
+/// Every operation applied to a buffer in order, kept for replaying a session.
+///
+/// Serializes to one operation per line as `insert <row> <index> <text>`,
+/// `delete <row> <index> <text>`, `transpose-chars <row> <index>`, `transpose-rows <row>`,
+/// `split-row <row> <index>` or `join-rows <row> <index>`. A replaced paragraph is
+/// `replace-paragraph <row> <old rows> <new rows>` followed by the old and then the new
+/// rows, one per line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OperationLog(Vec<EditOp>);
+
+impl OperationLog {
+    pub fn push(&mut self, op: EditOp) {
+        self.0.push(op);
+    }
+
+    pub fn ops(&self) -> &[EditOp] {
+        &self.0
+    }
+}
+
+impl fmt::Display for OperationLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for op in &self.0 {
+            match op {
+                EditOp::Insert { row, index, text } => writeln!(f, "insert {row} {index} {text}")?,
+                EditOp::Delete { row, index, text } => writeln!(f, "delete {row} {index} {text}")?,
+                EditOp::TransposeChars { row, index } => {
+                    writeln!(f, "transpose-chars {row} {index}")?
+                }
+                EditOp::TransposeRows { row } => writeln!(f, "transpose-rows {row}")?,
+                EditOp::SplitRow { row, index } => writeln!(f, "split-row {row} {index}")?,
+                EditOp::JoinRows { row, index } => writeln!(f, "join-rows {row} {index}")?,
+                EditOp::ReplaceParagraph { row, old, new } => {
+                    writeln!(f, "replace-paragraph {row} {} {}", old.len(), new.len())?;
+                    for line in old.iter().chain(new) {
+                        writeln!(f, "{line}")?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOperationError {
+    line: usize,
+}
+
+impl Error for ParseOperationError {}
+
+impl fmt::Display for ParseOperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid operation on line {}", self.line)
+    }
+}
+
+impl FromStr for OperationLog {
+    type Err = ParseOperationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Rows never contain a newline so each operation is one line, apart from the rows of
+        // a replaced paragraph on the lines after it. Split on '\n' only, a trailing '\r'
+        // belongs to the text.
+        let mut lines = s.split_terminator('\n').enumerate();
+        let mut ops = Vec::new();
+        while let Some((i, line)) = lines.next() {
+            let error = ParseOperationError { line: i + 1 };
+            let mut parts = line.splitn(4, ' ');
+            let kind = parts.next();
+            let row = parts.next().and_then(|x| x.parse().ok()).ok_or(error)?;
+            if kind == Some("transpose-rows") {
+                match parts.next() {
+                    Some(_) => return Err(error),
+                    None => ops.push(EditOp::TransposeRows { row }),
+                }
+                continue;
+            }
+
+            let index = parts.next().and_then(|x| x.parse().ok()).ok_or(error)?;
+            let op = match (kind, parts.next().map(str::to_string)) {
+                (Some("insert"), Some(text)) => EditOp::Insert { row, index, text },
+                (Some("delete"), Some(text)) => EditOp::Delete { row, index, text },
+                (Some("transpose-chars"), None) => EditOp::TransposeChars { row, index },
+                (Some("split-row"), None) => EditOp::SplitRow { row, index },
+                (Some("join-rows"), None) => EditOp::JoinRows { row, index },
+                // The second number is how many old rows follow, then how many new ones
+                (Some("replace-paragraph"), Some(count)) => {
+                    let count = count.parse().map_err(|_| error)?;
+                    let mut rows = |n| {
+                        (0..n)
+                            .map(|_| lines.next().map(|(_, line)| line.to_string()).ok_or(error))
+                            .collect::<Result<Vec<_>, _>>()
+                    };
+                    let old = rows(index)?;
+                    let new = rows(count)?;
+                    EditOp::ReplaceParagraph { row, old, new }
+                }
+                _ => return Err(error),
+            };
+            ops.push(op);
+        }
+
+        Ok(Self(ops))
+    }
+}
+
+/// First line of an undo file, bumped whenever the format changes
+const UNDO_FILE_HEADER: &str = "kilo-undo 1";
+
+/// The undo history of a buffer kept on disk between sessions. `hash` is the `content_hash`
+/// of the buffer the history leads up to so it is only restored onto the same content.
+///
+/// Serializes to the header line, the hash in hex and then the operations in the
+/// `OperationLog` format, oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UndoFile {
+    pub hash: u64,
+    pub ops: OperationLog,
+}
+
+impl fmt::Display for UndoFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{UNDO_FILE_HEADER}")?;
+        writeln!(f, "{:016x}", self.hash)?;
+        self.ops.fmt(f)
+    }
+}
+
+impl FromStr for UndoFile {
+    type Err = ParseOperationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '\n');
+        if parts.next() != Some(UNDO_FILE_HEADER) {
+            return Err(ParseOperationError { line: 1 });
+        }
+
+        let hash = parts
+            .next()
+            .and_then(|hash| u64::from_str_radix(hash, 16).ok())
+            .ok_or(ParseOperationError { line: 2 })?;
+        let ops = parts
+            .next()
+            .unwrap_or_default()
+            .parse::<OperationLog>()
+            .map_err(|err| ParseOperationError { line: err.line + 2 })?;
+
+        Ok(Self { hash, ops })
+    }
+}
+
+/// A hash of `content` that stays the same across builds and platforms (64 bit FNV-1a).
+pub fn content_hash(content: &str) -> u64 {
+    extend_content_hash(0xcbf29ce484222325, content)
+}
+
+/// Carry on `hash` of some text with `more` of it, so text kept in pieces hashes the same as
+/// when it is joined.
+pub fn extend_content_hash(hash: u64, more: &str) -> u64 {
+    more.bytes().fold(hash, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Caps the undo history both by the number of steps and by the memory they hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UndoLimits {
+    max_steps: usize,
+    max_bytes: usize,
+}
+
+impl UndoLimits {
+    pub fn new(max_steps: usize, max_bytes: usize) -> Self {
+        Self {
+            max_steps,
+            max_bytes,
+        }
+    }
+
+    pub fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+}
+
+impl Default for UndoLimits {
+    fn default() -> Self {
+        Self::new(1000, 1024 * 1024)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UndoStack {
+    undo: VecDeque<EditOp>,
+    redo: Vec<EditOp>,
+    limits: UndoLimits,
+    bytes: usize,
+}
+
+impl UndoStack {
+    pub fn new(limits: UndoLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    pub fn limits(&self) -> UndoLimits {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: UndoLimits) {
+        self.limits = limits;
+        self.enforce_limits();
+    }
+
+    pub fn undo_count(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_count(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// The operations that can be undone, oldest first.
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &EditOp> {
+        self.undo.iter()
+    }
+
+    /// Approximate memory held by both the undo and redo entries.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Record a new operation. This forgets everything that could be redone.
+    pub fn push(&mut self, op: EditOp) {
+        for op in self.redo.drain(..) {
+            self.bytes -= op.size();
+        }
+
+        self.bytes += op.size();
+        self.undo.push_back(op);
+        self.enforce_limits();
+    }
+
+    /// Take the most recent operation to revert, moving it onto the redo stack.
+    pub fn undo(&mut self) -> Option<EditOp> {
+        let op = self.undo.pop_back()?;
+        self.redo.push(op.clone());
+        Some(op)
+    }
+
+    /// Take the most recently reverted operation to apply again.
+    pub fn redo(&mut self) -> Option<EditOp> {
+        let op = self.redo.pop()?;
+        self.undo.push_back(op.clone());
+        Some(op)
+    }
+
+    fn enforce_limits(&mut self) {
+        while self.undo.len() > self.limits.max_steps
+            || (self.bytes > self.limits.max_bytes && !self.undo.is_empty())
+        {
+            if let Some(op) = self.undo.pop_front() {
+                self.bytes -= op.size();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(text: &str) -> EditOp {
+        EditOp::Insert {
+            row: 0,
+            index: 0,
+            text: text.to_string(),
+        }
+    }
+
+    fn undo_all(stack: &mut UndoStack) -> Vec<EditOp> {
+        std::iter::from_fn(|| stack.undo()).collect()
+    }
+
+    #[test]
+    fn operation_log_should_round_trip_through_its_text_form() {
+        let mut log = OperationLog::default();
+        log.push(insert("a b\t"));
+        log.push(EditOp::Delete {
+            row: 2,
+            index: 7,
+            text: "x\r".to_string(),
+        });
+        log.push(EditOp::TransposeChars { row: 1, index: 3 });
+        log.push(EditOp::TransposeRows { row: 4 });
+        log.push(EditOp::SplitRow { row: 0, index: 2 });
+        log.push(EditOp::JoinRows { row: 3, index: 0 });
+        log.push(EditOp::ReplaceParagraph {
+            row: 1,
+            old: vec!["insert 0 0 a".to_string(), String::new()],
+            new: vec!["one line".to_string()],
+        });
+        log.push(insert("after"));
+
+        assert_eq!(log.to_string().parse(), Ok(log));
+    }
+
+    #[test]
+    fn parsing_an_unknown_operation_should_report_the_line() {
+        let result = "insert 0 0 a\nreplace 0 0 b\n".parse::<OperationLog>();
+
+        assert_eq!(result, Err(ParseOperationError { line: 2 }));
+    }
+
+    #[test]
+    fn replaced_paragraph_missing_rows_should_report_its_line() {
+        let result = "insert 0 0 a\nreplace-paragraph 0 2 1\nold\nnew\n".parse::<OperationLog>();
+
+        assert_eq!(result, Err(ParseOperationError { line: 2 }));
+    }
+
+    #[test]
+    fn undo_file_should_round_trip_through_its_text_form() {
+        let mut ops = OperationLog::default();
+        ops.push(insert("a\r"));
+        let file = UndoFile {
+            hash: content_hash("abc"),
+            ops,
+        };
+
+        assert_eq!(file.to_string().parse(), Ok(file));
+        assert_eq!(
+            "kilo-undo 2\n".parse::<UndoFile>(),
+            Err(ParseOperationError { line: 1 })
+        );
+    }
+
+    #[test]
+    fn exceeding_the_byte_budget_should_evict_the_oldest_operations() {
+        let op_size = insert("aaaa").size();
+        let mut stack = UndoStack::new(UndoLimits::new(100, op_size * 2));
+
+        stack.push(insert("aaaa"));
+        stack.push(insert("bbbb"));
+        stack.push(insert("cccc"));
+
+        assert!(stack.bytes() <= op_size * 2);
+        assert_eq!(undo_all(&mut stack), vec![insert("cccc"), insert("bbbb")]);
+    }
+
+    #[test]
+    fn step_cap_should_apply_even_when_under_the_byte_budget() {
+        let mut stack = UndoStack::new(UndoLimits::new(2, usize::MAX));
+
+        stack.push(insert("a"));
+        stack.push(insert("b"));
+        stack.push(insert("c"));
+
+        assert_eq!(undo_all(&mut stack), vec![insert("c"), insert("b")]);
+    }
+
+    #[test]
+    fn an_operation_larger_than_the_budget_should_not_be_kept() {
+        let mut stack = UndoStack::new(UndoLimits::new(100, 8));
+
+        stack.push(insert(&"x".repeat(64)));
+
+        assert_eq!(stack.bytes(), 0);
+        assert_eq!(stack.undo(), None);
+    }
+
+    #[test]
+    fn pushing_should_release_the_memory_held_by_redo_entries() {
+        let mut stack = UndoStack::new(UndoLimits::default());
+        stack.push(insert("a"));
+        stack.undo();
+
+        stack.push(insert("b"));
+
+        assert_eq!(stack.bytes(), insert("b").size());
+        assert_eq!(stack.redo(), None);
+    }
+
+    #[test]
+    fn counts_should_follow_undo_and_redo() {
+        let mut stack = UndoStack::default();
+        stack.push(insert("a"));
+        stack.push(insert("b"));
+        stack.push(insert("c"));
+
+        stack.undo();
+
+        assert_eq!((stack.undo_count(), stack.redo_count()), (2, 1));
+    }
+}
@@ -0,0 +1,84 @@
+//! The parts of the editor that take focus but keep their state in the editor itself, as
+//! components borrowing it for as long as they handle an event or draw.
+
+use std::io::{self, Write};
+
+use crate::{
+    component::{Area, Component, EventOutcome, Focus},
+    input::InputEvent,
+    text::char_index,
+    Editor,
+};
+
+/// The text of the current buffer, edited and moved through by every event no other
+/// component takes.
+pub(crate) struct BufferView<'a>(pub &'a mut Editor);
+
+impl Component for BufferView<'_> {
+    fn handle_event(&mut self, event: InputEvent) -> EventOutcome {
+        self.0.handle_buffer_event(event)
+    }
+
+    fn draw(&self, mut writer: &mut dyn Write, _cols: u16, _rows: u16) -> io::Result<()> {
+        self.0.draw_rows(&mut writer)
+    }
+}
+
+/// Asking whether to replace the current match, answered with y, n, a or q.
+pub(crate) struct ReplaceView<'a>(pub &'a mut Editor);
+
+impl Component for ReplaceView<'_> {
+    fn handle_event(&mut self, event: InputEvent) -> EventOutcome {
+        self.0.handle_replace_event(event)
+    }
+
+    fn draw(&self, writer: &mut dyn Write, cols: u16, _rows: u16) -> io::Result<()> {
+        let Some(replace) = &self.0.replace else {
+            return Ok(());
+        };
+
+        let question = format!(
+            "Replace with \"{}\"? (y/n/a/q) [{}/{}]",
+            replace.replacement(),
+            replace.index() + 1,
+            replace.total()
+        );
+        write!(writer, "{}", &question[..char_index(cols as usize, &question)])
+    }
+
+    fn area(&self) -> Area {
+        Area::MessageBar
+    }
+}
+
+/// Asking whether to quit without saving. Any other event than an answer leaves the
+/// question and goes to the buffer.
+pub(crate) struct QuitQuestion<'a>(pub &'a mut Editor);
+
+impl Component for QuitQuestion<'_> {
+    fn handle_event(&mut self, event: InputEvent) -> EventOutcome {
+        match event {
+            InputEvent::InsertChar('y' | 'Y') => {
+                self.0.quit_confirmed = true;
+                EventOutcome::consumed().with_focus(Focus::Buffer)
+            }
+            InputEvent::InsertChar('n' | 'N') | InputEvent::Cancel => {
+                self.0.set_status_message("Quit cancelled");
+                EventOutcome::consumed().with_focus(Focus::Buffer)
+            }
+            event => {
+                self.0.focus = Focus::Buffer;
+                self.0.handle_buffer_event(event)
+            }
+        }
+    }
+
+    fn draw(&self, writer: &mut dyn Write, cols: u16, _rows: u16) -> io::Result<()> {
+        let question = "Quit without saving? (y/n)";
+        write!(writer, "{}", &question[..char_index(cols as usize, question)])
+    }
+
+    fn area(&self) -> Area {
+        Area::MessageBar
+    }
+}
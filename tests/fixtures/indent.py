@@ -0,0 +1,9 @@
+def walk(tree):
+  for node in tree:
+    if node.children:
+      for child in node.children:
+        if child.visible:
+          yield child
+    else:
+      yield node
+          # this comment is a little too wide
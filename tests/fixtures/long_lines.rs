@@ -0,0 +1,5 @@
+fn main() {
+		let value = some_call(1);
+	short();
+// a comment that is over thirty
+}
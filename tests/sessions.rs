@@ -0,0 +1,235 @@
+//! Scripted edit sessions compared against golden files.
+//!
+//! A scenario is a `tests/sessions/<name>.keys` script and its `<name>.golden`
+//! result. The first line of the script is `open <fixture>`, the rest is key
+//! notation: plain characters are typed as-is, named keys are written like
+//! `<Down>`, `<C-f>` or `<S-F3>`, `<lt>` types a literal `<` and line breaks
+//! are ignored so long scripts can be wrapped.
+//!
+//! Run with `UPDATE_GOLDEN=1` to write the golden files from the current behaviour.
+
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use async_std::channel;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use kilo_edit::{
+    buffer::Buffer, input, input::InputEvent, quit::QuitConfirm, terminal::Terminal, Editor,
+    QuitDecision,
+};
+use test_case::test_case;
+
+const COLS: u16 = 40;
+const ROWS: u16 = 10;
+
+fn parse_key(name: &str) -> Event {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = name;
+    loop {
+        modifiers |= match name.get(..2) {
+            Some("C-") => KeyModifiers::CONTROL,
+            Some("S-") => KeyModifiers::SHIFT,
+            Some("A-") => KeyModifiers::ALT,
+            _ => break,
+        };
+        name = &name[2..];
+    }
+
+    let code = match name {
+        "lt" => KeyCode::Char('<'),
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "BS" => KeyCode::Backspace,
+        "Del" => KeyCode::Delete,
+        "CR" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Tab" => KeyCode::Tab,
+        _ if name.starts_with('F') => KeyCode::F(name[1..].parse().expect("function key number")),
+        _ if name.chars().count() == 1 => KeyCode::Char(name.chars().next().unwrap()),
+        _ => panic!("Unknown key <{name}>"),
+    };
+
+    Event::Key(KeyEvent::new(code, modifiers))
+}
+
+fn parse_keys(notation: &str) -> Vec<Event> {
+    let mut keys = Vec::new();
+    let mut chars = notation.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' | '\r' => {}
+            '<' => {
+                let name = chars
+                    .by_ref()
+                    .take_while(|&ch| ch != '>')
+                    .collect::<String>();
+                keys.push(parse_key(&name));
+            }
+            ch => keys.push(Event::Key(KeyEvent::new(
+                KeyCode::Char(ch),
+                KeyModifiers::NONE,
+            ))),
+        }
+    }
+
+    keys
+}
+
+fn strip_escapes(output: &[u8]) -> String {
+    let output = String::from_utf8_lossy(output);
+    let mut frame = String::new();
+    let mut chars = output.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            // Skip the '[', any parameters and the final byte of the sequence
+            chars.next();
+            for ch in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&ch) {
+                    break;
+                }
+            }
+        } else if ch != '\r' {
+            frame.push(ch);
+        }
+    }
+
+    frame
+}
+
+fn run_session(name: &str, script: &str) -> String {
+    let (open, notation) = script.split_once('\n').unwrap_or((script, ""));
+    let fixture = open
+        .strip_prefix("open ")
+        .expect("script should start with 'open <fixture>'");
+
+    let mut editor = Editor::new(COLS, ROWS);
+    editor.set_buffer(Buffer::open(fixture).expect("fixture should open"));
+    for key in parse_keys(notation) {
+        if let Some(event) = input::translate(key) {
+            editor.process_event(event).unwrap();
+        }
+    }
+
+    let mut frame = Vec::new();
+    editor.refresh(&mut Terminal::new(&mut frame)).unwrap();
+
+    let saved_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.saved"));
+    editor
+        .buffer_mut()
+        .set_filename(saved_path.to_string_lossy());
+    editor.process_event(InputEvent::SaveBuffer).unwrap();
+    let saved = fs::read_to_string(&saved_path).unwrap();
+
+    format!("--- saved\n{saved}\n--- frame\n{}\n", strip_escapes(&frame))
+}
+
+fn scenarios() -> Vec<PathBuf> {
+    let mut scripts = fs::read_dir("tests/sessions")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some(OsStr::new("keys")))
+        .collect::<Vec<_>>();
+    scripts.sort();
+
+    scripts
+}
+
+/// Queue `events` in a channel like the input system does and handle them the way the main
+/// loop does, until the quit among them is decided.
+fn decide_queued_quit(editor: &mut Editor, events: &[InputEvent]) -> QuitDecision {
+    let (tx, rx) = channel::bounded(5);
+    for event in events {
+        tx.try_send(*event).unwrap();
+    }
+    loop {
+        match rx.try_recv() {
+            Ok(InputEvent::Quit) => {
+                let queued = std::iter::from_fn(|| rx.try_recv().ok());
+                break editor.quit_after(queued, Editor::process_event).unwrap();
+            }
+            Ok(event) => editor.process_event(event).unwrap(),
+            Err(_) => panic!("the events should end with a quit"),
+        }
+    }
+}
+
+/// Returns the decision on the quit among `events` and the file saved.
+#[test_case("save-then-quit", &[InputEvent::InsertChar('x'), InputEvent::SaveBuffer, InputEvent::Quit] => (QuitDecision::Quit, String::from("xabc")); "Save then quit")]
+#[test_case("quit-then-save", &[InputEvent::InsertChar('x'), InputEvent::Quit, InputEvent::SaveBuffer] => (QuitDecision::Quit, String::from("xabc")); "Save queued behind the quit")]
+#[test_case("quit-unsaved", &[InputEvent::InsertChar('x'), InputEvent::Quit] => (QuitDecision::Stay, String::from("abc")); "Unsaved changes")]
+fn events_queued_around_a_quit_should_be_handled_before_exiting(
+    name: &str,
+    events: &[InputEvent],
+) -> (QuitDecision, String) {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.queued"));
+    fs::write(&path, "abc").unwrap();
+    let mut editor = Editor::new(COLS, ROWS);
+    editor.set_buffer(Buffer::open(&path).unwrap());
+
+    let decision = decide_queued_quit(&mut editor, events);
+
+    (decision, fs::read_to_string(&path).unwrap())
+}
+
+/// Returns the decision on the quit among `events` and the first row of the buffer.
+#[test_case(&[InputEvent::InsertChar('y')] => (QuitDecision::Quit, String::from("xabc")); "Yes")]
+#[test_case(&[InputEvent::InsertChar('n')] => (QuitDecision::Stay, String::from("xabc")); "No")]
+#[test_case(&[InputEvent::Quit] => (QuitDecision::Quit, String::from("xabc")); "Quit again")]
+#[test_case(&[InputEvent::InsertChar('z')] => (QuitDecision::Stay, String::from("xzabc")); "Other key")]
+fn keys_queued_behind_a_quit_should_answer_the_quit_question(
+    answer: &[InputEvent],
+) -> (QuitDecision, String) {
+    let mut editor = Editor::new(COLS, ROWS);
+    editor.set_buffer("abc".parse().unwrap());
+    editor.set_quit_confirm(QuitConfirm::Prompt);
+
+    let events = [InputEvent::InsertChar('x'), InputEvent::Quit];
+    let events = events.iter().chain(answer).copied().collect::<Vec<_>>();
+    let decision = decide_queued_quit(&mut editor, &events);
+
+    let row = editor.buffer().buffer().borrow()[0].buffer().to_string();
+    (decision, row)
+}
+
+#[test]
+fn edit_sessions_should_match_golden_files() {
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+
+    for script_path in scenarios() {
+        let name = script_path
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let script = fs::read_to_string(&script_path).unwrap();
+        let actual = run_session(&name, &script);
+
+        let golden_path = script_path.with_extension("golden");
+        if update {
+            fs::write(&golden_path, &actual).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&golden_path).unwrap_or_default();
+        if actual != expected {
+            failures.push(format!("{name}:\n{actual}"));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "Sessions differ from golden files:\n{}",
+        failures.join("\n")
+    );
+}